@@ -0,0 +1,234 @@
+use super::error::AppError;
+use super::validation::{validate_date, validate_percentage};
+use crate::models::{AssetCategory, ExpenseCategory, Supplier};
+use crate::services::asset::{schedule, CreateAssetParams};
+use crate::services::money::{format_cents, to_cents};
+use crate::services::{AssetService, ExpenseService};
+use chrono::{Datelike, Local};
+use console::style;
+use dialoguer::{Input, Select};
+
+const ASSET_OPTIONS: &[&str] = &[
+    "Register asset",
+    "List assets",
+    "Depreciation schedule",
+    "Go back",
+];
+
+pub fn show_assets_menu(
+    asset_service: &AssetService,
+    expense_service: &ExpenseService,
+) -> Result<(), AppError> {
+    let selection = Select::new()
+        .with_prompt("Fixed assets")
+        .items(ASSET_OPTIONS)
+        .default(0)
+        .interact()?;
+
+    match selection {
+        0 => register_asset(asset_service, expense_service),
+        1 => list_assets(asset_service),
+        2 => show_schedule(asset_service),
+        _ => Ok(()),
+    }
+}
+
+fn register_asset(
+    asset_service: &AssetService,
+    expense_service: &ExpenseService,
+) -> Result<(), AppError> {
+    println!("{}", style("Register a fixed asset").bold());
+
+    // Assets bought with a recorded invoice take its supplier, date and value
+    let equipment: Vec<_> = expense_service
+        .list_expenses()
+        .map_err(AppError::from)?
+        .into_iter()
+        .filter(|e| e.category == ExpenseCategory::Equipment)
+        .collect();
+    let mut choices: Vec<String> = equipment
+        .iter()
+        .map(|e| {
+            format!(
+                "{} #{} - {} ({:.2}€)",
+                e.supplier.name, e.number, e.date, e.base
+            )
+        })
+        .collect();
+    choices.push("Not recorded as an expense".to_string());
+    let selection = Select::new()
+        .with_prompt("Purchase invoice")
+        .items(&choices)
+        .default(choices.len() - 1)
+        .interact()?;
+    let expense = equipment.get(selection);
+
+    let description: String = Input::new()
+        .with_prompt("Enter asset description")
+        .interact_text()?;
+
+    let supplier = match expense {
+        Some(expense) => expense.supplier.clone(),
+        None => {
+            let name: String = Input::new()
+                .with_prompt("Enter supplier name")
+                .interact_text()?;
+            let cif: String = Input::new()
+                .with_prompt("Enter supplier CIF/NIF")
+                .allow_empty(true)
+                .interact_text()?;
+            Supplier::new(name, cif, String::new(), None)
+        }
+    };
+
+    let purchase_date = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter the date it entered into service (YYYY-MM-DD)")
+            .default(
+                expense
+                    .map(|e| e.date.clone())
+                    .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string()),
+            )
+            .interact_text()?;
+
+        match validate_date(&input) {
+            Ok(()) if !input.is_empty() => break input,
+            Ok(()) => continue,
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    // The IVA that cannot be deducted is part of the acquisition value
+    let default_value = expense
+        .map(|e| e.base + e.iva_amount - e.deductible_iva())
+        .unwrap_or(0.0);
+    let value: f32 = Input::new()
+        .with_prompt("Enter acquisition value (€)")
+        .default(default_value)
+        .interact_text()?;
+
+    let category_names: Vec<String> = AssetCategory::ALL
+        .iter()
+        .map(|c| {
+            format!(
+                "{} (max {}%, {} years)",
+                c,
+                c.max_coefficient(),
+                c.max_years()
+            )
+        })
+        .collect();
+    let category = AssetCategory::ALL[Select::new()
+        .with_prompt("Select a category")
+        .items(&category_names)
+        .default(0)
+        .interact()?];
+
+    loop {
+        let coefficient: f32 = Input::new()
+            .with_prompt("Enter linear depreciation coefficient (% per year)")
+            .default(category.max_coefficient())
+            .interact_text()?;
+        if let Err(e) = validate_percentage(coefficient, "Coefficient") {
+            println!("{}", style(format!("Error: {}", e)).red());
+            continue;
+        }
+
+        let params = CreateAssetParams {
+            description: description.clone(),
+            supplier: supplier.clone(),
+            purchase_date: purchase_date.clone(),
+            value,
+            category,
+            coefficient,
+            expense_id: expense.map(|e| e.id.clone()),
+        };
+
+        match asset_service.create_asset(params) {
+            Ok(asset) => {
+                println!("\n{}", style("Asset registered successfully!").green());
+                println!("{}", asset);
+                return Ok(());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => {
+                println!("{}", style(format!("Error: {}", e)).red());
+            }
+            Err(e) => return Err(AppError::from(e)),
+        }
+    }
+}
+
+fn list_assets(asset_service: &AssetService) -> Result<(), AppError> {
+    let assets = asset_service.list_assets().map_err(AppError::from)?;
+
+    if assets.is_empty() {
+        println!("No assets found.");
+        return Ok(());
+    }
+
+    let year = Local::now().year();
+    for (i, asset) in assets.iter().enumerate() {
+        let years = schedule(asset);
+        let this_year = years
+            .iter()
+            .find(|y| y.year == year)
+            .map_or(0, |y| y.amount);
+        let depreciated: i64 = years
+            .iter()
+            .filter(|y| y.year <= year)
+            .map(|y| y.amount)
+            .sum();
+        println!("{}. {}", i + 1, style(&asset.description).bold());
+        println!(
+            "   {} - {:.2}€ at {}% per year",
+            asset.purchase_date, asset.value, asset.coefficient
+        );
+        println!(
+            "   Depreciation {}: {}€, pending after {}: {}€",
+            year,
+            format_cents(this_year),
+            year,
+            format_cents(to_cents(asset.value) - depreciated)
+        );
+        println!();
+    }
+
+    Ok(())
+}
+
+fn show_schedule(asset_service: &AssetService) -> Result<(), AppError> {
+    let assets = asset_service.list_assets().map_err(AppError::from)?;
+
+    if assets.is_empty() {
+        println!("No assets found.");
+        return Ok(());
+    }
+
+    let names: Vec<String> = assets
+        .iter()
+        .map(|a| format!("{} ({})", a.description, a.purchase_date))
+        .collect();
+    let asset = &assets[Select::new()
+        .with_prompt("Select an asset")
+        .items(&names)
+        .default(0)
+        .interact()?];
+
+    println!("\n{}", asset);
+    println!(
+        "\n{:<6} {:>14} {:>14} {:>14}",
+        "Year", "Depreciation", "Accumulated", "Pending"
+    );
+    for year in schedule(asset) {
+        println!(
+            "{:<6} {:>13}€ {:>13}€ {:>13}€",
+            year.year,
+            format_cents(year.amount),
+            format_cents(year.accumulated),
+            format_cents(year.remaining)
+        );
+    }
+    println!();
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use super::error::AppError;
+use crate::services::money::format_cents;
+use crate::services::{InvoiceService, ReconciliationService};
+use console::style;
+use dialoguer::{Confirm, Input};
+use std::path::Path;
+
+// Proposals at least this sure are accepted by default
+const CONFIDENT_SCORE: u32 = 70;
+
+pub fn reconcile_bank_statement(
+    reconciliation_service: &ReconciliationService,
+    invoice_service: &InvoiceService,
+) -> Result<(), AppError> {
+    println!("{}", style("Bank reconciliation").bold());
+
+    let path: String = Input::new()
+        .with_prompt(
+            "Enter the path of the Norma 43 or camt.053 statement (leave empty to go back)",
+        )
+        .allow_empty(true)
+        .interact_text()?;
+    if path.trim().is_empty() {
+        return Ok(());
+    }
+
+    let transactions = match reconciliation_service.read_statement(Path::new(path.trim())) {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            println!("{}", style(format!("Error: {}", e)).red());
+            return Ok(());
+        }
+    };
+    let credits = transactions.iter().filter(|t| t.is_credit()).count();
+    println!(
+        "{} movements read, {} of them credits",
+        transactions.len(),
+        credits
+    );
+
+    let proposals = reconciliation_service
+        .propose_matches(&transactions)
+        .map_err(AppError::from)?;
+    if proposals.is_empty() {
+        println!("No credits match unpaid invoices.");
+        return Ok(());
+    }
+
+    let mut reconciled = 0;
+    for proposal in &proposals {
+        let transaction = &proposal.transaction;
+        let invoice = &proposal.invoice;
+
+        println!(
+            "\n{} {}€ {}",
+            transaction.booking_date,
+            style(format_cents(transaction.amount)).bold(),
+            transaction.counterparty.as_deref().unwrap_or_default()
+        );
+        println!("   Concept: {}", transaction.description);
+        println!(
+            "   Invoice #{} of {} to {}, {:.2}€",
+            style(&invoice.id).bold(),
+            invoice.date,
+            invoice.client_name(),
+            invoice.total
+        );
+        println!(
+            "   Matched by: {}",
+            proposal
+                .reasons
+                .iter()
+                .map(|reason| reason.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        if Confirm::new()
+            .with_prompt("Record this payment?")
+            .default(proposal.score() >= CONFIDENT_SCORE)
+            .interact()?
+        {
+            match invoice_service.record_bank_payment(invoice, transaction) {
+                Ok(_) => reconciled += 1,
+                Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        style(format!("{} invoice(s) marked as paid", reconciled)).green()
+    );
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use super::error::AppError;
+use crate::models::User;
+use crate::services::bulk_import::{ImportOutcome, ImportReport, RowError};
+use crate::services::BulkImportService;
+use console::style;
+use dialoguer::{Confirm, Input, Select};
+use std::path::Path;
+
+const IMPORT_OPTIONS: &[&str] = &["Import clients", "Import invoices", "Go back"];
+
+pub fn show_bulk_import_menu(
+    bulk_import_service: &BulkImportService,
+    user: &User,
+) -> Result<(), AppError> {
+    let selection = Select::new()
+        .with_prompt("Bulk import from CSV or XLSX")
+        .items(IMPORT_OPTIONS)
+        .default(0)
+        .interact()?;
+    if selection == 2 {
+        return Ok(());
+    }
+
+    let path: String = Input::new()
+        .with_prompt("Enter the path of the CSV or XLSX file (leave empty to go back)")
+        .allow_empty(true)
+        .interact_text()?;
+    if path.trim().is_empty() {
+        return Ok(());
+    }
+    let path = Path::new(path.trim());
+
+    match selection {
+        0 => {
+            let report = match bulk_import_service.check_clients(path) {
+                Ok(report) => report,
+                Err(e) => {
+                    println!("{}", style(format!("Error: {}", e)).red());
+                    return Ok(());
+                }
+            };
+            print_report(&report, "client(s)");
+            if confirm_import(&report, "client(s)")? {
+                let outcome = bulk_import_service.import_clients(&report.valid);
+                print_outcome(&outcome, "client(s)");
+            }
+        }
+        _ => {
+            let report = match bulk_import_service.check_invoices(path, user) {
+                Ok(report) => report,
+                Err(e) => {
+                    println!("{}", style(format!("Error: {}", e)).red());
+                    return Ok(());
+                }
+            };
+            print_report(&report, "invoice(s)");
+            if confirm_import(&report, "invoice(s)")? {
+                let outcome = bulk_import_service.import_invoices(&report.valid);
+                print_outcome(&outcome, "invoice(s)");
+            }
+        }
+    }
+    Ok(())
+}
+
+// Dry-run report, shown before anything is stored
+fn print_report<T>(report: &ImportReport<T>, what: &str) {
+    println!("\n{}", style("Dry run").bold());
+    println!("{} {} ready to import", report.valid.len(), what);
+    print_rows(&report.duplicates, "already stored, skipped");
+    print_rows(&report.errors, "with errors");
+}
+
+fn print_rows(rows: &[RowError], title: &str) {
+    if rows.is_empty() {
+        return;
+    }
+    println!("{} row(s) {}:", rows.len(), title);
+    for row in rows {
+        println!("   Row {}: {}", row.row, row.message);
+    }
+}
+
+fn print_outcome(outcome: &ImportOutcome, what: &str) {
+    println!(
+        "{}",
+        style(format!("{} {} imported", outcome.imported, what)).green()
+    );
+    if outcome.failed.is_empty() {
+        return;
+    }
+    println!(
+        "{}",
+        style(format!(
+            "{} {} could not be saved:",
+            outcome.failed.len(),
+            what
+        ))
+        .red()
+    );
+    for message in &outcome.failed {
+        println!("   {}", message);
+    }
+    println!("Import the file again once fixed, those already stored are skipped.");
+}
+
+// Rows with errors are never imported, the user may fix the file and try again
+fn confirm_import<T>(report: &ImportReport<T>, what: &str) -> Result<bool, AppError> {
+    if report.valid.is_empty() {
+        println!("Nothing to import.");
+        return Ok(false);
+    }
+    Ok(Confirm::new()
+        .with_prompt(format!("Import the {} valid {}?", report.valid.len(), what))
+        .default(report.errors.is_empty())
+        .interact()?)
+}
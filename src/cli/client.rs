@@ -1,9 +1,10 @@
 use super::error::AppError;
-use super::validation::{validate_cif, validate_email};
+use super::validation::{validate_client_tax_id, validate_email};
 use crate::models::Client;
+use crate::services::einvoice::{country_code, is_eu_country, local_tax_id, vat_identifier};
 use crate::services::ClientService;
 use console::style;
-use dialoguer::{Input, Select};
+use dialoguer::{Confirm, Input, Select};
 
 pub fn create_client(client_service: &ClientService) -> Result<(), AppError> {
     println!("{}", style("Create a new client").bold());
@@ -27,18 +28,19 @@ pub fn create_client(client_service: &ClientService) -> Result<(), AppError> {
             .with_prompt("Enter client CIF/NIF")
             .interact_text()?;
 
-        match validate_cif(&cif_input) {
-            Ok(_) => break cif_input,
+        match validate_client_tax_id(&cif_input) {
+            Ok(_) => break local_tax_id(&cif_input),
             Err(e) => {
                 println!("{}", style(format!("Error: {}", e)).red());
                 println!(
                     "{}",
-                    style("CIF/NIF must be between 8-12 characters").yellow()
+                    style("Foreign clients need their VAT number with the country prefix (e.g. DE136695976)").yellow()
                 );
                 continue;
             }
         }
     };
+    check_vies(client_service, &cif)?;
 
     let address: String = Input::new()
         .with_prompt("Enter client address")
@@ -78,6 +80,52 @@ pub fn create_client(client_service: &ClientService) -> Result<(), AppError> {
     Ok(())
 }
 
+// Intra-community clients are checked in VIES before invoicing them without IVA
+fn check_vies(client_service: &ClientService, cif: &str) -> Result<(), AppError> {
+    let vat_id = vat_identifier(cif);
+    let country = country_code(&vat_id);
+    if country == "ES" || !is_eu_country(country) {
+        return Ok(());
+    }
+    if !Confirm::new()
+        .with_prompt("Check the VAT number in VIES?")
+        .default(true)
+        .interact()?
+    {
+        return Ok(());
+    }
+
+    match client_service.check_vat(&vat_id) {
+        Ok(check) if check.valid => {
+            println!(
+                "{}",
+                style(format!(
+                    "{} is valid for intra-community operations (VIES, {})",
+                    check.vat_id, check.request_date
+                ))
+                .green()
+            );
+            if let Some(name) = &check.name {
+                println!("   Registered name: {}", name);
+            }
+            if let Some(address) = &check.address {
+                println!("   Registered address: {}", address.replace('\n', ", "));
+            }
+        }
+        Ok(check) => println!(
+            "{}",
+            style(format!(
+                "VIES reports {} as not valid for intra-community operations ({}), invoices to it must charge IVA",
+                check.vat_id, check.request_date
+            ))
+            .red()
+        ),
+        Err(e) => println!("{}", style(format!("Could not check VIES: {}", e)).yellow()),
+    }
+
+    Ok(())
+}
+
 pub fn list_clients(client_service: &ClientService) -> Result<(), AppError> {
     println!("{}", style("Clients").bold());
 
@@ -104,6 +152,9 @@ pub fn list_clients(client_service: &ClientService) -> Result<(), AppError> {
         if let Some(email) = &client.email {
             println!("   Email: {}", email);
         }
+        if let Some(mandate) = &client.mandate {
+            println!("   SEPA mandate: {}", mandate);
+        }
         println!();
     }
 
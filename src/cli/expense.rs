@@ -0,0 +1,262 @@
+use super::error::AppError;
+use super::validation::{
+    validate_client_tax_id, validate_date, validate_email, validate_percentage,
+};
+use crate::models::{ExpenseCategory, Supplier};
+use crate::services::einvoice::local_tax_id;
+use crate::services::expense::CreateExpenseParams;
+use crate::services::ExpenseService;
+use chrono::Local;
+use console::style;
+use dialoguer::{Input, Select};
+use std::path::PathBuf;
+
+const EXPENSE_OPTIONS: &[&str] = &[
+    "Record expense",
+    "List expenses",
+    "Create supplier",
+    "List suppliers",
+    "Go back",
+];
+
+pub fn show_expenses_menu(expense_service: &ExpenseService) -> Result<(), AppError> {
+    let selection = Select::new()
+        .with_prompt("Expenses")
+        .items(EXPENSE_OPTIONS)
+        .default(0)
+        .interact()?;
+
+    match selection {
+        0 => record_expense(expense_service),
+        1 => list_expenses(expense_service),
+        2 => create_supplier(expense_service).map(|_| ()),
+        3 => list_suppliers(expense_service),
+        _ => Ok(()),
+    }
+}
+
+fn create_supplier(expense_service: &ExpenseService) -> Result<Supplier, AppError> {
+    println!("{}", style("Create a new supplier").bold());
+
+    let name: String = Input::new()
+        .with_prompt("Enter supplier name")
+        .interact_text()?;
+
+    let cif = loop {
+        let cif_input: String = Input::new()
+            .with_prompt("Enter supplier CIF/NIF")
+            .interact_text()?;
+
+        match validate_client_tax_id(&cif_input) {
+            Ok(_) => break local_tax_id(&cif_input),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    let address: String = Input::new()
+        .with_prompt("Enter supplier address")
+        .interact_text()?;
+
+    let email = loop {
+        let email_input: String = Input::new()
+            .with_prompt("Enter supplier email (optional, press enter to skip)")
+            .allow_empty(true)
+            .interact_text()?;
+
+        if email_input.is_empty() {
+            break None;
+        }
+
+        match validate_email(&email_input) {
+            Ok(_) => break Some(email_input),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    let supplier = expense_service
+        .create_supplier(name, cif, address, email)
+        .map_err(AppError::from)?;
+
+    println!("{}", style("Supplier created successfully!").green());
+    println!("{}", supplier);
+
+    Ok(supplier)
+}
+
+fn list_suppliers(expense_service: &ExpenseService) -> Result<(), AppError> {
+    let suppliers = expense_service.list_suppliers().map_err(AppError::from)?;
+
+    if suppliers.is_empty() {
+        println!("No suppliers found.");
+        return Ok(());
+    }
+
+    for (i, supplier) in suppliers.iter().enumerate() {
+        println!("{}. {}", i + 1, style(&supplier.name).bold());
+        println!("   CIF: {}", supplier.cif);
+        println!("   Address: {}", supplier.address);
+        if let Some(email) = &supplier.email {
+            println!("   Email: {}", email);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn select_supplier(expense_service: &ExpenseService) -> Result<Option<Supplier>, AppError> {
+    let suppliers = expense_service.list_suppliers().map_err(AppError::from)?;
+
+    let mut names: Vec<String> = suppliers.iter().map(|s| s.name.clone()).collect();
+    names.push("+ New supplier".to_string());
+    names.push("← Go Back".to_string());
+
+    let selection = Select::new()
+        .with_prompt("Select a supplier")
+        .items(&names)
+        .default(0)
+        .interact()?;
+
+    if selection == names.len() - 1 {
+        Ok(None)
+    } else if selection == names.len() - 2 {
+        create_supplier(expense_service).map(Some)
+    } else {
+        Ok(Some(suppliers[selection].clone()))
+    }
+}
+
+fn record_expense(expense_service: &ExpenseService) -> Result<(), AppError> {
+    println!("{}", style("Record a received invoice").bold());
+
+    let supplier = match select_supplier(expense_service)? {
+        Some(supplier) => supplier,
+        None => return Ok(()),
+    };
+
+    let number: String = Input::new()
+        .with_prompt("Enter the supplier's invoice number")
+        .interact_text()?;
+
+    let date = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter invoice date (YYYY-MM-DD)")
+            .default(Local::now().format("%Y-%m-%d").to_string())
+            .interact_text()?;
+
+        match validate_date(&input) {
+            Ok(()) => break input,
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    let category_names: Vec<&str> = ExpenseCategory::ALL.iter().map(|c| c.name()).collect();
+    let category = ExpenseCategory::ALL[Select::new()
+        .with_prompt("Select a category")
+        .items(&category_names)
+        .default(0)
+        .interact()?];
+
+    let base: f32 = Input::new()
+        .with_prompt("Enter taxable base (€)")
+        .interact_text()?;
+
+    // Self-employed quotas carry no IVA
+    let default_iva = if category == ExpenseCategory::SelfEmployedQuota {
+        0.0
+    } else {
+        21.0
+    };
+    let iva = read_percentage("Enter IVA percentage", default_iva)?;
+    let deductible_percent = read_percentage("Enter deductible percentage", 100.0)?;
+
+    let attachment = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter the path of the original PDF (optional, press enter to skip)")
+            .allow_empty(true)
+            .interact_text()?;
+
+        if input.trim().is_empty() {
+            break None;
+        }
+        let path = PathBuf::from(input.trim());
+        if path.is_file() {
+            break Some(path);
+        }
+        println!(
+            "{}",
+            style(format!("Error: File not found: {}", path.display())).red()
+        );
+    };
+
+    let params = CreateExpenseParams {
+        number,
+        date,
+        supplier,
+        category,
+        base,
+        iva,
+        deductible_percent,
+        attachment,
+    };
+
+    match expense_service.create_expense(params) {
+        Ok(expense) => {
+            println!("\n{}", style("Expense recorded successfully!").green());
+            println!("{}", expense);
+        }
+        Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+    }
+
+    Ok(())
+}
+
+fn read_percentage(prompt: &str, default: f32) -> Result<f32, AppError> {
+    loop {
+        let input: f32 = Input::new()
+            .with_prompt(prompt)
+            .default(default)
+            .interact_text()?;
+
+        match validate_percentage(input, prompt) {
+            Ok(()) => return Ok(input),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    }
+}
+
+fn list_expenses(expense_service: &ExpenseService) -> Result<(), AppError> {
+    let expenses = expense_service.list_expenses().map_err(AppError::from)?;
+
+    if expenses.is_empty() {
+        println!("No expenses found.");
+        return Ok(());
+    }
+
+    for (i, expense) in expenses.iter().enumerate() {
+        println!(
+            "{}. {} #{} - {}",
+            i + 1,
+            style(&expense.supplier.name).bold(),
+            expense.number,
+            expense.date
+        );
+        println!("   Category: {}", expense.category);
+        println!(
+            "   Total: {:.2}€ (deductible {:.2}€ + {:.2}€ IVA)",
+            expense.total,
+            expense.deductible_base(),
+            expense.deductible_iva()
+        );
+        if let Some(path) = expense_service.attachment_path(expense) {
+            println!("   Attachment: {}", path.display());
+        }
+        println!();
+    }
+
+    let total: f32 = expenses.iter().map(|e| e.deductible_base()).sum();
+    let iva: f32 = expenses.iter().map(|e| e.deductible_iva()).sum();
+    println!("Deductible total: {:.2}€ + {:.2}€ IVA", total, iva);
+
+    Ok(())
+}
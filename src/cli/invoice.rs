@@ -1,8 +1,10 @@
 use super::client::select_client;
 use super::error::AppError;
 use super::validation::{validate_date, validate_percentage};
-use crate::models::{Item, Rule, User};
-use crate::services::{ClientService, InvoiceService};
+use crate::models::{ExpenseCategory, Invoice, Item, Rule, User, SIMPLIFIED_INVOICE_LIMIT};
+use crate::services::einvoice_import::{invoice_direction, parse_einvoice, InvoiceDirection};
+use crate::services::invoice::CreateSimplifiedInvoiceParams;
+use crate::services::{ClientService, ExpenseService, InvoiceService};
 use console::style;
 use dialoguer::{Confirm, Input, Select};
 
@@ -77,6 +79,117 @@ pub fn create_invoice(
     } else {
         Some(custom_due_date)
     };
+    let rule = read_rule()?;
+    let items = read_items()?;
+    let params = crate::services::invoice::CreateInvoiceParams {
+        invoice_number,
+        date: invoice_date,
+        due_date: invoice_due_date,
+        user: user.clone(),
+        client,
+        rule,
+        items,
+        recurring_id: None,
+        quote_id: None,
+    };
+
+    let invoice = invoice_service
+        .create_invoice(params)
+        .map_err(AppError::from)?;
+    println!("\n{}", style("Invoice created successfully!").green());
+    println!("{}", invoice);
+    if Confirm::new()
+        .with_prompt("Generate PDF?")
+        .default(true)
+        .interact()?
+    {
+        let pdf_path = invoice_service
+            .generate_pdf(&invoice)
+            .map_err(AppError::from)?;
+        println!("PDF generated: {}", pdf_path);
+    }
+
+    Ok(())
+}
+
+// Create a simplified invoice (ticket), the client is optional and prices are entered
+// with the IVA included, as they are charged at the counter
+pub fn create_simplified_invoice(
+    client_service: &ClientService,
+    invoice_service: &InvoiceService,
+    user: &User,
+) -> Result<(), AppError> {
+    println!("{}", style("Create a simplified invoice").bold());
+    println!(
+        "Simplified invoices are limited to {:.2}€, IVA included",
+        SIMPLIFIED_INVOICE_LIMIT
+    );
+
+    let clients = client_service.list_clients().map_err(AppError::from)?;
+    let mut options = vec!["No client (final consumer)".to_string()];
+    options.extend(clients.iter().map(|client| client.name.clone()));
+    options.push("← Go Back".to_string());
+    let selection = Select::new()
+        .with_prompt("Select a client")
+        .items(&options)
+        .default(0)
+        .interact()?;
+    if selection == options.len() - 1 {
+        return Ok(());
+    }
+    let client = selection.checked_sub(1).map(|index| clients[index].clone());
+
+    let number: String = Input::new()
+        .with_prompt("Enter invoice number (leave empty for the next T series number)")
+        .allow_empty(true)
+        .interact_text()?;
+    let invoice_number = Some(number.trim().to_string()).filter(|number| !number.is_empty());
+    let date = read_optional_date("Enter invoice date (YYYY-MM-DD, leave empty for today)")?;
+    let rule = read_rule()?;
+
+    // Items are entered with the IVA included and stored at their net price
+    let with_iva = 1.0 + rule.iva / 100.0;
+    let items = read_items()?
+        .into_iter()
+        .map(|item| Item::new(item.description, item.quantity, item.price / with_iva))
+        .collect();
+
+    let params = CreateSimplifiedInvoiceParams {
+        invoice_number,
+        date,
+        user: user.clone(),
+        client,
+        rule,
+        items,
+    };
+    let invoice = match invoice_service.create_simplified_invoice(params) {
+        Ok(invoice) => invoice,
+        Err(e) => {
+            println!("{}", style(format!("Error: {}", e)).red());
+            return Ok(());
+        }
+    };
+    println!(
+        "\n{}",
+        style("Simplified invoice created successfully!").green()
+    );
+    println!("{}", invoice);
+    if Confirm::new()
+        .with_prompt("Generate receipt PDF?")
+        .default(true)
+        .interact()?
+    {
+        let pdf_path = invoice_service
+            .generate_pdf(&invoice)
+            .map_err(AppError::from)?;
+        println!("PDF generated: {}", pdf_path);
+    }
+
+    Ok(())
+}
+
+// Ask for the IVA and IRPF percentages of an invoice
+pub fn read_rule() -> Result<Rule, AppError> {
     println!("{}", style("Set invoice rules").bold());
 
     let iva: f32 = loop {
@@ -109,7 +222,27 @@ pub fn create_invoice(
         }
     };
 
-    let rule = Rule::new(iva, irpf);
+    Ok(Rule::new(iva, irpf))
+}
+
+// Ask for a date that may be left empty
+pub fn read_optional_date(prompt: &str) -> Result<Option<String>, AppError> {
+    loop {
+        let input: String = Input::new()
+            .with_prompt(prompt)
+            .allow_empty(true)
+            .interact_text()?;
+
+        match validate_date(input.trim()) {
+            Ok(()) if input.trim().is_empty() => return Ok(None),
+            Ok(()) => return Ok(Some(input.trim().to_string())),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    }
+}
+
+// Ask for the line items of an invoice, at least one
+pub fn read_items() -> Result<Vec<Item>, AppError> {
     println!("{}", style("Add items to the invoice").bold());
 
     let mut items = Vec::new();
@@ -137,33 +270,7 @@ pub fn create_invoice(
             break;
         }
     }
-    let params = crate::services::invoice::CreateInvoiceParams {
-        invoice_number,
-        date: invoice_date,
-        due_date: invoice_due_date,
-        user: user.clone(),
-        client,
-        rule,
-        items,
-    };
-
-    let invoice = invoice_service
-        .create_invoice(params)
-        .map_err(AppError::from)?;
-    println!("\n{}", style("Invoice created successfully!").green());
-    println!("{}", invoice);
-    if Confirm::new()
-        .with_prompt("Generate PDF?")
-        .default(true)
-        .interact()?
-    {
-        let pdf_path = invoice_service
-            .generate_pdf(&invoice)
-            .map_err(AppError::from)?;
-        println!("PDF generated: {}", pdf_path);
-    }
-
-    Ok(())
+    Ok(items)
 }
 
 pub fn list_invoices(invoice_service: &InvoiceService) -> Result<(), AppError> {
@@ -192,8 +299,12 @@ pub fn list_invoices(invoice_service: &InvoiceService) -> Result<(), AppError> {
             style(&invoice.id).bold(),
             invoice.date
         );
-        println!("   Client: {}", invoice.client.name);
+        println!("   Client: {}", invoice.client_name());
         println!("   Total: {:.2}€", invoice.total);
+        match &invoice.paid_date {
+            Some(paid_date) => println!("   Paid: {}", paid_date),
+            None => println!("   Pending, due {}", invoice.due_date),
+        }
         println!();
     }
     if Confirm::new()
@@ -220,6 +331,14 @@ pub fn list_invoices(invoice_service: &InvoiceService) -> Result<(), AppError> {
         }
 
         println!("\n{}", invoices[selection]);
+        if invoices[selection].paid_date.is_none()
+            && Confirm::new()
+                .with_prompt("Mark as paid?")
+                .default(false)
+                .interact()?
+        {
+            mark_paid(invoice_service, &invoices[selection])?;
+        }
         if Confirm::new()
             .with_prompt("Generate PDF?")
             .default(false)
@@ -230,6 +349,135 @@ pub fn list_invoices(invoice_service: &InvoiceService) -> Result<(), AppError> {
                 .map_err(AppError::from)?;
             println!("PDF generated: {}", pdf_path);
         }
+        if Confirm::new()
+            .with_prompt("Export UBL e-invoice (Peppol BIS 3.0)?")
+            .default(false)
+            .interact()?
+        {
+            match invoice_service.export_ubl(&invoices[selection]) {
+                Ok(xml_path) => println!("UBL invoice exported: {}", xml_path),
+                Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn mark_paid(invoice_service: &InvoiceService, invoice: &Invoice) -> Result<(), AppError> {
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    loop {
+        let paid_date: String = Input::new()
+            .with_prompt("Enter payment date (YYYY-MM-DD)")
+            .default(today.clone())
+            .interact_text()?;
+
+        if let Err(e) = validate_date(&paid_date) {
+            println!("{}", style(format!("Error: {}", e)).red());
+            continue;
+        }
+        match invoice_service.mark_paid(invoice, &paid_date) {
+            Ok(_) => {
+                println!(
+                    "{}",
+                    style(format!("Invoice #{} marked as paid", invoice.id)).green()
+                );
+                return Ok(());
+            }
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    }
+}
+
+// Import invoices from UBL, CII or Facturae XML files. Invoices issued by the user
+// are stored as invoices, the ones received from suppliers as expenses
+pub fn import_invoices(
+    invoice_service: &InvoiceService,
+    expense_service: &ExpenseService,
+    user: &User,
+) -> Result<(), AppError> {
+    println!("{}", style("Import e-invoice XML").bold());
+
+    let path: String = Input::new()
+        .with_prompt("Enter the path of the UBL, CII or Facturae XML file (leave empty to go back)")
+        .allow_empty(true)
+        .interact_text()?;
+    if path.trim().is_empty() {
+        return Ok(());
+    }
+
+    let imported = match std::fs::read_to_string(path.trim()).and_then(|xml| parse_einvoice(&xml)) {
+        Ok(imported) => imported,
+        Err(e) => {
+            println!("{}", style(format!("Error: {}", e)).red());
+            return Ok(());
+        }
+    };
+
+    let mut accepted = Vec::new();
+    for entry in &imported {
+        println!(
+            "\nInvoice #{} ({}) - {}",
+            style(&entry.invoice.id).bold(),
+            entry.format,
+            entry.invoice.date
+        );
+        println!("   From: {}", entry.invoice.user.name);
+        println!("   To: {}", entry.invoice.client_name());
+        println!("   Total: {:.2}€", entry.invoice.total);
+        for loss in &entry.losses {
+            println!("   {}", style(format!("Not mapped: {}", loss)).yellow());
+        }
+        match invoice_direction(&entry.invoice, &user.cif) {
+            Ok(direction) => {
+                if direction == InvoiceDirection::Received {
+                    println!("   Received from a supplier, stored as an expense");
+                }
+                accepted.push((entry, direction));
+            }
+            Err(e) => println!("   {}", style(format!("Skipped: {}", e)).red()),
+        }
+    }
+
+    if accepted.is_empty()
+        || !Confirm::new()
+            .with_prompt(format!("Store {} imported invoice(s)?", accepted.len()))
+            .default(true)
+            .interact()?
+    {
+        return Ok(());
+    }
+
+    for (entry, direction) in accepted {
+        let stored = match direction {
+            InvoiceDirection::Issued => invoice_service.save_imported_invoice(&entry.invoice),
+            InvoiceDirection::Received => {
+                let category = ExpenseCategory::ALL[Select::new()
+                    .with_prompt(format!("Category of invoice #{}", entry.invoice.id))
+                    .items(&ExpenseCategory::ALL)
+                    .default(0)
+                    .interact()?];
+                expense_service.save_imported_expense(&entry.invoice, category)
+            }
+        };
+        match stored {
+            Ok(true) => println!(
+                "{}",
+                style(format!("Invoice #{} stored", entry.invoice.id)).green()
+            ),
+            Ok(false) => println!(
+                "{}",
+                style(format!(
+                    "Invoice #{} already exists, skipped",
+                    entry.invoice.id
+                ))
+                .yellow()
+            ),
+            Err(e) => println!(
+                "{}",
+                style(format!("Invoice #{}: {}", entry.invoice.id, e)).red()
+            ),
+        }
     }
 
     Ok(())
@@ -1,28 +1,44 @@
+use super::asset::show_assets_menu;
+use super::bank::reconcile_bank_statement;
+use super::bulk_import::show_bulk_import_menu;
 use super::client::{create_client, list_clients};
 use super::error::AppError;
-use super::invoice::{create_invoice, list_invoices};
+use super::expense::show_expenses_menu;
+use super::invoice::{create_invoice, create_simplified_invoice, import_invoices, list_invoices};
+use super::quote::show_quotes_menu;
+use super::recurring::show_recurring_menu;
+use super::report::show_reports_menu;
+use super::sepa::show_direct_debit_menu;
+use super::stats::show_stats;
+use super::time_tracking::import_tracked_hours;
 use super::user::update_user;
+use crate::core::container::ServiceContainer;
 use crate::models::User;
-use crate::repository::Storage;
-use crate::services::{ClientService, InvoiceService};
 use dialoguer::Select;
 
 // Main menu options
 const MENU_OPTIONS: &[&str] = &[
     "Create invoice",
+    "Create simplified invoice",
     "List invoices",
+    "Recurring invoices",
+    "Quotes and proformas",
+    "Import e-invoice XML",
+    "Import tracked hours",
+    "Bulk import (CSV / XLSX)",
     "Create client",
     "List clients",
+    "SEPA direct debit",
+    "Bank reconciliation",
+    "Expenses",
+    "Fixed assets",
+    "Tax reports",
+    "Stats dashboard",
     "Update user profile",
     "Exit",
 ];
 
-pub fn show_main_menu(
-    client_service: &ClientService,
-    invoice_service: &InvoiceService,
-    storage: &Storage,
-    user: &mut User,
-) -> Result<bool, AppError> {
+pub fn show_main_menu(services: &ServiceContainer, user: &mut User) -> Result<bool, AppError> {
     let selection = Select::new()
         .with_prompt("Select an option")
         .items(MENU_OPTIONS)
@@ -31,14 +47,50 @@ pub fn show_main_menu(
 
     // Handle menu selection
     match selection {
-        0 => create_invoice(client_service, invoice_service, user)?,
-        1 => list_invoices(invoice_service)?,
-        2 => create_client(client_service)?,
-        3 => list_clients(client_service)?,
-        4 => {
-            *user = update_user(storage, user)?;
+        0 => create_invoice(services.client_service(), services.invoice_service(), user)?,
+        1 => {
+            create_simplified_invoice(services.client_service(), services.invoice_service(), user)?
         }
-        5 => {
+        2 => list_invoices(services.invoice_service())?,
+        3 => show_recurring_menu(
+            services.client_service(),
+            services.recurring_service(),
+            services.invoice_service(),
+            user,
+        )?,
+        4 => show_quotes_menu(
+            services.client_service(),
+            services.quote_service(),
+            services.invoice_service(),
+            user,
+        )?,
+        5 => import_invoices(services.invoice_service(), services.expense_service(), user)?,
+        6 => import_tracked_hours(
+            services.time_tracking_service(),
+            services.client_service(),
+            services.invoice_service(),
+            user,
+        )?,
+        7 => show_bulk_import_menu(services.bulk_import_service(), user)?,
+        8 => create_client(services.client_service())?,
+        9 => list_clients(services.client_service())?,
+        10 => show_direct_debit_menu(services.client_service(), services.sepa_service(), user)?,
+        11 => reconcile_bank_statement(
+            services.reconciliation_service(),
+            services.invoice_service(),
+        )?,
+        12 => show_expenses_menu(services.expense_service())?,
+        13 => show_assets_menu(services.asset_service(), services.expense_service())?,
+        14 => show_reports_menu(
+            services.report_service(),
+            services.accounting_service(),
+            user,
+        )?,
+        15 => show_stats(services.report_service())?,
+        16 => {
+            *user = update_user(services.storage(), user)?;
+        }
+        17 => {
             println!("Thank you for using Rusty Invoices!");
             return Ok(true); // Exit application
         }
@@ -1,11 +1,22 @@
+pub mod asset;
+pub mod bank;
+pub mod bulk_import;
 pub mod client;
 pub mod config;
 pub mod error;
+pub mod expense;
 pub mod invoice;
 pub mod menu;
+pub mod quote;
+pub mod recurring;
+pub mod report;
+pub mod sepa;
+pub mod stats;
+pub mod time_tracking;
 pub mod user;
 pub mod validation;
 
 pub use error::{AppError, AppResult};
 pub use menu::show_main_menu;
+pub use recurring::generate_due_invoices;
 pub use user::create_user;
@@ -0,0 +1,214 @@
+use super::client::select_client;
+use super::error::AppError;
+use super::invoice::{read_items, read_optional_date, read_rule};
+use crate::models::{Quote, QuoteKind, QuoteStatus, User};
+use crate::services::quote::CreateQuoteParams;
+use crate::services::{ClientService, InvoiceService, QuoteService};
+use chrono::Local;
+use console::style;
+use dialoguer::{Confirm, Input, Select};
+
+const QUOTE_OPTIONS: &[&str] = &["Create quote or proforma", "List quotes", "Go back"];
+
+pub fn show_quotes_menu(
+    client_service: &ClientService,
+    quote_service: &QuoteService,
+    invoice_service: &InvoiceService,
+    user: &User,
+) -> Result<(), AppError> {
+    let selection = Select::new()
+        .with_prompt("Quotes and proformas")
+        .items(QUOTE_OPTIONS)
+        .default(0)
+        .interact()?;
+
+    match selection {
+        0 => create_quote(client_service, quote_service, user),
+        1 => list_quotes(quote_service, invoice_service, user),
+        _ => Ok(()),
+    }
+}
+
+fn create_quote(
+    client_service: &ClientService,
+    quote_service: &QuoteService,
+    user: &User,
+) -> Result<(), AppError> {
+    let kind = QuoteKind::ALL[Select::new()
+        .with_prompt("Document")
+        .items(&QuoteKind::ALL)
+        .default(0)
+        .interact()?];
+    println!(
+        "{}",
+        style(format!("Create a {}", kind.to_string().to_lowercase())).bold()
+    );
+
+    let client = match select_client(client_service)? {
+        Some(client) => client,
+        None => return Ok(()),
+    };
+    let date = read_optional_date("Enter date (YYYY-MM-DD, leave empty for today)")?;
+    let valid_until = read_optional_date(
+        "Enter the validity date (YYYY-MM-DD, leave empty for 30 days from the date)",
+    )?;
+    let rule = read_rule()?;
+    let items = read_items()?;
+
+    let quote = match quote_service.create_quote(CreateQuoteParams {
+        kind,
+        date,
+        valid_until,
+        user: user.clone(),
+        client,
+        rule,
+        items,
+    }) {
+        Ok(quote) => quote,
+        Err(e) => {
+            println!("{}", style(format!("Error: {}", e)).red());
+            return Ok(());
+        }
+    };
+    println!(
+        "\n{}",
+        style(format!("{} created successfully!", kind)).green()
+    );
+    println!("{}", quote);
+
+    if Confirm::new()
+        .with_prompt("Generate PDF?")
+        .default(true)
+        .interact()?
+    {
+        let pdf_path = quote_service.generate_pdf(&quote).map_err(AppError::from)?;
+        println!("PDF generated: {}", pdf_path);
+    }
+    Ok(())
+}
+
+fn list_quotes(
+    quote_service: &QuoteService,
+    invoice_service: &InvoiceService,
+    user: &User,
+) -> Result<(), AppError> {
+    let quotes = quote_service.list_quotes().map_err(AppError::from)?;
+    if quotes.is_empty() {
+        println!("No quotes found.");
+        return Ok(());
+    }
+
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let mut options: Vec<String> = quotes
+        .iter()
+        .map(|quote| {
+            format!(
+                "{} #{} - {} - {} - {:.2}€ - {}",
+                quote.kind,
+                quote.id,
+                quote.date,
+                quote.client.name,
+                quote.total,
+                status_text(quote, &today)
+            )
+        })
+        .collect();
+    options.push("← Go Back".to_string());
+
+    let selection = Select::new()
+        .with_prompt("Select a quote")
+        .items(&options)
+        .default(0)
+        .interact()?;
+    if selection == options.len() - 1 {
+        return Ok(());
+    }
+
+    let mut quote = quotes[selection].clone();
+    println!("\n{}", quote);
+
+    if quote.invoice_id.is_none() {
+        let answers = ["Keep as it is", "Accepted", "Rejected"];
+        let answer = Select::new()
+            .with_prompt("Client answer")
+            .items(&answers)
+            .default(0)
+            .interact()?;
+        let status = match answer {
+            1 => Some(QuoteStatus::Accepted),
+            2 => Some(QuoteStatus::Rejected),
+            _ => None,
+        };
+        if let Some(status) = status {
+            quote = quote_service
+                .set_status(&quote, status)
+                .map_err(AppError::from)?;
+            println!(
+                "{}",
+                style(format!("{} #{} marked as {}", quote.kind, quote.id, status)).green()
+            );
+        }
+    }
+
+    if quote.status == QuoteStatus::Accepted
+        && quote.invoice_id.is_none()
+        && Confirm::new()
+            .with_prompt("Create the invoice now?")
+            .default(true)
+            .interact()?
+    {
+        convert_to_invoice(quote_service, invoice_service, &quote, user)?;
+    }
+
+    if Confirm::new()
+        .with_prompt("Generate PDF?")
+        .default(false)
+        .interact()?
+    {
+        let pdf_path = quote_service.generate_pdf(&quote).map_err(AppError::from)?;
+        println!("PDF generated: {}", pdf_path);
+    }
+    Ok(())
+}
+
+// Pending quotes past their validity date are shown as expired
+fn status_text(quote: &Quote, today: &str) -> String {
+    match (&quote.invoice_id, quote.status) {
+        (Some(invoice_id), _) => format!("Invoiced #{}", invoice_id),
+        (None, QuoteStatus::Pending) if quote.valid_until.as_str() < today => "Expired".to_string(),
+        (None, status) => status.to_string(),
+    }
+}
+
+fn convert_to_invoice(
+    quote_service: &QuoteService,
+    invoice_service: &InvoiceService,
+    quote: &Quote,
+    user: &User,
+) -> Result<(), AppError> {
+    let number: String = Input::new()
+        .with_prompt("Enter invoice number (leave empty for auto-generated)")
+        .allow_empty(true)
+        .interact_text()?;
+    let date = read_optional_date("Enter invoice date (YYYY-MM-DD, leave empty for today)")?;
+    let number = Some(number.trim().to_string()).filter(|number| !number.is_empty());
+
+    match quote_service.convert_to_invoice(quote, invoice_service, user, number, date) {
+        Ok(invoice) => {
+            println!("\n{}", style("Invoice created successfully!").green());
+            println!("{}", invoice);
+            if Confirm::new()
+                .with_prompt("Generate invoice PDF?")
+                .default(true)
+                .interact()?
+            {
+                let pdf_path = invoice_service
+                    .generate_pdf(&invoice)
+                    .map_err(AppError::from)?;
+                println!("PDF generated: {}", pdf_path);
+            }
+        }
+        Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+    }
+    Ok(())
+}
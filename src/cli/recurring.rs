@@ -0,0 +1,167 @@
+use super::client::select_client;
+use super::error::AppError;
+use super::invoice::{read_items, read_optional_date, read_rule};
+use crate::models::{Frequency, Invoice, User};
+use crate::services::recurring::CreateRecurringParams;
+use crate::services::{ClientService, InvoiceService, RecurringService};
+use chrono::{Datelike, Local};
+use console::style;
+use dialoguer::{Confirm, Input, Select};
+
+const RECURRING_OPTIONS: &[&str] = &[
+    "Create recurring invoice",
+    "List recurring invoices",
+    "Generate due invoices",
+    "Go back",
+];
+
+pub fn show_recurring_menu(
+    client_service: &ClientService,
+    recurring_service: &RecurringService,
+    invoice_service: &InvoiceService,
+    user: &User,
+) -> Result<(), AppError> {
+    let selection = Select::new()
+        .with_prompt("Recurring invoices")
+        .items(RECURRING_OPTIONS)
+        .default(0)
+        .interact()?;
+
+    match selection {
+        0 => create_recurring_invoice(client_service, recurring_service),
+        1 => list_recurring_invoices(recurring_service),
+        2 => {
+            // Show what is due first and create it once confirmed
+            let due = generate_due_invoices(recurring_service, invoice_service, user, true)?;
+            if due > 0
+                && Confirm::new()
+                    .with_prompt(format!("Create {} invoice(s)?", due))
+                    .default(true)
+                    .interact()?
+            {
+                generate_due_invoices(recurring_service, invoice_service, user, false)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn create_recurring_invoice(
+    client_service: &ClientService,
+    recurring_service: &RecurringService,
+) -> Result<(), AppError> {
+    println!("{}", style("Create a recurring invoice").bold());
+
+    let client = match select_client(client_service)? {
+        Some(client) => client,
+        None => return Ok(()),
+    };
+
+    let frequency = Frequency::ALL[Select::new()
+        .with_prompt("Frequency")
+        .items(&Frequency::ALL)
+        .default(0)
+        .interact()?];
+
+    let today = Local::now().date_naive();
+    let day_of_month: u32 = loop {
+        let day: u32 = Input::new()
+            .with_prompt("Day of the month to invoice (1-31, shorter months use their last day)")
+            .default(today.day())
+            .interact_text()?;
+        if (1..=31).contains(&day) {
+            break day;
+        }
+        println!("{}", style("Error: the day must be between 1 and 31").red());
+    };
+
+    let start_date = read_optional_date(
+        "Enter the first date to invoice from (YYYY-MM-DD, leave empty for today)",
+    )?;
+    let end_date =
+        read_optional_date("Enter the end date (YYYY-MM-DD, leave empty to keep invoicing)")?;
+
+    let rule = read_rule()?;
+    let items = read_items()?;
+
+    match recurring_service.create_template(CreateRecurringParams {
+        client,
+        rule,
+        items,
+        frequency,
+        day_of_month,
+        start_date: start_date.unwrap_or_else(|| today.format("%Y-%m-%d").to_string()),
+        end_date,
+    }) {
+        Ok(template) => println!(
+            "{}",
+            style(format!("Recurring invoice created: {}", template)).green()
+        ),
+        Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+    }
+    Ok(())
+}
+
+fn list_recurring_invoices(recurring_service: &RecurringService) -> Result<(), AppError> {
+    let templates = recurring_service.list_templates().map_err(AppError::from)?;
+
+    if templates.is_empty() {
+        println!("No recurring invoices found.");
+        return Ok(());
+    }
+
+    for (i, template) in templates.iter().enumerate() {
+        println!("{}. {}", i + 1, template);
+        for item in &template.items {
+            println!("   {}", item);
+        }
+    }
+    Ok(())
+}
+
+// Create, or only list with `dry_run`, the invoices due up to today. Returns how many
+pub fn generate_due_invoices(
+    recurring_service: &RecurringService,
+    invoice_service: &InvoiceService,
+    user: &User,
+    dry_run: bool,
+) -> Result<usize, AppError> {
+    let invoices = recurring_service
+        .generate_due(invoice_service, user, Local::now().date_naive(), dry_run)
+        .map_err(AppError::from)?;
+
+    if invoices.is_empty() {
+        println!("No recurring invoices are due.");
+        return Ok(0);
+    }
+
+    if dry_run {
+        println!("{}", style("Invoices due").bold());
+    }
+    for invoice in &invoices {
+        print_invoice(invoice, dry_run);
+    }
+    if !dry_run {
+        println!(
+            "{}",
+            style(format!("{} invoice(s) created", invoices.len())).green()
+        );
+    }
+    Ok(invoices.len())
+}
+
+fn print_invoice(invoice: &Invoice, dry_run: bool) {
+    let number = if dry_run {
+        String::new()
+    } else {
+        format!("#{} ", style(&invoice.id).bold())
+    };
+    println!(
+        "{}{} - {} - {:.2}€",
+        number,
+        invoice.date,
+        invoice.client_name(),
+        invoice.total
+    );
+}
@@ -0,0 +1,473 @@
+use super::error::AppError;
+use crate::models::User;
+use crate::services::modelo303::Modelo303;
+use crate::services::modelo347::{Modelo347, THRESHOLD};
+use crate::services::modelo349::{Modelo349, OperationKey};
+use crate::services::money::{format_cents, parse_cents, to_cents};
+use crate::services::period::{DateRange, Quarter};
+use crate::services::{AccountingService, ReportService};
+use chrono::Datelike;
+use console::style;
+use dialoguer::{Confirm, Input, Select};
+use std::collections::BTreeMap;
+
+const REPORT_OPTIONS: &[&str] = &[
+    "Modelo 303 (IVA quarterly)",
+    "Modelo 130 (IRPF quarterly pre-payment)",
+    "Modelo 347 (annual operations with third parties)",
+    "Modelo 349 (intra-community operations)",
+    "Libro registro de facturas expedidas",
+    "Accounting journal (asientos)",
+    "Plain-text accounting (ledger / beancount)",
+    "Excel workbook (invoices, tax by rate, clients)",
+    "Go back",
+];
+
+pub fn show_reports_menu(
+    report_service: &ReportService,
+    accounting_service: &AccountingService,
+    user: &User,
+) -> Result<(), AppError> {
+    let selection = Select::new()
+        .with_prompt("Select a report")
+        .items(REPORT_OPTIONS)
+        .default(0)
+        .interact()?;
+
+    match selection {
+        0 => modelo303(report_service, user),
+        1 => modelo130(report_service, user),
+        2 => modelo347(report_service, user),
+        3 => modelo349(report_service, user),
+        4 => issued_invoices_ledger(report_service),
+        5 => accounting_journal(accounting_service),
+        6 => plain_text_accounting(accounting_service),
+        7 => excel_workbook(report_service),
+        _ => Ok(()),
+    }
+}
+
+// Ask for the quarter to report, defaulting to the last closed one
+fn select_quarter() -> Result<Quarter, AppError> {
+    let default = Quarter::previous(chrono::Local::now().date_naive());
+
+    let year: i32 = Input::new()
+        .with_prompt("Enter year")
+        .default(default.year)
+        .interact_text()?;
+
+    loop {
+        let quarter: u32 = Input::new()
+            .with_prompt("Enter quarter (1-4)")
+            .default(default.quarter)
+            .interact_text()?;
+
+        match Quarter::new(year, quarter) {
+            Ok(quarter) => return Ok(quarter),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    }
+}
+
+fn modelo303(report_service: &ReportService, user: &User) -> Result<(), AppError> {
+    let quarter = select_quarter()?;
+    let report = report_service.modelo303(quarter).map_err(AppError::from)?;
+
+    print_modelo303(&report);
+
+    if Confirm::new()
+        .with_prompt("Export JSON and AEAT presentation file?")
+        .default(true)
+        .interact()?
+    {
+        let paths = report_service
+            .export_modelo303(&report, user)
+            .map_err(AppError::from)?;
+        for path in paths {
+            println!("Report exported: {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_modelo303(report: &Modelo303) {
+    println!(
+        "\n{}",
+        style(format!("Modelo 303 - {}", report.quarter)).bold()
+    );
+    println!("Invoices included: {}", report.invoice_count);
+    print_boxes(report.rows(), &report.warnings);
+}
+
+fn print_boxes(rows: Vec<(u16, &'static str, String)>, warnings: &[String]) {
+    println!();
+    for (number, description, value) in rows {
+        println!("[{:>3}] {:<55} {:>15}", number, description, value);
+    }
+
+    for warning in warnings {
+        println!("{}", style(warning).yellow());
+    }
+    println!();
+}
+
+fn modelo130(report_service: &ReportService, user: &User) -> Result<(), AppError> {
+    let quarter = select_quarter()?;
+
+    // Recorded expenses are the starting point, they can be adjusted for anything not tracked
+    let recorded = report_service
+        .deductible_expenses(quarter)
+        .map_err(AppError::from)?;
+    let expenses: f32 = Input::new()
+        .with_prompt(format!(
+            "Enter deductible expenses of {} (€, this quarter only)",
+            quarter
+        ))
+        .default(recorded as f32 / 100.0)
+        .interact_text()?;
+
+    let previous_year_net = loop {
+        let input: String = Input::new()
+            .with_prompt(
+                "Enter previous year net income for the art. 110.3.c reduction (leave empty if above 12,000€)",
+            )
+            .allow_empty(true)
+            .interact_text()?;
+        if input.trim().is_empty() {
+            break None;
+        }
+        match parse_cents(&input.replace(',', ".")) {
+            Some(cents) => break Some(cents),
+            None => println!("{}", style("Error: Enter an amount such as 8500.00").red()),
+        }
+    };
+
+    let report = report_service
+        .modelo130(quarter, to_cents(expenses), previous_year_net)
+        .map_err(AppError::from)?;
+
+    println!(
+        "\n{}",
+        style(format!("Modelo 130 - {}", report.quarter)).bold()
+    );
+    print_boxes(report.rows(), &report.warnings);
+
+    if Confirm::new()
+        .with_prompt(
+            "Export JSON and AEAT presentation file? (the filing is stored for later quarters)",
+        )
+        .default(true)
+        .interact()?
+    {
+        let paths = report_service
+            .export_modelo130(&report, user)
+            .map_err(AppError::from)?;
+        for path in paths {
+            println!("Report exported: {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn modelo347(report_service: &ReportService, user: &User) -> Result<(), AppError> {
+    let year: i32 = Input::new()
+        .with_prompt("Enter year")
+        .default(chrono::Local::now().year() - 1)
+        .interact_text()?;
+
+    let report = report_service.modelo347(year).map_err(AppError::from)?;
+    print_modelo347(&report);
+
+    if report.clients.is_empty() {
+        return Ok(());
+    }
+
+    if Confirm::new()
+        .with_prompt("Export JSON and AEAT presentation file?")
+        .default(true)
+        .interact()?
+    {
+        let paths = report_service
+            .export_modelo347(&report, user)
+            .map_err(AppError::from)?;
+        for path in paths {
+            println!("Report exported: {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_modelo347(report: &Modelo347) {
+    println!(
+        "\n{}",
+        style(format!("Modelo 347 - {}", report.year)).bold()
+    );
+    println!(
+        "Clients above {}€: {} ({} below the threshold)",
+        format_cents(THRESHOLD),
+        report.clients.len(),
+        report.below_threshold
+    );
+    println!();
+
+    if report.clients.is_empty() {
+        println!("No clients have to be declared.");
+        return;
+    }
+
+    println!(
+        "{:<10} {:<25} {:>11} {:>11} {:>11} {:>11} {:>12}",
+        "CIF/NIF", "Name", "1T", "2T", "3T", "4T", "Total"
+    );
+    for client in &report.clients {
+        let name: String = client.name.chars().take(25).collect();
+        println!(
+            "{:<10} {:<25} {:>11} {:>11} {:>11} {:>11} {:>12}",
+            client.cif,
+            name,
+            format_cents(client.quarters[0]),
+            format_cents(client.quarters[1]),
+            format_cents(client.quarters[2]),
+            format_cents(client.quarters[3]),
+            format_cents(client.total)
+        );
+        if let Some(issue) = &client.issue {
+            println!("{}", style(format!("  {}", issue)).yellow());
+        }
+        if client.province.is_none() {
+            println!(
+                "{}",
+                style("  No Spanish postal code in the address, province left blank").yellow()
+            );
+        }
+    }
+    println!("Total: {}€", format_cents(report.total()));
+    println!();
+}
+
+fn modelo349(report_service: &ReportService, user: &User) -> Result<(), AppError> {
+    let quarter = select_quarter()?;
+
+    // The invoices do not say whether goods or services were supplied
+    let operators = report_service
+        .modelo349_operators(quarter)
+        .map_err(AppError::from)?;
+    let key_names: Vec<&str> = OperationKey::ALL.iter().map(|k| k.description()).collect();
+    let mut keys = BTreeMap::new();
+    for (vat_id, name) in operators {
+        let selection = Select::new()
+            .with_prompt(format!("Operation key for {} ({})", name, vat_id))
+            .items(&key_names)
+            .default(0)
+            .interact()?;
+        keys.insert(vat_id, OperationKey::ALL[selection]);
+    }
+
+    let report = report_service
+        .modelo349(quarter, &keys)
+        .map_err(AppError::from)?;
+    print_modelo349(&report);
+
+    if report.operations.is_empty() && report.rectifications.is_empty() {
+        return Ok(());
+    }
+
+    if Confirm::new()
+        .with_prompt("Export JSON and AEAT presentation file? (the declared amounts are stored)")
+        .default(true)
+        .interact()?
+    {
+        let paths = report_service
+            .export_modelo349(&report, user)
+            .map_err(AppError::from)?;
+        for path in paths {
+            println!("Report exported: {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_modelo349(report: &Modelo349) {
+    println!(
+        "\n{}",
+        style(format!("Modelo 349 - {}", report.quarter)).bold()
+    );
+    println!();
+
+    if report.operations.is_empty() {
+        println!("No intra-community operations in this quarter.");
+    }
+    for op in &report.operations {
+        println!(
+            "{:<16} {:<35} {} {:>12}€",
+            op.vat_id,
+            op.name.chars().take(35).collect::<String>(),
+            op.key.code(),
+            format_cents(op.amount)
+        );
+    }
+
+    if !report.rectifications.is_empty() {
+        println!("\n{}", style("Rectifications").bold());
+        for r in &report.rectifications {
+            println!(
+                "{:<16} {:<35} {} {}: {}€ -> {}€",
+                r.vat_id,
+                r.name.chars().take(35).collect::<String>(),
+                r.key.code(),
+                r.quarter,
+                format_cents(r.declared),
+                format_cents(r.rectified)
+            );
+        }
+    }
+
+    for warning in &report.warnings {
+        println!("{}", style(warning).yellow());
+    }
+    println!();
+}
+
+// Ask for a quarter or a whole year
+fn select_range() -> Result<DateRange, AppError> {
+    let period = Select::new()
+        .with_prompt("Select period")
+        .items(&["Quarter", "Full year"])
+        .default(0)
+        .interact()?;
+    if period == 0 {
+        return Ok(DateRange::from(select_quarter()?));
+    }
+
+    let year: i32 = Input::new()
+        .with_prompt("Enter year")
+        .default(chrono::Local::now().year() - 1)
+        .interact_text()?;
+    Ok(DateRange::year(year))
+}
+
+fn issued_invoices_ledger(report_service: &ReportService) -> Result<(), AppError> {
+    let range = select_range()?;
+
+    let entries = report_service
+        .issued_invoices_ledger(range)
+        .map_err(AppError::from)?;
+
+    println!(
+        "\n{}",
+        style(format!("Libro registro de facturas expedidas - {}", range)).bold()
+    );
+    if entries.is_empty() {
+        println!("No invoices issued in this period.");
+        return Ok(());
+    }
+    for entry in &entries {
+        println!(
+            "{} {:<12} {:<10} {:<25} {:>10}€ {:>10}€ {:>10}€",
+            entry.date.format("%d/%m/%Y"),
+            entry.number,
+            entry.nif,
+            entry.name.chars().take(25).collect::<String>(),
+            format_cents(entry.base),
+            format_cents(entry.iva),
+            format_cents(entry.total)
+        );
+    }
+    println!();
+
+    if Confirm::new()
+        .with_prompt("Export as CSV and XLSX?")
+        .default(true)
+        .interact()?
+    {
+        let paths = report_service
+            .export_issued_invoices_ledger(&entries, range)
+            .map_err(AppError::from)?;
+        for path in paths {
+            println!("Report exported: {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn accounting_journal(accounting_service: &AccountingService) -> Result<(), AppError> {
+    let range = select_range()?;
+    let entries = accounting_service.journal(range).map_err(AppError::from)?;
+
+    println!(
+        "\n{}",
+        style(format!("Accounting journal - {}", range)).bold()
+    );
+    if entries.is_empty() {
+        println!("No invoices issued in this period.");
+        return Ok(());
+    }
+    for entry in &entries {
+        println!(
+            "{} {} {}",
+            style(format!("#{}", entry.number)).bold(),
+            entry.date.format("%d/%m/%Y"),
+            entry.description
+        );
+        for line in &entry.lines {
+            println!(
+                "   {:<12} {:<35} {:>10} {:>10}",
+                line.account,
+                line.account_name.chars().take(35).collect::<String>(),
+                if line.debit != 0 {
+                    format_cents(line.debit)
+                } else {
+                    String::new()
+                },
+                if line.credit != 0 {
+                    format_cents(line.credit)
+                } else {
+                    String::new()
+                }
+            );
+        }
+    }
+    println!();
+
+    if Confirm::new()
+        .with_prompt("Export as CSV, ContaPlus and A3 files?")
+        .default(true)
+        .interact()?
+    {
+        let paths = accounting_service
+            .export_journal(&entries, range)
+            .map_err(AppError::from)?;
+        for path in paths {
+            println!("Journal exported: {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+fn plain_text_accounting(accounting_service: &AccountingService) -> Result<(), AppError> {
+    let range = select_range()?;
+    let paths = accounting_service
+        .export_plain_text(range)
+        .map_err(AppError::from)?;
+    for path in paths {
+        println!("Exported: {}", path);
+    }
+    Ok(())
+}
+
+fn excel_workbook(report_service: &ReportService) -> Result<(), AppError> {
+    let year: i32 = Input::new()
+        .with_prompt("Enter year")
+        .default(chrono::Local::now().year())
+        .interact_text()?;
+    let path = report_service.export_xlsx(year).map_err(AppError::from)?;
+    println!("Exported: {}", path);
+    println!("One sheet per quarter, the taxes by rate and a summary per client");
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use super::client::select_client;
+use super::error::AppError;
+use super::validation::{validate_bic, validate_date, validate_iban};
+use crate::models::{SepaMandate, SepaScheme, User};
+use crate::services::iban::{format_iban, normalize_iban, spanish_bank_name};
+use crate::services::sepa::{creditor_identifier, sequence_types};
+use crate::services::{ClientService, SepaService};
+use chrono::{Duration, Local};
+use console::style;
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+
+const SEPA_OPTIONS: &[&str] = &[
+    "Register client mandate",
+    "Create collection batch",
+    "Go back",
+];
+
+pub fn show_direct_debit_menu(
+    client_service: &ClientService,
+    sepa_service: &SepaService,
+    user: &User,
+) -> Result<(), AppError> {
+    let selection = Select::new()
+        .with_prompt("SEPA direct debit")
+        .items(SEPA_OPTIONS)
+        .default(0)
+        .interact()?;
+
+    match selection {
+        0 => register_mandate(client_service),
+        1 => create_collection_batch(sepa_service, user),
+        _ => Ok(()),
+    }
+}
+
+fn register_mandate(client_service: &ClientService) -> Result<(), AppError> {
+    let Some(client) = select_client(client_service)? else {
+        return Ok(());
+    };
+    if let Some(mandate) = &client.mandate {
+        println!("Current mandate: {}", mandate);
+    }
+
+    let id: String = Input::new()
+        .with_prompt("Enter the mandate reference")
+        .default(
+            client
+                .mandate
+                .as_ref()
+                .map(|m| m.id.clone())
+                .unwrap_or_default(),
+        )
+        .validate_with(|input: &String| -> Result<(), &str> {
+            if input.trim().is_empty() || input.trim().len() > 35 {
+                Err("The reference must have between 1 and 35 characters")
+            } else {
+                Ok(())
+            }
+        })
+        .interact_text()?;
+
+    let signature_date = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter the signature date (YYYY-MM-DD)")
+            .default(Local::now().format("%Y-%m-%d").to_string())
+            .interact_text()?;
+
+        match validate_date(&input) {
+            Ok(()) => break input,
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    let iban = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter the client's IBAN")
+            .interact_text()?;
+
+        match validate_iban(&input) {
+            Ok(()) if !input.trim().is_empty() => break normalize_iban(&input),
+            Ok(()) => println!("{}", style("Error: The IBAN is required").red()),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+    if let Some(bank) = spanish_bank_name(&iban) {
+        println!("Bank: {}", bank);
+    }
+
+    let bic = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter the BIC of the client's bank (optional, press enter to skip)")
+            .allow_empty(true)
+            .interact_text()?;
+
+        match validate_bic(&input) {
+            Ok(()) if input.trim().is_empty() => break None,
+            Ok(()) => break Some(input.trim().to_uppercase()),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    let schemes: Vec<String> = SepaScheme::ALL
+        .iter()
+        .map(|scheme| match scheme {
+            SepaScheme::Core => "CORE (consumers and businesses)".to_string(),
+            SepaScheme::B2b => "B2B (businesses only, no refunds)".to_string(),
+        })
+        .collect();
+    let scheme = Select::new()
+        .with_prompt("Select the scheme of the mandate")
+        .items(&schemes)
+        .default(0)
+        .interact()?;
+
+    let mandate = SepaMandate::new(id, signature_date, iban, bic, SepaScheme::ALL[scheme]);
+    let client = client_service
+        .set_mandate(&client, mandate)
+        .map_err(AppError::from)?;
+
+    println!("{}", style("Mandate registered successfully!").green());
+    if let Some(mandate) = &client.mandate {
+        println!("{}: {}", client.name, mandate);
+    }
+
+    Ok(())
+}
+
+fn create_collection_batch(sepa_service: &SepaService, user: &User) -> Result<(), AppError> {
+    let Some(iban) = &user.iban else {
+        println!(
+            "{}",
+            style("Add your IBAN to the user profile to collect direct debits.").yellow()
+        );
+        return Ok(());
+    };
+    println!(
+        "Creditor identifier: {}  IBAN: {}",
+        creditor_identifier(&user.cif),
+        format_iban(iban)
+    );
+
+    let candidates = sepa_service
+        .direct_debit_candidates()
+        .map_err(AppError::from)?;
+    if candidates.is_empty() {
+        println!("No unpaid invoices of clients with a SEPA mandate.");
+        return Ok(());
+    }
+
+    let items: Vec<String> = candidates
+        .iter()
+        .zip(sequence_types(&candidates))
+        .map(|(debit, sequence)| {
+            format!(
+                "#{} {} - {} - {:.2}€ ({} {})",
+                debit.invoice.id,
+                debit.invoice.date,
+                debit.invoice.client_name(),
+                debit.invoice.total,
+                debit.mandate.scheme,
+                sequence
+            )
+        })
+        .collect();
+    let selected = MultiSelect::new()
+        .with_prompt("Select the invoices to collect (space to select, enter to confirm)")
+        .items(&items)
+        .interact()?;
+    if selected.is_empty() {
+        return Ok(());
+    }
+
+    // CORE collections must reach the bank at least one business day in advance
+    let collection_date = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter the collection date (YYYY-MM-DD)")
+            .default(
+                (Local::now() + Duration::days(2))
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            )
+            .interact_text()?;
+
+        match validate_date(&input) {
+            Ok(()) => break input,
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    let debits: Vec<_> = selected
+        .into_iter()
+        .map(|i| candidates[i].clone())
+        .collect();
+    let total: f32 = debits.iter().map(|debit| debit.invoice.total).sum();
+    if !Confirm::new()
+        .with_prompt(format!(
+            "Collect {} invoices for {:.2}€ on {}?",
+            debits.len(),
+            total,
+            collection_date
+        ))
+        .default(true)
+        .interact()?
+    {
+        return Ok(());
+    }
+
+    let path = sepa_service
+        .export_direct_debit(user, &debits, &collection_date)
+        .map_err(AppError::from)?;
+    println!("{}", style("Direct debit batch created!").green());
+    println!("Upload it to your bank: {}", path);
+
+    Ok(())
+}
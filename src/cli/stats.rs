@@ -0,0 +1,157 @@
+use super::error::AppError;
+use crate::services::money::format_cents;
+use crate::services::stats::{bar, change_percent, Stats, MONTHS};
+use crate::services::ReportService;
+use chrono::Datelike;
+use console::style;
+use dialoguer::{Confirm, Input};
+
+// Width in terminal cells of the longest bar
+const BAR_WIDTH: usize = 40;
+
+pub fn show_stats(report_service: &ReportService) -> Result<(), AppError> {
+    let year: i32 = Input::new()
+        .with_prompt("Enter year")
+        .default(chrono::Local::now().year())
+        .interact_text()?;
+
+    let stats = report_service.stats(year).map_err(AppError::from)?;
+    print_stats(&stats);
+
+    if Confirm::new()
+        .with_prompt("Export as JSON?")
+        .default(false)
+        .interact()?
+    {
+        let path = report_service
+            .export_stats(&stats)
+            .map_err(AppError::from)?;
+        println!("Stats exported: {}", path);
+    }
+
+    Ok(())
+}
+
+fn print_stats(stats: &Stats) {
+    let current = &stats.current;
+    let previous = &stats.previous;
+
+    println!(
+        "\n{}",
+        style(format!("Income summary {}", stats.year())).bold()
+    );
+    println!(
+        "Revenue:        {:>12}€  {}",
+        format_cents(current.revenue),
+        versus(current.revenue, previous.revenue, previous.year)
+    );
+    println!(
+        "Expenses:       {:>12}€  {}",
+        format_cents(current.expenses),
+        versus(current.expenses, previous.expenses, previous.year)
+    );
+    println!(
+        "Profit:         {:>12}€  {}",
+        format_cents(current.profit()),
+        versus(current.profit(), previous.profit(), previous.year)
+    );
+    println!("IVA collected:  {:>12}€", format_cents(current.iva));
+    println!("IRPF withheld:  {:>12}€", format_cents(current.irpf));
+    println!("Invoices:       {:>12}", current.invoice_count);
+    match stats.average_days_to_pay {
+        Some(days) => println!(
+            "Days to pay:    {:>12.1}  (average of {} paid invoices)",
+            days, stats.paid_count
+        ),
+        None => println!("Days to pay:    {:>12}  (no paid invoices)", "-"),
+    }
+
+    if current.invoice_count == 0 && previous.invoice_count == 0 {
+        println!("\nNo invoices in {} or {}.\n", current.year, previous.year);
+        return;
+    }
+
+    // Both years share the scale so the bars can be compared
+    println!(
+        "\n{}  {} {} / {}",
+        style("Revenue per month").bold(),
+        style("█").green(),
+        current.year,
+        style(previous.year).dim()
+    );
+    let max = current
+        .months
+        .iter()
+        .chain(previous.months.iter())
+        .copied()
+        .max()
+        .unwrap_or(0);
+    for (month, name) in MONTHS.iter().enumerate() {
+        print_row(name, current.months[month], max, false);
+        print_row("", previous.months[month], max, true);
+    }
+
+    println!("\n{}", style("Revenue per quarter").bold());
+    let quarters = current.quarters();
+    let previous_quarters = previous.quarters();
+    let max = quarters
+        .iter()
+        .chain(previous_quarters.iter())
+        .copied()
+        .max()
+        .unwrap_or(0);
+    for quarter in 0..4 {
+        print_row(&format!("{}T", quarter + 1), quarters[quarter], max, false);
+        print_row("", previous_quarters[quarter], max, true);
+    }
+
+    println!("\n{}", style("Top clients").bold());
+    let max = stats.clients.first().map(|c| c.revenue).unwrap_or(0);
+    for client in stats.top_clients() {
+        println!(
+            "{:<30} {} {:>12}€  ({} invoices)",
+            truncate(&client.name, 30),
+            style(padded_bar(client.revenue, max)).green(),
+            format_cents(client.revenue),
+            client.invoice_count
+        );
+    }
+    if stats.clients.len() > stats.top_clients().len() {
+        println!(
+            "... and {} more clients",
+            stats.clients.len() - stats.top_clients().len()
+        );
+    }
+    println!();
+}
+
+fn print_row(label: &str, value: i64, max: i64, previous_year: bool) {
+    let bar = padded_bar(value, max);
+    let amount = format!("{:>12}€", format_cents(value));
+    if previous_year {
+        println!("{:<4} {} {}", label, style(bar).dim(), style(amount).dim());
+    } else {
+        println!("{:<4} {} {}", label, style(bar).green(), amount);
+    }
+}
+
+fn padded_bar(value: i64, max: i64) -> String {
+    format!("{:<width$}", bar(value, max, BAR_WIDTH), width = BAR_WIDTH)
+}
+
+fn versus(current: i64, previous: i64, year: i32) -> String {
+    match change_percent(current, previous) {
+        Some(change) => format!("{:+.1}% vs {}", change, year),
+        None => format!("no data for {}", year),
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut truncated: String = text.chars().take(width - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
@@ -0,0 +1,219 @@
+use super::error::AppError;
+use super::invoice::read_rule;
+use super::validation::validate_date;
+use crate::models::{Client, User};
+use crate::services::invoice::CreateInvoiceParams;
+use crate::services::money::to_cents;
+use crate::services::period::DateRange;
+use crate::services::time_tracking::{
+    draft_invoices, format_hours, AmbiguousDates, DateOrder, ItemGrouping, ProjectKey, TimeEntry,
+};
+use crate::services::{ClientService, InvoiceService, TimeTrackingService};
+use chrono::{Datelike, Local, NaiveDate};
+use console::style;
+use dialoguer::{Confirm, Input, Select};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Create invoices from the hours exported by Toggl Track, Clockify or Harvest
+pub fn import_tracked_hours(
+    time_tracking_service: &TimeTrackingService,
+    client_service: &ClientService,
+    invoice_service: &InvoiceService,
+    user: &User,
+) -> Result<(), AppError> {
+    println!("{}", style("Import tracked hours").bold());
+
+    let path: String = Input::new()
+        .with_prompt(
+            "Enter the path of the Toggl, Clockify or Harvest CSV export (leave empty to go back)",
+        )
+        .allow_empty(true)
+        .interact_text()?;
+    if path.trim().is_empty() {
+        return Ok(());
+    }
+
+    let path = Path::new(path.trim());
+    let mut sheet = time_tracking_service.read_export(path, None);
+    if let Err(e) = &sheet {
+        if e.get_ref().is_some_and(|e| e.is::<AmbiguousDates>()) {
+            println!("{}", style(e).yellow());
+            let order = DateOrder::ALL[Select::new()
+                .with_prompt("Date order of the export")
+                .items(&DateOrder::ALL)
+                .default(0)
+                .interact()?];
+            sheet = time_tracking_service.read_export(path, Some(order));
+        }
+    }
+    let sheet = match sheet {
+        Ok(sheet) => sheet,
+        Err(e) => {
+            println!("{}", style(format!("Error: {}", e)).red());
+            return Ok(());
+        }
+    };
+    println!(
+        "{} entries read from a {} export",
+        sheet.entries.len(),
+        sheet.tracker
+    );
+
+    let range = read_range()?;
+    let entries: Vec<_> = sheet
+        .entries
+        .into_iter()
+        .filter(|entry| entry.billable && range.contains(&entry.date))
+        .collect();
+    if entries.is_empty() {
+        println!("No billable hours from {}.", range);
+        return Ok(());
+    }
+
+    let mapping = map_projects(time_tracking_service, client_service, &entries)?;
+    if mapping.is_empty() {
+        println!("No project was assigned to a client.");
+        return Ok(());
+    }
+
+    let grouping = ItemGrouping::ALL[Select::new()
+        .with_prompt("Invoice items")
+        .items(&ItemGrouping::ALL)
+        .default(0)
+        .interact()?];
+    let hourly_rate: f32 = loop {
+        let rate: f32 = Input::new()
+            .with_prompt("Enter the hourly rate (€)")
+            .interact_text()?;
+        if rate > 0.0 {
+            break rate;
+        }
+        println!("{}", style("Error: The hourly rate must be positive").red());
+    };
+
+    let drafts = draft_invoices(&entries, range, &mapping, grouping, to_cents(hourly_rate));
+    let rule = read_rule()?;
+
+    let mut created = 0;
+    for draft in drafts {
+        println!(
+            "\n{} - {} h in {} entries",
+            style(&draft.client.name).bold(),
+            format_hours(draft.seconds),
+            draft.entries
+        );
+        for item in &draft.items {
+            println!("   {}", item);
+        }
+
+        if !Confirm::new()
+            .with_prompt("Create this invoice?")
+            .default(true)
+            .interact()?
+        {
+            continue;
+        }
+        match invoice_service.create_invoice(CreateInvoiceParams {
+            invoice_number: None,
+            date: None,
+            due_date: None,
+            user: user.clone(),
+            client: draft.client,
+            rule: rule.clone(),
+            items: draft.items,
+            recurring_id: None,
+            quote_id: None,
+        }) {
+            Ok(invoice) => {
+                created += 1;
+                println!(
+                    "{}",
+                    style(format!(
+                        "Invoice #{} created, {:.2}€",
+                        invoice.id, invoice.total
+                    ))
+                    .green()
+                );
+            }
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    }
+
+    println!(
+        "{}",
+        style(format!("{} invoice(s) created", created)).green()
+    );
+    Ok(())
+}
+
+// Ask for the period to invoice, the previous month by default
+fn read_range() -> Result<DateRange, AppError> {
+    let first_of_month = Local::now().date_naive().with_day(1).unwrap();
+    let to = first_of_month.pred_opt().unwrap();
+    let from = to.with_day(1).unwrap();
+
+    loop {
+        let range = DateRange {
+            from: read_date("Invoice hours from (YYYY-MM-DD)", from)?,
+            to: read_date("Invoice hours to (YYYY-MM-DD)", to)?,
+        };
+        if range.from <= range.to {
+            return Ok(range);
+        }
+        println!(
+            "{}",
+            style("Error: The end date must not be before the start date").red()
+        );
+    }
+}
+
+fn read_date(prompt: &str, default: NaiveDate) -> Result<NaiveDate, AppError> {
+    loop {
+        let input: String = Input::new()
+            .with_prompt(prompt)
+            .default(default.format("%Y-%m-%d").to_string())
+            .interact_text()?;
+        if let Err(e) = validate_date(&input) {
+            println!("{}", style(format!("Error: {}", e)).red());
+            continue;
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+            return Ok(date);
+        }
+    }
+}
+
+// Projects found in the local clients by name or tax ID are assigned automatically,
+// the user chooses the client of the rest or skips them
+fn map_projects(
+    time_tracking_service: &TimeTrackingService,
+    client_service: &ClientService,
+    entries: &[TimeEntry],
+) -> Result<BTreeMap<ProjectKey, Client>, AppError> {
+    let projects = time_tracking_service
+        .map_projects(entries)
+        .map_err(AppError::from)?;
+    let clients = client_service.list_clients().map_err(AppError::from)?;
+
+    let mut mapping = BTreeMap::new();
+    for (key, client) in projects {
+        if let Some(client) = client {
+            println!("{} → {}", key, client.name);
+            mapping.insert(key, client);
+            continue;
+        }
+
+        let mut options: Vec<String> = clients.iter().map(|c| c.name.clone()).collect();
+        options.push("Skip these hours".to_string());
+        let selection = Select::new()
+            .with_prompt(format!("Client for {}", key))
+            .items(&options)
+            .default(options.len() - 1)
+            .interact()?;
+        if let Some(client) = clients.get(selection) {
+            mapping.insert(key, client.clone());
+        }
+    }
+    Ok(mapping)
+}
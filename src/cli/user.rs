@@ -1,7 +1,9 @@
 use super::error::AppError;
-use super::validation::{validate_cif, validate_email, validate_iban};
+use super::validation::{validate_bic, validate_cif, validate_email, validate_iban};
 use crate::models::User;
 use crate::repository::Storage;
+use crate::services::einvoice::local_tax_id;
+use crate::services::iban::{format_iban, normalize_iban, spanish_bank_name};
 use console::style;
 use dialoguer::Input;
 
@@ -20,7 +22,7 @@ pub fn create_user(storage: &Storage) -> Result<User, AppError> {
             .interact_text()?;
 
         match validate_cif(&input) {
-            Ok(()) => break input,
+            Ok(()) => break local_tax_id(&input),
             Err(e) => {
                 println!("{}", style(format!("Error: {}", e)).red());
                 continue;
@@ -47,28 +49,16 @@ pub fn create_user(storage: &Storage) -> Result<User, AppError> {
         }
     };
 
-    let iban: String = loop {
-        let input: String = Input::new()
-            .with_prompt("Enter your IBAN (optional, press enter to skip)")
-            .allow_empty(true)
-            .interact_text()?;
+    let (iban, bic) = ask_bank_account(None, None)?;
 
-        match validate_iban(&input) {
-            Ok(()) => break input,
-            Err(e) => {
-                println!("{}", style(format!("Error: {}", e)).red());
-                continue;
-            }
-        }
-    };
-
-    let user = User::new(
+    let mut user = User::new(
         name,
         address,
         cif,
         if email.is_empty() { None } else { Some(email) },
-        if iban.is_empty() { None } else { Some(iban) },
+        iban,
     );
+    user.bic = bic;
 
     storage.save_user(&user).map_err(AppError::from)?;
     println!("{}", style("User profile created successfully!").green());
@@ -95,10 +85,20 @@ pub fn update_user(storage: &Storage, user: &User) -> Result<User, AppError> {
         .default(user.name.clone())
         .interact_text()?;
 
-    let cif: String = Input::new()
-        .with_prompt("Enter your CIF/NIE")
-        .default(user.cif.clone())
-        .interact_text()?;
+    let cif: String = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter your CIF/NIE")
+            .default(user.cif.clone())
+            .interact_text()?;
+
+        match validate_cif(&input) {
+            Ok(()) => break local_tax_id(&input),
+            Err(e) => {
+                println!("{}", style(format!("Error: {}", e)).red());
+                continue;
+            }
+        }
+    };
 
     let address: String = Input::new()
         .with_prompt("Enter your address")
@@ -111,22 +111,59 @@ pub fn update_user(storage: &Storage, user: &User) -> Result<User, AppError> {
         .allow_empty(true)
         .interact_text()?;
 
-    let iban: String = Input::new()
-        .with_prompt("Enter your IBAN (optional, press enter to skip)")
-        .default(user.iban.clone().unwrap_or_default())
-        .allow_empty(true)
-        .interact_text()?;
+    let (iban, bic) = ask_bank_account(user.iban.as_deref(), user.bic.as_deref())?;
 
-    let updated_user = User::new(
+    let mut updated_user = User::new(
         name,
         address,
         cif,
         if email.is_empty() { None } else { Some(email) },
-        if iban.is_empty() { None } else { Some(iban) },
+        iban,
     );
+    updated_user.bic = bic;
 
     storage.save_user(&updated_user).map_err(AppError::from)?;
     println!("{}", style("User profile updated successfully!").green());
 
     Ok(updated_user)
 }
+
+// IBAN and BIC of the account where clients pay, both optional
+fn ask_bank_account(
+    iban: Option<&str>,
+    bic: Option<&str>,
+) -> Result<(Option<String>, Option<String>), AppError> {
+    let iban = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter your IBAN (optional, press enter to skip)")
+            .default(iban.map(format_iban).unwrap_or_default())
+            .allow_empty(true)
+            .interact_text()?;
+
+        match validate_iban(&input) {
+            Ok(()) => break normalize_iban(&input),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+    if iban.is_empty() {
+        return Ok((None, None));
+    }
+    if let Some(bank) = spanish_bank_name(&iban) {
+        println!("Bank: {}", bank);
+    }
+
+    let bic = loop {
+        let input: String = Input::new()
+            .with_prompt("Enter the BIC of your bank (optional, press enter to skip)")
+            .default(bic.unwrap_or_default().to_string())
+            .allow_empty(true)
+            .interact_text()?;
+
+        match validate_bic(&input) {
+            Ok(()) => break input.trim().to_uppercase(),
+            Err(e) => println!("{}", style(format!("Error: {}", e)).red()),
+        }
+    };
+
+    Ok((Some(iban), if bic.is_empty() { None } else { Some(bic) }))
+}
@@ -1,112 +1,68 @@
 use crate::cli::error::{AppError, AppResult};
-use crate::errors::ValidationError;
+use crate::services::iban;
+use crate::services::tax_id::validate_spanish_tax_id;
+use crate::services::validation;
 
-// Input validation functions
+// Input validation functions, the rules themselves live in services::validation
 
 pub fn validate_email(email: &str) -> AppResult<()> {
-    if email.is_empty() {
-        return Ok(());
-    }
-
-    // Email format validation
-    if !email.contains('@') || !email.contains('.') {
-        return Err(AppError::Validation(ValidationError::InvalidEmail {
-            email: email.to_string(),
-        }));
-    }
-
-    // Check that @ is not at the beginning or end
-    if email.starts_with('@') || email.ends_with('@') {
-        return Err(AppError::Validation(ValidationError::InvalidEmail {
-            email: email.to_string(),
-        }));
-    }
-
-    // Check that there's at least one character before @
-    if let Some(at_pos) = email.find('@') {
-        if at_pos == 0 {
-            return Err(AppError::Validation(ValidationError::InvalidEmail {
-                email: email.to_string(),
-            }));
-        }
-    }
-
-    Ok(())
+    validation::validate_email(email).map_err(AppError::Validation)
 }
 
+// Spanish NIF, NIE or CIF with its control character, as required for the issuer
 pub fn validate_cif(cif: &str) -> AppResult<()> {
-    if cif.is_empty() {
-        return Err(AppError::Validation(ValidationError::RequiredFieldEmpty {
-            field: "CIF/NIE".to_string(),
-        }));
-    }
-
-    // CIF/NIE length check
-    if cif.len() < 8 || cif.len() > 12 {
-        return Err(AppError::Validation(ValidationError::InvalidCif {
-            cif: cif.to_string(),
-        }));
-    }
+    validate_spanish_tax_id(cif).map_err(AppError::Validation)
+}
 
-    Ok(())
+// Clients and suppliers may also be identified by a VAT number of another country
+pub fn validate_client_tax_id(cif: &str) -> AppResult<()> {
+    validation::validate_client_tax_id(cif).map_err(AppError::Validation)
 }
 
 pub fn validate_date(date_str: &str) -> AppResult<()> {
-    if date_str.is_empty() {
-        return Ok(());
-    }
-
-    // Date format validation
-    if chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d").is_err() {
-        return Err(AppError::Validation(ValidationError::InvalidDate {
-            date: date_str.to_string(),
-        }));
-    }
-
-    Ok(())
+    validation::validate_date(date_str).map_err(AppError::Validation)
 }
 
 pub fn validate_iban(iban: &str) -> AppResult<()> {
-    if iban.is_empty() {
+    if iban.trim().is_empty() {
         return Ok(());
     }
 
-    // IBAN length check
-    if iban.len() < 15 || iban.len() > 34 {
-        return Err(AppError::Validation(ValidationError::InvalidIban {
-            iban: iban.to_string(),
-        }));
-    }
+    iban::validate_iban(iban).map_err(AppError::Validation)
+}
 
-    // IBAN alphanumeric check
-    if !iban.chars().all(|c| c.is_alphanumeric()) {
-        return Err(AppError::Validation(ValidationError::InvalidIban {
-            iban: iban.to_string(),
-        }));
+pub fn validate_bic(bic: &str) -> AppResult<()> {
+    if bic.trim().is_empty() {
+        return Ok(());
     }
 
-    Ok(())
+    iban::validate_bic(bic).map_err(AppError::Validation)
 }
 
 pub fn validate_percentage(value: f32, _name: &str) -> AppResult<()> {
-    if !(0.0..=100.0).contains(&value) {
-        return Err(AppError::Validation(ValidationError::InvalidPercentage {
-            value,
-        }));
-    }
-
-    Ok(())
+    validation::validate_percentage(value).map_err(AppError::Validation)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::errors::ValidationError;
 
     #[test]
     fn test_validate_cif_valid() {
-        assert!(validate_cif("12345678A").is_ok());
-        assert!(validate_cif("87654321B").is_ok());
-        assert!(validate_cif("123456789012").is_ok());
+        // DNI, NIE, CIF with digit and letter controls, special NIF
+        assert!(validate_cif("12345678Z").is_ok());
+        assert!(validate_cif("00000000T").is_ok());
+        assert!(validate_cif("X1234567L").is_ok());
+        assert!(validate_cif("Y1234567X").is_ok());
+        assert!(validate_cif("Z1234567R").is_ok());
+        assert!(validate_cif("B12345674").is_ok());
+        assert!(validate_cif("A28015865").is_ok());
+        assert!(validate_cif("Q2826000H").is_ok());
+        assert!(validate_cif("G12345674").is_ok());
+        assert!(validate_cif("G1234567D").is_ok());
+        assert!(validate_cif("K1234567L").is_ok());
+        assert!(validate_cif("es b-12345674").is_ok());
     }
 
     #[test]
@@ -114,6 +70,61 @@ mod tests {
         assert!(validate_cif("").is_err());
         assert!(validate_cif("123").is_err());
         assert!(validate_cif("12345678901234567890").is_err());
+        assert!(validate_cif("12345678A").is_err());
+        assert!(validate_cif("DE123456789").is_err());
+    }
+
+    #[test]
+    fn test_validate_cif_explains_the_problem() {
+        let error = |cif: &str| match validate_cif(cif) {
+            Err(AppError::Validation(e)) => e,
+            other => panic!("unexpected result for {}: {:?}", cif, other),
+        };
+
+        assert!(matches!(
+            error("1234567Z"),
+            ValidationError::InvalidTaxIdLength { length: 8, .. }
+        ));
+        assert!(matches!(
+            error("12345678A"),
+            ValidationError::InvalidNifLetter { expected: 'Z', .. }
+        ));
+        assert!(matches!(
+            error("X1234567A"),
+            ValidationError::InvalidNieLetter { expected: 'L', .. }
+        ));
+        assert!(matches!(
+            error("I12345674"),
+            ValidationError::InvalidCifOrganisation { letter: 'I', .. }
+        ));
+        // Companies need a digit, public bodies a letter
+        match error("B1234567D") {
+            ValidationError::InvalidCifControl { expected, .. } => assert_eq!(expected, "4"),
+            other => panic!("unexpected error {:?}", other),
+        }
+        match error("Q28260001") {
+            ValidationError::InvalidCifControl { expected, .. } => assert_eq!(expected, "H"),
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(matches!(
+            error("K1234567A"),
+            ValidationError::InvalidNifLetter { expected: 'L', .. }
+        ));
+        assert!(matches!(
+            error("1234X678Z"),
+            ValidationError::InvalidCif { .. }
+        ));
+    }
+
+    #[test]
+    fn test_validate_client_tax_id() {
+        assert!(validate_client_tax_id("B12345674").is_ok());
+        assert!(validate_client_tax_id("ESB12345674").is_ok());
+        assert!(validate_client_tax_id("DE136695976").is_ok());
+        assert!(validate_client_tax_id("DE123456789").is_err());
+        assert!(validate_client_tax_id("CHE123456789").is_ok());
+        assert!(validate_client_tax_id("B12345670").is_err());
+        assert!(validate_client_tax_id("").is_err());
     }
 
     #[test]
@@ -133,7 +144,8 @@ mod tests {
 
     #[test]
     fn test_validate_iban_valid() {
-        assert!(validate_iban("ES1234567890123456789012").is_ok());
+        assert!(validate_iban("ES9121000418450200051332").is_ok());
+        assert!(validate_iban("ES91 2100 0418 4502 0005 1332").is_ok());
         assert!(validate_iban("GB82WEST12345698765432").is_ok());
         assert!(validate_iban("").is_ok());
     }
@@ -143,6 +155,16 @@ mod tests {
         assert!(validate_iban("123").is_err());
         assert!(validate_iban("ES1234567890123456789012345678901234567890").is_err());
         assert!(validate_iban("ES12-3456-7890-1234-5678-9012").is_err());
+        assert!(validate_iban("ES1234567890123456789012").is_err());
+    }
+
+    #[test]
+    fn test_validate_bic() {
+        assert!(validate_bic("CAIXESBBXXX").is_ok());
+        assert!(validate_bic("bsabesbb").is_ok());
+        assert!(validate_bic("").is_ok());
+        assert!(validate_bic("CAIXESB").is_err());
+        assert!(validate_bic("1AIXESBBXXX").is_err());
     }
 
     #[test]
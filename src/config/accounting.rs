@@ -0,0 +1,67 @@
+// Accounts of the journal exported for the accounting software of the gestoría
+
+use serde::{Deserialize, Serialize};
+
+// Accounts used for the entries, padded with zeros to subaccounts of the configured length
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountMapping {
+    pub clients_account: String,    // 430 Clientes, one subaccount per client
+    pub sales_account: String,      // 705 Prestaciones de servicios, or 700 Ventas de mercaderías
+    pub output_vat_account: String, // 477 Hacienda Pública, IVA repercutido
+    pub withholding_account: String, // 473 Hacienda Pública, retenciones y pagos a cuenta
+    pub subaccount_digits: usize,   // Length of the subaccounts, between 8 and 12
+    pub company_code: String,       // Company code of the A3 import
+}
+
+impl Default for AccountMapping {
+    fn default() -> Self {
+        AccountMapping {
+            clients_account: "430".to_string(),
+            sales_account: "705".to_string(),
+            output_vat_account: "477".to_string(),
+            withholding_account: "473".to_string(),
+            subaccount_digits: 8,
+            company_code: "00001".to_string(),
+        }
+    }
+}
+
+impl AccountMapping {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(8..=12).contains(&self.subaccount_digits) {
+            return Err(format!(
+                "Subaccounts must have between 8 and 12 digits, got: {}",
+                self.subaccount_digits
+            ));
+        }
+        for account in [
+            &self.clients_account,
+            &self.sales_account,
+            &self.output_vat_account,
+            &self.withholding_account,
+        ] {
+            if account.len() < 3
+                || account.len() >= self.subaccount_digits
+                || !account.chars().all(|c| c.is_ascii_digit())
+            {
+                return Err(format!(
+                    "Account '{}' must have 3 or more digits and be shorter than the subaccounts",
+                    account
+                ));
+            }
+        }
+        if self.company_code.len() > 5 || !self.company_code.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!(
+                "A3 company code '{}' must have up to 5 digits",
+                self.company_code
+            ));
+        }
+        Ok(())
+    }
+
+    // Account padded with zeros up to the subaccount length ("477" -> "47700000")
+    pub fn subaccount(&self, account: &str) -> String {
+        format!("{:0<width$}", account, width = self.subaccount_digits)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::config::error::ConfigError;
+use crate::config::{AccountMapping, ImportMapping};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -16,6 +17,14 @@ pub struct AppConfig {
 
     // UI configuration
     pub ui: UiConfig,
+
+    // Accounts of the journal exported for the accounting software
+    #[serde(default)]
+    pub accounting: AccountMapping,
+
+    // Columns read by the bulk import of clients and invoices
+    #[serde(default)]
+    pub import: ImportMapping,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +40,9 @@ pub struct PdfConfig {
     pub font_size: f32,
     pub page_width: f32,
     pub page_height: f32,
+    // Produce Factur-X / ZUGFeRD PDF/A-3b files with the CII XML embedded
+    #[serde(default)]
+    pub factur_x: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +110,12 @@ impl AppConfig {
             config.pdf.output_dir = PathBuf::from(pdf_dir);
         }
 
+        if let Ok(factur_x) = std::env::var("RUSTY_INVOICES_FACTUR_X") {
+            config.pdf.factur_x = factur_x.parse().map_err(|e| ConfigError::ParseError {
+                message: format!("Invalid Factur-X flag: {}", e),
+            })?;
+        }
+
         if let Ok(iva) = std::env::var("RUSTY_INVOICES_DEFAULT_IVA") {
             config.tax.default_iva = iva.parse().map_err(|e| ConfigError::ParseError {
                 message: format!("Invalid IVA value: {}", e),
@@ -157,6 +175,13 @@ impl AppConfig {
             });
         }
 
+        self.accounting
+            .validate()
+            .map_err(|message| ConfigError::ValidationError { message })?;
+        self.import
+            .validate()
+            .map_err(|message| ConfigError::ValidationError { message })?;
+
         Ok(())
     }
 }
@@ -176,6 +201,7 @@ impl Default for AppConfig {
                 font_size: 12.0,
                 page_width: 210.0,  // A4 width in mm
                 page_height: 297.0, // A4 height in mm
+                factur_x: false,
             },
             tax: TaxConfig {
                 default_iva: 21.0,
@@ -187,6 +213,8 @@ impl Default for AppConfig {
                 confirm_prompts: true,
                 show_debug_info: false,
             },
+            accounting: AccountMapping::default(),
+            import: ImportMapping::default(),
         }
     }
 }
@@ -0,0 +1,85 @@
+// Columns read by the bulk import of clients and invoices, found by their header names
+
+use serde::{Deserialize, Serialize};
+
+// Header of the column holding each client field
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientColumns {
+    pub name: String,
+    pub cif: String,
+    pub address: String,
+    pub email: String, // Optional column
+}
+
+impl Default for ClientColumns {
+    fn default() -> Self {
+        ClientColumns {
+            name: "name".to_string(),
+            cif: "cif".to_string(),
+            address: "address".to_string(),
+            email: "email".to_string(),
+        }
+    }
+}
+
+// Header of the column holding each invoice field. Every row is an item, rows with
+// the same number make up one invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvoiceColumns {
+    pub number: String,
+    pub date: String,
+    pub due_date: String, // Optional column, 30 days after the date when empty
+    pub client_cif: String,
+    pub description: String,
+    pub quantity: String, // Optional column, 1 when empty
+    pub price: String,
+    pub iva: String,
+    pub irpf: String,      // Optional column, 0 when empty
+    pub paid_date: String, // Optional column
+}
+
+impl Default for InvoiceColumns {
+    fn default() -> Self {
+        InvoiceColumns {
+            number: "number".to_string(),
+            date: "date".to_string(),
+            due_date: "due_date".to_string(),
+            client_cif: "client_cif".to_string(),
+            description: "description".to_string(),
+            quantity: "quantity".to_string(),
+            price: "price".to_string(),
+            iva: "iva".to_string(),
+            irpf: "irpf".to_string(),
+            paid_date: "paid_date".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportMapping {
+    pub clients: ClientColumns,
+    pub invoices: InvoiceColumns,
+}
+
+impl ImportMapping {
+    pub fn validate(&self) -> Result<(), String> {
+        let columns = [
+            &self.clients.name,
+            &self.clients.cif,
+            &self.clients.address,
+            &self.invoices.number,
+            &self.invoices.date,
+            &self.invoices.client_cif,
+            &self.invoices.description,
+            &self.invoices.price,
+            &self.invoices.iva,
+        ];
+        if columns.iter().any(|column| column.trim().is_empty()) {
+            return Err("Import columns of required fields cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
+pub mod accounting;
 pub mod app_config;
 pub mod error;
+pub mod import;
 
 #[cfg(test)]
 mod tests;
 
+pub use accounting::AccountMapping;
 pub use app_config::AppConfig;
+pub use import::{ClientColumns, ImportMapping, InvoiceColumns};
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod config_tests {
-    use crate::config::AppConfig;
+    use crate::config::{AccountMapping, AppConfig};
 
     #[test]
     fn test_default_config() {
@@ -12,6 +12,7 @@ mod config_tests {
         assert_eq!(config.pdf.font_size, 12.0);
         assert_eq!(config.pdf.page_width, 210.0);
         assert_eq!(config.pdf.page_height, 297.0);
+        assert!(!config.pdf.factur_x);
         assert_eq!(config.ui.welcome_message, "Welcome to Rusty Invoices");
         assert!(config.ui.confirm_prompts);
         assert!(!config.ui.show_debug_info);
@@ -38,4 +39,42 @@ mod config_tests {
 
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_config_without_factur_x_flag_parses() {
+        let json = serde_json::to_string(&AppConfig::default()).unwrap();
+        let json = json.replace(",\"factur_x\":false", "");
+        let config: AppConfig = serde_json::from_str(&json).unwrap();
+
+        assert!(!config.pdf.factur_x);
+    }
+
+    #[test]
+    fn test_account_mapping_validation() {
+        assert!(AccountMapping::default().validate().is_ok());
+        for mapping in [
+            AccountMapping {
+                subaccount_digits: 7,
+                ..AccountMapping::default()
+            },
+            AccountMapping {
+                clients_account: "43A".to_string(),
+                ..AccountMapping::default()
+            },
+            AccountMapping {
+                sales_account: "70500000".to_string(),
+                ..AccountMapping::default()
+            },
+        ] {
+            assert!(mapping.validate().is_err());
+        }
+
+        let config: AppConfig = serde_json::from_str(
+            &serde_json::to_string(&AppConfig::default())
+                .unwrap()
+                .replace("\"sales_account\":\"705\"", "\"sales_account\":\"700\""),
+        )
+        .unwrap();
+        assert_eq!(config.accounting.sales_account, "700");
+    }
 }
@@ -1,7 +1,8 @@
-use super::container::ServiceContainer;
-use crate::cli::{create_user, show_main_menu, AppResult};
+use super::container::{invoice_service, open_storage, ServiceContainer};
+use crate::cli::{create_user, generate_due_invoices, show_main_menu, AppError, AppResult};
 use crate::config::AppConfig;
 use crate::models::User;
+use crate::services::RecurringService;
 
 // Main application class
 pub struct App {
@@ -44,15 +45,33 @@ impl App {
     // Run the main application loop
     pub fn run(&mut self) -> AppResult<()> {
         loop {
-            if show_main_menu(
-                self.services.client_service(),
-                self.services.invoice_service(),
-                self.services.storage(),
-                &mut self.user,
-            )? {
+            if show_main_menu(&self.services, &mut self.user)? {
                 break;
             }
         }
         Ok(())
     }
+
+    // Create the recurring invoices due today, for running from cron. Nothing is asked
+    // and no banner is shown: without a user profile it fails instead of creating one
+    pub fn generate_due(dry_run: bool) -> AppResult<()> {
+        let config = AppConfig::load().map_err(AppError::Config)?;
+        let storage = open_storage(&config)?;
+        let user =
+            storage
+                .get_user()
+                .map_err(AppError::from)?
+                .ok_or_else(|| AppError::InvalidInput {
+                    message: "No user profile yet, run rusty-cli without arguments to create it"
+                        .to_string(),
+                })?;
+
+        generate_due_invoices(
+            &RecurringService::new(storage.clone()),
+            &invoice_service(&config, &storage)?,
+            &user,
+            dry_run,
+        )?;
+        Ok(())
+    }
 }
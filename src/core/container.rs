@@ -1,50 +1,76 @@
 use crate::cli::error::{AppError, AppResult};
 use crate::config::AppConfig;
 use crate::repository::Storage;
-use crate::services::{ClientService, InvoiceService, PdfService};
+use crate::services::vies::{SoapViesClient, VIES_ENDPOINT};
+use crate::services::{
+    AccountingService, AssetService, BulkImportService, ClientService, ExpenseService,
+    InvoiceService, PdfService, QuoteService, ReconciliationService, RecurringService,
+    ReportService, SepaService, TimeTrackingService, UblService,
+};
 
 // Service container
 pub struct ServiceContainer {
     pub storage: Storage,
     pub client_service: ClientService,
     pub invoice_service: InvoiceService,
+    pub recurring_service: RecurringService,
+    pub quote_service: QuoteService,
+    pub expense_service: ExpenseService,
+    pub asset_service: AssetService,
+    pub report_service: ReportService,
+    pub accounting_service: AccountingService,
+    pub reconciliation_service: ReconciliationService,
+    pub sepa_service: SepaService,
+    pub time_tracking_service: TimeTrackingService,
+    pub bulk_import_service: BulkImportService,
 }
 
 impl ServiceContainer {
     // Create service container
     pub fn new(config: AppConfig) -> AppResult<Self> {
-        // Initialize storage using config
-        let storage_path =
-            config
-                .storage
-                .base_path
-                .to_str()
-                .ok_or_else(|| AppError::InvalidInput {
-                    message: "Invalid characters in storage path".to_string(),
-                })?;
-
-        let storage = Storage::new(storage_path).map_err(AppError::Io)?;
-
-        // Set up PDF service using config
-        let pdfs_dir_str =
-            config
-                .pdf
-                .output_dir
-                .to_str()
-                .ok_or_else(|| AppError::InvalidInput {
-                    message: "Invalid characters in PDF directory path".to_string(),
-                })?;
-
-        let pdf_service = PdfService::new(pdfs_dir_str.to_string()).map_err(AppError::Io)?;
+        let storage = open_storage(&config)?;
+        let pdf_service = pdf_service(&config)?;
+        let exports_dir = exports_dir(&config)?;
+        let ubl_service = UblService::new(exports_dir.clone()).map_err(AppError::Io)?;
 
         // Initialize services with dependencies
-        let client_service = ClientService::new(storage.clone());
-        let invoice_service = InvoiceService::new(storage.clone(), pdf_service);
+        let client_service = ClientService::new(
+            storage.clone(),
+            Box::new(SoapViesClient::new(VIES_ENDPOINT.to_string())),
+        );
+        let quote_service = QuoteService::new(storage.clone(), pdf_service.clone());
+        let invoice_service = InvoiceService::new(storage.clone(), pdf_service, ubl_service);
+        let recurring_service = RecurringService::new(storage.clone());
+        let expense_service = ExpenseService::new(storage.clone());
+        let asset_service = AssetService::new(storage.clone());
+        let report_service =
+            ReportService::new(storage.clone(), exports_dir.clone()).map_err(AppError::Io)?;
+        let accounting_service = AccountingService::new(
+            storage.clone(),
+            exports_dir.clone(),
+            config.accounting.clone(),
+        )
+        .map_err(AppError::Io)?;
+        let reconciliation_service = ReconciliationService::new(storage.clone());
+        let time_tracking_service = TimeTrackingService::new(storage.clone());
+        let bulk_import_service = BulkImportService::new(storage.clone(), config.import.clone());
+        let sepa_service =
+            SepaService::new(storage.clone(), exports_dir.clone()).map_err(AppError::Io)?;
 
         Ok(ServiceContainer {
             storage,
             client_service,
             invoice_service,
+            recurring_service,
+            quote_service,
+            expense_service,
+            asset_service,
+            report_service,
+            accounting_service,
+            reconciliation_service,
+            sepa_service,
+            time_tracking_service,
+            bulk_import_service,
         })
     }
 
@@ -62,4 +88,105 @@ impl ServiceContainer {
     pub fn invoice_service(&self) -> &InvoiceService {
         &self.invoice_service
     }
+
+    // Get recurring invoice service reference
+    pub fn recurring_service(&self) -> &RecurringService {
+        &self.recurring_service
+    }
+
+    // Get quote and proforma service reference
+    pub fn quote_service(&self) -> &QuoteService {
+        &self.quote_service
+    }
+
+    // Get expense service reference
+    pub fn expense_service(&self) -> &ExpenseService {
+        &self.expense_service
+    }
+
+    // Get asset service reference
+    pub fn asset_service(&self) -> &AssetService {
+        &self.asset_service
+    }
+
+    // Get report service reference
+    pub fn report_service(&self) -> &ReportService {
+        &self.report_service
+    }
+
+    // Get accounting export service reference
+    pub fn accounting_service(&self) -> &AccountingService {
+        &self.accounting_service
+    }
+
+    // Get bank reconciliation service reference
+    pub fn reconciliation_service(&self) -> &ReconciliationService {
+        &self.reconciliation_service
+    }
+
+    // Get SEPA direct debit service reference
+    pub fn sepa_service(&self) -> &SepaService {
+        &self.sepa_service
+    }
+
+    // Get time tracking import service reference
+    pub fn time_tracking_service(&self) -> &TimeTrackingService {
+        &self.time_tracking_service
+    }
+
+    // Get bulk import service reference
+    pub fn bulk_import_service(&self) -> &BulkImportService {
+        &self.bulk_import_service
+    }
+}
+
+// Storage at the configured path
+pub fn open_storage(config: &AppConfig) -> AppResult<Storage> {
+    let storage_path = config
+        .storage
+        .base_path
+        .to_str()
+        .ok_or_else(|| AppError::InvalidInput {
+            message: "Invalid characters in storage path".to_string(),
+        })?;
+    Storage::new(storage_path).map_err(AppError::Io)
+}
+
+// Invoices with their PDFs and e-invoice exports where the configuration says, what the
+// scheduled generation of recurring invoices needs without the rest of the container
+pub fn invoice_service(config: &AppConfig, storage: &Storage) -> AppResult<InvoiceService> {
+    let ubl_service = UblService::new(exports_dir(config)?).map_err(AppError::Io)?;
+    Ok(InvoiceService::new(
+        storage.clone(),
+        pdf_service(config)?,
+        ubl_service,
+    ))
+}
+
+fn pdf_service(config: &AppConfig) -> AppResult<PdfService> {
+    let pdfs_dir_str = config
+        .pdf
+        .output_dir
+        .to_str()
+        .ok_or_else(|| AppError::InvalidInput {
+            message: "Invalid characters in PDF directory path".to_string(),
+        })?;
+
+    PdfService::new(
+        pdfs_dir_str.to_string(),
+        config.pdf.factur_x,
+        config.tax.currency.clone(),
+    )
+    .map_err(AppError::Io)
+}
+
+// E-invoice exports live next to the stored data
+fn exports_dir(config: &AppConfig) -> AppResult<String> {
+    let exports_dir = config.storage.base_path.join("exports");
+    exports_dir
+        .to_str()
+        .map(str::to_string)
+        .ok_or_else(|| AppError::InvalidInput {
+            message: "Invalid characters in exports directory path".to_string(),
+        })
 }
@@ -9,9 +9,50 @@ pub enum ValidationError {
     #[error("CIF/NIE format is invalid: {cif}")]
     InvalidCif { cif: String },
 
+    #[error("Tax ID {tax_id} has {length} characters, Spanish NIF, NIE and CIF have 9")]
+    InvalidTaxIdLength { tax_id: String, length: usize },
+
+    #[error("NIF {nif} has a wrong control letter, expected {expected}")]
+    InvalidNifLetter { nif: String, expected: char },
+
+    #[error("NIE {nie} has a wrong control letter, expected {expected}")]
+    InvalidNieLetter { nie: String, expected: char },
+
+    #[error("CIF {cif} starts with {letter}, which is not an organisation type")]
+    InvalidCifOrganisation { cif: String, letter: char },
+
+    #[error("CIF {cif} has a wrong control character, expected {expected}")]
+    InvalidCifControl { cif: String, expected: String },
+
+    #[error("VAT number {vat_id} does not start with the code of an EU member state")]
+    InvalidVatCountry { vat_id: String },
+
+    #[error("VAT number {vat_id} does not match the format {format}")]
+    InvalidVatFormat { vat_id: String, format: String },
+
+    #[error("VAT number {vat_id} has a wrong check digit")]
+    InvalidVatChecksum { vat_id: String },
+
     #[error("IBAN format is invalid: {iban}")]
     InvalidIban { iban: String },
 
+    #[error("IBAN {iban} starts with {country}, which is not an IBAN country")]
+    InvalidIbanCountry { iban: String, country: String },
+
+    #[error("IBAN {iban} has {length} characters, {country} IBANs have {expected}")]
+    InvalidIbanLength {
+        iban: String,
+        country: String,
+        length: usize,
+        expected: usize,
+    },
+
+    #[error("IBAN {iban} has wrong check digits")]
+    InvalidIbanChecksum { iban: String },
+
+    #[error("BIC format is invalid: {bic} (8 or 11 characters, e.g. CAIXESBBXXX)")]
+    InvalidBic { bic: String },
+
     #[error("Date format is invalid: {date}")]
     InvalidDate { date: String },
 
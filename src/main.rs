@@ -8,7 +8,7 @@ mod models;
 mod repository;
 mod services;
 
-use cli::AppResult;
+use cli::{AppError, AppResult};
 use core::App;
 fn main() {
     // Catch any unexpected panics and show user friendly messages
@@ -35,9 +35,27 @@ fn main() {
 }
 
 fn run_app() -> AppResult<()> {
-    // Create and initialize the application with dependency injection
-    let mut app = App::new()?;
+    // Commands for scheduled runs, without arguments the interactive menu is shown
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let dry_run = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => None,
+        ["generate-due"] => Some(false),
+        ["generate-due", "--dry-run"] => Some(true),
+        _ => {
+            return Err(AppError::InvalidInput {
+                message: format!(
+                    "Unknown command '{}', usage: rusty-cli [generate-due [--dry-run]]",
+                    args.join(" ")
+                ),
+            })
+        }
+    };
 
-    // Run the main application loop
-    app.run()
+    match dry_run {
+        // Scheduled runs only build what they need and never prompt
+        Some(dry_run) => App::generate_due(dry_run),
+        // Create and initialize the application with dependency injection, then run the
+        // main application loop
+        None => App::new()?.run(),
+    }
 }
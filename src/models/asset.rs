@@ -0,0 +1,119 @@
+// Fixed asset model for the register of amortizable equipment
+// Categories follow the simplified depreciation table for estimación directa
+
+use super::supplier::Supplier;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssetCategory {
+    Buildings,         // Edificaciones y otras construcciones
+    Installations,     // Instalaciones, mobiliario, enseres y resto del inmovilizado material
+    Machinery,         // Maquinaria
+    Transport,         // Elementos de transporte
+    ComputerEquipment, // Equipos para tratamiento de la información y programas informáticos
+    Tools,             // Útiles y herramientas
+}
+
+impl AssetCategory {
+    pub const ALL: [AssetCategory; 6] = [
+        AssetCategory::ComputerEquipment,
+        AssetCategory::Installations,
+        AssetCategory::Machinery,
+        AssetCategory::Transport,
+        AssetCategory::Tools,
+        AssetCategory::Buildings,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AssetCategory::Buildings => "Edificaciones y otras construcciones",
+            AssetCategory::Installations => "Instalaciones, mobiliario y enseres",
+            AssetCategory::Machinery => "Maquinaria",
+            AssetCategory::Transport => "Elementos de transporte",
+            AssetCategory::ComputerEquipment => "Equipos y programas informáticos",
+            AssetCategory::Tools => "Útiles y herramientas",
+        }
+    }
+
+    // Maximum linear coefficient (% per year) of the official table
+    pub fn max_coefficient(&self) -> f32 {
+        match self {
+            AssetCategory::Buildings => 3.0,
+            AssetCategory::Installations => 10.0,
+            AssetCategory::Machinery => 12.0,
+            AssetCategory::Transport => 16.0,
+            AssetCategory::ComputerEquipment => 26.0,
+            AssetCategory::Tools => 30.0,
+        }
+    }
+
+    // Maximum depreciation period in years of the official table
+    pub fn max_years(&self) -> u32 {
+        match self {
+            AssetCategory::Buildings => 68,
+            AssetCategory::Installations => 20,
+            AssetCategory::Machinery => 18,
+            AssetCategory::Transport => 14,
+            AssetCategory::ComputerEquipment => 10,
+            AssetCategory::Tools => 8,
+        }
+    }
+
+    // Lowest coefficient that still depreciates the asset within the maximum period
+    pub fn min_coefficient(&self) -> f32 {
+        100.0 / self.max_years() as f32
+    }
+}
+
+impl fmt::Display for AssetCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Asset {
+    pub id: String,
+    pub description: String,
+    pub supplier: Supplier,
+    pub purchase_date: String, // Date the asset entered into service (YYYY-MM-DD)
+    pub value: f32,            // Acquisition value to depreciate
+    pub category: AssetCategory,
+    pub coefficient: f32,           // Linear depreciation, % per year
+    pub expense_id: Option<String>, // Received invoice the asset was bought with
+}
+
+impl Asset {
+    pub fn new(
+        id: String,
+        description: String,
+        supplier: Supplier,
+        purchase_date: String,
+        value: f32,
+        category: AssetCategory,
+        coefficient: f32,
+    ) -> Self {
+        Asset {
+            id,
+            description,
+            supplier,
+            purchase_date,
+            value,
+            category,
+            coefficient,
+            expense_id: None,
+        }
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ASSET: {}", self.description)?;
+        writeln!(f, "Supplier: {}", self.supplier.name)?;
+        writeln!(f, "Purchase date: {}", self.purchase_date)?;
+        writeln!(f, "Value: {:.2}€", self.value)?;
+        writeln!(f, "Category: {}", self.category)?;
+        write!(f, "Coefficient: {}% per year", self.coefficient)
+    }
+}
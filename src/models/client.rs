@@ -1,3 +1,4 @@
+use super::mandate::SepaMandate;
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Debug};
 
@@ -7,6 +8,10 @@ pub struct Client {
     pub cif: String,
     pub address: String,
     pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mandate: Option<SepaMandate>, // SEPA Direct Debit authorisation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subaccount: Option<String>, // 430 subaccount, given on the first journal export
 }
 
 impl Client {
@@ -16,6 +21,8 @@ impl Client {
             cif,
             address,
             email,
+            mandate: None,
+            subaccount: None,
         }
     }
 }
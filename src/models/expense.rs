@@ -0,0 +1,135 @@
+// Expense model representing an invoice received from a supplier
+// Only the deductible percentage of base and IVA counts for tax reports
+
+use super::supplier::Supplier;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ExpenseCategory {
+    Supplies,             // Gastos de suministros (electricity, internet, phone)
+    SelfEmployedQuota,    // Cuota de autónomos
+    Equipment,            // Equipment and small tools
+    Rent,                 // Alquiler
+    ProfessionalServices, // Gestoría, lawyers, other professionals
+    Software,             // Software and subscriptions
+    Travel,               // Travel and subsistence
+    Other,
+}
+
+impl ExpenseCategory {
+    pub const ALL: [ExpenseCategory; 8] = [
+        ExpenseCategory::Supplies,
+        ExpenseCategory::SelfEmployedQuota,
+        ExpenseCategory::Equipment,
+        ExpenseCategory::Rent,
+        ExpenseCategory::ProfessionalServices,
+        ExpenseCategory::Software,
+        ExpenseCategory::Travel,
+        ExpenseCategory::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExpenseCategory::Supplies => "Gastos de suministros",
+            ExpenseCategory::SelfEmployedQuota => "Cuota de autónomos",
+            ExpenseCategory::Equipment => "Equipos",
+            ExpenseCategory::Rent => "Alquiler",
+            ExpenseCategory::ProfessionalServices => "Servicios profesionales",
+            ExpenseCategory::Software => "Software y suscripciones",
+            ExpenseCategory::Travel => "Viajes y dietas",
+            ExpenseCategory::Other => "Otros gastos",
+        }
+    }
+}
+
+impl fmt::Display for ExpenseCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Expense {
+    pub id: String,     // Storage identifier, unique across suppliers
+    pub number: String, // Invoice number given by the supplier
+    pub date: String,   // Invoice issue date (YYYY-MM-DD)
+    pub supplier: Supplier,
+    pub category: ExpenseCategory,
+    pub base: f32,                  // Taxable base
+    pub iva: f32,                   // IVA rate charged
+    pub iva_amount: f32,            // IVA amount charged
+    pub deductible_percent: f32,    // Share of the expense used for the activity
+    pub total: f32,                 // Base plus IVA
+    pub attachment: Option<String>, // File name of the original PDF in the attachments directory
+}
+
+impl Expense {
+    // Create a new expense, calculating IVA and total from the base
+    pub fn new(
+        number: String,
+        date: String,
+        supplier: Supplier,
+        category: ExpenseCategory,
+        base: f32,
+        iva: f32,
+        deductible_percent: f32,
+    ) -> Self {
+        let iva_amount = base * (iva / 100.0);
+        let id = format!("{}_{}", supplier.cif, number)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        Expense {
+            id,
+            number,
+            date,
+            supplier,
+            category,
+            base,
+            iva,
+            iva_amount,
+            deductible_percent,
+            total: base + iva_amount,
+            attachment: None,
+        }
+    }
+
+    pub fn deductible_base(&self) -> f32 {
+        self.base * self.deductible_percent / 100.0
+    }
+
+    pub fn deductible_iva(&self) -> f32 {
+        self.iva_amount * self.deductible_percent / 100.0
+    }
+}
+
+impl fmt::Display for Expense {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "EXPENSE #{} - {}", self.number, self.date)?;
+        writeln!(f, "Category: {}", self.category)?;
+        writeln!(f, "\nSUPPLIER:")?;
+        writeln!(f, "{}", self.supplier)?;
+        writeln!(f, "\nBase: {:.2}€", self.base)?;
+        writeln!(f, "IVA ({}%): {:.2}€", self.iva, self.iva_amount)?;
+        writeln!(f, "Total: {:.2}€", self.total)?;
+        write!(
+            f,
+            "Deductible ({}%): {:.2}€ base, {:.2}€ IVA",
+            self.deductible_percent,
+            self.deductible_base(),
+            self.deductible_iva()
+        )?;
+        if let Some(attachment) = &self.attachment {
+            write!(f, "\nAttachment: {}", attachment)?;
+        }
+        Ok(())
+    }
+}
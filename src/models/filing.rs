@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+// Modelo 130 filed for a quarter, kept so later quarters of the year can
+// accumulate its expenses and deduct what was already paid
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Modelo130Filing {
+    pub year: i32,
+    pub quarter: u32,
+    pub expenses: f32,    // Deductible expenses entered for the quarter
+    pub payment: f32,     // Box 07, pre-payment of the quarter
+    pub compensated: f32, // Box 15, negative results of previous quarters applied
+    pub result: f32,      // Box 19, result of the self-assessment
+}
+
+impl Modelo130Filing {
+    pub fn new(
+        year: i32,
+        quarter: u32,
+        expenses: f32,
+        payment: f32,
+        compensated: f32,
+        result: f32,
+    ) -> Self {
+        Modelo130Filing {
+            year,
+            quarter,
+            expenses,
+            payment,
+            compensated,
+            result,
+        }
+    }
+}
+
+// Modelo 349 declared for a quarter, kept to detect later changes that need a rectification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Modelo349Filing {
+    pub year: i32,
+    pub quarter: u32,
+    pub operations: Vec<Modelo349Operation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Modelo349Operation {
+    pub vat_id: String, // With country prefix
+    pub name: String,
+    pub key: char,   // Operation key (E, S, T...)
+    pub amount: f32, // Taxable base declared
+}
+
+impl Modelo349Filing {
+    pub fn new(year: i32, quarter: u32, operations: Vec<Modelo349Operation>) -> Self {
+        Modelo349Filing {
+            year,
+            quarter,
+            operations,
+        }
+    }
+}
+
+impl Modelo349Operation {
+    pub fn new(vat_id: String, name: String, key: char, amount: f32) -> Self {
+        Modelo349Operation {
+            vat_id,
+            name,
+            key,
+            amount,
+        }
+    }
+}
@@ -8,20 +8,38 @@ use super::user::User;
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Debug};
 
+// Highest total, IVA included, of a simplified invoice (Real Decreto 1619/2012, art. 4)
+pub const SIMPLIFIED_INVOICE_LIMIT: f32 = 400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InvoiceKind {
+    #[default]
+    Full, // Factura completa
+    Simplified, // Factura simplificada, the client data is optional
+}
+
 // Invoice struct containing all invoice data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Invoice {
-    pub id: String,       // Unique invoice identifier
-    pub date: String,     // Invoice issue date (YYYY-MM-DD)
-    pub due_date: String, // Payment due date (YYYY-MM-DD)
-    pub user: User,       // Invoice issuer details
-    pub client: Client,   // Client details
-    pub rule: Rule,       // Tax rules (IVA, IRPF)
-    pub items: Vec<Item>, // Line items on the invoice
-    pub subtotal: f32,    // Sum of all items before taxes
-    pub iva_amount: f32,  // IVA tax amount
-    pub irpf_amount: f32, // IRPF tax amount
-    pub total: f32,       // Final amount after taxes
+    pub id: String,             // Unique invoice identifier
+    pub date: String,           // Invoice issue date (YYYY-MM-DD)
+    pub due_date: String,       // Payment due date (YYYY-MM-DD)
+    pub user: User,             // Invoice issuer details
+    pub client: Option<Client>, // Client details, only missing on simplified invoices
+    pub rule: Rule,             // Tax rules (IVA, IRPF)
+    pub items: Vec<Item>,       // Line items on the invoice
+    pub subtotal: f32,          // Sum of all items before taxes
+    pub iva_amount: f32,        // IVA tax amount
+    pub irpf_amount: f32,       // IRPF tax amount
+    pub total: f32,             // Final amount after taxes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paid_date: Option<String>, // Date the payment was received (YYYY-MM-DD)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurring_id: Option<String>, // Template the invoice was generated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<String>, // Quote or proforma the invoice was created from
+    #[serde(default)]
+    pub kind: InvoiceKind,
 }
 
 impl Invoice {
@@ -50,28 +68,74 @@ impl Invoice {
             date,
             due_date,
             user,
-            client,
+            client: Some(client),
             rule,
             items,
             subtotal,
             iva_amount,
             irpf_amount,
             total,
+            paid_date: None,
+            recurring_id: None,
+            quote_id: None,
+            kind: InvoiceKind::Full,
         }
     }
+
+    // Create a simplified invoice, with or without the client data
+    pub fn simplified(
+        id: String,
+        date: String,
+        user: User,
+        client: Option<Client>,
+        rule: Rule,
+        items: Vec<Item>,
+    ) -> Self {
+        let placeholder = Client::new(String::new(), String::new(), String::new(), None);
+        let mut invoice = Invoice::new(id, date.clone(), date, user, placeholder, rule, items);
+        invoice.client = client;
+        invoice.kind = InvoiceKind::Simplified;
+        invoice
+    }
+
+    // Name of the client, simplified invoices may be issued without one
+    pub fn client_name(&self) -> &str {
+        self.client
+            .as_ref()
+            .map_or("Final consumer", |client| client.name.as_str())
+    }
+
+    // Tax ID of the client, empty when there is no client
+    pub fn client_cif(&self) -> &str {
+        self.client
+            .as_ref()
+            .map_or("", |client| client.cif.as_str())
+    }
 }
 
 // Display implementation for console output
 impl fmt::Display for Invoice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "INVOICE #{} - {}", self.id, self.date)?;
+        let title = match self.kind {
+            InvoiceKind::Full => "INVOICE",
+            InvoiceKind::Simplified => "SIMPLIFIED INVOICE",
+        };
+        writeln!(f, "{} #{} - {}", title, self.id, self.date)?;
         writeln!(f, "Due Date: {}", self.due_date)?;
+        if let Some(paid_date) = &self.paid_date {
+            writeln!(f, "Paid: {}", paid_date)?;
+        }
+        if let Some(quote_id) = &self.quote_id {
+            writeln!(f, "Quote: #{}", quote_id)?;
+        }
 
         writeln!(f, "\nISSUER:")?;
         writeln!(f, "{}", self.user)?;
 
-        writeln!(f, "\nCLIENT:")?;
-        writeln!(f, "{}", self.client)?;
+        if let Some(client) = &self.client {
+            writeln!(f, "\nCLIENT:")?;
+            writeln!(f, "{}", client)?;
+        }
 
         writeln!(f, "\nITEMS:")?;
         for (i, item) in self.items.iter().enumerate() {
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// SEPA Direct Debit scheme the mandate was signed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SepaScheme {
+    Core, // Consumers and businesses, refundable for 8 weeks
+    B2b,  // Businesses only, not refundable
+}
+
+impl SepaScheme {
+    pub const ALL: [SepaScheme; 2] = [SepaScheme::Core, SepaScheme::B2b];
+
+    // Local instrument code of the pain.008 message
+    pub fn code(&self) -> &'static str {
+        match self {
+            SepaScheme::Core => "CORE",
+            SepaScheme::B2b => "B2B",
+        }
+    }
+}
+
+impl fmt::Display for SepaScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+// Authorisation of a client to collect its invoices by direct debit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SepaMandate {
+    pub id: String,             // Unique mandate reference, up to 35 characters
+    pub signature_date: String, // Date the client signed the mandate (YYYY-MM-DD)
+    pub iban: String,           // Debtor account
+    pub bic: Option<String>,    // Debtor bank, optional inside the EEA
+    pub scheme: SepaScheme,
+    #[serde(default)]
+    pub last_collection: Option<String>, // Date of the last collection, none before the first one
+}
+
+impl SepaMandate {
+    pub fn new(
+        id: String,
+        signature_date: String,
+        iban: String,
+        bic: Option<String>,
+        scheme: SepaScheme,
+    ) -> Self {
+        SepaMandate {
+            id,
+            signature_date,
+            iban,
+            bic,
+            scheme,
+            last_collection: None,
+        }
+    }
+}
+
+impl fmt::Display for SepaMandate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, signed {}, IBAN {})",
+            self.id, self.scheme, self.signature_date, self.iban
+        )
+    }
+}
@@ -1,14 +1,32 @@
+pub mod asset;
 pub mod client;
+pub mod expense;
+pub mod filing;
 pub mod invoice;
 pub mod item;
+pub mod mandate;
+pub mod quote;
+pub mod reconciliation;
+pub mod recurring;
 pub mod rules;
+pub mod supplier;
 pub mod user;
+pub mod vies;
 
 #[cfg(test)]
 mod tests;
 
+pub use asset::{Asset, AssetCategory};
 pub use client::Client;
-pub use invoice::Invoice;
+pub use expense::{Expense, ExpenseCategory};
+pub use filing::{Modelo130Filing, Modelo349Filing, Modelo349Operation};
+pub use invoice::{Invoice, InvoiceKind, SIMPLIFIED_INVOICE_LIMIT};
 pub use item::Item;
+pub use mandate::{SepaMandate, SepaScheme};
+pub use quote::{Quote, QuoteKind, QuoteStatus};
+pub use reconciliation::Reconciliation;
+pub use recurring::{Frequency, RecurringInvoice};
 pub use rules::Rule;
+pub use supplier::Supplier;
 pub use user::User;
+pub use vies::ViesCheck;
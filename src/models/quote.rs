@@ -0,0 +1,144 @@
+// Quotes (presupuestos) and proforma invoices sent before invoicing a project
+// They share the items and tax rules of an invoice but are not tax documents
+
+use super::client::Client;
+use super::item::Item;
+use super::rules::Rule;
+use super::user::User;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuoteKind {
+    Quote,    // Presupuesto
+    Proforma, // Factura proforma
+}
+
+impl QuoteKind {
+    pub const ALL: [QuoteKind; 2] = [QuoteKind::Quote, QuoteKind::Proforma];
+
+    // Prefix of the document numbers, each kind has its own series
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            QuoteKind::Quote => "P",
+            QuoteKind::Proforma => "PF",
+        }
+    }
+}
+
+impl fmt::Display for QuoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QuoteKind::Quote => "Quote",
+            QuoteKind::Proforma => "Proforma invoice",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuoteStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+impl fmt::Display for QuoteStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            QuoteStatus::Pending => "Pending",
+            QuoteStatus::Accepted => "Accepted",
+            QuoteStatus::Rejected => "Rejected",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub id: String, // Number in the series of its kind
+    pub kind: QuoteKind,
+    pub date: String,        // Issue date (YYYY-MM-DD)
+    pub valid_until: String, // Last day the offer can be accepted (YYYY-MM-DD)
+    pub user: User,
+    pub client: Client,
+    pub rule: Rule,
+    pub items: Vec<Item>,
+    pub subtotal: f32,
+    pub iva_amount: f32,
+    pub irpf_amount: f32,
+    pub total: f32,
+    pub status: QuoteStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invoice_id: Option<String>, // Invoice created from the quote once accepted
+}
+
+impl Quote {
+    // Create a pending quote, totals are calculated like those of an invoice
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: String,
+        kind: QuoteKind,
+        date: String,
+        valid_until: String,
+        user: User,
+        client: Client,
+        rule: Rule,
+        items: Vec<Item>,
+    ) -> Self {
+        let subtotal = items.iter().map(|item| item.total()).sum();
+        let iva_amount = subtotal * (rule.iva / 100.0);
+        let irpf_amount = subtotal * (rule.irpf / 100.0);
+        let total = subtotal + iva_amount - irpf_amount;
+
+        Quote {
+            id,
+            kind,
+            date,
+            valid_until,
+            user,
+            client,
+            rule,
+            items,
+            subtotal,
+            iva_amount,
+            irpf_amount,
+            total,
+            status: QuoteStatus::Pending,
+            invoice_id: None,
+        }
+    }
+}
+
+impl fmt::Display for Quote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} #{} - {}",
+            self.kind.to_string().to_uppercase(),
+            self.id,
+            self.date
+        )?;
+        writeln!(f, "Valid until: {}", self.valid_until)?;
+        writeln!(f, "Status: {}", self.status)?;
+        if let Some(invoice_id) = &self.invoice_id {
+            writeln!(f, "Invoiced: #{}", invoice_id)?;
+        }
+
+        writeln!(f, "\nCLIENT:")?;
+        writeln!(f, "{}", self.client)?;
+
+        writeln!(f, "\nITEMS:")?;
+        for (i, item) in self.items.iter().enumerate() {
+            writeln!(f, "{}. {}", i + 1, item)?;
+        }
+
+        writeln!(f, "\nSUMMARY:")?;
+        writeln!(f, "Subtotal: {:.2}€", self.subtotal)?;
+        writeln!(f, "IVA ({}%): {:.2}€", self.rule.iva, self.iva_amount)?;
+        writeln!(f, "IRPF ({}%): -{:.2}€", self.rule.irpf, self.irpf_amount)?;
+        writeln!(f, "TOTAL: {:.2}€", self.total)?;
+
+        Ok(())
+    }
+}
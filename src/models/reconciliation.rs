@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+// Bank movement accepted as the payment of an invoice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reconciliation {
+    pub invoice_id: String,
+    pub transaction_id: String, // Identifier of the movement, stable across statement downloads
+    pub booking_date: String,   // Date the bank booked the movement (YYYY-MM-DD)
+    pub amount: f32,            // Amount credited
+    pub description: String,    // Remittance text of the movement
+    pub reconciled_at: String,  // Local time of the confirmation (RFC 3339)
+}
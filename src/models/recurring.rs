@@ -0,0 +1,71 @@
+// Recurring invoice template for clients billed the same items periodically
+
+use super::client::Client;
+use super::item::Item;
+use super::rules::Rule;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Frequency {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Frequency {
+    pub const ALL: [Frequency; 3] = [Frequency::Monthly, Frequency::Quarterly, Frequency::Yearly];
+
+    // Months between two invoices
+    pub fn months(&self) -> u32 {
+        match self {
+            Frequency::Monthly => 1,
+            Frequency::Quarterly => 3,
+            Frequency::Yearly => 12,
+        }
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Frequency::Monthly => "Monthly",
+            Frequency::Quarterly => "Quarterly",
+            Frequency::Yearly => "Yearly",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringInvoice {
+    pub id: String,
+    pub client: Client,
+    pub rule: Rule,
+    pub items: Vec<Item>,
+    pub frequency: Frequency,
+    pub day_of_month: u32,  // 1-31, the last day is used in shorter months
+    pub start_date: String, // First month billed (YYYY-MM-DD)
+    pub end_date: Option<String>, // No invoices after this date (YYYY-MM-DD)
+}
+
+impl RecurringInvoice {
+    // Amount of every invoice before taxes
+    pub fn subtotal(&self) -> f32 {
+        self.items.iter().map(|item| item.total()).sum()
+    }
+}
+
+impl fmt::Display for RecurringInvoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} on day {} from {}",
+            self.client.name, self.frequency, self.day_of_month, self.start_date
+        )?;
+        if let Some(end_date) = &self.end_date {
+            write!(f, " until {}", end_date)?;
+        }
+        write!(f, " ({:.2}€ + taxes)", self.subtotal())
+    }
+}
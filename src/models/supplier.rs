@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Supplier {
+    pub name: String,
+    pub cif: String,
+    pub address: String,
+    pub email: Option<String>,
+}
+
+impl Supplier {
+    pub fn new(name: String, cif: String, address: String, email: Option<String>) -> Self {
+        Supplier {
+            name,
+            cif,
+            address,
+            email,
+        }
+    }
+}
+
+impl fmt::Display for Supplier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Name: {}\nCIF/NIF: {}\nAddress: {}",
+            self.name, self.cif, self.address
+        )?;
+        if let Some(email) = &self.email {
+            write!(f, "\nEmail: {}", email)?;
+        }
+        Ok(())
+    }
+}
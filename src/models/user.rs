@@ -1,3 +1,4 @@
+use crate::services::iban::{format_iban, spanish_bank_name};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Debug};
 
@@ -8,6 +9,8 @@ pub struct User {
     pub cif: String,
     pub email: Option<String>,
     pub iban: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bic: Option<String>, // BIC of the bank holding the IBAN
 }
 
 impl User {
@@ -24,6 +27,7 @@ impl User {
             cif,
             email,
             iban,
+            bic: None,
         }
     }
 }
@@ -39,7 +43,13 @@ impl fmt::Display for User {
             write!(f, "\nEmail: {}", email)?;
         }
         if let Some(iban) = &self.iban {
-            write!(f, "\nIBAN: {}", iban)?;
+            write!(f, "\nIBAN: {}", format_iban(iban))?;
+            if let Some(bank) = spanish_bank_name(iban) {
+                write!(f, " ({})", bank)?;
+            }
+        }
+        if let Some(bic) = &self.bic {
+            write!(f, "\nBIC: {}", bic)?;
         }
         Ok(())
     }
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+// Answer of the VIES service about a VAT number, kept as evidence that the
+// intra-community status of a client was verified on a given date
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViesCheck {
+    pub vat_id: String,          // Full VAT number with its country prefix
+    pub valid: bool,             // Whether the number is active for intra-EU operations
+    pub name: Option<String>,    // Registered name, when the member state shares it
+    pub address: Option<String>, // Registered address, when the member state shares it
+    pub request_date: String,    // Consultation date as returned by VIES
+    pub checked_at: String,      // Local time of the consultation (RFC 3339)
+}
@@ -1,7 +1,24 @@
-use crate::models::{Client, Invoice, User};
+use crate::models::{
+    Asset, Client, Expense, Invoice, Modelo130Filing, Modelo349Filing, Quote, Reconciliation,
+    RecurringInvoice, Supplier, User, ViesCheck,
+};
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+// File name for an invoice number or tax ID. They come from users and imported files
+// ("2024/001"), so everything but letters, digits, '-' and '_' becomes '_'
+pub fn file_stem(id: &str) -> String {
+    id.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
 
 // File-based storage
 #[derive(Clone)]
@@ -51,7 +68,7 @@ impl Storage {
     // Client storage methods
     pub fn save_client(&self, client: &Client) -> io::Result<()> {
         let clients_dir = self.ensure_directory_exists("clients")?;
-        let filename = format!("{}.json", client.cif);
+        let filename = format!("{}.json", file_stem(&client.cif));
         let file_path = clients_dir.join(filename);
 
         let json = serde_json::to_string_pretty(&client)?;
@@ -86,10 +103,29 @@ impl Storage {
 
     // Invoice storage methods
     pub fn save_invoice(&self, invoice: &Invoice) -> io::Result<()> {
+        if invoice.id.trim().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invoice number cannot be empty",
+            ));
+        }
         let invoices_dir = self.ensure_directory_exists("invoices")?;
-        let filename = format!("{}.json", invoice.id);
+        let filename = format!("{}.json", file_stem(&invoice.id));
         let file_path = invoices_dir.join(filename);
 
+        // Numbers that only differ in replaced characters would share a file
+        if let Some(stored) = self.read_invoice(&file_path)? {
+            if stored.id != invoice.id {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "Invoice number {} clashes with the stored invoice {}",
+                        invoice.id, stored.id
+                    ),
+                ));
+            }
+        }
+
         let json = serde_json::to_string_pretty(&invoice)?;
         let mut file = File::create(file_path)?;
         file.write_all(json.as_bytes())?;
@@ -120,6 +156,366 @@ impl Storage {
         Ok(invoices)
     }
 
+    pub fn get_invoice(&self, id: &str) -> io::Result<Option<Invoice>> {
+        let invoices_dir = self.ensure_directory_exists("invoices")?;
+        let file_path = invoices_dir.join(format!("{}.json", file_stem(id)));
+
+        Ok(self
+            .read_invoice(&file_path)?
+            .filter(|invoice| invoice.id == id))
+    }
+
+    fn read_invoice(&self, file_path: &Path) -> io::Result<Option<Invoice>> {
+        if !file_path.exists() {
+            return Ok(None);
+        }
+
+        let mut file = File::open(file_path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let invoice: Invoice = serde_json::from_str(&contents)?;
+        Ok(Some(invoice))
+    }
+
+    // Quote and proforma storage methods
+    pub fn save_quote(&self, quote: &Quote) -> io::Result<()> {
+        let quotes_dir = self.ensure_directory_exists("quotes")?;
+        let filename = format!("{}.json", file_stem(&quote.id));
+        let file_path = quotes_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&quote)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_quotes(&self) -> io::Result<Vec<Quote>> {
+        let quotes_dir = self.ensure_directory_exists("quotes")?;
+        let mut quotes = Vec::new();
+
+        for entry in fs::read_dir(quotes_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<Quote>(&contents) {
+                    Ok(quote) => quotes.push(quote),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(quotes)
+    }
+
+    // Supplier storage methods
+    pub fn save_supplier(&self, supplier: &Supplier) -> io::Result<()> {
+        let suppliers_dir = self.ensure_directory_exists("suppliers")?;
+        let filename = format!("{}.json", file_stem(&supplier.cif));
+        let file_path = suppliers_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&supplier)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_suppliers(&self) -> io::Result<Vec<Supplier>> {
+        let suppliers_dir = self.ensure_directory_exists("suppliers")?;
+        let mut suppliers = Vec::new();
+
+        for entry in fs::read_dir(suppliers_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<Supplier>(&contents) {
+                    Ok(supplier) => suppliers.push(supplier),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(suppliers)
+    }
+
+    // Expense storage methods
+    pub fn save_expense(&self, expense: &Expense) -> io::Result<()> {
+        let expenses_dir = self.ensure_directory_exists("expenses")?;
+        let filename = format!("{}.json", expense.id);
+        let file_path = expenses_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&expense)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_expenses(&self) -> io::Result<Vec<Expense>> {
+        let expenses_dir = self.ensure_directory_exists("expenses")?;
+        let mut expenses = Vec::new();
+
+        for entry in fs::read_dir(expenses_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<Expense>(&contents) {
+                    Ok(expense) => expenses.push(expense),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(expenses)
+    }
+
+    // Copy the original document of an expense, returning its stored file name
+    pub fn save_expense_attachment(&self, expense_id: &str, source: &Path) -> io::Result<String> {
+        let attachments_dir = self.ensure_directory_exists("expenses/attachments")?;
+        let extension = source
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("pdf")
+            .to_lowercase();
+        let filename = format!("{}.{}", expense_id, extension);
+
+        fs::copy(source, attachments_dir.join(&filename))?;
+        Ok(filename)
+    }
+
+    pub fn expense_attachment_path(&self, filename: &str) -> PathBuf {
+        self.base_path
+            .join("expenses")
+            .join("attachments")
+            .join(filename)
+    }
+
+    // Fixed asset storage methods
+    pub fn save_asset(&self, asset: &Asset) -> io::Result<()> {
+        let assets_dir = self.ensure_directory_exists("assets")?;
+        let filename = format!("{}.json", asset.id);
+        let file_path = assets_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&asset)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_assets(&self) -> io::Result<Vec<Asset>> {
+        let assets_dir = self.ensure_directory_exists("assets")?;
+        let mut assets = Vec::new();
+
+        for entry in fs::read_dir(assets_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<Asset>(&contents) {
+                    Ok(asset) => assets.push(asset),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(assets)
+    }
+
+    // Tax filing storage methods
+    pub fn save_modelo130_filing(&self, filing: &Modelo130Filing) -> io::Result<()> {
+        let filings_dir = self.ensure_directory_exists("modelo130")?;
+        let filename = format!("{}-{}T.json", filing.year, filing.quarter);
+        let file_path = filings_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&filing)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_modelo130_filings(&self) -> io::Result<Vec<Modelo130Filing>> {
+        let filings_dir = self.ensure_directory_exists("modelo130")?;
+        let mut filings = Vec::new();
+
+        for entry in fs::read_dir(filings_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<Modelo130Filing>(&contents) {
+                    Ok(filing) => filings.push(filing),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(filings)
+    }
+
+    pub fn save_modelo349_filing(&self, filing: &Modelo349Filing) -> io::Result<()> {
+        let filings_dir = self.ensure_directory_exists("modelo349")?;
+        let filename = format!("{}-{}T.json", filing.year, filing.quarter);
+        let file_path = filings_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&filing)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_modelo349_filings(&self) -> io::Result<Vec<Modelo349Filing>> {
+        let filings_dir = self.ensure_directory_exists("modelo349")?;
+        let mut filings = Vec::new();
+
+        for entry in fs::read_dir(filings_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<Modelo349Filing>(&contents) {
+                    Ok(filing) => filings.push(filing),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(filings)
+    }
+
+    // VIES consultations, every check of a VAT number is kept in its history file
+    pub fn save_vies_check(&self, check: &ViesCheck) -> io::Result<()> {
+        let mut checks = self.list_vies_checks(&check.vat_id)?;
+        checks.push(check.clone());
+
+        let vies_dir = self.ensure_directory_exists("vies")?;
+        let file_path = vies_dir.join(format!("{}.json", file_stem(&check.vat_id)));
+        let json = serde_json::to_string_pretty(&checks)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_vies_checks(&self, vat_id: &str) -> io::Result<Vec<ViesCheck>> {
+        let vies_dir = self.ensure_directory_exists("vies")?;
+        let file_path = vies_dir.join(format!("{}.json", file_stem(vat_id)));
+
+        if !file_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut file = File::open(file_path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let checks: Vec<ViesCheck> = serde_json::from_str(&contents)?;
+        Ok(checks)
+    }
+
+    // Bank movements confirmed as invoice payments, one per invoice
+    pub fn save_reconciliation(&self, reconciliation: &Reconciliation) -> io::Result<()> {
+        let reconciliations_dir = self.ensure_directory_exists("reconciliations")?;
+        let filename = format!("{}.json", file_stem(&reconciliation.invoice_id));
+        let file_path = reconciliations_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&reconciliation)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_reconciliations(&self) -> io::Result<Vec<Reconciliation>> {
+        let reconciliations_dir = self.ensure_directory_exists("reconciliations")?;
+        let mut reconciliations = Vec::new();
+
+        for entry in fs::read_dir(reconciliations_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<Reconciliation>(&contents) {
+                    Ok(reconciliation) => reconciliations.push(reconciliation),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(reconciliations)
+    }
+
+    // Recurring invoice templates
+    pub fn save_recurring_invoice(&self, template: &RecurringInvoice) -> io::Result<()> {
+        let recurring_dir = self.ensure_directory_exists("recurring")?;
+        let filename = format!("{}.json", template.id);
+        let file_path = recurring_dir.join(filename);
+
+        let json = serde_json::to_string_pretty(&template)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(json.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn list_recurring_invoices(&self) -> io::Result<Vec<RecurringInvoice>> {
+        let recurring_dir = self.ensure_directory_exists("recurring")?;
+        let mut templates = Vec::new();
+
+        for entry in fs::read_dir(recurring_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
+                let mut file = File::open(path)?;
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+
+                match serde_json::from_str::<RecurringInvoice>(&contents) {
+                    Ok(template) => templates.push(template),
+                    Err(_) => continue, // Skip invalid files
+                }
+            }
+        }
+
+        Ok(templates)
+    }
+
     // User storage methods
     pub fn save_user(&self, user: &User) -> io::Result<()> {
         let user_file = self.base_path.join("user.json");
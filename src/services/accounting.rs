@@ -0,0 +1,119 @@
+// Exports of the issued invoices for accounting software
+
+use crate::config::AccountMapping;
+use crate::models::{Client, Invoice};
+use crate::repository::Storage;
+use crate::services::einvoice::local_tax_id;
+use crate::services::journal::{self, JournalEntry};
+use crate::services::period::DateRange;
+use crate::services::plain_text;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+
+pub struct AccountingService {
+    storage: Storage,
+    output_dir: String, // Directory where the exports are written
+    mapping: AccountMapping,
+}
+
+impl AccountingService {
+    pub fn new(storage: Storage, output_dir: String, mapping: AccountMapping) -> io::Result<Self> {
+        std::fs::create_dir_all(&output_dir)?;
+        Ok(AccountingService {
+            storage,
+            output_dir,
+            mapping,
+        })
+    }
+
+    pub fn journal(&self, range: DateRange) -> io::Result<Vec<JournalEntry>> {
+        let invoices = self.storage.list_invoices()?;
+        let subaccounts = self.client_subaccounts(&invoices)?;
+        Ok(journal::journal_entries(
+            &invoices,
+            range,
+            &subaccounts,
+            &self.mapping,
+        ))
+    }
+
+    // Subaccounts of the invoiced clients, new ones are stored with the client so a
+    // client added later cannot take them over
+    fn client_subaccounts(&self, invoices: &[Invoice]) -> io::Result<BTreeMap<String, String>> {
+        let clients = self.storage.list_clients()?;
+        let subaccounts = journal::client_subaccounts(invoices, &clients, &self.mapping);
+
+        let mut stored: BTreeMap<String, Client> = clients
+            .into_iter()
+            .map(|client| (local_tax_id(&client.cif), client))
+            .collect();
+        for client in invoices
+            .iter()
+            .filter_map(|invoice| invoice.client.as_ref())
+        {
+            let tax_id = local_tax_id(&client.cif);
+            let client = stored
+                .entry(tax_id.clone())
+                .or_insert_with(|| client.clone());
+            if client.subaccount != subaccounts.get(&tax_id).cloned() {
+                client.subaccount = subaccounts.get(&tax_id).cloned();
+                self.storage.save_client(client)?;
+            }
+        }
+        Ok(subaccounts)
+    }
+
+    // Write the journal as CSV and in the ContaPlus and A3 import formats
+    pub fn export_journal(
+        &self,
+        entries: &[JournalEntry],
+        range: DateRange,
+    ) -> io::Result<Vec<String>> {
+        let name = format!(
+            "diario_{}_{}",
+            range.from.format("%Y%m%d"),
+            range.to.format("%Y%m%d")
+        );
+
+        Ok(vec![
+            self.write(&format!("{}.csv", name), &journal::to_csv(entries)?)?,
+            self.write(
+                &format!("{}_contaplus.txt", name),
+                &journal::to_contaplus(entries),
+            )?,
+            self.write(
+                &format!("{}_a3.txt", name),
+                &journal::to_a3(entries, &self.mapping.company_code),
+            )?,
+        ])
+    }
+
+    // Write the invoices of the range as ledger-cli and beancount files
+    pub fn export_plain_text(&self, range: DateRange) -> io::Result<Vec<String>> {
+        let invoices = self.storage.list_invoices()?;
+        let name = format!(
+            "invoices_{}_{}",
+            range.from.format("%Y%m%d"),
+            range.to.format("%Y%m%d")
+        );
+
+        Ok(vec![
+            self.write(
+                &format!("{}.ledger", name),
+                plain_text::to_ledger(&invoices, range).as_bytes(),
+            )?,
+            self.write(
+                &format!("{}.beancount", name),
+                plain_text::to_beancount(&invoices, range).as_bytes(),
+            )?,
+        ])
+    }
+
+    fn write(&self, file_name: &str, contents: &[u8]) -> io::Result<String> {
+        let output_path = format!("{}/{}", self.output_dir, file_name);
+        let mut file = File::create(&output_path)?;
+        file.write_all(contents)?;
+        Ok(output_path)
+    }
+}
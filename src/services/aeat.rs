@@ -0,0 +1,36 @@
+// Field helpers for the fixed-width files accepted by the AEAT presentation service
+// Files are ISO-8859-1 encoded, alphanumeric fields are uppercase and blank padded
+
+// Alphanumeric field, left aligned and truncated to the width
+pub fn alpha(value: &str, width: usize) -> String {
+    let value: String = value
+        .trim()
+        .to_uppercase()
+        .chars()
+        .map(|c| if (c as u32) < 0x100 { c } else { ' ' })
+        .take(width)
+        .collect();
+    format!("{:<width$}", value, width = width)
+}
+
+// Unsigned numeric field, zero padded on the left
+pub fn numeric(value: u64, width: usize) -> String {
+    format!("{:0>width$}", value, width = width)
+}
+
+// Amount in cents, negative amounts start with "N" instead of the first zero
+pub fn amount(cents: i64, width: usize) -> String {
+    let digits = numeric(cents.unsigned_abs(), width);
+    if cents < 0 {
+        format!("N{}", &digits[1..])
+    } else {
+        digits
+    }
+}
+
+// Encode a record for the AEAT, which only accepts ISO-8859-1
+pub fn to_latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+        .collect()
+}
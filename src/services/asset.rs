@@ -0,0 +1,157 @@
+// Fixed asset register and linear depreciation schedules
+// The first year is prorated by the days the asset was in service
+
+use crate::models::{Asset, AssetCategory, Supplier};
+use crate::repository::storage::Storage;
+use crate::services::money::{percent_of, to_cents};
+use crate::services::period::DateRange;
+use chrono::{Datelike, NaiveDate};
+use std::io;
+use uuid::Uuid;
+
+// Parameters for registering an asset
+pub struct CreateAssetParams {
+    pub description: String,
+    pub supplier: Supplier,
+    pub purchase_date: String,
+    pub value: f32,
+    pub category: AssetCategory,
+    pub coefficient: f32,
+    pub expense_id: Option<String>,
+}
+
+// Depreciation of one fiscal year, in cents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepreciationYear {
+    pub year: i32,
+    pub amount: i64,
+    pub accumulated: i64,
+    pub remaining: i64,
+}
+
+pub struct AssetService {
+    storage: Storage,
+}
+
+impl AssetService {
+    pub fn new(storage: Storage) -> Self {
+        AssetService { storage }
+    }
+
+    pub fn create_asset(&self, params: CreateAssetParams) -> io::Result<Asset> {
+        let category = params.category;
+        if params.coefficient < category.min_coefficient()
+            || params.coefficient > category.max_coefficient()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Coefficient for {} must be between {:.2}% and {}%",
+                    category,
+                    category.min_coefficient(),
+                    category.max_coefficient()
+                ),
+            ));
+        }
+        if params.value <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Asset value must be greater than zero",
+            ));
+        }
+
+        let mut asset = Asset::new(
+            Uuid::new_v4().to_string(),
+            params.description,
+            params.supplier,
+            params.purchase_date,
+            params.value,
+            category,
+            params.coefficient,
+        );
+        asset.expense_id = params.expense_id;
+
+        self.storage.save_asset(&asset)?;
+        Ok(asset)
+    }
+
+    // Assets sorted by purchase date
+    pub fn list_assets(&self) -> io::Result<Vec<Asset>> {
+        let mut assets = self.storage.list_assets()?;
+        assets.sort_by(|a, b| a.purchase_date.cmp(&b.purchase_date));
+        Ok(assets)
+    }
+}
+
+// Periods of each year in which the asset depreciates, with the amount of the year
+fn depreciation_periods(asset: &Asset) -> Vec<(NaiveDate, NaiveDate, i64)> {
+    let Ok(start) = NaiveDate::parse_from_str(&asset.purchase_date, "%Y-%m-%d") else {
+        return Vec::new();
+    };
+    let value = to_cents(asset.value);
+    let annual = percent_of(value, asset.coefficient);
+    if annual <= 0 {
+        return Vec::new();
+    }
+
+    let mut periods = Vec::new();
+    let mut accumulated = 0;
+    let mut from = start;
+    while accumulated < value {
+        let year_start = NaiveDate::from_ymd_opt(from.year(), 1, 1).unwrap();
+        let year_end = NaiveDate::from_ymd_opt(from.year(), 12, 31).unwrap();
+        let year_days = (year_end - year_start).num_days() + 1;
+        let days = (year_end - from).num_days() + 1;
+
+        let amount = (annual * days / year_days).min(value - accumulated);
+        // The last year only runs for as long as the remaining value needs
+        let to = if accumulated + amount == value && amount < annual * days / year_days {
+            let needed = (amount * year_days + annual - 1) / annual;
+            from + chrono::Days::new(needed.max(1) as u64 - 1)
+        } else {
+            year_end
+        };
+
+        periods.push((from, to, amount));
+        accumulated += amount;
+        from = year_end.succ_opt().unwrap();
+    }
+
+    periods
+}
+
+// Yearly schedule until the asset is fully depreciated
+pub fn schedule(asset: &Asset) -> Vec<DepreciationYear> {
+    let value = to_cents(asset.value);
+    let mut accumulated = 0;
+
+    depreciation_periods(asset)
+        .into_iter()
+        .map(|(from, _, amount)| {
+            accumulated += amount;
+            DepreciationYear {
+                year: from.year(),
+                amount,
+                accumulated,
+                remaining: value - accumulated,
+            }
+        })
+        .collect()
+}
+
+// Depreciation falling within a date range, spread evenly over the days of each year
+pub fn depreciation_in(asset: &Asset, range: DateRange) -> i64 {
+    depreciation_periods(asset)
+        .into_iter()
+        .map(|(from, to, amount)| {
+            let overlap_from = from.max(range.from);
+            let overlap_to = to.min(range.to);
+            if overlap_from > overlap_to {
+                return 0;
+            }
+            let days = (to - from).num_days() + 1;
+            let overlap = (overlap_to - overlap_from).num_days() + 1;
+            (amount as f64 * overlap as f64 / days as f64).round() as i64
+        })
+        .sum()
+}
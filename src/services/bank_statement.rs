@@ -0,0 +1,319 @@
+// Bank statements downloaded from the bank: the Spanish Norma 43 (AEB/CSB43) fixed
+// width file and the ISO 20022 camt.053 XML statement
+
+use crate::services::money::{format_cents, parse_cents};
+use crate::services::xml::{child, children, text};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::io;
+
+// Movement of a bank account, credits are positive and debits negative
+#[derive(Debug, Clone, PartialEq)]
+pub struct BankTransaction {
+    pub account: String,      // IBAN, or entity, office and number in Norma 43
+    pub booking_date: String, // YYYY-MM-DD
+    pub value_date: String,   // YYYY-MM-DD
+    pub amount: i64,          // Cents
+    pub reference: String,    // Bank reference of the movement, may be empty
+    pub counterparty: Option<String>, // Name of the payer or payee, when the bank sends it
+    pub description: String,  // Remittance information
+    pub sequence: u32,        // Order among identical movements of the statement, from 0
+}
+
+impl BankTransaction {
+    // Identifier that stays the same when the movement appears in another statement,
+    // identical movements of a day are told apart by their order
+    pub fn id(&self) -> String {
+        // FNV-1a, stable across runs and platforms unlike the standard hasher
+        let hash = [&self.account, &self.reference, &self.description]
+            .iter()
+            .flat_map(|field| field.bytes().chain([0]))
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        let id = format!(
+            "{}-{}-{:016x}",
+            self.booking_date,
+            format_cents(self.amount),
+            hash
+        );
+        match self.sequence {
+            0 => id,
+            sequence => format!("{}-{}", id, sequence),
+        }
+    }
+
+    pub fn is_credit(&self) -> bool {
+        self.amount > 0
+    }
+}
+
+// Read a statement in either format
+pub fn parse_statement(contents: &[u8]) -> io::Result<Vec<BankTransaction>> {
+    let text = decode(contents);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with('<') {
+        parse_camt053(text)
+    } else if text.starts_with("11") {
+        parse_norma43(text)
+    } else {
+        Err(invalid(
+            "Unknown statement format, expected Norma 43 or camt.053".to_string(),
+        ))
+    }
+}
+
+// Norma 43 files are usually ISO-8859-1, camt.053 files UTF-8
+fn decode(contents: &[u8]) -> String {
+    match std::str::from_utf8(contents) {
+        Ok(text) => text.to_string(),
+        Err(_) => contents.iter().map(|byte| *byte as char).collect(),
+    }
+}
+
+// Norma 43: 80 character records, 11 opens an account, 22 is a movement, 23 adds
+// concept lines to it, 33 closes the account with its totals and 88 ends the file
+pub fn parse_norma43(contents: &str) -> io::Result<Vec<BankTransaction>> {
+    let mut transactions = Vec::new();
+    let mut account: Option<String> = None;
+    let mut opened_at = 0;
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Vec<char> = line
+            .chars()
+            .chain(std::iter::repeat(' '))
+            .take(80)
+            .collect();
+        let field = |from: usize, to: usize| record[from - 1..to].iter().collect::<String>();
+        let error = |message: &str| invalid(format!("Norma 43 line {}: {}", number + 1, message));
+
+        match field(1, 2).as_str() {
+            "11" => {
+                account = Some(format!("{}{}{}", field(3, 6), field(7, 10), field(11, 20)));
+                opened_at = transactions.len();
+            }
+            "22" => {
+                let account = account
+                    .clone()
+                    .ok_or_else(|| error("movement outside an account"))?;
+                let booking_date =
+                    norma43_date(&field(11, 16)).ok_or_else(|| error("invalid date"))?;
+                let value_date =
+                    norma43_date(&field(17, 22)).ok_or_else(|| error("invalid value date"))?;
+                let amount = norma43_amount(&field(28, 28), &field(29, 42))
+                    .ok_or_else(|| error("invalid amount"))?;
+                let reference = [field(53, 64), field(65, 80)]
+                    .iter()
+                    .map(|r| r.trim())
+                    .filter(|r| !r.is_empty() && r.chars().any(|c| c != '0'))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                transactions.push(BankTransaction {
+                    account,
+                    booking_date,
+                    value_date,
+                    amount,
+                    reference,
+                    counterparty: None,
+                    description: String::new(),
+                    sequence: 0,
+                });
+            }
+            "23" => {
+                let Some(transaction) = transactions[opened_at..].last_mut() else {
+                    return Err(error("concept without a movement"));
+                };
+                for concept in [field(5, 42), field(43, 80)] {
+                    let concept = concept.trim();
+                    if !concept.is_empty() {
+                        if !transaction.description.is_empty() {
+                            transaction.description.push(' ');
+                        }
+                        transaction.description.push_str(concept);
+                    }
+                }
+            }
+            "33" => {
+                let movements = &transactions[opened_at..];
+                let debits: Vec<i64> = movements
+                    .iter()
+                    .filter(|t| t.amount < 0)
+                    .map(|t| -t.amount)
+                    .collect();
+                let credits: Vec<i64> = movements
+                    .iter()
+                    .filter(|t| t.amount > 0)
+                    .map(|t| t.amount)
+                    .collect();
+                let expected = (
+                    field(21, 25).trim().parse::<usize>().ok(),
+                    field(26, 39).trim().parse::<i64>().ok(),
+                    field(40, 44).trim().parse::<usize>().ok(),
+                    field(45, 58).trim().parse::<i64>().ok(),
+                );
+                let found = (
+                    Some(debits.len()),
+                    Some(debits.iter().sum()),
+                    Some(credits.len()),
+                    Some(credits.iter().sum()),
+                );
+                if expected != found {
+                    return Err(error("the account totals do not match its movements"));
+                }
+                account = None;
+            }
+            "24" | "88" => {}
+            other => return Err(error(&format!("unknown record type '{}'", other))),
+        }
+    }
+
+    number_repeated(&mut transactions);
+    Ok(transactions)
+}
+
+fn norma43_date(yymmdd: &str) -> Option<String> {
+    NaiveDate::parse_from_str(&format!("20{}", yymmdd), "%Y%m%d")
+        .ok()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+// Sign code 1 is a debit and 2 a credit, amounts have two implicit decimals
+fn norma43_amount(sign: &str, digits: &str) -> Option<i64> {
+    let cents: i64 = digits.trim().parse().ok()?;
+    match sign {
+        "1" => Some(-cents),
+        "2" => Some(cents),
+        _ => None,
+    }
+}
+
+// camt.053: booked entries of every statement, with one movement per transaction
+// detail when the bank groups several of them in one entry
+pub fn parse_camt053(contents: &str) -> io::Result<Vec<BankTransaction>> {
+    let document = roxmltree::Document::parse(contents)
+        .map_err(|e| invalid(format!("Invalid camt.053 XML: {}", e)))?;
+    let statements = child(document.root_element(), "BkToCstmrStmt")
+        .map(|node| children(node, "Stmt"))
+        .ok_or_else(|| invalid("The XML is not a camt.053 statement".to_string()))?;
+
+    let mut transactions = Vec::new();
+    for statement in statements {
+        let account = text(statement, &["Acct", "Id", "IBAN"])
+            .or_else(|| text(statement, &["Acct", "Id", "Othr", "Id"]))
+            .unwrap_or_default()
+            .to_string();
+
+        for entry in children(statement, "Ntry") {
+            let status = text(entry, &["Sts"]).or_else(|| text(entry, &["Sts", "Cd"]));
+            if status.is_some_and(|status| status != "BOOK") {
+                continue;
+            }
+
+            let booking_date = camt_date(entry, "BookgDt")
+                .ok_or_else(|| invalid("camt.053 entry without a booking date".to_string()))?;
+            let value_date = camt_date(entry, "ValDt").unwrap_or_else(|| booking_date.clone());
+            let debit = text(entry, &["CdtDbtInd"]) == Some("DBIT");
+            let entry_reference = text(entry, &["AcctSvcrRef"]).unwrap_or_default();
+            let entry_info = text(entry, &["AddtlNtryInf"]).unwrap_or_default();
+
+            let details: Vec<_> = child(entry, "NtryDtls")
+                .map(|node| children(node, "TxDtls"))
+                .unwrap_or_default();
+            // The amount of a single detail is the entry amount, several details carry their own
+            let entry_amount = camt_amount(entry, &["Amt"]);
+            let parts: Vec<(Option<roxmltree::Node>, Option<i64>)> = match details.len() {
+                0 => vec![(None, entry_amount)],
+                1 => vec![(Some(details[0]), entry_amount)],
+                _ => details
+                    .iter()
+                    .map(|detail| {
+                        let amount = camt_amount(*detail, &["AmtDtls", "TxAmt", "Amt"])
+                            .or_else(|| camt_amount(*detail, &["Amt"]));
+                        (Some(*detail), amount)
+                    })
+                    .collect(),
+            };
+
+            for (detail, amount) in parts {
+                let amount = amount.ok_or_else(|| {
+                    invalid(format!(
+                        "camt.053 entry of {} without an amount",
+                        booking_date
+                    ))
+                })?;
+                let detail_text = |path: &[&str]| detail.and_then(|node| text(node, path));
+
+                let reference = detail_text(&["Refs", "EndToEndId"])
+                    .filter(|id| *id != "NOTPROVIDED")
+                    .or_else(|| detail_text(&["Refs", "AcctSvcrRef"]))
+                    .unwrap_or(entry_reference)
+                    .to_string();
+                let party = if debit { "Cdtr" } else { "Dbtr" };
+                let counterparty = detail_text(&["RltdPties", party, "Nm"])
+                    .or_else(|| detail_text(&["RltdPties", party, "Pty", "Nm"]))
+                    .map(str::to_string);
+                let mut description: Vec<&str> = detail
+                    .and_then(|node| child(node, "RmtInf"))
+                    .map(|node| {
+                        children(node, "Ustrd")
+                            .into_iter()
+                            .filter_map(|n| n.text().map(str::trim))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                if let Some(reference) = detail_text(&["RmtInf", "Strd", "CdtrRefInf", "Ref"]) {
+                    description.push(reference);
+                }
+                if description.is_empty() && !entry_info.is_empty() {
+                    description.push(entry_info);
+                }
+
+                transactions.push(BankTransaction {
+                    account: account.clone(),
+                    booking_date: booking_date.clone(),
+                    value_date: value_date.clone(),
+                    amount: if debit { -amount } else { amount },
+                    reference,
+                    counterparty,
+                    description: description.join(" "),
+                    sequence: 0,
+                });
+            }
+        }
+    }
+
+    number_repeated(&mut transactions);
+    Ok(transactions)
+}
+
+// Two equal transfers of the same day have the same fields, number them in the order
+// the bank lists them so each one keeps its own identifier
+fn number_repeated(transactions: &mut [BankTransaction]) {
+    let mut seen: HashMap<String, u32> = HashMap::new();
+    for transaction in transactions {
+        let count = seen.entry(transaction.id()).or_default();
+        transaction.sequence = *count;
+        *count += 1;
+    }
+}
+
+fn camt_date(entry: roxmltree::Node, element: &str) -> Option<String> {
+    text(entry, &[element, "Dt"])
+        .or_else(|| text(entry, &[element, "DtTm"]))
+        .and_then(|date| date.get(..10))
+        .filter(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+        .map(str::to_string)
+}
+
+fn camt_amount(node: roxmltree::Node, path: &[&str]) -> Option<i64> {
+    text(node, path).and_then(parse_cents).map(i64::abs)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
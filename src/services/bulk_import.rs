@@ -0,0 +1,576 @@
+// Bulk import of clients and historical invoices from CSV files or spreadsheets, used
+// when migrating from another tool. Columns are found by the header names configured
+// in the mapping, rows are checked with the same rules as the interactive forms and
+// nothing is stored until the report has been reviewed
+
+use crate::config::{ClientColumns, ImportMapping, InvoiceColumns};
+use crate::models::{Client, Invoice, Item, Rule, User};
+use crate::repository::storage::file_stem;
+use crate::repository::Storage;
+use crate::services::einvoice::local_tax_id;
+use crate::services::validation::{
+    validate_client_tax_id, validate_date, validate_email, validate_percentage,
+};
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+// Spreadsheet formats read with calamine, anything else is read as CSV
+const SPREADSHEET_EXTENSIONS: &[&str] = &["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+// A row that cannot be imported, numbered as in the spreadsheet (the header is row 1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub row: usize,
+    pub message: String,
+}
+
+// Outcome of checking a file: what would be imported, what already exists and what fails
+#[derive(Debug, Clone)]
+pub struct ImportReport<T> {
+    pub valid: Vec<T>,
+    pub duplicates: Vec<RowError>,
+    pub errors: Vec<RowError>,
+}
+
+// Outcome of storing the checked records. One that cannot be written is reported and the
+// rest are still stored, running the import again skips those already stored
+#[derive(Debug, Clone, Default)]
+pub struct ImportOutcome {
+    pub imported: usize,
+    pub failed: Vec<String>,
+}
+
+// Cells of the first sheet of a spreadsheet, or of a CSV file separated by commas or
+// semicolons, as text
+pub fn read_table(path: &Path) -> io::Result<Vec<Vec<String>>> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if SPREADSHEET_EXTENSIONS.contains(&extension.as_str()) {
+        read_spreadsheet(path)
+    } else {
+        parse_csv(&std::fs::read(path)?)
+    }
+}
+
+fn read_spreadsheet(path: &Path) -> io::Result<Vec<Vec<String>>> {
+    let mut workbook = open_workbook_auto(path)
+        .map_err(|e| invalid(format!("Cannot open the spreadsheet: {}", e)))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| invalid("The spreadsheet has no sheets".to_string()))?
+        .map_err(|e| invalid(format!("Cannot read the spreadsheet: {}", e)))?;
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(cell_text).collect())
+        .collect())
+}
+
+// Dates are written as YYYY-MM-DD, whole numbers without decimals
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(_) | Data::DateTimeIso(_) => cell
+            .as_date()
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| cell.to_string()),
+        Data::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+            format!("{}", *value as i64)
+        }
+        _ => cell.to_string(),
+    }
+}
+
+// Spreadsheets saved as CSV with a Spanish locale use semicolons
+pub fn parse_csv(contents: &[u8]) -> io::Result<Vec<Vec<String>>> {
+    let text = String::from_utf8_lossy(contents);
+    let text = text.trim_start_matches('\u{feff}');
+    let header = text.lines().next().unwrap_or_default();
+    let delimiter = if header.matches(';').count() > header.matches(',').count() {
+        b';'
+    } else {
+        b','
+    };
+
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes())
+        .records()
+        .map(|record| {
+            record
+                .map(|record| record.iter().map(str::to_string).collect())
+                .map_err(|e| invalid(format!("Invalid CSV file: {}", e)))
+        })
+        .collect()
+}
+
+// Position of every mapped column, required columns must be in the header
+struct Header {
+    positions: HashMap<String, usize>,
+}
+
+impl Header {
+    fn new(row: &[String], required: &[&str]) -> io::Result<Self> {
+        let positions: HashMap<String, usize> = row
+            .iter()
+            .enumerate()
+            .map(|(position, name)| (name.trim().to_lowercase(), position))
+            .collect();
+        let missing: Vec<&str> = required
+            .iter()
+            .filter(|column| !positions.contains_key(&column.trim().to_lowercase()))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            return Err(invalid(format!(
+                "Missing columns: {}. Check the import mapping in the configuration",
+                missing.join(", ")
+            )));
+        }
+        Ok(Header { positions })
+    }
+
+    fn get<'a>(&self, row: &'a [String], column: &str) -> &'a str {
+        self.positions
+            .get(&column.trim().to_lowercase())
+            .and_then(|position| row.get(*position))
+            .map(|value| value.trim())
+            .unwrap_or_default()
+    }
+}
+
+fn required(value: &str, field: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Err(format!("Required field is empty: {}", field));
+    }
+    Ok(())
+}
+
+// Amounts with a decimal point or a decimal comma ("1234.50", "1.234,50", "1,234.50"):
+// the last of ',' and '.' separates the decimals and the other one groups thousands. A
+// single separator before three digits ("1.234") could be either, so it is an error
+fn parse_amount(text: &str) -> Result<f32, String> {
+    let text = text.trim();
+    let value = text.replace(['€', ' '], "");
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value.as_str()),
+    };
+    let malformed = || text.to_string();
+
+    let (integer, fraction, thousands) = match (digits.rfind(','), digits.rfind('.')) {
+        (None, None) => (digits, "", None),
+        (Some(comma), Some(dot)) => {
+            let (decimal, thousands) = if comma > dot {
+                (comma, '.')
+            } else {
+                (dot, ',')
+            };
+            (&digits[..decimal], &digits[decimal + 1..], Some(thousands))
+        }
+        (Some(position), None) | (None, Some(position)) => {
+            let separator = digits.as_bytes()[position] as char;
+            let (integer, fraction) = (&digits[..position], &digits[position + 1..]);
+            if integer.contains(separator) {
+                // "1.234.567", every separator groups thousands
+                (digits, "", Some(separator))
+            } else if fraction.len() == 3
+                && (1..=3).contains(&integer.len())
+                && !integer.starts_with('0')
+            {
+                return Err(format!("{} (thousands or decimals?)", text));
+            } else {
+                (integer, fraction, None)
+            }
+        }
+    };
+    let integer = match thousands {
+        Some(separator) if thousands_grouped(integer, separator) => integer.replace(separator, ""),
+        Some(_) => return Err(malformed()),
+        None => integer.to_string(),
+    };
+    if integer.is_empty() && fraction.is_empty()
+        || !integer.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(malformed());
+    }
+
+    // Padded with zeros so that ",5" and "12," still read as numbers
+    format!("{}0{}.{}0", sign, integer, fraction)
+        .parse()
+        .ok()
+        .filter(|amount: &f32| amount.is_finite())
+        .ok_or_else(malformed)
+}
+
+// "1.234.567": up to three digits, then groups of three
+fn thousands_grouped(integer: &str, separator: char) -> bool {
+    let mut groups = integer.split(separator);
+    groups
+        .next()
+        .is_some_and(|first| (1..=3).contains(&first.len()))
+        && groups.all(|group| group.len() == 3)
+}
+
+fn is_empty_row(row: &[String]) -> bool {
+    row.iter().all(|value| value.trim().is_empty())
+}
+
+// Clients of the table. Those whose tax ID is already stored, or repeated in the file,
+// are reported as duplicates and left out
+pub fn check_clients(
+    table: &[Vec<String>],
+    columns: &ClientColumns,
+    existing: &[Client],
+) -> io::Result<ImportReport<Client>> {
+    let Some((header, rows)) = table.split_first() else {
+        return Err(invalid("The file is empty".to_string()));
+    };
+    let header = Header::new(header, &[&columns.name, &columns.cif, &columns.address])?;
+
+    let mut known: HashMap<String, String> = existing
+        .iter()
+        .map(|client| (local_tax_id(&client.cif), client.name.clone()))
+        .collect();
+    let mut report = ImportReport {
+        valid: Vec::new(),
+        duplicates: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (index, row) in rows.iter().enumerate() {
+        let row_number = index + 2;
+        if is_empty_row(row) {
+            continue;
+        }
+        let name = header.get(row, &columns.name);
+        let cif = header.get(row, &columns.cif).to_uppercase();
+        let address = header.get(row, &columns.address);
+        let email = header.get(row, &columns.email);
+
+        let validation = required(name, "name")
+            .and_then(|_| validate_client_tax_id(&cif).map_err(|e| e.to_string()))
+            .and_then(|_| required(address, "address"))
+            .and_then(|_| validate_email(email).map_err(|e| e.to_string()));
+        if let Err(message) = validation {
+            report.errors.push(RowError {
+                row: row_number,
+                message,
+            });
+            continue;
+        }
+
+        let tax_id = local_tax_id(&cif);
+        if let Some(known_name) = known.get(&tax_id) {
+            report.duplicates.push(RowError {
+                row: row_number,
+                message: format!("{} is already registered as {}", cif, known_name),
+            });
+            continue;
+        }
+        known.insert(tax_id.clone(), name.to_string());
+        report.valid.push(Client::new(
+            name.to_string(),
+            tax_id,
+            address.to_string(),
+            Some(email.to_string()).filter(|email| !email.is_empty()),
+        ));
+    }
+    Ok(report)
+}
+
+// An item row with the invoice fields it repeats
+struct InvoiceRow {
+    row: usize,
+    date: String,
+    due_date: String,
+    client: Client,
+    rule: (f32, f32),
+    paid_date: String,
+    item: Item,
+}
+
+// `files` holds the file name of every stored or accepted invoice number, numbers like
+// "2023/015" and "2023_015" would be saved to the same file
+fn check_invoice_row(
+    row_number: usize,
+    number: &str,
+    header: &Header,
+    row: &[String],
+    columns: &InvoiceColumns,
+    clients: &HashMap<String, Client>,
+    files: &HashMap<String, String>,
+) -> Result<InvoiceRow, String> {
+    if let Some(other) = files
+        .get(&file_stem(number))
+        .filter(|other| *other != number)
+    {
+        return Err(format!(
+            "the number would be saved to the same file as invoice {}",
+            other
+        ));
+    }
+    let date = header.get(row, &columns.date);
+    let due_date = header.get(row, &columns.due_date);
+    let paid_date = header.get(row, &columns.paid_date);
+    let client_cif = header.get(row, &columns.client_cif);
+    let description = header.get(row, &columns.description);
+
+    required(date, "date")?;
+    for date in [date, due_date, paid_date] {
+        validate_date(date).map_err(|e| e.to_string())?;
+    }
+    required(client_cif, "client_cif")?;
+    let client = clients
+        .get(&local_tax_id(client_cif))
+        .cloned()
+        .ok_or_else(|| format!("Client {} not found, import the clients first", client_cif))?;
+    required(description, "description")?;
+
+    let quantity = match header.get(row, &columns.quantity) {
+        "" => 1,
+        quantity => quantity
+            .parse::<u32>()
+            .ok()
+            .or_else(|| {
+                parse_amount(quantity)
+                    .ok()
+                    .filter(|q| q.fract() == 0.0)
+                    .map(|q| q as u32)
+            })
+            .filter(|quantity| *quantity > 0)
+            .ok_or_else(|| format!("Invalid quantity: {}", quantity))?,
+    };
+    let price = header.get(row, &columns.price);
+    let price = parse_amount(price).map_err(|value| format!("Invalid price: {}", value))?;
+    let percentage = |column: &str, default: f32| -> Result<f32, String> {
+        let value = header.get(row, column);
+        if value.is_empty() {
+            return Ok(default);
+        }
+        let percentage = parse_amount(value.trim_end_matches('%'))
+            .map_err(|value| format!("Invalid percentage: {}", value))?;
+        validate_percentage(percentage).map_err(|e| e.to_string())?;
+        Ok(percentage)
+    };
+    let iva = header.get(row, &columns.iva);
+    required(iva, "iva")?;
+    let iva = percentage(&columns.iva, 0.0)?;
+    let irpf = percentage(&columns.irpf, 0.0)?;
+
+    Ok(InvoiceRow {
+        row: row_number,
+        date: date.to_string(),
+        due_date: due_date.to_string(),
+        client,
+        rule: (iva, irpf),
+        paid_date: paid_date.to_string(),
+        item: Item::new(description.to_string(), quantity, price),
+    })
+}
+
+// Invoices of the table, issued by the user to clients already stored or in `clients`.
+// Numbers already stored are reported as duplicates. An invoice with a wrong row, or
+// rows that disagree on its date, client or rates, is not imported at all
+pub fn check_invoices(
+    table: &[Vec<String>],
+    columns: &InvoiceColumns,
+    clients: &[Client],
+    existing_numbers: &HashSet<String>,
+    user: &User,
+) -> io::Result<ImportReport<Invoice>> {
+    let Some((header, rows)) = table.split_first() else {
+        return Err(invalid("The file is empty".to_string()));
+    };
+    let header = Header::new(
+        header,
+        &[
+            &columns.number,
+            &columns.date,
+            &columns.client_cif,
+            &columns.description,
+            &columns.price,
+            &columns.iva,
+        ],
+    )?;
+    let clients: HashMap<String, Client> = clients
+        .iter()
+        .map(|client| (local_tax_id(&client.cif), client.clone()))
+        .collect();
+
+    let mut report = ImportReport {
+        valid: Vec::new(),
+        duplicates: Vec::new(),
+        errors: Vec::new(),
+    };
+    // Number -> rows, in the order the invoices appear
+    let mut invoices: BTreeMap<usize, (String, Vec<InvoiceRow>)> = BTreeMap::new();
+    let mut first_rows: HashMap<String, usize> = HashMap::new();
+    let mut failed: HashSet<String> = HashSet::new();
+    let mut files: HashMap<String, String> = existing_numbers
+        .iter()
+        .map(|number| (file_stem(number), number.clone()))
+        .collect();
+    for (index, row) in rows.iter().enumerate() {
+        let row_number = index + 2;
+        if is_empty_row(row) {
+            continue;
+        }
+        let number = header.get(row, &columns.number).to_string();
+        if number.is_empty() {
+            report.errors.push(RowError {
+                row: row_number,
+                message: "Required field is empty: number".to_string(),
+            });
+            continue;
+        }
+        if existing_numbers.contains(&number) {
+            if first_rows.insert(number.clone(), row_number).is_none() {
+                report.duplicates.push(RowError {
+                    row: row_number,
+                    message: format!("Invoice {} already exists", number),
+                });
+            }
+            continue;
+        }
+
+        match check_invoice_row(row_number, &number, &header, row, columns, &clients, &files) {
+            Ok(item_row) => {
+                files.insert(file_stem(&number), number.clone());
+                let first = *first_rows.entry(number.clone()).or_insert(row_number);
+                invoices
+                    .entry(first)
+                    .or_insert_with(|| (number, Vec::new()))
+                    .1
+                    .push(item_row);
+            }
+            Err(message) => {
+                first_rows.entry(number.clone()).or_insert(row_number);
+                failed.insert(number.clone());
+                report.errors.push(RowError {
+                    row: row_number,
+                    message: format!("Invoice {}: {}", number, message),
+                });
+            }
+        }
+    }
+
+    for (number, rows) in invoices.into_values() {
+        if failed.contains(&number) {
+            continue;
+        }
+        let first = &rows[0];
+        if let Some(different) = rows.iter().find(|row| {
+            row.date != first.date
+                || row.client.cif != first.client.cif
+                || row.rule != first.rule
+                || row.due_date != first.due_date
+                || row.paid_date != first.paid_date
+        }) {
+            report.errors.push(RowError {
+                row: different.row,
+                message: format!(
+                    "Invoice {}: date, client, rates and payment must be the same as in row {}",
+                    number, first.row
+                ),
+            });
+            continue;
+        }
+
+        let due_date = if first.due_date.is_empty() {
+            due_in_30_days(&first.date)
+        } else {
+            first.due_date.clone()
+        };
+        let mut invoice = Invoice::new(
+            number,
+            first.date.clone(),
+            due_date,
+            user.clone(),
+            first.client.clone(),
+            Rule::new(first.rule.0, first.rule.1),
+            rows.iter().map(|row| row.item.clone()).collect(),
+        );
+        invoice.paid_date = Some(first.paid_date.clone()).filter(|date| !date.is_empty());
+        report.valid.push(invoice);
+    }
+    report.errors.sort_by_key(|error| error.row);
+    Ok(report)
+}
+
+fn due_in_30_days(date: &str) -> String {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.checked_add_days(chrono::Days::new(30)))
+        .map(|due| due.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| date.to_string())
+}
+
+pub struct BulkImportService {
+    storage: Storage,
+    mapping: ImportMapping,
+}
+
+impl BulkImportService {
+    pub fn new(storage: Storage, mapping: ImportMapping) -> Self {
+        BulkImportService { storage, mapping }
+    }
+
+    // Dry run of a clients file, nothing is stored
+    pub fn check_clients(&self, path: &Path) -> io::Result<ImportReport<Client>> {
+        check_clients(
+            &read_table(path)?,
+            &self.mapping.clients,
+            &self.storage.list_clients()?,
+        )
+    }
+
+    // Dry run of an invoices file, nothing is stored
+    pub fn check_invoices(&self, path: &Path, user: &User) -> io::Result<ImportReport<Invoice>> {
+        let numbers: HashSet<String> = self
+            .storage
+            .list_invoices()?
+            .into_iter()
+            .map(|invoice| invoice.id)
+            .collect();
+        check_invoices(
+            &read_table(path)?,
+            &self.mapping.invoices,
+            &self.storage.list_clients()?,
+            &numbers,
+            user,
+        )
+    }
+
+    pub fn import_clients(&self, clients: &[Client]) -> ImportOutcome {
+        let mut outcome = ImportOutcome::default();
+        for client in clients {
+            match self.storage.save_client(client) {
+                Ok(()) => outcome.imported += 1,
+                Err(e) => outcome.failed.push(format!("Client {}: {}", client.cif, e)),
+            }
+        }
+        outcome
+    }
+
+    pub fn import_invoices(&self, invoices: &[Invoice]) -> ImportOutcome {
+        let mut outcome = ImportOutcome::default();
+        for invoice in invoices {
+            match self.storage.save_invoice(invoice) {
+                Ok(()) => outcome.imported += 1,
+                Err(e) => outcome
+                    .failed
+                    .push(format!("Invoice {}: {}", invoice.id, e)),
+            }
+        }
+        outcome
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
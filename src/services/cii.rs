@@ -0,0 +1,215 @@
+// UN/CEFACT Cross Industry Invoice (CII D16B) following the Factur-X / ZUGFeRD EN 16931 profile
+
+use crate::models::Invoice;
+use crate::services::einvoice::{buyer, country_code, vat_identifier, InvoiceTotals, TaxCategory};
+use crate::services::money::{format_cents, to_cents};
+use crate::services::xml::XmlWriter;
+
+pub const RSM_NS: &str = "urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100";
+pub const RAM_NS: &str =
+    "urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100";
+pub const UDT_NS: &str = "urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100";
+const QDT_NS: &str = "urn:un:unece:uncefact:data:standard:QualifiedDataType:100";
+
+// Guideline identifier of the Factur-X EN 16931 (COMFORT) profile
+pub const EN16931_GUIDELINE: &str = "urn:cen.eu:en16931:2017";
+
+const CURRENCY: &str = "EUR";
+
+// Build the CII document for an invoice
+pub fn to_cii_xml(invoice: &Invoice) -> String {
+    let totals = InvoiceTotals::from_invoice(invoice);
+    let category = TaxCategory::for_rate(invoice.rule.iva);
+    let mut xml = XmlWriter::new();
+
+    xml.start(
+        "rsm:CrossIndustryInvoice",
+        &[
+            ("xmlns:rsm", RSM_NS),
+            ("xmlns:ram", RAM_NS),
+            ("xmlns:udt", UDT_NS),
+            ("xmlns:qdt", QDT_NS),
+        ],
+    );
+
+    xml.start("rsm:ExchangedDocumentContext", &[]);
+    xml.start("ram:GuidelineSpecifiedDocumentContextParameter", &[]);
+    xml.leaf("ram:ID", &[], EN16931_GUIDELINE);
+    xml.end();
+    xml.end();
+
+    xml.start("rsm:ExchangedDocument", &[]);
+    xml.leaf("ram:ID", &[], &invoice.id);
+    xml.leaf("ram:TypeCode", &[], "380");
+    write_date(&mut xml, "ram:IssueDateTime", &invoice.date);
+    if totals.prepaid > 0 {
+        xml.start("ram:IncludedNote", &[]);
+        xml.leaf(
+            "ram:Content",
+            &[],
+            &format!(
+                "IRPF withholding {}%: {} EUR",
+                invoice.rule.irpf,
+                format_cents(totals.prepaid)
+            ),
+        );
+        xml.end();
+    }
+    xml.end();
+
+    xml.start("rsm:SupplyChainTradeTransaction", &[]);
+
+    // Lines
+    for (i, (item, amount)) in invoice.items.iter().zip(&totals.lines).enumerate() {
+        xml.start("ram:IncludedSupplyChainTradeLineItem", &[]);
+        xml.start("ram:AssociatedDocumentLineDocument", &[]);
+        xml.leaf("ram:LineID", &[], &(i + 1).to_string());
+        xml.end();
+        xml.start("ram:SpecifiedTradeProduct", &[]);
+        xml.leaf("ram:Name", &[], &item.description);
+        xml.end();
+        xml.start("ram:SpecifiedLineTradeAgreement", &[]);
+        xml.start("ram:NetPriceProductTradePrice", &[]);
+        xml.leaf("ram:ChargeAmount", &[], &format_cents(to_cents(item.price)));
+        xml.end();
+        xml.end();
+        xml.start("ram:SpecifiedLineTradeDelivery", &[]);
+        xml.leaf(
+            "ram:BilledQuantity",
+            &[("unitCode", "C62")],
+            &item.quantity.to_string(),
+        );
+        xml.end();
+        xml.start("ram:SpecifiedLineTradeSettlement", &[]);
+        xml.start("ram:ApplicableTradeTax", &[]);
+        xml.leaf("ram:TypeCode", &[], "VAT");
+        xml.leaf("ram:CategoryCode", &[], category.code());
+        xml.leaf(
+            "ram:RateApplicablePercent",
+            &[],
+            &format!("{:.2}", invoice.rule.iva),
+        );
+        xml.end();
+        xml.start("ram:SpecifiedTradeSettlementLineMonetarySummation", &[]);
+        xml.leaf("ram:LineTotalAmount", &[], &format_cents(*amount));
+        xml.end();
+        xml.end();
+        xml.end();
+    }
+
+    // Parties
+    xml.start("ram:ApplicableHeaderTradeAgreement", &[]);
+    xml.leaf("ram:BuyerReference", &[], &invoice.id);
+    write_party(
+        &mut xml,
+        "ram:SellerTradeParty",
+        &invoice.user.name,
+        &invoice.user.address,
+        invoice.user.email.as_deref(),
+        &vat_identifier(&invoice.user.cif),
+    );
+    let buyer = buyer(invoice);
+    write_party(
+        &mut xml,
+        "ram:BuyerTradeParty",
+        &buyer.name,
+        &buyer.address,
+        buyer.email.as_deref(),
+        &vat_identifier(&buyer.cif),
+    );
+    xml.end();
+
+    xml.start("ram:ApplicableHeaderTradeDelivery", &[]);
+    xml.end();
+
+    // Settlement
+    xml.start("ram:ApplicableHeaderTradeSettlement", &[]);
+    xml.leaf("ram:PaymentReference", &[], &invoice.id);
+    xml.leaf("ram:InvoiceCurrencyCode", &[], CURRENCY);
+    if let Some(iban) = &invoice.user.iban {
+        xml.start("ram:SpecifiedTradeSettlementPaymentMeans", &[]);
+        xml.leaf("ram:TypeCode", &[], "58");
+        xml.start("ram:PayeePartyCreditorFinancialAccount", &[]);
+        xml.leaf("ram:IBANID", &[], &iban.replace(' ', ""));
+        xml.end();
+        xml.end();
+    }
+    for (category, (rate, taxable, tax)) in &totals.breakdown {
+        xml.start("ram:ApplicableTradeTax", &[]);
+        xml.leaf("ram:CalculatedAmount", &[], &format_cents(*tax));
+        xml.leaf("ram:TypeCode", &[], "VAT");
+        if let Some(reason) = category.exemption_reason() {
+            xml.leaf("ram:ExemptionReason", &[], reason);
+        }
+        xml.leaf("ram:BasisAmount", &[], &format_cents(*taxable));
+        xml.leaf("ram:CategoryCode", &[], category.code());
+        xml.leaf("ram:RateApplicablePercent", &[], &format!("{:.2}", rate));
+        xml.end();
+    }
+    xml.start("ram:SpecifiedTradePaymentTerms", &[]);
+    write_date(&mut xml, "ram:DueDateDateTime", &invoice.due_date);
+    xml.end();
+    xml.start("ram:SpecifiedTradeSettlementHeaderMonetarySummation", &[]);
+    xml.leaf(
+        "ram:LineTotalAmount",
+        &[],
+        &format_cents(totals.line_extension),
+    );
+    xml.leaf(
+        "ram:TaxBasisTotalAmount",
+        &[],
+        &format_cents(totals.line_extension),
+    );
+    xml.leaf(
+        "ram:TaxTotalAmount",
+        &[("currencyID", CURRENCY)],
+        &format_cents(totals.tax),
+    );
+    xml.leaf(
+        "ram:GrandTotalAmount",
+        &[],
+        &format_cents(totals.tax_inclusive),
+    );
+    xml.leaf("ram:TotalPrepaidAmount", &[], &format_cents(totals.prepaid));
+    xml.leaf("ram:DuePayableAmount", &[], &format_cents(totals.payable));
+    xml.end();
+    xml.end();
+
+    xml.finish()
+}
+
+fn write_party(
+    xml: &mut XmlWriter,
+    element: &str,
+    name: &str,
+    address: &str,
+    email: Option<&str>,
+    vat_id: &str,
+) {
+    xml.start(element, &[]);
+    xml.leaf("ram:Name", &[], name);
+    xml.start("ram:PostalTradeAddress", &[]);
+    xml.leaf("ram:LineOne", &[], address);
+    xml.leaf("ram:CountryID", &[], country_code(vat_id));
+    xml.end();
+    if let Some(email) = email {
+        xml.start("ram:URIUniversalCommunication", &[]);
+        xml.leaf("ram:URIID", &[("schemeID", "EM")], email);
+        xml.end();
+    }
+    xml.start("ram:SpecifiedTaxRegistration", &[]);
+    xml.leaf("ram:ID", &[("schemeID", "VA")], vat_id);
+    xml.end();
+    xml.end();
+}
+
+// CII dates use format 102 (YYYYMMDD)
+fn write_date(xml: &mut XmlWriter, element: &str, date: &str) {
+    xml.start(element, &[]);
+    xml.leaf(
+        "udt:DateTimeString",
+        &[("format", "102")],
+        &date.replace('-', ""),
+    );
+    xml.end();
+}
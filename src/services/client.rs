@@ -1,14 +1,20 @@
-use crate::models::Client;
+use crate::models::{Client, SepaMandate, ViesCheck};
 use crate::repository::storage::Storage;
+use crate::services::einvoice::country_code;
+use crate::services::iban::{normalize_iban, validate_bic, validate_iban};
+use crate::services::vat::validate_eu_vat;
+use crate::services::vies::ViesClient;
+use chrono::{Local, NaiveDate};
 use std::io;
 
 pub struct ClientService {
     storage: Storage,
+    vies: Box<dyn ViesClient>,
 }
 
 impl ClientService {
-    pub fn new(storage: Storage) -> Self {
-        ClientService { storage }
+    pub fn new(storage: Storage, vies: Box<dyn ViesClient>) -> Self {
+        ClientService { storage, vies }
     }
 
     pub fn create_client(
@@ -26,4 +32,64 @@ impl ClientService {
     pub fn list_clients(&self) -> io::Result<Vec<Client>> {
         self.storage.list_clients()
     }
+
+    // Store the SEPA Direct Debit mandate signed by a client, replacing any previous one
+    pub fn set_mandate(&self, client: &Client, mut mandate: SepaMandate) -> io::Result<Client> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        mandate.id = mandate.id.trim().to_string();
+        if mandate.id.is_empty() || mandate.id.len() > 35 {
+            return Err(invalid(format!(
+                "Mandate reference '{}' must have between 1 and 35 characters",
+                mandate.id
+            )));
+        }
+        NaiveDate::parse_from_str(&mandate.signature_date, "%Y-%m-%d").map_err(|_| {
+            invalid(format!(
+                "Invalid signature date '{}'",
+                mandate.signature_date
+            ))
+        })?;
+        mandate.iban = normalize_iban(&mandate.iban);
+        validate_iban(&mandate.iban).map_err(|e| invalid(e.to_string()))?;
+        if let Some(bic) = &mandate.bic {
+            validate_bic(bic).map_err(|e| invalid(e.to_string()))?;
+        }
+
+        let mut client = client.clone();
+        // A new mandate starts a new sequence of collections
+        if let Some(previous) = client.mandate.take() {
+            if previous.id == mandate.id && mandate.last_collection.is_none() {
+                mandate.last_collection = previous.last_collection;
+            }
+        }
+        client.mandate = Some(mandate);
+        self.storage.save_client(&client)?;
+        Ok(client)
+    }
+
+    // Check an EU VAT number in VIES. Every consultation is stored with its date for
+    // audit purposes, and a number already checked today is not asked again
+    pub fn check_vat(&self, vat_id: &str) -> io::Result<ViesCheck> {
+        let vat_id = vat_id.trim().to_uppercase().replace([' ', '-', '.'], "");
+        validate_eu_vat(&vat_id)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        if let Some(check) = self
+            .storage
+            .list_vies_checks(&vat_id)?
+            .into_iter()
+            .rev()
+            .find(|check| check.checked_at.starts_with(&today))
+        {
+            return Ok(check);
+        }
+
+        let mut check = self.vies.check_vat(country_code(&vat_id), &vat_id[2..])?;
+        // Keep the number as it was asked, VIES may answer without the prefix
+        check.vat_id = vat_id;
+        self.storage.save_vies_check(&check)?;
+        Ok(check)
+    }
 }
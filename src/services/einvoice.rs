@@ -0,0 +1,126 @@
+// Invoice semantics shared by the EN 16931 e-invoice formats (UBL and CII)
+
+use crate::models::{Client, Invoice};
+use crate::services::money::{percent_of, to_cents};
+use std::collections::BTreeMap;
+
+// VAT category of a tax breakdown (UNCL 5305 subset)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TaxCategory {
+    Standard,
+    Exempt,
+}
+
+impl TaxCategory {
+    pub fn for_rate(rate: f32) -> Self {
+        if rate > 0.0 {
+            TaxCategory::Standard
+        } else {
+            TaxCategory::Exempt
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            TaxCategory::Standard => "S",
+            TaxCategory::Exempt => "E",
+        }
+    }
+
+    // Exempt breakdowns must state why no IVA is charged
+    pub fn exemption_reason(&self) -> Option<&'static str> {
+        match self {
+            TaxCategory::Standard => None,
+            TaxCategory::Exempt => Some("Exento de IVA"),
+        }
+    }
+}
+
+// Amounts of the invoice in cents, rounded so that every EN 16931 sum rule holds
+pub struct InvoiceTotals {
+    pub lines: Vec<i64>,
+    pub line_extension: i64,
+    pub breakdown: BTreeMap<TaxCategory, (f32, i64, i64)>, // rate, taxable amount, tax amount
+    pub tax: i64,
+    pub tax_inclusive: i64,
+    pub prepaid: i64,
+    pub payable: i64,
+}
+
+impl InvoiceTotals {
+    pub fn from_invoice(invoice: &Invoice) -> Self {
+        let lines: Vec<i64> = invoice
+            .items
+            .iter()
+            .map(|item| item.quantity as i64 * to_cents(item.price))
+            .collect();
+        let line_extension = lines.iter().sum();
+
+        // All lines share the invoice rule, so there is a single category today
+        let mut breakdown = BTreeMap::new();
+        let category = TaxCategory::for_rate(invoice.rule.iva);
+        let tax = percent_of(line_extension, invoice.rule.iva);
+        breakdown.insert(category, (invoice.rule.iva, line_extension, tax));
+
+        let tax_inclusive = line_extension + tax;
+        // IRPF is withheld by the client and paid to the AEAT on our behalf,
+        // EN 16931 has no withholding concept so it is reported as prepaid
+        let prepaid = percent_of(line_extension, invoice.rule.irpf);
+
+        InvoiceTotals {
+            lines,
+            line_extension,
+            breakdown,
+            tax,
+            tax_inclusive,
+            prepaid,
+            payable: tax_inclusive - prepaid,
+        }
+    }
+}
+
+// VAT identifier with country prefix, Spanish tax IDs get "ES" prepended
+pub fn vat_identifier(cif: &str) -> String {
+    let cif = cif.trim().to_uppercase();
+    if cif.len() > 2 && cif.chars().take(2).all(|c| c.is_ascii_alphabetic()) {
+        // Spanish NIE (X/Y/Z) and CIF letters are followed by digits, not letters
+        cif
+    } else {
+        format!("ES{}", cif)
+    }
+}
+
+// Buyer party of an e-invoice, an empty party for simplified invoices without client
+pub fn buyer(invoice: &Invoice) -> Client {
+    invoice.client.clone().unwrap_or_else(|| {
+        Client::new(
+            invoice.client_name().to_string(),
+            String::new(),
+            String::new(),
+            None,
+        )
+    })
+}
+
+pub fn country_code(vat_id: &str) -> &str {
+    &vat_id[..2]
+}
+
+// Tax ID as stored locally: Spanish VAT numbers lose their "ES" prefix
+pub fn local_tax_id(vat_id: &str) -> String {
+    let vat_id = vat_id.trim().to_uppercase().replace([' ', '-'], "");
+    match vat_id.strip_prefix("ES") {
+        Some(spanish) if !spanish.is_empty() => spanish.to_string(),
+        _ => vat_id,
+    }
+}
+
+// EU member states by VAT prefix (Greece uses "EL" instead of its ISO code)
+const EU_VAT_PREFIXES: &[&str] = &[
+    "AT", "BE", "BG", "CY", "CZ", "DE", "DK", "EE", "EL", "ES", "FI", "FR", "HR", "HU", "IE", "IT",
+    "LT", "LU", "LV", "MT", "NL", "PL", "PT", "RO", "SE", "SI", "SK",
+];
+
+pub fn is_eu_country(code: &str) -> bool {
+    EU_VAT_PREFIXES.contains(&code)
+}
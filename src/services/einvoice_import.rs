@@ -0,0 +1,689 @@
+// Import of e-invoice XML (UBL 2.1, UN/CEFACT CII and Facturae 3.2.x) into invoices
+// Anything the invoice model cannot represent is reported as a mapping loss
+
+use crate::models::{Client, Invoice, Item, Rule, User};
+use crate::services::einvoice::local_tax_id;
+use crate::services::money::to_cents;
+use crate::services::xml::{child, children, find, text};
+use chrono::NaiveDate;
+use roxmltree::{Document, Node};
+use std::fmt;
+use std::io;
+
+// E-invoice syntaxes we can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EInvoiceFormat {
+    Ubl,
+    Cii,
+    Facturae,
+}
+
+impl fmt::Display for EInvoiceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EInvoiceFormat::Ubl => write!(f, "UBL 2.1"),
+            EInvoiceFormat::Cii => write!(f, "UN/CEFACT CII"),
+            EInvoiceFormat::Facturae => write!(f, "Facturae"),
+        }
+    }
+}
+
+// An invoice read from XML together with what could not be mapped
+#[derive(Debug, Clone)]
+pub struct ImportedInvoice {
+    pub format: EInvoiceFormat,
+    pub invoice: Invoice,
+    pub losses: Vec<String>,
+}
+
+// Side of an imported invoice seen from the user: issued to a client or received
+// from a supplier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceDirection {
+    Issued,
+    Received,
+}
+
+// Compare the parties with the user's tax ID. Received invoices must not be stored as
+// issued ones or they would count as revenue and output IVA
+pub fn invoice_direction(invoice: &Invoice, own_tax_id: &str) -> io::Result<InvoiceDirection> {
+    let own = local_tax_id(own_tax_id);
+    if local_tax_id(&invoice.user.cif) == own {
+        Ok(InvoiceDirection::Issued)
+    } else if local_tax_id(invoice.client_cif()) == own {
+        Ok(InvoiceDirection::Received)
+    } else {
+        Err(invalid(format!(
+            "Invoice {} is from {} ({}) to {} ({}), neither is your tax ID {}",
+            invoice.id,
+            invoice.user.name,
+            invoice.user.cif,
+            invoice.client_name(),
+            invoice.client_cif(),
+            own
+        )))
+    }
+}
+
+// Parse an e-invoice document, detecting the syntax from its root element
+// Facturae batches may hold several invoices, so a list is returned
+pub fn parse_einvoice(xml: &str) -> io::Result<Vec<ImportedInvoice>> {
+    let doc = Document::parse(xml).map_err(|e| invalid(format!("Invalid XML: {}", e)))?;
+    let root = doc.root_element();
+
+    match root.tag_name().name() {
+        "Invoice" | "CreditNote" => parse_ubl(root).map(|invoice| vec![invoice]),
+        "CrossIndustryInvoice" => parse_cii(root).map(|invoice| vec![invoice]),
+        "Facturae" => parse_facturae(root),
+        other => Err(invalid(format!(
+            "Unsupported e-invoice document with root element '{}'",
+            other
+        ))),
+    }
+}
+
+// Collects the fields of one document before building the invoice
+struct InvoiceDraft {
+    format: EInvoiceFormat,
+    id: String,
+    date: String,
+    due_date: Option<String>,
+    seller: Party,
+    buyer: Party,
+    iban: Option<String>,
+    iva_rates: Vec<(f32, i64)>, // rate, taxable amount in cents
+    irpf: f32,
+    items: Vec<Item>,
+    expected_total: Option<i64>,
+    losses: Vec<String>,
+}
+
+#[derive(Default, Clone)]
+struct Party {
+    name: String,
+    tax_id: String,
+    address: String,
+    email: Option<String>,
+}
+
+impl InvoiceDraft {
+    fn new(format: EInvoiceFormat) -> Self {
+        InvoiceDraft {
+            format,
+            id: String::new(),
+            date: String::new(),
+            due_date: None,
+            seller: Party::default(),
+            buyer: Party::default(),
+            iban: None,
+            iva_rates: Vec::new(),
+            irpf: 0.0,
+            items: Vec::new(),
+            expected_total: None,
+            losses: Vec::new(),
+        }
+    }
+
+    fn lose(&mut self, message: String) {
+        self.losses.push(message);
+    }
+
+    // Add a line, keeping fractional quantities by folding them into the price
+    fn add_line(&mut self, description: &str, quantity: f32, price: f32, line_total: f32) {
+        let description = if description.is_empty() {
+            self.lose(format!("Line {} has no description", self.items.len() + 1));
+            format!("Line {}", self.items.len() + 1)
+        } else {
+            description.to_string()
+        };
+
+        if quantity >= 0.0 && quantity.fract() == 0.0 {
+            let item = Item::new(description, quantity as u32, price);
+            if (to_cents(item.total()) - to_cents(line_total)).abs() > 1 {
+                self.lose(format!(
+                    "Line '{}': line discounts or charges not mapped ({:.2} x {:.2} != {:.2})",
+                    item.description, quantity, price, line_total
+                ));
+            }
+            self.items.push(item);
+        } else {
+            self.lose(format!(
+                "Line '{}': quantity {} is not a whole number, imported as 1 x {:.2}",
+                description, quantity, line_total
+            ));
+            self.items.push(Item::new(description, 1, line_total));
+        }
+    }
+
+    fn build(mut self) -> io::Result<ImportedInvoice> {
+        if self.id.is_empty() {
+            return Err(invalid(format!("{} invoice has no number", self.format)));
+        }
+        if NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").is_err() {
+            return Err(invalid(format!(
+                "Invoice {} has an invalid issue date '{}'",
+                self.id, self.date
+            )));
+        }
+        if self.items.is_empty() {
+            return Err(invalid(format!("Invoice {} has no lines", self.id)));
+        }
+
+        let due_date = match self.due_date.take() {
+            Some(due) => due,
+            None => {
+                self.lose("No due date, defaulted to 30 days after the issue date".to_string());
+                NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|d| d.checked_add_days(chrono::Days::new(30)))
+                    .map(|d| d.format("%Y-%m-%d").to_string())
+                    .unwrap_or_else(|| self.date.clone())
+            }
+        };
+
+        // The invoice model applies a single IVA rate to every line
+        self.iva_rates
+            .sort_by_key(|(_, taxable)| std::cmp::Reverse(*taxable));
+        let iva = self.iva_rates.first().map(|(rate, _)| *rate).unwrap_or(0.0);
+        if self.iva_rates.len() > 1 {
+            let rates: Vec<String> = self
+                .iva_rates
+                .iter()
+                .map(|(rate, _)| format!("{}%", rate))
+                .collect();
+            self.lose(format!(
+                "Several IVA rates ({}), all lines imported at {}%",
+                rates.join(", "),
+                iva
+            ));
+        }
+
+        let user = User::new(
+            self.seller.name,
+            self.seller.address,
+            self.seller.tax_id,
+            self.seller.email,
+            self.iban,
+        );
+        let client = Client::new(
+            self.buyer.name,
+            self.buyer.tax_id,
+            self.buyer.address,
+            self.buyer.email,
+        );
+        let invoice = Invoice::new(
+            self.id,
+            self.date,
+            due_date,
+            user,
+            client,
+            Rule::new(iva, self.irpf),
+            self.items,
+        );
+
+        if let Some(expected) = self.expected_total {
+            if (to_cents(invoice.total) - expected).abs() > 1 {
+                self.losses.push(format!(
+                    "Recalculated total {:.2} differs from the document total {:.2}",
+                    invoice.total,
+                    expected as f64 / 100.0
+                ));
+            }
+        }
+
+        Ok(ImportedInvoice {
+            format: self.format,
+            invoice,
+            losses: self.losses,
+        })
+    }
+}
+
+fn parse_ubl(root: Node) -> io::Result<ImportedInvoice> {
+    let mut draft = InvoiceDraft::new(EInvoiceFormat::Ubl);
+    let credit_note = root.tag_name().name() == "CreditNote";
+    let sign = if credit_note { -1.0 } else { 1.0 };
+    if credit_note {
+        draft.lose("Credit note imported as an invoice with negative prices".to_string());
+    }
+
+    draft.id = text(root, &["ID"]).unwrap_or_default().to_string();
+    draft.date = text(root, &["IssueDate"]).unwrap_or_default().to_string();
+    draft.due_date = text(root, &["DueDate"])
+        .or_else(|| text(root, &["PaymentMeans", "PaymentDueDate"]))
+        .map(str::to_string);
+    check_currency(&mut draft, text(root, &["DocumentCurrencyCode"]));
+
+    draft.seller = ubl_party(find(root, &["AccountingSupplierParty", "Party"]));
+    draft.buyer = ubl_party(find(root, &["AccountingCustomerParty", "Party"]));
+    draft.iban = children(root, "PaymentMeans")
+        .into_iter()
+        .find_map(|means| text(means, &["PayeeFinancialAccount", "ID"]))
+        .map(str::to_string);
+
+    for tax_total in children(root, "TaxTotal") {
+        for subtotal in children(tax_total, "TaxSubtotal") {
+            let rate = number(text(subtotal, &["TaxCategory", "Percent"]));
+            let taxable = to_cents(number(text(subtotal, &["TaxableAmount"])));
+            draft.iva_rates.push((rate, taxable));
+        }
+    }
+
+    // IRPF has no place in EN 16931, we read it back from our own note format
+    draft.irpf = children(root, "Note")
+        .into_iter()
+        .filter_map(|note| note.text())
+        .find_map(irpf_rate_from_note)
+        .unwrap_or(0.0);
+    let prepaid = number(text(root, &["LegalMonetaryTotal", "PrepaidAmount"]));
+    if prepaid != 0.0 && draft.irpf == 0.0 {
+        draft.lose(format!(
+            "Prepaid amount {:.2} not mapped (no IRPF rate found)",
+            prepaid
+        ));
+    }
+    let payable = text(root, &["LegalMonetaryTotal", "PayableAmount"]);
+    draft.expected_total =
+        payable.map(|p| to_cents(sign * (number(Some(p)) + prepaid_if_unmapped(&draft, prepaid))));
+
+    if !children(root, "AllowanceCharge").is_empty() {
+        draft.lose("Document level allowances and charges not mapped".to_string());
+    }
+
+    let (line_name, quantity_name) = if credit_note {
+        ("CreditNoteLine", "CreditedQuantity")
+    } else {
+        ("InvoiceLine", "InvoicedQuantity")
+    };
+    for line in children(root, line_name) {
+        let description = text(line, &["Item", "Name"])
+            .or_else(|| text(line, &["Item", "Description"]))
+            .unwrap_or_default();
+        let quantity = number(text(line, &[quantity_name]));
+        let mut price = number(text(line, &["Price", "PriceAmount"]));
+        if let Some(base) = text(line, &["Price", "BaseQuantity"]) {
+            let base = number(Some(base));
+            if base > 0.0 {
+                price /= base;
+            }
+        }
+        let line_total = number(text(line, &["LineExtensionAmount"]));
+        draft.add_line(description, quantity, sign * price, sign * line_total);
+    }
+
+    draft.build()
+}
+
+fn ubl_party(party: Option<Node>) -> Party {
+    let Some(party) = party else {
+        return Party::default();
+    };
+
+    let name = text(party, &["PartyLegalEntity", "RegistrationName"])
+        .or_else(|| text(party, &["PartyName", "Name"]))
+        .unwrap_or_default();
+    let tax_id = text(party, &["PartyTaxScheme", "CompanyID"])
+        .or_else(|| text(party, &["PartyLegalEntity", "CompanyID"]))
+        .or_else(|| text(party, &["EndpointID"]))
+        .unwrap_or_default();
+    let address = find(party, &["PostalAddress"])
+        .map(|a| {
+            join_address(&[
+                text(a, &["StreetName"]),
+                text(a, &["AdditionalStreetName"]),
+                text(a, &["PostalZone"]),
+                text(a, &["CityName"]),
+                text(a, &["CountrySubentity"]),
+            ])
+        })
+        .unwrap_or_default();
+
+    Party {
+        name: name.to_string(),
+        tax_id: local_tax_id(tax_id),
+        address,
+        email: text(party, &["Contact", "ElectronicMail"]).map(str::to_string),
+    }
+}
+
+fn parse_cii(root: Node) -> io::Result<ImportedInvoice> {
+    let mut draft = InvoiceDraft::new(EInvoiceFormat::Cii);
+    let transaction = child(root, "SupplyChainTradeTransaction")
+        .ok_or_else(|| invalid("CII invoice has no trade transaction".to_string()))?;
+    let agreement = child(transaction, "ApplicableHeaderTradeAgreement");
+    let settlement = child(transaction, "ApplicableHeaderTradeSettlement");
+
+    draft.id = text(root, &["ExchangedDocument", "ID"])
+        .unwrap_or_default()
+        .to_string();
+    draft.date = cii_date(text(
+        root,
+        &["ExchangedDocument", "IssueDateTime", "DateTimeString"],
+    ))
+    .unwrap_or_default();
+    match text(root, &["ExchangedDocument", "TypeCode"]) {
+        Some("380") | None => {}
+        Some(code) => draft.lose(format!(
+            "Document type {} imported as a plain invoice",
+            code
+        )),
+    }
+
+    draft.seller = cii_party(agreement.and_then(|a| child(a, "SellerTradeParty")));
+    draft.buyer = cii_party(agreement.and_then(|a| child(a, "BuyerTradeParty")));
+
+    if let Some(settlement) = settlement {
+        check_currency(&mut draft, text(settlement, &["InvoiceCurrencyCode"]));
+        draft.due_date = cii_date(text(
+            settlement,
+            &[
+                "SpecifiedTradePaymentTerms",
+                "DueDateDateTime",
+                "DateTimeString",
+            ],
+        ));
+        draft.iban = children(settlement, "SpecifiedTradeSettlementPaymentMeans")
+            .into_iter()
+            .find_map(|means| text(means, &["PayeePartyCreditorFinancialAccount", "IBANID"]))
+            .map(str::to_string);
+        for tax in children(settlement, "ApplicableTradeTax") {
+            let rate = number(text(tax, &["RateApplicablePercent"]));
+            let taxable = to_cents(number(text(tax, &["BasisAmount"])));
+            draft.iva_rates.push((rate, taxable));
+        }
+        if !children(settlement, "SpecifiedTradeAllowanceCharge").is_empty() {
+            draft.lose("Document level allowances and charges not mapped".to_string());
+        }
+
+        draft.irpf = children(
+            child(root, "ExchangedDocument").unwrap_or(root),
+            "IncludedNote",
+        )
+        .into_iter()
+        .filter_map(|note| text(note, &["Content"]))
+        .find_map(irpf_rate_from_note)
+        .unwrap_or(0.0);
+        let summation = child(
+            settlement,
+            "SpecifiedTradeSettlementHeaderMonetarySummation",
+        );
+        let prepaid = number(summation.and_then(|s| text(s, &["TotalPrepaidAmount"])));
+        if prepaid != 0.0 && draft.irpf == 0.0 {
+            draft.lose(format!(
+                "Prepaid amount {:.2} not mapped (no IRPF rate found)",
+                prepaid
+            ));
+        }
+        draft.expected_total = summation
+            .and_then(|s| text(s, &["DuePayableAmount"]))
+            .map(|p| to_cents(number(Some(p)) + prepaid_if_unmapped(&draft, prepaid)));
+    }
+
+    for line in children(transaction, "IncludedSupplyChainTradeLineItem") {
+        let description = text(line, &["SpecifiedTradeProduct", "Name"]).unwrap_or_default();
+        let quantity = number(text(
+            line,
+            &["SpecifiedLineTradeDelivery", "BilledQuantity"],
+        ));
+        let agreement = child(line, "SpecifiedLineTradeAgreement");
+        let price_node = agreement.and_then(|a| child(a, "NetPriceProductTradePrice"));
+        let mut price = number(price_node.and_then(|p| text(p, &["ChargeAmount"])));
+        if let Some(base) = price_node.and_then(|p| text(p, &["BasisQuantity"])) {
+            let base = number(Some(base));
+            if base > 0.0 {
+                price /= base;
+            }
+        }
+        let line_total = number(text(
+            line,
+            &[
+                "SpecifiedLineTradeSettlement",
+                "SpecifiedTradeSettlementLineMonetarySummation",
+                "LineTotalAmount",
+            ],
+        ));
+        draft.add_line(description, quantity, price, line_total);
+    }
+
+    draft.build()
+}
+
+fn cii_party(party: Option<Node>) -> Party {
+    let Some(party) = party else {
+        return Party::default();
+    };
+
+    let tax_id = children(party, "SpecifiedTaxRegistration")
+        .into_iter()
+        .filter_map(|r| find(r, &["ID"]))
+        .find(|id| id.attribute("schemeID") == Some("VA"))
+        .or_else(|| find(party, &["SpecifiedTaxRegistration", "ID"]))
+        .and_then(|id| id.text())
+        .unwrap_or_default();
+    let address = child(party, "PostalTradeAddress")
+        .map(|a| {
+            join_address(&[
+                text(a, &["LineOne"]),
+                text(a, &["LineTwo"]),
+                text(a, &["PostcodeCode"]),
+                text(a, &["CityName"]),
+            ])
+        })
+        .unwrap_or_default();
+
+    Party {
+        name: text(party, &["Name"]).unwrap_or_default().to_string(),
+        tax_id: local_tax_id(tax_id),
+        address,
+        email: text(party, &["URIUniversalCommunication", "URIID"]).map(str::to_string),
+    }
+}
+
+// CII format 102 dates (YYYYMMDD) to YYYY-MM-DD
+fn cii_date(value: Option<&str>) -> Option<String> {
+    value
+        .and_then(|v| NaiveDate::parse_from_str(v, "%Y%m%d").ok())
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+fn parse_facturae(root: Node) -> io::Result<Vec<ImportedInvoice>> {
+    let seller = facturae_party(find(root, &["Parties", "SellerParty"]));
+    let buyer = facturae_party(find(root, &["Parties", "BuyerParty"]));
+    let invoices = find(root, &["Invoices"])
+        .map(|i| children(i, "Invoice"))
+        .unwrap_or_default();
+    if invoices.is_empty() {
+        return Err(invalid("Facturae file contains no invoices".to_string()));
+    }
+
+    let mut imported = Vec::new();
+    for node in invoices {
+        let mut draft = InvoiceDraft::new(EInvoiceFormat::Facturae);
+        let series = text(node, &["InvoiceHeader", "InvoiceSeriesCode"]).unwrap_or_default();
+        let invoice_number = text(node, &["InvoiceHeader", "InvoiceNumber"]).unwrap_or_default();
+        draft.id = format!("{}{}", series, invoice_number);
+        draft.date = text(node, &["InvoiceIssueData", "IssueDate"])
+            .unwrap_or_default()
+            .to_string();
+        check_currency(
+            &mut draft,
+            text(node, &["InvoiceIssueData", "InvoiceCurrencyCode"]),
+        );
+        match text(node, &["InvoiceHeader", "InvoiceClass"]) {
+            Some("OO") | None => {}
+            Some(class) => draft.lose(format!(
+                "Invoice class {} (rectifying or copy) imported as a plain invoice",
+                class
+            )),
+        }
+
+        draft.seller = seller.clone();
+        draft.buyer = buyer.clone();
+
+        let installments = find(node, &["PaymentDetails"])
+            .map(|p| children(p, "Installment"))
+            .unwrap_or_default();
+        if let Some(first) = installments.first() {
+            draft.due_date = text(*first, &["InstallmentDueDate"]).map(str::to_string);
+            draft.iban = text(*first, &["AccountToBeCredited", "IBAN"]).map(str::to_string);
+        }
+        if installments.len() > 1 {
+            draft.lose(format!(
+                "{} payment installments, only the first due date is kept",
+                installments.len()
+            ));
+        }
+
+        for tax in find(node, &["TaxesOutputs"])
+            .map(|t| children(t, "Tax"))
+            .unwrap_or_default()
+        {
+            let rate = number(text(tax, &["TaxRate"]));
+            let taxable = to_cents(number(text(tax, &["TaxableBase", "TotalAmount"])));
+            match text(tax, &["TaxTypeCode"]) {
+                Some("01") => draft.iva_rates.push((rate, taxable)),
+                Some(code) => {
+                    draft.lose(format!("Output tax type {} ({}%) not mapped", code, rate))
+                }
+                None => draft.iva_rates.push((rate, taxable)),
+            }
+            if text(tax, &["EquivalenceSurcharge"]).is_some() {
+                draft.lose("Recargo de equivalencia not mapped".to_string());
+            }
+        }
+        for tax in find(node, &["TaxesWithheld"])
+            .map(|t| children(t, "Tax"))
+            .unwrap_or_default()
+        {
+            let rate = number(text(tax, &["TaxRate"]));
+            match text(tax, &["TaxTypeCode"]) {
+                Some("04") | None => draft.irpf = rate,
+                Some(code) => {
+                    draft.lose(format!("Withheld tax type {} ({}%) not mapped", code, rate))
+                }
+            }
+        }
+
+        let totals = child(node, "InvoiceTotals");
+        draft.expected_total = totals
+            .and_then(|t| text(t, &["InvoiceTotal"]))
+            .map(|t| to_cents(number(Some(t))));
+        let discounts = number(totals.and_then(|t| text(t, &["TotalGeneralDiscounts"])));
+        let surcharges = number(totals.and_then(|t| text(t, &["TotalGeneralSurcharges"])));
+        if discounts != 0.0 || surcharges != 0.0 {
+            draft.lose("General discounts and surcharges not mapped".to_string());
+        }
+
+        for line in find(node, &["Items"])
+            .map(|i| children(i, "InvoiceLine"))
+            .unwrap_or_default()
+        {
+            let description = text(line, &["ItemDescription"]).unwrap_or_default();
+            let quantity = number(text(line, &["Quantity"]));
+            let price = number(text(line, &["UnitPriceWithoutTax"]));
+            let line_total =
+                number(text(line, &["GrossAmount"]).or_else(|| text(line, &["TotalCost"])));
+            draft.add_line(description, quantity, price, line_total);
+        }
+
+        imported.push(draft.build()?);
+    }
+
+    Ok(imported)
+}
+
+fn facturae_party(party: Option<Node>) -> Party {
+    let Some(party) = party else {
+        return Party::default();
+    };
+
+    let tax_id = text(party, &["TaxIdentification", "TaxIdentificationNumber"]).unwrap_or_default();
+    let (name, entity) = if let Some(legal) = child(party, "LegalEntity") {
+        (
+            text(legal, &["CorporateName"])
+                .unwrap_or_default()
+                .to_string(),
+            Some(legal),
+        )
+    } else if let Some(individual) = child(party, "Individual") {
+        let parts: Vec<&str> = ["Name", "FirstSurname", "SecondSurname"]
+            .iter()
+            .filter_map(|field| text(individual, &[field]))
+            .collect();
+        (parts.join(" "), Some(individual))
+    } else {
+        (String::new(), None)
+    };
+
+    let address = entity
+        .and_then(|e| child(e, "AddressInSpain").or_else(|| child(e, "OverseasAddress")))
+        .map(|a| {
+            join_address(&[
+                text(a, &["Address"]),
+                text(a, &["PostCode"]).or_else(|| text(a, &["PostCodeAndTown"])),
+                text(a, &["Town"]),
+                text(a, &["Province"]),
+            ])
+        })
+        .unwrap_or_default();
+
+    Party {
+        name,
+        tax_id: local_tax_id(tax_id),
+        address,
+        email: entity
+            .and_then(|e| text(e, &["ContactDetails", "ElectronicMail"]))
+            .map(str::to_string),
+    }
+}
+
+fn check_currency(draft: &mut InvoiceDraft, currency: Option<&str>) {
+    if let Some(code) = currency {
+        if code != "EUR" {
+            draft.lose(format!(
+                "Currency {} not supported, amounts imported as EUR",
+                code
+            ));
+        }
+    }
+}
+
+// Read the rate from notes such as "IRPF withholding 15%: 150.00 EUR"
+fn irpf_rate_from_note(note: &str) -> Option<f32> {
+    if !note.to_uppercase().contains("IRPF") {
+        return None;
+    }
+    let before_percent = &note[..note.find('%')?];
+    let start = before_percent
+        .rfind(|c: char| !(c.is_ascii_digit() || c == '.' || c == ','))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    before_percent[start..].replace(',', ".").parse().ok()
+}
+
+// When the prepaid amount is not explained by IRPF it must be added back to
+// the payable amount before comparing it with our recalculated total
+fn prepaid_if_unmapped(draft: &InvoiceDraft, prepaid: f32) -> f32 {
+    if draft.irpf == 0.0 {
+        prepaid
+    } else {
+        0.0
+    }
+}
+
+fn join_address(parts: &[Option<&str>]) -> String {
+    parts
+        .iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+fn number(value: Option<&str>) -> f32 {
+    value.and_then(|v| v.trim().parse().ok()).unwrap_or(0.0)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,57 @@
+// EPC069-12 "GiroCode": QR code with the data of a SEPA credit transfer, which
+// banking apps read to prefill the payment of an invoice
+
+use crate::models::Invoice;
+use crate::services::iban::{normalize_iban, validate_iban};
+use crate::services::money::{format_cents, to_cents};
+use qrcode::{Color, EcLevel, QrCode};
+use std::io;
+
+// Largest amount the standard accepts, 999,999,999.99€
+const MAX_AMOUNT: i64 = 99_999_999_999;
+
+// Payload of the QR code, or None when the issuer has no valid IBAN to pay to
+pub fn epc_payload(invoice: &Invoice) -> Option<String> {
+    let iban = normalize_iban(invoice.user.iban.as_deref()?);
+    validate_iban(&iban).ok()?;
+
+    // The amount is optional, without it the payer types it in
+    let cents = to_cents(invoice.total);
+    let amount = if (1..=MAX_AMOUNT).contains(&cents) {
+        format!("EUR{}", format_cents(cents))
+    } else {
+        String::new()
+    };
+
+    let lines = [
+        "BCD".to_string(), // Service tag
+        "002".to_string(), // Version, the BIC is optional inside the EEA
+        "1".to_string(),   // UTF-8
+        "SCT".to_string(), // SEPA credit transfer
+        invoice.user.bic.clone().unwrap_or_default(),
+        truncate(&invoice.user.name, 70),
+        iban,
+        amount,
+        String::new(), // Purpose code
+        String::new(), // Structured creditor reference
+        truncate(&format!("Invoice {}", invoice.id), 140),
+    ];
+
+    Some(lines.join("\n"))
+}
+
+// Modules of the QR code row by row, true for dark ones, with the width of a row
+pub fn epc_qr_modules(payload: &str) -> io::Result<(usize, Vec<bool>)> {
+    let code = QrCode::with_error_correction_level(payload, EcLevel::M)
+        .map_err(|e| io::Error::other(format!("Failed to encode the payment QR code: {}", e)))?;
+    let modules = code
+        .to_colors()
+        .into_iter()
+        .map(|color| color == Color::Dark)
+        .collect();
+    Ok((code.width(), modules))
+}
+
+fn truncate(text: &str, length: usize) -> String {
+    text.trim().chars().take(length).collect()
+}
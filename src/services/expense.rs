@@ -0,0 +1,147 @@
+use crate::models::{Expense, ExpenseCategory, Invoice, Supplier};
+use crate::repository::storage::Storage;
+use crate::services::einvoice::local_tax_id;
+use std::io;
+use std::path::PathBuf;
+
+// Parameters for recording a received invoice
+pub struct CreateExpenseParams {
+    pub number: String,
+    pub date: String,
+    pub supplier: Supplier,
+    pub category: ExpenseCategory,
+    pub base: f32,
+    pub iva: f32,
+    pub deductible_percent: f32,
+    pub attachment: Option<PathBuf>, // Original PDF to keep with the expense
+}
+
+pub struct ExpenseService {
+    storage: Storage,
+}
+
+impl ExpenseService {
+    pub fn new(storage: Storage) -> Self {
+        ExpenseService { storage }
+    }
+
+    pub fn create_supplier(
+        &self,
+        name: String,
+        cif: String,
+        address: String,
+        email: Option<String>,
+    ) -> io::Result<Supplier> {
+        let supplier = Supplier::new(name, cif, address, email);
+        self.storage.save_supplier(&supplier)?;
+        Ok(supplier)
+    }
+
+    pub fn list_suppliers(&self) -> io::Result<Vec<Supplier>> {
+        self.storage.list_suppliers()
+    }
+
+    pub fn create_expense(&self, params: CreateExpenseParams) -> io::Result<Expense> {
+        let mut expense = Expense::new(
+            params.number,
+            params.date,
+            params.supplier,
+            params.category,
+            params.base,
+            params.iva,
+            params.deductible_percent,
+        );
+
+        if self
+            .storage
+            .list_expenses()?
+            .iter()
+            .any(|e| e.id == expense.id)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "Invoice {} from {} is already recorded",
+                    expense.number, expense.supplier.name
+                ),
+            ));
+        }
+
+        if let Some(source) = params.attachment {
+            if !source
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Attachment '{}' is not a PDF file", source.display()),
+                ));
+            }
+            expense.attachment = Some(self.storage.save_expense_attachment(&expense.id, &source)?);
+        }
+
+        self.storage.save_expense(&expense)?;
+        Ok(expense)
+    }
+
+    // Record a supplier invoice read from an e-invoice, false when it is already recorded
+    pub fn save_imported_expense(
+        &self,
+        invoice: &Invoice,
+        category: ExpenseCategory,
+    ) -> io::Result<bool> {
+        if invoice.rule.irpf > 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invoice {} from {} has an IRPF withholding, record it by hand",
+                    invoice.id, invoice.user.name
+                ),
+            ));
+        }
+
+        let stored = self
+            .storage
+            .list_suppliers()?
+            .into_iter()
+            .find(|supplier| local_tax_id(&supplier.cif) == local_tax_id(&invoice.user.cif));
+        let supplier = match stored {
+            Some(supplier) => supplier,
+            None => self.create_supplier(
+                invoice.user.name.clone(),
+                invoice.user.cif.clone(),
+                invoice.user.address.clone(),
+                invoice.user.email.clone(),
+            )?,
+        };
+
+        match self.create_expense(CreateExpenseParams {
+            number: invoice.id.clone(),
+            date: invoice.date.clone(),
+            supplier,
+            category,
+            base: invoice.subtotal,
+            iva: invoice.rule.iva,
+            deductible_percent: 100.0,
+            attachment: None,
+        }) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Expenses sorted by date
+    pub fn list_expenses(&self) -> io::Result<Vec<Expense>> {
+        let mut expenses = self.storage.list_expenses()?;
+        expenses.sort_by(|a, b| a.date.cmp(&b.date).then(a.number.cmp(&b.number)));
+        Ok(expenses)
+    }
+
+    pub fn attachment_path(&self, expense: &Expense) -> Option<PathBuf> {
+        expense
+            .attachment
+            .as_deref()
+            .map(|filename| self.storage.expense_attachment_path(filename))
+    }
+}
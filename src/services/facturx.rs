@@ -0,0 +1,405 @@
+// Factur-X / ZUGFeRD packaging: turns a rendered invoice PDF into a PDF/A-3b
+// document carrying the CII XML as an associated file
+
+use chrono::{DateTime, Utc};
+use printpdf::lopdf::xref::XrefType;
+use printpdf::lopdf::{self, Dictionary, Object, Stream, StringFormat};
+use std::io::{self, Write};
+
+// File name mandated by Factur-X 1.0 / ZUGFeRD 2.x for the embedded XML
+pub const XML_FILE_NAME: &str = "factur-x.xml";
+const CONFORMANCE_LEVEL: &str = "EN 16931";
+const PRODUCER: &str = "Rusty Invoices";
+// Comment with bytes above 127 that PDF/A requires on the line after the header
+const BINARY_COMMENT: &[u8] = b"%\xE2\xE3\xCF\xD3\n";
+
+// Document properties written both to the Info dictionary and the XMP metadata
+pub struct DocumentProperties<'a> {
+    pub title: &'a str,
+    pub author: &'a str,
+}
+
+// Add the CII XML, XMP metadata and output intent that PDF/A-3b and Factur-X require
+pub fn embed_factur_x(
+    pdf: &[u8],
+    cii_xml: &str,
+    properties: &DocumentProperties,
+) -> io::Result<Vec<u8>> {
+    let mut doc = lopdf::Document::load_mem(pdf).map_err(pdf_error)?;
+    let now = Utc::now();
+
+    // PDF/A-3 is based on PDF 1.7. The binary comment is added after saving, which
+    // needs a cross-reference table rather than a stream to fix the offsets
+    doc.version = "1.7".to_string();
+    doc.reference_table.cross_reference_type = XrefType::CrossReferenceTable;
+
+    // Embedded file and its file specification
+    let mut params = Dictionary::new();
+    params.set("ModDate", pdf_string(&pdf_date(&now)));
+    params.set("Size", Object::Integer(cii_xml.len() as i64));
+    let mut file_dict = Dictionary::new();
+    file_dict.set("Type", Object::Name(b"EmbeddedFile".to_vec()));
+    file_dict.set("Subtype", Object::Name(b"text/xml".to_vec()));
+    file_dict.set("Params", Object::Dictionary(params));
+    let file_id = doc.add_object(Stream::new(file_dict, cii_xml.as_bytes().to_vec()));
+
+    let mut embedded = Dictionary::new();
+    embedded.set("F", Object::Reference(file_id));
+    embedded.set("UF", Object::Reference(file_id));
+    let mut filespec = Dictionary::new();
+    filespec.set("Type", Object::Name(b"Filespec".to_vec()));
+    filespec.set("F", pdf_string(XML_FILE_NAME));
+    filespec.set("UF", pdf_string(XML_FILE_NAME));
+    filespec.set("Desc", pdf_string("Factur-X invoice"));
+    filespec.set("AFRelationship", Object::Name(b"Alternative".to_vec()));
+    filespec.set("EF", Object::Dictionary(embedded));
+    let filespec_id = doc.add_object(filespec);
+
+    // XMP metadata, must stay uncompressed
+    let xmp = xmp_metadata(properties, &now);
+    let mut metadata_dict = Dictionary::new();
+    metadata_dict.set("Type", Object::Name(b"Metadata".to_vec()));
+    metadata_dict.set("Subtype", Object::Name(b"XML".to_vec()));
+    let metadata_id =
+        doc.add_object(Stream::new(metadata_dict, xmp.into_bytes()).with_compression(false));
+
+    // Output intent with an sRGB profile, matching the DeviceRGB colours we draw with
+    let mut icc_dict = Dictionary::new();
+    icc_dict.set("N", Object::Integer(3));
+    let icc_id = doc.add_object(Stream::new(icc_dict, srgb_icc_profile()));
+    let mut intent = Dictionary::new();
+    intent.set("Type", Object::Name(b"OutputIntent".to_vec()));
+    intent.set("S", Object::Name(b"GTS_PDFA1".to_vec()));
+    intent.set("OutputConditionIdentifier", pdf_string("sRGB IEC61966-2.1"));
+    intent.set("Info", pdf_string("sRGB IEC61966-2.1"));
+    intent.set("DestOutputProfile", Object::Reference(icc_id));
+
+    // Info dictionary consistent with the XMP metadata
+    let mut info = Dictionary::new();
+    info.set("Title", pdf_string(properties.title));
+    info.set("Author", pdf_string(properties.author));
+    info.set("Creator", pdf_string(PRODUCER));
+    info.set("Producer", pdf_string(PRODUCER));
+    info.set("CreationDate", pdf_string(&pdf_date(&now)));
+    info.set("ModDate", pdf_string(&pdf_date(&now)));
+    let info_id = doc.add_object(info);
+    doc.trailer.set("Info", Object::Reference(info_id));
+
+    // Embedded TrueType CID fonts must declare how CIDs map to glyphs
+    for object in doc.objects.values_mut() {
+        add_cid_to_gid_map(object);
+    }
+
+    // Other name trees of the catalog are kept, only EmbeddedFiles gets our file
+    let mut names = match doc.catalog().map_err(pdf_error)?.get(b"Names") {
+        Ok(Object::Dictionary(names)) => names.clone(),
+        Ok(Object::Reference(id)) => doc.get_dictionary(*id).cloned().unwrap_or_default(),
+        _ => Dictionary::new(),
+    };
+    let mut embedded_files = match names.get(b"EmbeddedFiles") {
+        Ok(Object::Dictionary(tree)) => tree.clone(),
+        Ok(Object::Reference(id)) => doc.get_dictionary(*id).cloned().unwrap_or_default(),
+        _ => Dictionary::new(),
+    };
+    let mut entries = match embedded_files.get(b"Names") {
+        Ok(Object::Array(entries)) => entries.clone(),
+        _ => Vec::new(),
+    };
+    add_name_entry(&mut entries, XML_FILE_NAME, filespec_id);
+    embedded_files.set("Names", Object::Array(entries));
+    names.set("EmbeddedFiles", Object::Dictionary(embedded_files));
+
+    let catalog = doc.catalog_mut().map_err(pdf_error)?;
+    catalog.set("Names", Object::Dictionary(names));
+    catalog.set("AF", Object::Array(vec![Object::Reference(filespec_id)]));
+    catalog.set("Metadata", Object::Reference(metadata_id));
+    catalog.set(
+        "OutputIntents",
+        Object::Array(vec![Object::Dictionary(intent)]),
+    );
+
+    // Optional content configurations need a name in PDF/A-2 and later
+    if let Ok(Object::Dictionary(oc_properties)) = catalog.get_mut(b"OCProperties") {
+        if let Ok(Object::Dictionary(default_config)) = oc_properties.get_mut(b"D") {
+            default_config.set("Name", pdf_string("Default"));
+        }
+    }
+
+    let mut output = Vec::new();
+    doc.save_to(&mut output)?;
+    insert_binary_comment(&output)
+}
+
+// Add a file to the flat key/value array of a name tree, keeping the keys sorted and
+// replacing an older file with the same name
+fn add_name_entry(entries: &mut Vec<Object>, name: &str, file_id: lopdf::ObjectId) {
+    let mut pairs: Vec<(Vec<u8>, Object)> = entries
+        .chunks(2)
+        .filter_map(|pair| match pair {
+            [Object::String(key, _), value] if key != name.as_bytes() => {
+                Some((key.clone(), value.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    pairs.push((name.as_bytes().to_vec(), Object::Reference(file_id)));
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    *entries = pairs
+        .into_iter()
+        .flat_map(|(key, value)| [Object::String(key, StringFormat::Literal), value])
+        .collect();
+}
+
+// lopdf has no option for the binary comment: insert it after the header line of the
+// saved file and shift the offsets of the cross-reference table by its length
+fn insert_binary_comment(pdf: &[u8]) -> io::Result<Vec<u8>> {
+    let layout_error = || io::Error::other("PDF/A conversion error: unexpected file layout");
+    let header_end = pdf
+        .iter()
+        .position(|b| *b == b'\n')
+        .ok_or_else(layout_error)?
+        + 1;
+    let startxref = pdf
+        .windows(10)
+        .rposition(|w| w == b"startxref\n")
+        .ok_or_else(layout_error)?;
+    let xref_start: usize = std::str::from_utf8(&pdf[startxref + 10..])
+        .ok()
+        .and_then(|tail| tail.lines().next())
+        .and_then(|offset| offset.trim().parse().ok())
+        .filter(|offset| (header_end..startxref).contains(offset))
+        .ok_or_else(layout_error)?;
+    let shift = BINARY_COMMENT.len();
+
+    let mut output = Vec::with_capacity(pdf.len() + shift);
+    output.extend_from_slice(&pdf[..header_end]);
+    output.extend_from_slice(BINARY_COMMENT);
+    output.extend_from_slice(&pdf[header_end..xref_start]);
+    // In-use entries are "oooooooooo ggggg n " lines of 20 bytes
+    for line in pdf[xref_start..startxref].split_inclusive(|b| *b == b'\n') {
+        let offset = std::str::from_utf8(&line[..line.len().min(10)])
+            .ok()
+            .and_then(|offset| offset.parse::<usize>().ok());
+        match offset {
+            Some(offset) if line.len() == 20 && line.ends_with(b" n \n") => {
+                write!(output, "{:010}", offset + shift)?;
+                output.extend_from_slice(&line[10..]);
+            }
+            _ => output.extend_from_slice(line),
+        }
+    }
+    write!(output, "startxref\n{}\n%%EOF", xref_start + shift)?;
+    Ok(output)
+}
+
+fn add_cid_to_gid_map(object: &mut Object) {
+    match object {
+        Object::Dictionary(dict) => {
+            let is_cid_font = matches!(
+                dict.get(b"Subtype"),
+                Ok(Object::Name(name)) if name == b"CIDFontType2"
+            );
+            if is_cid_font && !dict.has(b"CIDToGIDMap") {
+                dict.set("CIDToGIDMap", Object::Name(b"Identity".to_vec()));
+            }
+            for (_, value) in dict.iter_mut() {
+                add_cid_to_gid_map(value);
+            }
+        }
+        Object::Array(items) => items.iter_mut().for_each(add_cid_to_gid_map),
+        _ => {}
+    }
+}
+
+fn pdf_string(text: &str) -> Object {
+    if text.is_ascii() {
+        Object::String(text.as_bytes().to_vec(), StringFormat::Literal)
+    } else {
+        // Non ASCII text strings are encoded as UTF-16BE with a byte order mark
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
+}
+
+fn pdf_error(e: lopdf::Error) -> io::Error {
+    io::Error::other(format!("PDF/A conversion error: {}", e))
+}
+
+fn pdf_date(date: &DateTime<Utc>) -> String {
+    date.format("D:%Y%m%d%H%M%S+00'00'").to_string()
+}
+
+fn xmp_metadata(properties: &DocumentProperties, now: &DateTime<Utc>) -> String {
+    let date = now.format("%Y-%m-%dT%H:%M:%S+00:00").to_string();
+    let title = crate::services::xml::escape(properties.title);
+    let author = crate::services::xml::escape(properties.author);
+
+    let fx_property = |name: &str, description: &str| {
+        format!(
+            r#"              <rdf:li rdf:parseType="Resource">
+                <pdfaProperty:name>{}</pdfaProperty:name>
+                <pdfaProperty:valueType>Text</pdfaProperty:valueType>
+                <pdfaProperty:category>external</pdfaProperty:category>
+                <pdfaProperty:description>{}</pdfaProperty:description>
+              </rdf:li>
+"#,
+            name, description
+        )
+    };
+    let fx_properties = [
+        fx_property("DocumentFileName", "Name of the embedded XML invoice file"),
+        fx_property("DocumentType", "INVOICE"),
+        fx_property("Version", "Version of the Factur-X XML schema"),
+        fx_property("ConformanceLevel", "Factur-X profile of the XML invoice"),
+    ]
+    .concat();
+
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/">
+      <pdfaid:part>3</pdfaid:part>
+      <pdfaid:conformance>B</pdfaid:conformance>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+      <dc:format>application/pdf</dc:format>
+      <dc:title><rdf:Alt><rdf:li xml:lang="x-default">{title}</rdf:li></rdf:Alt></dc:title>
+      <dc:creator><rdf:Seq><rdf:li>{author}</rdf:li></rdf:Seq></dc:creator>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:pdf="http://ns.adobe.com/pdf/1.3/">
+      <pdf:Producer>{producer}</pdf:Producer>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+      <xmp:CreatorTool>{producer}</xmp:CreatorTool>
+      <xmp:CreateDate>{date}</xmp:CreateDate>
+      <xmp:ModifyDate>{date}</xmp:ModifyDate>
+      <xmp:MetadataDate>{date}</xmp:MetadataDate>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:fx="urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#">
+      <fx:DocumentType>INVOICE</fx:DocumentType>
+      <fx:DocumentFileName>{file_name}</fx:DocumentFileName>
+      <fx:Version>1.0</fx:Version>
+      <fx:ConformanceLevel>{level}</fx:ConformanceLevel>
+    </rdf:Description>
+    <rdf:Description rdf:about="" xmlns:pdfaExtension="http://www.aiim.org/pdfa/ns/extension/" xmlns:pdfaSchema="http://www.aiim.org/pdfa/ns/schema#" xmlns:pdfaProperty="http://www.aiim.org/pdfa/ns/property#">
+      <pdfaExtension:schemas>
+        <rdf:Bag>
+          <rdf:li rdf:parseType="Resource">
+            <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>
+            <pdfaSchema:namespaceURI>urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#</pdfaSchema:namespaceURI>
+            <pdfaSchema:prefix>fx</pdfaSchema:prefix>
+            <pdfaSchema:property>
+              <rdf:Seq>
+{fx_properties}              </rdf:Seq>
+            </pdfaSchema:property>
+          </rdf:li>
+        </rdf:Bag>
+      </pdfaExtension:schemas>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{feff}',
+        title = title,
+        author = author,
+        producer = PRODUCER,
+        date = date,
+        file_name = XML_FILE_NAME,
+        level = CONFORMANCE_LEVEL,
+        fx_properties = fx_properties,
+    )
+}
+
+// Build a compact ICC v2 display profile for sRGB (D50 adapted primaries and
+// the IEC 61966-2.1 tone curve sampled in a 1024 entry table)
+fn srgb_icc_profile() -> Vec<u8> {
+    fn s15_fixed16(value: f64) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    fn xyz_tag(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for value in [x, y, z] {
+            tag.extend_from_slice(&s15_fixed16(value));
+        }
+        tag
+    }
+
+    fn text_description(text: &str) -> Vec<u8> {
+        let mut tag = b"desc\0\0\0\0".to_vec();
+        tag.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+        tag.extend_from_slice(text.as_bytes());
+        tag.push(0);
+        // Empty Unicode and ScriptCode descriptions
+        tag.extend_from_slice(&[0; 8]);
+        tag.extend_from_slice(&[0; 3]);
+        tag.extend_from_slice(&[0; 67]);
+        tag
+    }
+
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    let samples = 1024u32;
+    curve.extend_from_slice(&samples.to_be_bytes());
+    for i in 0..samples {
+        let v = i as f64 / (samples - 1) as f64;
+        let linear = if v <= 0.04045 {
+            v / 12.92
+        } else {
+            ((v + 0.055) / 1.055).powf(2.4)
+        };
+        curve.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    let mut copyright = b"text\0\0\0\0".to_vec();
+    copyright.extend_from_slice(b"No copyright, use freely\0");
+
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", text_description("sRGB IEC61966-2.1")),
+        (b"cprt", copyright),
+        (b"wtpt", xyz_tag(0.9642, 1.0, 0.8249)),
+        (b"rXYZ", xyz_tag(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz_tag(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz_tag(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+
+    // Lay out tag data after the header and tag table, 4 byte aligned
+    let data_start = 128 + 4 + tags.len() * 12;
+    let mut table = Vec::new();
+    let mut data = Vec::new();
+    table.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    for (signature, content) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        data.extend_from_slice(content);
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+    }
+
+    let size = 128 + table.len() + data.len();
+    let mut header = Vec::with_capacity(128);
+    header.extend_from_slice(&(size as u32).to_be_bytes());
+    header.extend_from_slice(&[0; 4]); // preferred CMM
+    header.extend_from_slice(&0x0210_0000u32.to_be_bytes()); // version 2.1
+    header.extend_from_slice(b"mntrRGB XYZ ");
+    for value in [2024u16, 1, 1, 0, 0, 0] {
+        header.extend_from_slice(&value.to_be_bytes());
+    }
+    header.extend_from_slice(b"acsp");
+    header.extend_from_slice(&[0; 24]); // platform, flags, manufacturer, model, attributes
+    header.extend_from_slice(&0u32.to_be_bytes()); // perceptual rendering intent
+    header.extend_from_slice(&s15_fixed16(0.9642));
+    header.extend_from_slice(&s15_fixed16(1.0));
+    header.extend_from_slice(&s15_fixed16(0.8249));
+    header.resize(128, 0);
+
+    [header, table, data].concat()
+}
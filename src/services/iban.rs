@@ -0,0 +1,229 @@
+// ISO 13616 IBAN validation and formatting, and identification of Spanish banks
+// from the entity code of their account numbers
+
+use crate::errors::ValidationError;
+
+// IBAN length per country, from the SWIFT IBAN registry
+const IBAN_LENGTHS: &[(&str, usize)] = &[
+    ("AD", 24),
+    ("AE", 23),
+    ("AL", 28),
+    ("AT", 20),
+    ("AZ", 28),
+    ("BA", 20),
+    ("BE", 16),
+    ("BG", 22),
+    ("BH", 22),
+    ("BR", 29),
+    ("BY", 28),
+    ("CH", 21),
+    ("CR", 22),
+    ("CY", 28),
+    ("CZ", 24),
+    ("DE", 22),
+    ("DK", 18),
+    ("DO", 28),
+    ("EE", 20),
+    ("EG", 29),
+    ("ES", 24),
+    ("FI", 18),
+    ("FO", 18),
+    ("FR", 27),
+    ("GB", 22),
+    ("GE", 22),
+    ("GI", 23),
+    ("GL", 18),
+    ("GR", 27),
+    ("GT", 28),
+    ("HR", 21),
+    ("HU", 28),
+    ("IE", 22),
+    ("IL", 23),
+    ("IQ", 23),
+    ("IS", 26),
+    ("IT", 27),
+    ("JO", 30),
+    ("KW", 30),
+    ("KZ", 20),
+    ("LB", 28),
+    ("LC", 32),
+    ("LI", 21),
+    ("LT", 20),
+    ("LU", 20),
+    ("LV", 21),
+    ("MC", 27),
+    ("MD", 24),
+    ("ME", 22),
+    ("MK", 19),
+    ("MR", 27),
+    ("MT", 31),
+    ("MU", 30),
+    ("NL", 18),
+    ("NO", 15),
+    ("PK", 24),
+    ("PL", 28),
+    ("PS", 29),
+    ("PT", 25),
+    ("QA", 29),
+    ("RO", 24),
+    ("RS", 22),
+    ("SA", 24),
+    ("SC", 31),
+    ("SE", 24),
+    ("SI", 19),
+    ("SK", 24),
+    ("SM", 27),
+    ("TN", 24),
+    ("TR", 26),
+    ("UA", 29),
+    ("VA", 22),
+    ("VG", 24),
+    ("XK", 20),
+];
+
+// Banco de España entity codes of the most common banks and savings banks. Codes of
+// banks absorbed in a merger still appear on old account numbers, so they are kept
+// with the bank that took them over
+const SPANISH_BANKS: &[(&str, &str)] = &[
+    ("0019", "Deutsche Bank"),
+    ("0030", "Banesto (historical, now Banco Santander)"),
+    ("0049", "Banco Santander"),
+    ("0061", "Banca March"),
+    ("0065", "Barclays Bank"),
+    ("0073", "Openbank"),
+    ("0075", "Banco Popular (historical, now Banco Santander)"),
+    ("0081", "Banco de Sabadell"),
+    ("0128", "Bankinter"),
+    ("0131", "Novo Banco"),
+    ("0138", "Bankoa"),
+    ("0149", "BNP Paribas"),
+    ("0162", "HSBC Bank"),
+    ("0182", "Banco Bilbao Vizcaya Argentaria"),
+    ("0186", "Banco Mediolanum"),
+    ("0198", "Banco Cooperativo Español"),
+    ("0234", "Banco Caminos"),
+    ("0237", "Cajasur Banco"),
+    ("0239", "EVO Banco"),
+    ("0487", "Banco Mare Nostrum (historical, now CaixaBank)"),
+    ("1465", "ING Bank"),
+    ("1474", "Citibank Europe"),
+    ("1491", "Triodos Bank"),
+    ("2038", "Bankia (historical, now CaixaBank)"),
+    ("2045", "Caixa Ontinyent"),
+    ("2048", "Liberbank (historical, now Unicaja Banco)"),
+    ("2056", "Colonya Caixa Pollença"),
+    ("2080", "Abanca"),
+    ("2085", "Ibercaja Banco"),
+    ("2095", "Kutxabank"),
+    ("2100", "CaixaBank"),
+    ("2103", "Unicaja Banco"),
+    ("3008", "Caja Rural de Navarra"),
+    ("3023", "Caja Rural de Granada"),
+    ("3025", "Caixa d'Enginyers"),
+    ("3035", "Laboral Kutxa"),
+    ("3058", "Cajamar Caja Rural"),
+    ("3059", "Caja Rural de Asturias"),
+    ("3076", "Cajasiete"),
+    ("3081", "Eurocaja Rural"),
+    ("3159", "Caixa Popular"),
+    ("3183", "Arquia Bank"),
+    ("3187", "Caja Rural del Sur"),
+    ("3190", "Globalcaja"),
+];
+
+// IBAN without spaces and in uppercase, as stored and exported
+pub fn normalize_iban(iban: &str) -> String {
+    iban.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+pub fn validate_iban(iban: &str) -> Result<(), ValidationError> {
+    let iban = normalize_iban(iban);
+    let invalid = || ValidationError::InvalidIban { iban: iban.clone() };
+
+    if iban.len() < 5 || !iban.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(invalid());
+    }
+    let country = &iban[..2];
+    if !country.chars().all(|c| c.is_ascii_uppercase())
+        || !iban[2..4].chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let Some(expected) = IBAN_LENGTHS
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|(_, length)| *length)
+    else {
+        return Err(ValidationError::InvalidIbanCountry {
+            country: country.to_string(),
+            iban,
+        });
+    };
+    if iban.len() != expected {
+        return Err(ValidationError::InvalidIbanLength {
+            country: country.to_string(),
+            length: iban.len(),
+            expected,
+            iban,
+        });
+    }
+
+    if mod97(&iban) != 1 {
+        return Err(ValidationError::InvalidIbanChecksum { iban });
+    }
+
+    Ok(())
+}
+
+// The country code and check digits move to the end and letters count as 10 to 35
+fn mod97(iban: &str) -> u32 {
+    let (head, tail) = iban.split_at(4);
+    tail.chars().chain(head.chars()).fold(0, |remainder, c| {
+        let value = c.to_digit(36).unwrap_or(0);
+        let width = if value >= 10 { 100 } else { 10 };
+        (remainder * width + value) % 97
+    })
+}
+
+// Printed form of an IBAN, in groups of four characters
+pub fn format_iban(iban: &str) -> String {
+    normalize_iban(iban)
+        .chars()
+        .collect::<Vec<_>>()
+        .chunks(4)
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Name of the bank holding a Spanish account, from the entity code after the check digits
+pub fn spanish_bank_name(iban: &str) -> Option<&'static str> {
+    let iban = normalize_iban(iban);
+    if !iban.starts_with("ES") || iban.len() < 8 {
+        return None;
+    }
+    let entity = &iban[4..8];
+    SPANISH_BANKS
+        .iter()
+        .find(|(code, _)| *code == entity)
+        .map(|(_, name)| *name)
+}
+
+// BIC (ISO 9362): bank, country and location codes and an optional branch code
+pub fn validate_bic(bic: &str) -> Result<(), ValidationError> {
+    let bic = bic.trim().to_uppercase();
+    let chars: Vec<char> = bic.chars().collect();
+    let valid = (chars.len() == 8 || chars.len() == 11)
+        && chars[..6].iter().all(char::is_ascii_uppercase)
+        && chars[6..].iter().all(char::is_ascii_alphanumeric);
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::InvalidBic { bic })
+    }
+}
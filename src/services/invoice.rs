@@ -1,7 +1,12 @@
-use crate::models::{Client, Invoice, Item, Rule, User};
+use crate::models::{
+    Client, Invoice, InvoiceKind, Item, Reconciliation, Rule, User, SIMPLIFIED_INVOICE_LIMIT,
+};
 use crate::repository::storage::Storage;
+use crate::services::bank_statement::BankTransaction;
+use crate::services::money::{format_cents, to_cents};
 use crate::services::pdf::PdfService;
-use chrono::{Local, NaiveDate};
+use crate::services::ubl::UblService;
+use chrono::{Datelike, Local, NaiveDate};
 use std::io;
 use uuid::Uuid;
 
@@ -14,18 +19,47 @@ pub struct CreateInvoiceParams {
     pub client: Client,
     pub rule: Rule,
     pub items: Vec<Item>,
+    pub recurring_id: Option<String>,
+    pub quote_id: Option<String>,
+}
+
+// Parameters for creating a simplified invoice (factura simplificada)
+pub struct CreateSimplifiedInvoiceParams {
+    pub invoice_number: Option<String>,
+    pub date: Option<String>,
+    pub user: User,
+    pub client: Option<Client>,
+    pub rule: Rule,
+    pub items: Vec<Item>,
+}
+
+// Simplified invoices are numbered in their own yearly series, T2025-0001...
+const SIMPLIFIED_SERIES_PREFIX: &str = "T";
+
+// Next number of the simplified series, one more than the highest of the year
+pub fn next_simplified_number(invoices: &[Invoice], year: i32) -> String {
+    let series = format!("{}{}-", SIMPLIFIED_SERIES_PREFIX, year);
+    let last = invoices
+        .iter()
+        .filter(|invoice| invoice.kind == InvoiceKind::Simplified)
+        .filter_map(|invoice| invoice.id.strip_prefix(&series)?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("{}{:04}", series, last + 1)
 }
 
 pub struct InvoiceService {
     storage: Storage,
     pdf_service: PdfService,
+    ubl_service: UblService,
 }
 
 impl InvoiceService {
-    pub fn new(storage: Storage, pdf_service: PdfService) -> Self {
+    pub fn new(storage: Storage, pdf_service: PdfService, ubl_service: UblService) -> Self {
         InvoiceService {
             storage,
             pdf_service,
+            ubl_service,
         }
     }
 
@@ -61,7 +95,7 @@ impl InvoiceService {
             }
         };
 
-        let invoice = Invoice::new(
+        let mut invoice = Invoice::new(
             id,
             invoice_date,
             invoice_due_date,
@@ -70,6 +104,57 @@ impl InvoiceService {
             params.rule,
             params.items,
         );
+        invoice.recurring_id = params.recurring_id;
+        invoice.quote_id = params.quote_id;
+        self.storage.save_invoice(&invoice)?;
+
+        Ok(invoice)
+    }
+
+    // Simplified invoices are due on their date and may only be issued up to the limit,
+    // IVA included. Without client data there is nobody to withhold IRPF
+    pub fn create_simplified_invoice(
+        &self,
+        params: CreateSimplifiedInvoiceParams,
+    ) -> io::Result<Invoice> {
+        if params.items.is_empty() {
+            return Err(invalid(
+                "A simplified invoice needs at least one item".to_string(),
+            ));
+        }
+        if params.client.is_none() && params.rule.irpf != 0.0 {
+            return Err(invalid(
+                "IRPF can only be withheld when the client is identified".to_string(),
+            ));
+        }
+
+        let date = match params.date {
+            Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|_| invalid(format!("Invalid date '{}'", date)))?,
+            None => Local::now().date_naive(),
+        };
+        let id = match params.invoice_number {
+            Some(number) => number,
+            None => next_simplified_number(&self.storage.list_invoices()?, date.year()),
+        };
+
+        let invoice = Invoice::simplified(
+            id,
+            date.format("%Y-%m-%d").to_string(),
+            params.user,
+            params.client,
+            params.rule,
+            params.items,
+        );
+        // IRPF withheld from the total does not count towards the limit
+        let amount = to_cents(invoice.subtotal) + to_cents(invoice.iva_amount);
+        if amount > to_cents(SIMPLIFIED_INVOICE_LIMIT) {
+            return Err(invalid(format!(
+                "Simplified invoices are limited to {:.2}€ IVA included, this one is {}€",
+                SIMPLIFIED_INVOICE_LIMIT,
+                format_cents(amount)
+            )));
+        }
         self.storage.save_invoice(&invoice)?;
 
         Ok(invoice)
@@ -79,7 +164,66 @@ impl InvoiceService {
         self.storage.list_invoices()
     }
 
+    // Store an imported invoice unless one with the same number already exists
+    pub fn save_imported_invoice(&self, invoice: &Invoice) -> io::Result<bool> {
+        if self.storage.get_invoice(&invoice.id)?.is_some() {
+            return Ok(false);
+        }
+        self.storage.save_invoice(invoice)?;
+        Ok(true)
+    }
+
+    // Record the date the payment of an invoice was received
+    pub fn mark_paid(&self, invoice: &Invoice, paid_date: &str) -> io::Result<Invoice> {
+        let paid = NaiveDate::parse_from_str(paid_date, "%Y-%m-%d").map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid payment date '{}'", paid_date),
+            )
+        })?;
+        if NaiveDate::parse_from_str(&invoice.date, "%Y-%m-%d").is_ok_and(|issued| paid < issued) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Payment date {} is before the invoice date {}",
+                    paid_date, invoice.date
+                ),
+            ));
+        }
+
+        let mut invoice = invoice.clone();
+        invoice.paid_date = Some(paid_date.to_string());
+        self.storage.save_invoice(&invoice)?;
+        Ok(invoice)
+    }
+
+    // Mark an invoice as paid by a bank movement, keeping which movement paid it
+    pub fn record_bank_payment(
+        &self,
+        invoice: &Invoice,
+        transaction: &BankTransaction,
+    ) -> io::Result<Invoice> {
+        let invoice = self.mark_paid(invoice, &transaction.booking_date)?;
+        self.storage.save_reconciliation(&Reconciliation {
+            invoice_id: invoice.id.clone(),
+            transaction_id: transaction.id(),
+            booking_date: transaction.booking_date.clone(),
+            amount: transaction.amount as f32 / 100.0,
+            description: transaction.description.clone(),
+            reconciled_at: Local::now().to_rfc3339(),
+        })?;
+        Ok(invoice)
+    }
+
     pub fn generate_pdf(&self, invoice: &Invoice) -> io::Result<String> {
         self.pdf_service.generate_invoice_pdf(invoice)
     }
+
+    pub fn export_ubl(&self, invoice: &Invoice) -> io::Result<String> {
+        self.ubl_service.export_invoice(invoice)
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
 }
@@ -0,0 +1,341 @@
+// Double-entry journal of the issued invoices with the accounts of the Spanish
+// Plan General de Contabilidad, exported for the accounting software of the gestoría
+
+use crate::config::AccountMapping;
+use crate::models::{Client, Invoice};
+use crate::services::einvoice::local_tax_id;
+use crate::services::libro_registro::number_key;
+use crate::services::money::{format_cents, to_cents};
+use crate::services::period::DateRange;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalLine {
+    pub account: String,
+    pub account_name: String,
+    pub debit: i64,            // Cents
+    pub credit: i64,           // Cents
+    pub vat_base: Option<i64>, // Taxable base of the IVA line, in cents
+}
+
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub number: u32,
+    pub date: NaiveDate,
+    pub invoice_id: String,
+    pub description: String,
+    pub client_account: String, // Counterpart of the sales and tax lines
+    pub iva_rate: f32,
+    pub lines: Vec<JournalLine>,
+}
+
+// Subaccount of every client by tax ID: the one stored with the client, or else the
+// clients account followed by the last digits of its tax ID. A client whose digits are
+// already taken gets the next free number, so stored clients never lose theirs
+pub fn client_subaccounts(
+    invoices: &[Invoice],
+    clients: &[Client],
+    mapping: &AccountMapping,
+) -> BTreeMap<String, String> {
+    let width = mapping.subaccount_digits - mapping.clients_account.len();
+    let modulo = 10u64.pow(width as u32);
+
+    let mut used = BTreeSet::new();
+    let mut accounts = BTreeMap::new();
+    for client in clients {
+        let Some(account) = &client.subaccount else {
+            continue;
+        };
+        // Subaccounts given with another account length are given again
+        let number = account
+            .strip_prefix(mapping.clients_account.as_str())
+            .filter(|number| number.len() == width)
+            .and_then(|number| number.parse::<u64>().ok());
+        if number.is_some_and(|number| used.insert(number)) {
+            accounts.insert(local_tax_id(&client.cif), account.clone());
+        }
+    }
+
+    let tax_ids: BTreeSet<String> = invoices
+        .iter()
+        .map(|invoice| local_tax_id(invoice.client_cif()))
+        .filter(|tax_id| !accounts.contains_key(tax_id))
+        .collect();
+    for tax_id in tax_ids {
+        let digits: String = tax_id.chars().filter(char::is_ascii_digit).collect();
+        let tail = &digits[digits.len().saturating_sub(width)..];
+        let mut number = tail.parse::<u64>().unwrap_or(0);
+        while !used.insert(number) {
+            number = (number + 1) % modulo;
+        }
+        accounts.insert(
+            tax_id,
+            format!(
+                "{}{:0width$}",
+                mapping.clients_account,
+                number,
+                width = width
+            ),
+        );
+    }
+    accounts
+}
+
+// One entry per invoice issued in the range, in date and invoice number order:
+// the client owes the total and the withholding, which are the sales plus IVA
+pub fn journal_entries(
+    invoices: &[Invoice],
+    range: DateRange,
+    subaccounts: &BTreeMap<String, String>,
+    mapping: &AccountMapping,
+) -> Vec<JournalEntry> {
+    let mut invoices: Vec<(NaiveDate, &Invoice)> = invoices
+        .iter()
+        .filter(|invoice| range.contains(&invoice.date))
+        .filter_map(|invoice| {
+            let date = NaiveDate::parse_from_str(&invoice.date, "%Y-%m-%d").ok()?;
+            Some((date, invoice))
+        })
+        .collect();
+    invoices.sort_by_key(|(date, invoice)| (*date, number_key(&invoice.id)));
+
+    invoices
+        .into_iter()
+        .enumerate()
+        .map(|(i, (date, invoice))| {
+            let base = to_cents(invoice.subtotal);
+            let iva = to_cents(invoice.iva_amount);
+            let irpf = to_cents(invoice.irpf_amount);
+            let client_account = subaccounts[&local_tax_id(invoice.client_cif())].clone();
+
+            let mut lines = vec![JournalLine {
+                account: client_account.clone(),
+                account_name: invoice.client_name().to_string(),
+                debit: base + iva - irpf,
+                credit: 0,
+                vat_base: None,
+            }];
+            if irpf != 0 {
+                lines.push(JournalLine {
+                    account: mapping.subaccount(&mapping.withholding_account),
+                    account_name: "H.P. retenciones y pagos a cuenta".to_string(),
+                    debit: irpf,
+                    credit: 0,
+                    vat_base: None,
+                });
+            }
+            lines.push(JournalLine {
+                account: mapping.subaccount(&mapping.sales_account),
+                account_name: if mapping.sales_account.starts_with("700") {
+                    "Ventas de mercaderías".to_string()
+                } else {
+                    "Prestaciones de servicios".to_string()
+                },
+                debit: 0,
+                credit: base,
+                vat_base: None,
+            });
+            if iva != 0 {
+                lines.push(JournalLine {
+                    account: mapping.subaccount(&mapping.output_vat_account),
+                    account_name: "H.P. IVA repercutido".to_string(),
+                    debit: 0,
+                    credit: iva,
+                    vat_base: Some(base),
+                });
+            }
+
+            JournalEntry {
+                number: i as u32 + 1,
+                date,
+                invoice_id: invoice.id.clone(),
+                description: format!("Fra. {} {}", invoice.id, invoice.client_name()),
+                client_account,
+                iva_rate: invoice.rule.iva,
+                lines,
+            }
+        })
+        .collect()
+}
+
+pub const CSV_COLUMNS: &[&str] = &[
+    "Asiento",
+    "Fecha",
+    "Cuenta",
+    "Nombre Cuenta",
+    "Concepto",
+    "Documento",
+    "Debe",
+    "Haber",
+];
+
+// Semicolon separated with decimal commas, as the libro registro export
+pub fn to_csv(entries: &[JournalEntry]) -> io::Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_writer(Vec::new());
+
+    writer.write_record(CSV_COLUMNS)?;
+    for entry in entries {
+        for line in &entry.lines {
+            writer.write_record([
+                entry.number.to_string(),
+                entry.date.format("%d/%m/%Y").to_string(),
+                line.account.clone(),
+                line.account_name.clone(),
+                entry.description.clone(),
+                entry.invoice_id.clone(),
+                format_cents(line.debit).replace('.', ","),
+                format_cents(line.credit).replace('.', ","),
+            ])?;
+        }
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+// Field of a fixed width record
+enum Field {
+    Text(String, usize),   // Left aligned, padded with spaces
+    Number(String, usize), // Right aligned, padded with spaces
+    Zeros(u64, usize),     // Right aligned, padded with zeros
+}
+
+fn record(fields: &[Field]) -> String {
+    let mut line = String::new();
+    for field in fields {
+        match field {
+            Field::Text(text, width) => {
+                let text: String = text.chars().take(*width).collect();
+                line.push_str(&format!("{:<width$}", text, width = width));
+            }
+            Field::Number(number, width) => {
+                line.push_str(&format!("{:>width$}", number, width = width))
+            }
+            Field::Zeros(number, width) => {
+                line.push_str(&format!("{:0>width$}", number, width = width))
+            }
+        }
+    }
+    line
+}
+
+// Number at the end of an invoice id ("2025-0042" -> 42), the invoice field of
+// ContaPlus is numeric
+fn invoice_number(invoice_id: &str) -> u64 {
+    let digits: String = invoice_id
+        .chars()
+        .rev()
+        .take_while(char::is_ascii_digit)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    digits.parse().unwrap_or(0) % 100_000_000
+}
+
+// ContaPlus XDiario import file (SUENLACE.DAT layout), one fixed width record per line
+// with the amounts in the euro columns
+pub fn to_contaplus(entries: &[JournalEntry]) -> Vec<u8> {
+    let mut output = String::new();
+    for entry in entries {
+        for line in &entry.lines {
+            // The client line has no counterpart, the others have the client
+            let contra = if line.account == entry.client_account {
+                String::new()
+            } else {
+                entry.client_account.clone()
+            };
+            let vat_base = line.vat_base.unwrap_or(0);
+            let vat_rate = line.vat_base.map_or(0.0, |_| entry.iva_rate);
+            let amount = |cents: i64| format!("{:.2}", cents as f64 / 100.0);
+
+            let fields = [
+                Field::Number(entry.number.to_string(), 6), // Asien
+                Field::Text(entry.date.format("%Y%m%d").to_string(), 8), // Fecha
+                Field::Text(line.account.clone(), 12),      // SubCta
+                Field::Text(contra, 12),                    // Contra
+                Field::Number(amount(0), 16),               // PtaDebe
+                Field::Text(entry.description.clone(), 25), // Concepto
+                Field::Number(amount(0), 16),               // PtaHaber
+                Field::Number(invoice_number(&entry.invoice_id).to_string(), 8), // Factura
+                Field::Number(amount(vat_base), 16),        // BaseImpo
+                Field::Number(format!("{:.2}", vat_rate), 5), // IVA
+                Field::Number("0.00".to_string(), 5),       // RecEquiv
+                Field::Text(entry.invoice_id.clone(), 10),  // Documento
+                Field::Text(String::new(), 3),              // Departa
+                Field::Text(String::new(), 6),              // Clave
+                Field::Text(String::new(), 1),              // Estado
+                Field::Number("0".to_string(), 6),          // NCasado
+                Field::Number("0".to_string(), 1),          // TCasado
+                Field::Number("0".to_string(), 6),          // Trans
+                Field::Number("0.000000".to_string(), 16),  // Cambio
+                Field::Number(amount(0), 16),               // DebeME
+                Field::Number(amount(0), 16),               // HaberME
+                Field::Text(String::new(), 1),              // Auxiliar
+                Field::Text(String::new(), 1),              // Serie
+                Field::Text(String::new(), 4),              // Sucursal
+                Field::Text(String::new(), 5),              // CodDivisa
+                Field::Number(amount(0), 16),               // ImpAuxME
+                Field::Text("2".to_string(), 1),            // MonedaUso, euros
+                Field::Number(amount(line.debit), 16),      // EuroDebe
+                Field::Number(amount(line.credit), 16),     // EuroHaber
+                Field::Number(amount(vat_base), 16),        // BaseEuro
+                Field::Text("F".to_string(), 1),            // NoConv
+                Field::Text(String::new(), 10),             // NumeroInv
+            ];
+            output.push_str(&record(&fields));
+            output.push_str("\r\n");
+        }
+    }
+    output.into_bytes()
+}
+
+// A3 import of entries without IVA register (type 0 records of 254 characters),
+// the first line of an entry is marked I, the last one U and the others M
+pub fn to_a3(entries: &[JournalEntry], company_code: &str) -> Vec<u8> {
+    let company: u64 = company_code.parse().unwrap_or(1);
+    let mut output = String::new();
+    for entry in entries {
+        for (i, line) in entry.lines.iter().enumerate() {
+            let position = if i == 0 {
+                "I"
+            } else if i + 1 == entry.lines.len() {
+                "U"
+            } else {
+                "M"
+            };
+            let (side, cents) = if line.debit != 0 {
+                ("D", line.debit)
+            } else {
+                ("H", line.credit)
+            };
+
+            let fields = [
+                Field::Text("5".to_string(), 1),                             // Euros
+                Field::Zeros(company, 5),                                    // Company
+                Field::Text(entry.date.format("%Y%m%d").to_string(), 8),     // Date
+                Field::Text("0".to_string(), 1),                             // Entry without IVA
+                Field::Text(line.account.clone(), 12),                       // Account
+                Field::Text(line.account_name.clone(), 30),                  // Account name
+                Field::Text(side.to_string(), 1),                            // Debit or credit
+                Field::Text(entry.invoice_id.clone(), 10),                   // Document
+                Field::Text(position.to_string(), 1),                        // Line of the entry
+                Field::Text(entry.description.clone(), 30),                  // Description
+                Field::Text(format!("{:+014.2}", cents as f64 / 100.0), 14), // Amount
+                Field::Text(String::new(), 137),                             // Reserved
+                Field::Text("N".to_string(), 1),                             // Analytic
+                Field::Text(String::new(), 1),                               // Reserved
+                Field::Text("E".to_string(), 1),                             // Currency
+                Field::Text("N".to_string(), 1),                             // Generated
+            ];
+            output.push_str(&record(&fields));
+            output.push_str("\r\n");
+        }
+    }
+    output.into_bytes()
+}
@@ -0,0 +1,177 @@
+// Libro registro de facturas expedidas in the layout recommended by the AEAT
+// One row per invoice and IVA rate, exported as CSV or XLSX
+
+use crate::models::Invoice;
+use crate::services::einvoice::local_tax_id;
+use crate::services::money::{format_cents, to_cents};
+use crate::services::period::{DateRange, Quarter};
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, XlsxError};
+use std::io;
+
+pub const COLUMNS: &[&str] = &[
+    "Ejercicio",
+    "Periodo",
+    "Fecha Expedición",
+    "Fecha Operación",
+    "Serie",
+    "Número",
+    "NIF Destinatario",
+    "Nombre Destinatario",
+    "Base Imponible",
+    "Tipo de IVA",
+    "Cuota IVA Repercutida",
+    "Tipo Retención IRPF",
+    "Importe Retenido IRPF",
+    "Total Factura",
+];
+
+#[derive(Debug, Clone)]
+pub struct LedgerEntry {
+    pub date: NaiveDate,
+    pub number: String,
+    pub nif: String,
+    pub name: String,
+    pub base: i64,
+    pub iva_rate: f32,
+    pub iva: i64,
+    pub irpf_rate: f32,
+    pub irpf: i64,
+    pub total: i64, // Base plus IVA, withholding is not subtracted
+}
+
+// Invoices issued in the range, sorted by invoice number
+pub fn issued_invoices(invoices: &[Invoice], range: DateRange) -> Vec<LedgerEntry> {
+    let mut entries: Vec<LedgerEntry> = invoices
+        .iter()
+        .filter(|i| range.contains(&i.date))
+        .filter_map(|invoice| {
+            let date = NaiveDate::parse_from_str(&invoice.date, "%Y-%m-%d").ok()?;
+            let base = to_cents(invoice.subtotal);
+            let iva = to_cents(invoice.iva_amount);
+            Some(LedgerEntry {
+                date,
+                number: invoice.id.clone(),
+                nif: local_tax_id(invoice.client_cif()),
+                name: invoice
+                    .client
+                    .as_ref()
+                    .map(|client| client.name.clone())
+                    .unwrap_or_default(),
+                base,
+                iva_rate: invoice.rule.iva,
+                iva,
+                irpf_rate: invoice.rule.irpf,
+                irpf: to_cents(invoice.irpf_amount),
+                total: base + iva,
+            })
+        })
+        .collect();
+
+    entries.sort_by_key(|entry| number_key(&entry.number));
+    entries
+}
+
+// Sort "A2" before "A10": compare the prefix, then the trailing number
+pub fn number_key(number: &str) -> (String, u64, String) {
+    let digits_start = number
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let (prefix, digits) = number.split_at(digits_start);
+    (
+        prefix.to_string(),
+        digits.parse().unwrap_or(0),
+        number.to_string(),
+    )
+}
+
+fn row(entry: &LedgerEntry) -> [String; 14] {
+    let date = entry.date.format("%d/%m/%Y").to_string();
+    [
+        entry.date.year().to_string(),
+        Quarter::of(entry.date).code(),
+        date,
+        String::new(),
+        String::new(),
+        entry.number.clone(),
+        entry.nif.clone(),
+        entry.name.clone(),
+        format_cents(entry.base),
+        format!("{:.2}", entry.iva_rate),
+        format_cents(entry.iva),
+        format!("{:.2}", entry.irpf_rate),
+        format_cents(entry.irpf),
+        format_cents(entry.total),
+    ]
+}
+
+// Semicolon separated with decimal commas, as opened by a Spanish spreadsheet
+pub fn to_csv(entries: &[LedgerEntry]) -> io::Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_writer(Vec::new());
+
+    writer.write_record(COLUMNS)?;
+    for entry in entries {
+        let mut fields = row(entry);
+        for amount in fields.iter_mut().skip(8) {
+            *amount = amount.replace('.', ",");
+        }
+        writer.write_record(&fields)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+pub fn to_xlsx(entries: &[LedgerEntry]) -> io::Result<Vec<u8>> {
+    build_xlsx(entries).map_err(|e| io::Error::other(e.to_string()))
+}
+
+fn build_xlsx(entries: &[LedgerEntry]) -> Result<Vec<u8>, XlsxError> {
+    let mut workbook = Workbook::new();
+    let header = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("dd/mm/yyyy");
+    let amount_format = Format::new().set_num_format("#,##0.00");
+    let percent_format = Format::new().set_num_format("0.00");
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Facturas expedidas")?;
+    for (col, title) in COLUMNS.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, &header)?;
+        sheet.set_column_width(col as u16, 14)?;
+    }
+    sheet.set_column_width(7, 32)?;
+
+    for (i, entry) in entries.iter().enumerate() {
+        let r = i as u32 + 1;
+        let date = ExcelDateTime::from_ymd(
+            entry.date.year() as u16,
+            entry.date.month() as u8,
+            entry.date.day() as u8,
+        )?;
+        sheet.write_number(r, 0, entry.date.year())?;
+        sheet.write_string(r, 1, Quarter::of(entry.date).code())?;
+        sheet.write_datetime_with_format(r, 2, &date, &date_format)?;
+        sheet.write_string(r, 5, &entry.number)?;
+        sheet.write_string(r, 6, &entry.nif)?;
+        sheet.write_string(r, 7, &entry.name)?;
+        for (col, cents) in [
+            (8, entry.base),
+            (10, entry.iva),
+            (12, entry.irpf),
+            (13, entry.total),
+        ] {
+            sheet.write_number_with_format(r, col, cents as f64 / 100.0, &amount_format)?;
+        }
+        sheet.write_number_with_format(r, 9, entry.iva_rate, &percent_format)?;
+        sheet.write_number_with_format(r, 11, entry.irpf_rate, &percent_format)?;
+    }
+
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, entries.len() as u32, COLUMNS.len() as u16 - 1)?;
+
+    workbook.save_to_buffer()
+}
@@ -1,7 +1,56 @@
+pub mod accounting;
+pub mod aeat;
+pub mod asset;
+pub mod bank_statement;
+pub mod bulk_import;
+pub mod cii;
 pub mod client;
+pub mod einvoice;
+pub mod einvoice_import;
+pub mod epc_qr;
+pub mod expense;
+pub mod facturx;
+pub mod iban;
 pub mod invoice;
+pub mod journal;
+pub mod libro_registro;
+pub mod modelo130;
+pub mod modelo303;
+pub mod modelo347;
+pub mod modelo349;
+pub mod money;
 pub mod pdf;
+pub mod period;
+pub mod plain_text;
+pub mod quote;
+pub mod reconciliation;
+pub mod recurring;
+pub mod report;
+pub mod sepa;
+pub mod spreadsheet;
+pub mod stats;
+pub mod tax_id;
+pub mod time_tracking;
+pub mod ubl;
+pub mod validation;
+pub mod vat;
+pub mod vies;
+pub mod xml;
 
+#[cfg(test)]
+mod tests;
+
+pub use accounting::AccountingService;
+pub use asset::AssetService;
+pub use bulk_import::BulkImportService;
 pub use client::ClientService;
+pub use expense::ExpenseService;
 pub use invoice::InvoiceService;
 pub use pdf::PdfService;
+pub use quote::QuoteService;
+pub use reconciliation::ReconciliationService;
+pub use recurring::RecurringService;
+pub use report::ReportService;
+pub use sepa::SepaService;
+pub use time_tracking::TimeTrackingService;
+pub use ubl::UblService;
@@ -0,0 +1,247 @@
+// Modelo 130: quarterly IRPF pre-payment for activities under estimación directa
+// Income and withholdings are cumulative from January, amounts are kept in cents
+
+use crate::models::{Invoice, Modelo130Filing, User};
+use crate::services::aeat::{alpha, amount, to_latin1};
+use crate::services::money::{format_cents, percent_of, to_cents};
+use crate::services::period::Quarter;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+// Boxes written to the presentation file, section II (agriculture) stays empty
+const BOXES: &[u16] = &[
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+];
+
+// Reduction of art. 110.3.c RIRPF by previous year net income: (up to, reduction)
+const REDUCTIONS: &[(i64, i64)] = &[
+    (900000, 10000),
+    (1000000, 7500),
+    (1100000, 5000),
+    (1200000, 2500),
+];
+
+pub fn box_description(number: u16) -> &'static str {
+    match number {
+        1 => "Ingresos computables",
+        2 => "Gastos fiscalmente deducibles",
+        3 => "Rendimiento neto",
+        4 => "20% del rendimiento neto",
+        5 => "Pagos fraccionados de trimestres anteriores",
+        6 => "Retenciones e ingresos a cuenta",
+        7 => "Pago fraccionado previo del trimestre",
+        12 => "Suma de pagos fraccionados previos",
+        13 => "Minoración por deducción art. 110.3.c",
+        14 => "Diferencia",
+        15 => "Resultados negativos de trimestres anteriores",
+        16 => "Deducción por préstamo de vivienda habitual",
+        17 => "Total",
+        18 => "Resultado de autoliquidaciones anteriores",
+        19 => "Resultado de la autoliquidación",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Modelo130 {
+    pub quarter: Quarter,
+    pub expenses: i64, // Deductible expenses entered for this quarter
+    pub boxes: BTreeMap<u16, i64>,
+    pub warnings: Vec<String>,
+}
+
+impl Modelo130 {
+    // Compute the quarter from the invoices of the year, the expenses of this
+    // quarter and the filings stored for the previous quarters
+    pub fn calculate(
+        invoices: &[Invoice],
+        quarter: Quarter,
+        expenses: i64,
+        previous: &[Modelo130Filing],
+        previous_year_net: Option<i64>,
+    ) -> Self {
+        let mut warnings = Vec::new();
+        let year_to_date = |date: &str| {
+            (1..=quarter.quarter)
+                .filter_map(|q| Quarter::new(quarter.year, q).ok())
+                .any(|q| q.contains(date))
+        };
+
+        let mut earlier = Vec::new();
+        for q in 1..quarter.quarter {
+            match previous
+                .iter()
+                .find(|f| f.year == quarter.year && f.quarter == q)
+            {
+                Some(filing) => earlier.push(filing),
+                None => warnings.push(format!(
+                    "No Modelo 130 stored for {} {}T, its expenses and payments are not included",
+                    quarter.year, q
+                )),
+            }
+        }
+
+        let income: i64 = invoices
+            .iter()
+            .filter(|i| year_to_date(&i.date))
+            .map(|i| to_cents(i.subtotal))
+            .sum();
+        let withheld: i64 = invoices
+            .iter()
+            .filter(|i| year_to_date(&i.date))
+            .map(|i| to_cents(i.irpf_amount))
+            .sum();
+        let total_expenses = expenses + earlier.iter().map(|f| to_cents(f.expenses)).sum::<i64>();
+
+        let net = income - total_expenses;
+        let twenty_percent = percent_of(net, 20.0).max(0);
+        let paid: i64 = earlier
+            .iter()
+            .map(|f| to_cents(f.payment))
+            .filter(|p| *p > 0)
+            .sum();
+        let payment = twenty_percent - paid - withheld;
+
+        let sum = payment.max(0);
+        let reduction = previous_year_net
+            .and_then(|net| {
+                REDUCTIONS
+                    .iter()
+                    .find(|(limit, _)| net <= *limit)
+                    .map(|(_, reduction)| *reduction)
+            })
+            .unwrap_or(0);
+        let difference = sum - reduction;
+
+        // Negative results of earlier quarters not yet compensated
+        let pending: i64 = earlier
+            .iter()
+            .map(|f| (-to_cents(f.result)).max(0) - to_cents(f.compensated))
+            .sum();
+        let compensated = if difference > 0 {
+            pending.clamp(0, difference)
+        } else {
+            0
+        };
+        let total = difference - compensated;
+
+        let mut boxes = BTreeMap::new();
+        for (number, value) in [
+            (1, income),
+            (2, total_expenses),
+            (3, net),
+            (4, twenty_percent),
+            (5, paid),
+            (6, withheld),
+            (7, payment),
+            (12, sum),
+            (13, reduction),
+            (14, difference),
+            (15, compensated),
+            (17, total),
+            (19, total),
+        ] {
+            boxes.insert(number, value);
+        }
+
+        Modelo130 {
+            quarter,
+            expenses,
+            boxes,
+            warnings,
+        }
+    }
+
+    pub fn value(&self, number: u16) -> i64 {
+        self.boxes.get(&number).copied().unwrap_or(0)
+    }
+
+    pub fn rows(&self) -> Vec<(u16, &'static str, String)> {
+        BOXES
+            .iter()
+            .filter(|number| self.boxes.contains_key(number))
+            .map(|number| {
+                (
+                    *number,
+                    box_description(*number),
+                    format!("{}€", format_cents(self.value(*number))),
+                )
+            })
+            .collect()
+    }
+
+    // Declaration type: I (ingreso) or N (negativa, also used for a zero result)
+    pub fn declaration_type(&self) -> char {
+        if self.value(19) > 0 {
+            'I'
+        } else {
+            'N'
+        }
+    }
+
+    // What has to be stored for the following quarters
+    pub fn to_filing(&self) -> Modelo130Filing {
+        let euros = |cents: i64| cents as f32 / 100.0;
+        Modelo130Filing::new(
+            self.quarter.year,
+            self.quarter.quarter,
+            euros(self.expenses),
+            euros(self.value(7)),
+            euros(self.value(15)),
+            euros(self.value(19)),
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        let boxes: BTreeMap<String, String> = self
+            .boxes
+            .iter()
+            .map(|(number, value)| (format!("{:02}", number), format_cents(*value)))
+            .collect();
+
+        json!({
+            "model": "130",
+            "year": self.quarter.year,
+            "period": self.quarter.code(),
+            "declaration_type": self.declaration_type().to_string(),
+            "boxes": boxes,
+            "warnings": self.warnings,
+        })
+    }
+
+    // Presentation file following the DR130 record layout
+    pub fn to_aeat_file(&self, user: &User) -> Vec<u8> {
+        let year = self.quarter.year;
+        let period = self.quarter.code();
+        let mut record = String::new();
+
+        record.push_str(&format!("<T130{}{}0000>", year, period));
+        record.push_str("<AUX>");
+        record.push_str(&alpha("", 70));
+        record.push_str("0100"); // Program version
+        record.push_str(&alpha("", 4));
+        record.push_str(&alpha("", 9)); // NIF of the software developer
+        record.push_str(&alpha("", 213));
+        record.push_str("</AUX>");
+
+        record.push_str("<T13001000>");
+        record.push(' '); // Not a complementary page
+        record.push(self.declaration_type());
+        record.push_str(&alpha(&user.cif, 9));
+        record.push_str(&alpha(&user.name, 60));
+        record.push_str(&alpha("", 20));
+        record.push_str(&year.to_string());
+        record.push_str(&period);
+        for number in BOXES {
+            record.push_str(&amount(self.value(*number), 17));
+        }
+        record.push(' '); // Not a complementary declaration
+        record.push_str(&alpha("", 13)); // Receipt number of the previous declaration
+        record.push_str(&alpha("", 34)); // IBAN, only for direct debit
+        record.push_str("</T13001000>");
+
+        record.push_str(&format!("</T130{}{}0000>", year, period));
+        record.push_str("\r\n");
+        to_latin1(&record)
+    }
+}
@@ -0,0 +1,237 @@
+// Modelo 303: quarterly IVA self-assessment built from the stored invoices
+// Box numbers follow the current AEAT form, amounts are kept in cents
+
+use crate::models::{Expense, Invoice, User};
+use crate::services::aeat::{alpha, amount, to_latin1};
+use crate::services::einvoice::{country_code, is_eu_country, vat_identifier};
+use crate::services::money::{format_cents, to_cents};
+use crate::services::period::Quarter;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+// Boxes of the IVA accrued at each general regime rate: (rate, base, rate box, cuota)
+const ACCRUED_BOXES: &[(f32, u16, u16, u16)] = &[
+    (4.0, 1, 2, 3),
+    (5.0, 153, 154, 155),
+    (10.0, 4, 5, 6),
+    (21.0, 7, 8, 9),
+];
+
+// Percentage boxes hold hundredths of a percent rather than cents
+const PERCENT_BOXES: &[u16] = &[2, 5, 8, 65, 154];
+
+// Boxes written on each page of the presentation file, in layout order
+const PAGE_1: &[u16] = &[1, 2, 3, 153, 154, 155, 4, 5, 6, 7, 8, 9, 27, 28, 29, 45, 46];
+const PAGE_3: &[u16] = &[59, 120, 64, 65, 66, 69, 71];
+
+// Human readable description of every box we fill
+pub fn box_description(number: u16) -> &'static str {
+    match number {
+        1 | 4 | 7 | 153 => "Régimen general - Base imponible",
+        2 | 5 | 8 | 154 => "Régimen general - Tipo %",
+        3 | 6 | 9 | 155 => "Régimen general - Cuota",
+        27 => "Total cuota devengada",
+        28 => "Operaciones interiores corrientes - Base",
+        29 => "Operaciones interiores corrientes - Cuota deducible",
+        45 => "Total a deducir",
+        46 => "Resultado régimen general",
+        59 => "Entregas intracomunitarias de bienes y servicios",
+        120 => "Operaciones no sujetas por reglas de localización",
+        64 => "Suma de resultados",
+        65 => "% Atribuible a la Administración del Estado",
+        66 => "Atribuible a la Administración del Estado",
+        69 => "Resultado",
+        71 => "Resultado de la liquidación",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Modelo303 {
+    pub quarter: Quarter,
+    pub invoice_count: usize,
+    pub boxes: BTreeMap<u16, i64>,
+    pub warnings: Vec<String>, // Invoices that could not be placed in any box
+}
+
+impl Modelo303 {
+    // Aggregate the invoices issued and the expenses received in the quarter
+    pub fn from_invoices(invoices: &[Invoice], expenses: &[Expense], quarter: Quarter) -> Self {
+        let mut boxes: BTreeMap<u16, i64> = BTreeMap::new();
+        let mut warnings = Vec::new();
+        let mut invoice_count = 0;
+
+        for invoice in invoices.iter().filter(|i| quarter.contains(&i.date)) {
+            invoice_count += 1;
+            let base = to_cents(invoice.subtotal);
+
+            if invoice.rule.iva == 0.0 {
+                let country = vat_identifier(invoice.client_cif());
+                let country = country_code(&country);
+                if country == "ES" {
+                    warnings.push(format!(
+                        "Invoice {}: domestic operation without IVA is not declared in Modelo 303",
+                        invoice.id
+                    ));
+                } else if is_eu_country(country) {
+                    *boxes.entry(59).or_default() += base;
+                } else {
+                    *boxes.entry(120).or_default() += base;
+                }
+                continue;
+            }
+
+            match ACCRUED_BOXES
+                .iter()
+                .find(|(rate, ..)| *rate == invoice.rule.iva)
+            {
+                Some((rate, base_box, rate_box, cuota_box)) => {
+                    *boxes.entry(*base_box).or_default() += base;
+                    boxes.insert(*rate_box, to_cents(*rate));
+                    *boxes.entry(*cuota_box).or_default() += to_cents(invoice.iva_amount);
+                }
+                None => warnings.push(format!(
+                    "Invoice {}: IVA rate {}% has no Modelo 303 box",
+                    invoice.id, invoice.rule.iva
+                )),
+            }
+        }
+
+        let accrued: i64 = ACCRUED_BOXES
+            .iter()
+            .map(|(.., cuota_box)| boxes.get(cuota_box).copied().unwrap_or(0))
+            .sum();
+        for expense in expenses.iter().filter(|e| quarter.contains(&e.date)) {
+            if expense.iva > 0.0 {
+                *boxes.entry(28).or_default() += to_cents(expense.deductible_base());
+                *boxes.entry(29).or_default() += to_cents(expense.deductible_iva());
+            }
+        }
+        let deductible = boxes.get(&29).copied().unwrap_or(0);
+        let result = accrued - deductible;
+
+        boxes.insert(27, accrued);
+        boxes.insert(45, deductible);
+        boxes.insert(46, result);
+        boxes.insert(64, result);
+        boxes.insert(65, 10000);
+        boxes.insert(66, result);
+        boxes.insert(69, result);
+        boxes.insert(71, result);
+
+        Modelo303 {
+            quarter,
+            invoice_count,
+            boxes,
+            warnings,
+        }
+    }
+
+    pub fn value(&self, number: u16) -> i64 {
+        self.boxes.get(&number).copied().unwrap_or(0)
+    }
+
+    // Boxes with a value, in form order, formatted for display
+    pub fn rows(&self) -> Vec<(u16, &'static str, String)> {
+        PAGE_1
+            .iter()
+            .chain(PAGE_3)
+            .filter(|number| self.boxes.get(number).is_some_and(|v| *v != 0))
+            .map(|number| {
+                let value = format_cents(self.value(*number));
+                let value = if PERCENT_BOXES.contains(number) {
+                    format!("{}%", value)
+                } else {
+                    format!("{}€", value)
+                };
+                (*number, box_description(*number), value)
+            })
+            .collect()
+    }
+
+    // Declaration type: I (ingreso), C (a compensar), D (devolución, only in 4T) or N (sin actividad)
+    pub fn declaration_type(&self) -> char {
+        let result = self.value(71);
+        if result > 0 {
+            'I'
+        } else if result < 0 && self.quarter.quarter == 4 {
+            'D'
+        } else if result < 0 {
+            'C'
+        } else {
+            'N'
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let boxes: BTreeMap<String, String> = self
+            .boxes
+            .iter()
+            .map(|(number, value)| (format!("{:02}", number), format_cents(*value)))
+            .collect();
+
+        json!({
+            "model": "303",
+            "year": self.quarter.year,
+            "period": self.quarter.code(),
+            "invoices": self.invoice_count,
+            "declaration_type": self.declaration_type().to_string(),
+            "boxes": boxes,
+            "warnings": self.warnings,
+        })
+    }
+
+    // Presentation file following the DR303 record layout (pages 01 and 03)
+    // Optional indicators are written with their "not applicable" value
+    pub fn to_aeat_file(&self, user: &User) -> Vec<u8> {
+        let year = self.quarter.year;
+        let period = self.quarter.code();
+        let mut record = String::new();
+
+        record.push_str(&format!("<T303{}{}0000>", year, period));
+        record.push_str("<AUX>");
+        record.push_str(&alpha("", 70));
+        record.push_str("0100"); // Program version
+        record.push_str(&alpha("", 4));
+        record.push_str(&alpha("", 9)); // NIF of the software developer
+        record.push_str(&alpha("", 213));
+        record.push_str("</AUX>");
+
+        record.push_str("<T30301000>");
+        record.push(' '); // Not a complementary page
+        record.push(self.declaration_type());
+        record.push_str(&alpha(&user.cif, 9));
+        record.push_str(&alpha(&user.name, 80));
+        record.push_str(&alpha("", 15));
+        record.push_str(&year.to_string());
+        record.push_str(&period);
+        // Foral taxation, REDEME, joint filing, insolvency, cash accounting,
+        // cash accounting recipient, special pro rata and its revocation
+        record.push_str("2222");
+        record.push_str(&alpha("", 8));
+        record.push_str(" 2222");
+        for number in PAGE_1 {
+            record.push_str(&self.box_field(*number));
+        }
+        record.push_str("</T30301000>");
+
+        record.push_str("<T30303000>");
+        for number in PAGE_3 {
+            record.push_str(&self.box_field(*number));
+        }
+        record.push_str("</T30303000>");
+
+        record.push_str(&format!("</T303{}{}0000>", year, period));
+        record.push_str("\r\n");
+        to_latin1(&record)
+    }
+
+    fn box_field(&self, number: u16) -> String {
+        let width = if PERCENT_BOXES.contains(&number) {
+            5
+        } else {
+            17
+        };
+        amount(self.value(number), width)
+    }
+}
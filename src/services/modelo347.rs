@@ -0,0 +1,203 @@
+// Modelo 347: annual declaration of operations with third parties above 3,005.06€
+// Amounts include IVA and are broken down per quarter, kept in cents
+
+use crate::models::{Invoice, User};
+use crate::services::aeat::{alpha, numeric, to_latin1};
+use crate::services::einvoice::{country_code, local_tax_id, vat_identifier};
+use crate::services::money::{format_cents, to_cents};
+use crate::services::period::Quarter;
+use crate::services::tax_id::validate_spanish_tax_id;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+pub const THRESHOLD: i64 = 300506;
+
+// Operation key B: sales and services rendered
+const OPERATION_KEY: char = 'B';
+
+const RECORD_LENGTH: usize = 500;
+
+// A client that has to be declared
+#[derive(Debug, Clone)]
+pub struct DeclaredClient {
+    pub cif: String,
+    pub name: String,
+    pub province: Option<String>, // First two digits of the postal code
+    pub quarters: [i64; 4],
+    pub total: i64,
+    pub issue: Option<String>, // Why the tax ID would be rejected by the AEAT
+}
+
+#[derive(Debug, Clone)]
+pub struct Modelo347 {
+    pub year: i32,
+    pub clients: Vec<DeclaredClient>,
+    pub below_threshold: usize,
+}
+
+impl Modelo347 {
+    pub fn from_invoices(invoices: &[Invoice], year: i32) -> Self {
+        let mut grouped: BTreeMap<String, DeclaredClient> = BTreeMap::new();
+
+        let mut sorted: Vec<&Invoice> = invoices.iter().collect();
+        sorted.sort_by(|a, b| a.date.cmp(&b.date));
+
+        for invoice in sorted {
+            let Some(quarter) = (1..=4)
+                .filter_map(|q| Quarter::new(year, q).ok())
+                .find(|q| q.contains(&invoice.date))
+            else {
+                continue;
+            };
+
+            // Simplified invoices without the client data cannot be attributed to anyone
+            let Some(invoice_client) = &invoice.client else {
+                continue;
+            };
+            // Foreign clients are declared in Modelo 349, not here
+            let cif = invoice_client.cif.trim();
+            if !cif.is_empty() && country_code(&vat_identifier(cif)) != "ES" {
+                continue;
+            }
+            let cif = local_tax_id(cif);
+
+            let client = grouped
+                .entry(cif.clone())
+                .or_insert_with(|| DeclaredClient {
+                    issue: tax_id_issue(&cif),
+                    cif,
+                    name: String::new(),
+                    province: None,
+                    quarters: [0; 4],
+                    total: 0,
+                });
+            // The most recent invoice has the current name and address
+            client.name = invoice_client.name.clone();
+            client.province = province_code(&invoice_client.address);
+            let amount = to_cents(invoice.subtotal) + to_cents(invoice.iva_amount);
+            client.quarters[quarter.quarter as usize - 1] += amount;
+            client.total += amount;
+        }
+
+        let (clients, below): (Vec<DeclaredClient>, Vec<DeclaredClient>) = grouped
+            .into_values()
+            .partition(|client| client.total > THRESHOLD);
+
+        Modelo347 {
+            year,
+            clients,
+            below_threshold: below.len(),
+        }
+    }
+
+    pub fn total(&self) -> i64 {
+        self.clients.iter().map(|c| c.total).sum()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let clients: Vec<Value> = self
+            .clients
+            .iter()
+            .map(|client| {
+                json!({
+                    "cif": client.cif,
+                    "name": client.name,
+                    "province": client.province,
+                    "operation_key": OPERATION_KEY.to_string(),
+                    "quarters": client.quarters.iter().map(|q| format_cents(*q)).collect::<Vec<_>>(),
+                    "total": format_cents(client.total),
+                    "issue": client.issue,
+                })
+            })
+            .collect();
+
+        json!({
+            "model": "347",
+            "year": self.year,
+            "threshold": format_cents(THRESHOLD),
+            "total": format_cents(self.total()),
+            "clients": clients,
+        })
+    }
+
+    // Presentation file: one declarant record (type 1) and one record per client (type 2)
+    pub fn to_aeat_file(&self, user: &User) -> Vec<u8> {
+        let year = self.year.to_string();
+        let nif = alpha(&local_tax_id(&user.cif), 9);
+        let mut file = String::new();
+
+        let mut declarant = String::new();
+        declarant.push('1');
+        declarant.push_str("347");
+        declarant.push_str(&year);
+        declarant.push_str(&nif);
+        declarant.push_str(&alpha(&user.name, 40));
+        declarant.push('T'); // Telematic presentation
+        declarant.push_str(&alpha("", 9)); // Contact phone
+        declarant.push_str(&alpha(&user.name, 40)); // Contact person
+        declarant.push_str(&format!("347{}", numeric(1, 10))); // Declaration number
+        declarant.push_str(&alpha("", 2)); // Neither complementary nor substitute
+        declarant.push_str(&numeric(0, 13)); // Previous declaration number
+        declarant.push_str(&numeric(self.clients.len() as u64, 9));
+        declarant.push_str(&signed(self.total()));
+        declarant.push_str(&numeric(0, 9)); // Properties
+        declarant.push_str(&signed(0)); // Property rentals
+        push_record(&mut file, declarant);
+
+        for client in &self.clients {
+            let mut record = String::new();
+            record.push('2');
+            record.push_str("347");
+            record.push_str(&year);
+            record.push_str(&nif);
+            record.push_str(&alpha(&client.cif, 9));
+            record.push_str(&alpha("", 9)); // Legal representative
+            record.push_str(&alpha(&client.name, 40));
+            record.push('D'); // Declared party
+            record.push_str(&alpha(client.province.as_deref().unwrap_or(""), 2));
+            record.push_str(&alpha("", 2)); // Country, only for foreign parties
+            record.push(' ');
+            record.push(OPERATION_KEY);
+            record.push_str(&signed(client.total));
+            record.push_str("  "); // Insurance and business rental flags
+            record.push_str(&numeric(0, 15)); // Cash received
+            record.push_str(&signed(0)); // Property transfers subject to IVA
+            record.push_str(&numeric(0, 4)); // Year of the cash operations
+            for quarter in client.quarters {
+                record.push_str(&signed(quarter));
+                record.push_str(&signed(0)); // Property transfers in the quarter
+            }
+            push_record(&mut file, record);
+        }
+
+        to_latin1(&file)
+    }
+}
+
+// Amount with a leading sign position (blank or "N") followed by 15 digits in cents
+fn signed(cents: i64) -> String {
+    let sign = if cents < 0 { 'N' } else { ' ' };
+    format!("{}{}", sign, numeric(cents.unsigned_abs(), 15))
+}
+
+fn push_record(file: &mut String, record: String) {
+    file.push_str(&format!("{:<width$}", record, width = RECORD_LENGTH));
+    file.push_str("\r\n");
+}
+
+// Spanish postal codes start with the province code
+fn province_code(address: &str) -> Option<String> {
+    address
+        .split(|c: char| !c.is_ascii_digit())
+        .find(|token| token.len() == 5)
+        .map(|postal_code| postal_code[..2].to_string())
+        .filter(|code| ("01".."53").contains(&code.as_str()))
+}
+
+// Why the AEAT would reject a Spanish NIF, NIE or CIF
+fn tax_id_issue(cif: &str) -> Option<String> {
+    if cif.is_empty() {
+        return Some("Missing tax ID".to_string());
+    }
+    validate_spanish_tax_id(cif).err().map(|e| e.to_string())
+}
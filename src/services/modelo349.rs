@@ -0,0 +1,380 @@
+// Modelo 349: recapitulative statement of intra-community operations
+// Changes to quarters that were already declared are reported as rectifications
+
+use crate::models::{Invoice, Modelo349Filing, Modelo349Operation, User};
+use crate::services::aeat::{alpha, numeric, to_latin1};
+use crate::services::einvoice::{country_code, is_eu_country, local_tax_id, vat_identifier};
+use crate::services::money::{format_cents, to_cents};
+use crate::services::period::Quarter;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+const RECORD_LENGTH: usize = 500;
+
+// Operation keys that apply to the supplier side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OperationKey {
+    Goods,            // E: intra-community supplies of goods
+    ExemptImport,     // M: supplies after a tax-exempt import
+    RepresentedGoods, // H: supplies after an exempt import, by a tax representative
+    Services,         // S: services rendered to EU businesses
+    Triangular,       // T: triangular operations
+}
+
+impl OperationKey {
+    pub const ALL: [OperationKey; 5] = [
+        OperationKey::Services,
+        OperationKey::Goods,
+        OperationKey::Triangular,
+        OperationKey::ExemptImport,
+        OperationKey::RepresentedGoods,
+    ];
+
+    pub fn code(&self) -> char {
+        match self {
+            OperationKey::Goods => 'E',
+            OperationKey::ExemptImport => 'M',
+            OperationKey::RepresentedGoods => 'H',
+            OperationKey::Services => 'S',
+            OperationKey::Triangular => 'T',
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<Self> {
+        OperationKey::ALL.into_iter().find(|key| key.code() == code)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            OperationKey::Goods => "E - Entregas intracomunitarias de bienes",
+            OperationKey::ExemptImport => "M - Entregas posteriores a una importación exenta",
+            OperationKey::RepresentedGoods => {
+                "H - Entregas posteriores a una importación exenta (representante fiscal)"
+            }
+            OperationKey::Services => "S - Prestaciones intracomunitarias de servicios",
+            OperationKey::Triangular => "T - Operaciones triangulares",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub vat_id: String,
+    pub name: String,
+    pub key: OperationKey,
+    pub amount: i64,
+}
+
+// Correction of an operation declared in an earlier quarter
+#[derive(Debug, Clone)]
+pub struct Rectification {
+    pub vat_id: String,
+    pub name: String,
+    pub key: OperationKey,
+    pub quarter: Quarter,
+    pub declared: i64,
+    pub rectified: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Modelo349 {
+    pub quarter: Quarter,
+    pub operations: Vec<Operation>,
+    pub rectifications: Vec<Rectification>,
+    pub updated_filings: Vec<Modelo349Filing>, // Earlier quarters as they stand after rectifying
+    pub warnings: Vec<String>,
+}
+
+type Grouped = BTreeMap<(String, OperationKey), (String, i64)>;
+
+impl Modelo349 {
+    // Operators missing from `keys` are declared as services
+    pub fn from_invoices(
+        invoices: &[Invoice],
+        quarter: Quarter,
+        keys: &BTreeMap<String, OperationKey>,
+        previous: &[Modelo349Filing],
+    ) -> Self {
+        let mut warnings = Vec::new();
+        let grouped = group(invoices, quarter, keys, &mut warnings);
+
+        let mut operations = Vec::new();
+        for ((vat_id, key), (name, amount)) in grouped {
+            if amount < 0 {
+                warnings.push(format!(
+                    "{} ({}): negative total {} not declared",
+                    name,
+                    vat_id,
+                    format_cents(amount)
+                ));
+                continue;
+            }
+            operations.push(Operation {
+                vat_id,
+                name,
+                key,
+                amount,
+            });
+        }
+
+        // Recalculate every earlier quarter we declared and compare
+        let mut rectifications = Vec::new();
+        let mut updated_filings = Vec::new();
+        let mut earlier: Vec<&Modelo349Filing> = previous
+            .iter()
+            .filter(|f| (f.year, f.quarter) < (quarter.year, quarter.quarter))
+            .collect();
+        earlier.sort_by_key(|f| (f.year, f.quarter));
+
+        for filing in earlier {
+            let Ok(filed_quarter) = Quarter::new(filing.year, filing.quarter) else {
+                continue;
+            };
+            let mut filed_keys = keys.clone();
+            let mut declared: Grouped = BTreeMap::new();
+            for op in &filing.operations {
+                let key = OperationKey::from_code(op.key).unwrap_or(OperationKey::Services);
+                filed_keys.insert(op.vat_id.clone(), key);
+                declared.insert(
+                    (op.vat_id.clone(), key),
+                    (op.name.clone(), to_cents(op.amount)),
+                );
+            }
+            let current = group(invoices, filed_quarter, &filed_keys, &mut Vec::new());
+
+            let operators: BTreeSet<&(String, OperationKey)> =
+                declared.keys().chain(current.keys()).collect();
+            let mut changed = Vec::new();
+            for operator in operators {
+                let before = declared.get(operator).map_or(0, |(_, amount)| *amount);
+                let after = current
+                    .get(operator)
+                    .map_or(0, |(_, amount)| (*amount).max(0));
+                if before == after {
+                    continue;
+                }
+                let name = current
+                    .get(operator)
+                    .or_else(|| declared.get(operator))
+                    .map(|(name, _)| name.clone())
+                    .unwrap_or_default();
+                changed.push(Rectification {
+                    vat_id: operator.0.clone(),
+                    name,
+                    key: operator.1,
+                    quarter: filed_quarter,
+                    declared: before,
+                    rectified: after,
+                });
+            }
+
+            if !changed.is_empty() {
+                updated_filings.push(Modelo349Filing::new(
+                    filing.year,
+                    filing.quarter,
+                    to_filing_operations(
+                        current.into_iter().filter(|(_, (_, amount))| *amount >= 0),
+                    ),
+                ));
+                rectifications.extend(changed);
+            }
+        }
+
+        Modelo349 {
+            quarter,
+            operations,
+            rectifications,
+            updated_filings,
+            warnings,
+        }
+    }
+
+    pub fn total(&self) -> i64 {
+        self.operations.iter().map(|op| op.amount).sum()
+    }
+
+    pub fn rectified_total(&self) -> i64 {
+        self.rectifications.iter().map(|r| r.rectified).sum()
+    }
+
+    pub fn to_filing(&self) -> Modelo349Filing {
+        Modelo349Filing::new(
+            self.quarter.year,
+            self.quarter.quarter,
+            to_filing_operations(
+                self.operations
+                    .iter()
+                    .map(|op| ((op.vat_id.clone(), op.key), (op.name.clone(), op.amount))),
+            ),
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        let operations: Vec<Value> = self
+            .operations
+            .iter()
+            .map(|op| {
+                json!({
+                    "vat_id": op.vat_id,
+                    "name": op.name,
+                    "key": op.key.code().to_string(),
+                    "amount": format_cents(op.amount),
+                })
+            })
+            .collect();
+        let rectifications: Vec<Value> = self
+            .rectifications
+            .iter()
+            .map(|r| {
+                json!({
+                    "vat_id": r.vat_id,
+                    "name": r.name,
+                    "key": r.key.code().to_string(),
+                    "year": r.quarter.year,
+                    "period": r.quarter.code(),
+                    "declared": format_cents(r.declared),
+                    "rectified": format_cents(r.rectified),
+                })
+            })
+            .collect();
+
+        json!({
+            "model": "349",
+            "year": self.quarter.year,
+            "period": self.quarter.code(),
+            "total": format_cents(self.total()),
+            "operations": operations,
+            "rectifications": rectifications,
+            "warnings": self.warnings,
+        })
+    }
+
+    // Presentation file: declarant record (type 1), then operations and rectifications (type 2)
+    pub fn to_aeat_file(&self, user: &User) -> Vec<u8> {
+        let year = self.quarter.year.to_string();
+        let nif = alpha(&local_tax_id(&user.cif), 9);
+        let rectified_operators = self
+            .rectifications
+            .iter()
+            .map(|r| &r.vat_id)
+            .collect::<BTreeSet<_>>()
+            .len();
+        let mut file = String::new();
+
+        let mut declarant = String::new();
+        declarant.push('1');
+        declarant.push_str("349");
+        declarant.push_str(&year);
+        declarant.push_str(&nif);
+        declarant.push_str(&alpha(&user.name, 40));
+        declarant.push('T'); // Telematic presentation
+        declarant.push_str(&alpha("", 9)); // Contact phone
+        declarant.push_str(&alpha(&user.name, 40)); // Contact person
+        declarant.push_str(&format!("349{}", numeric(1, 10))); // Declaration number
+        declarant.push_str(&alpha("", 2)); // Neither complementary nor substitute
+        declarant.push_str(&numeric(0, 13)); // Previous declaration number
+        declarant.push_str(&self.quarter.code());
+        declarant.push_str(&numeric(self.operations.len() as u64, 9));
+        declarant.push_str(&numeric(self.total().max(0) as u64, 15));
+        declarant.push_str(&numeric(rectified_operators as u64, 9));
+        declarant.push_str(&numeric(self.rectified_total() as u64, 15));
+        push_record(&mut file, declarant);
+
+        for op in &self.operations {
+            let mut record = operator_prefix(&year, &nif, &op.vat_id, &op.name, op.key);
+            record.push_str(&numeric(op.amount as u64, 13));
+            push_record(&mut file, record);
+        }
+
+        for r in &self.rectifications {
+            let mut record = operator_prefix(&year, &nif, &r.vat_id, &r.name, r.key);
+            record.push_str(&alpha("", 13));
+            record.push_str(&r.quarter.year.to_string());
+            record.push_str(&r.quarter.code());
+            record.push_str(&numeric(r.rectified as u64, 13));
+            record.push_str(&numeric(r.declared.max(0) as u64, 13));
+            push_record(&mut file, record);
+        }
+
+        to_latin1(&file)
+    }
+}
+
+// EU operators invoiced in a quarter, so a key can be chosen for each of them
+pub fn intra_eu_operators(invoices: &[Invoice], quarter: Quarter) -> Vec<(String, String)> {
+    let mut operators = BTreeMap::new();
+    for invoice in invoices.iter().filter(|i| quarter.contains(&i.date)) {
+        if let Some(vat_id) = operator_vat_id(invoice.client_cif()) {
+            operators.insert(vat_id, invoice.client_name().to_string());
+        }
+    }
+    operators.into_iter().collect()
+}
+
+// VAT number of a client registered for VAT in another member state
+fn operator_vat_id(cif: &str) -> Option<String> {
+    let vat_id = vat_identifier(&local_tax_id(cif));
+    let country = country_code(&vat_id);
+    (country != "ES" && is_eu_country(country)).then_some(vat_id)
+}
+
+fn group(
+    invoices: &[Invoice],
+    quarter: Quarter,
+    keys: &BTreeMap<String, OperationKey>,
+    warnings: &mut Vec<String>,
+) -> Grouped {
+    let mut grouped: Grouped = BTreeMap::new();
+    let mut sorted: Vec<&Invoice> = invoices
+        .iter()
+        .filter(|i| quarter.contains(&i.date))
+        .collect();
+    sorted.sort_by(|a, b| a.date.cmp(&b.date));
+
+    for invoice in sorted {
+        let Some(vat_id) = operator_vat_id(invoice.client_cif()) else {
+            continue;
+        };
+        if invoice.rule.iva != 0.0 {
+            warnings.push(format!(
+                "Invoice {} to {} charges IVA, it is not an exempt intra-community operation",
+                invoice.id, vat_id
+            ));
+            continue;
+        }
+        let key = keys.get(&vat_id).copied().unwrap_or(OperationKey::Services);
+        let entry = grouped.entry((vat_id, key)).or_default();
+        entry.0 = invoice.client_name().to_string();
+        entry.1 += to_cents(invoice.subtotal);
+    }
+
+    grouped
+}
+
+fn to_filing_operations(
+    grouped: impl Iterator<Item = ((String, OperationKey), (String, i64))>,
+) -> Vec<Modelo349Operation> {
+    grouped
+        .map(|((vat_id, key), (name, amount))| {
+            Modelo349Operation::new(vat_id, name, key.code(), amount as f32 / 100.0)
+        })
+        .collect()
+}
+
+fn operator_prefix(year: &str, nif: &str, vat_id: &str, name: &str, key: OperationKey) -> String {
+    let mut record = String::new();
+    record.push('2');
+    record.push_str("349");
+    record.push_str(year);
+    record.push_str(nif);
+    record.push_str(&alpha("", 58));
+    record.push_str(&alpha(vat_id, 17));
+    record.push_str(&alpha(name, 40));
+    record.push(key.code());
+    record
+}
+
+fn push_record(file: &mut String, record: String) {
+    file.push_str(&format!("{:<width$}", record, width = RECORD_LENGTH));
+    file.push_str("\r\n");
+}
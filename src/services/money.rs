@@ -0,0 +1,24 @@
+// Helpers to work with amounts as whole cents so exported totals add up exactly
+
+// Convert an amount in euros to cents, rounding half away from zero
+pub fn to_cents(amount: f32) -> i64 {
+    (amount as f64 * 100.0).round() as i64
+}
+
+// Apply a percentage to an amount in cents, rounding to the nearest cent
+pub fn percent_of(cents: i64, percent: f32) -> i64 {
+    (cents as f64 * percent as f64 / 100.0).round() as i64
+}
+
+// Format cents as a plain decimal amount (e.g. 1234 -> "12.34")
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let abs = cents.abs();
+    format!("{}{}.{:02}", sign, abs / 100, abs % 100)
+}
+
+// Parse a decimal amount (e.g. "12.34") into cents
+pub fn parse_cents(text: &str) -> Option<i64> {
+    let value: f64 = text.trim().parse().ok()?;
+    Some((value * 100.0).round() as i64)
+}
@@ -1,18 +1,74 @@
 // PDF Service for generating invoice PDFs
 
-use crate::models::Invoice;
+use crate::models::{Invoice, InvoiceKind, Quote, QuoteKind};
+use crate::repository::storage::file_stem;
+use crate::services::cii::to_cii_xml;
+use crate::services::epc_qr::{epc_payload, epc_qr_modules};
+use crate::services::facturx::{embed_factur_x, DocumentProperties};
+use crate::services::iban::format_iban;
 use printpdf::*;
 use std::fs::File;
 use std::io::{self, BufWriter};
 
+// Fonts embedded in Factur-X output, PDF/A does not allow the standard 14 fonts
+const EMBEDDED_FONT_REGULAR: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+const EMBEDDED_FONT_BOLD: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+
+// Width of the simplified invoice receipts, the usual thermal paper roll
+const RECEIPT_WIDTH: f32 = 80.0;
+
+// Wording of each kind of document, all of them share the invoice layout
+struct DocumentLayout {
+    title: &'static str,
+    number_label: &'static str,
+    due_label: &'static str,
+    footer: &'static str,
+    file_prefix: &'static str,
+    payment_qr: bool, // Show the SEPA transfer QR code
+    e_invoice: bool,  // May be a Factur-X invoice
+}
+
+const INVOICE_LAYOUT: DocumentLayout = DocumentLayout {
+    title: "INVOICE",
+    number_label: "INVOICE #",
+    due_label: "DUE DATE",
+    footer: "Thank you for your business!",
+    file_prefix: "invoice",
+    payment_qr: true,
+    e_invoice: true,
+};
+
+const QUOTE_LAYOUT: DocumentLayout = DocumentLayout {
+    title: "QUOTE",
+    number_label: "QUOTE #",
+    due_label: "VALID UNTIL",
+    footer: "We look forward to working with you!",
+    file_prefix: "quote",
+    payment_qr: false,
+    e_invoice: false,
+};
+
+// Proformas can be paid in advance, but they are never a tax invoice
+const PROFORMA_LAYOUT: DocumentLayout = DocumentLayout {
+    title: "PROFORMA INVOICE",
+    number_label: "PROFORMA #",
+    due_label: "VALID UNTIL",
+    footer: "This proforma is not a tax invoice",
+    file_prefix: "proforma",
+    payment_qr: true,
+    e_invoice: false,
+};
+
 // Service for generating PDF invoices
 #[derive(Clone)]
 pub struct PdfService {
     output_dir: String, // Directory where generated PDFs will be stored
+    factur_x: bool,     // Produce PDF/A-3b with the CII XML embedded
+    currency: String,   // Currency of the amounts, the payment QR code is only for euros
 }
 
 impl PdfService {
-    pub fn new(output_dir: String) -> io::Result<Self> {
+    pub fn new(output_dir: String, factur_x: bool, currency: String) -> io::Result<Self> {
         std::fs::create_dir_all(&output_dir).map_err(|e| {
             io::Error::new(
                 io::ErrorKind::PermissionDenied,
@@ -22,25 +78,46 @@ impl PdfService {
                 ),
             )
         })?;
-        Ok(PdfService { output_dir })
+        Ok(PdfService {
+            output_dir,
+            factur_x,
+            currency,
+        })
     }
 
     // Generate a PDF invoice from an Invoice model
     pub fn generate_invoice_pdf(&self, invoice: &Invoice) -> io::Result<String> {
+        match invoice.kind {
+            InvoiceKind::Full => self.render(invoice, &INVOICE_LAYOUT),
+            InvoiceKind::Simplified => self.render_receipt(invoice),
+        }
+    }
+
+    // Generate the PDF of a quote or proforma, with its validity date in place of the due date
+    pub fn generate_quote_pdf(&self, quote: &Quote) -> io::Result<String> {
+        let document = Invoice::new(
+            quote.id.clone(),
+            quote.date.clone(),
+            quote.valid_until.clone(),
+            quote.user.clone(),
+            quote.client.clone(),
+            quote.rule.clone(),
+            quote.items.clone(),
+        );
+        let layout = match quote.kind {
+            QuoteKind::Quote => &QUOTE_LAYOUT,
+            QuoteKind::Proforma => &PROFORMA_LAYOUT,
+        };
+        self.render(&document, layout)
+    }
+
+    fn render(&self, invoice: &Invoice, layout: &DocumentLayout) -> io::Result<String> {
         // Create a PDF document with A4 dimensions
-        let (doc, page1, layer1) = PdfDocument::new("Invoice", Mm(210.0), Mm(297.0), "Layer 1");
+        let (doc, page1, layer1) = PdfDocument::new(layout.title, Mm(210.0), Mm(297.0), "Layer 1");
         let current_layer = doc.get_page(page1).get_layer(layer1);
 
         // Load fonts
-        let font_regular = doc
-            .add_builtin_font(BuiltinFont::Helvetica)
-            .map_err(|e| io::Error::other(format!("Failed to load regular font: {}", e)))?;
-        let font_bold = doc
-            .add_builtin_font(BuiltinFont::HelveticaBold)
-            .map_err(|e| io::Error::other(format!("Failed to load bold font: {}", e)))?;
-        let font_italic = doc
-            .add_builtin_font(BuiltinFont::HelveticaOblique)
-            .map_err(|e| io::Error::other(format!("Failed to load italic font: {}", e)))?;
+        let (font_regular, font_bold, font_italic) = self.load_fonts(&doc)?;
 
         // Define colors
         let blue_color = printpdf::Color::Rgb(Rgb::new(0.0, 0.35, 0.7, None));
@@ -52,7 +129,7 @@ impl PdfService {
         self.add_text_with_color(
             &current_layer,
             &font_bold,
-            "INVOICE",
+            layout.title,
             24.0,
             Mm(30.0),
             Mm(270.0),
@@ -63,7 +140,7 @@ impl PdfService {
         self.add_text_with_color(
             &current_layer,
             &font_bold,
-            "INVOICE #",
+            layout.number_label,
             11.0,
             Mm(145.0),
             Mm(275.0),
@@ -103,7 +180,7 @@ impl PdfService {
         self.add_text_with_color(
             &current_layer,
             &font_bold,
-            "DUE DATE",
+            layout.due_label,
             11.0,
             Mm(145.0),
             Mm(245.0),
@@ -174,10 +251,14 @@ impl PdfService {
         }
 
         if let Some(iban) = &invoice.user.iban {
+            let mut account = format!("IBAN: {}", format_iban(iban));
+            if let Some(bic) = &invoice.user.bic {
+                account.push_str(&format!("   BIC: {}", bic));
+            }
             self.add_text_with_color(
                 &current_layer,
                 &font_regular,
-                &format!("IBAN: {}", iban),
+                &account,
                 10.0,
                 Mm(30.0),
                 Mm(215.0),
@@ -185,57 +266,59 @@ impl PdfService {
             );
         }
 
-        // Add client details
-        self.add_text_with_color(
-            &current_layer,
-            &font_bold,
-            "BILL TO",
-            14.0,
-            Mm(30.0),
-            Mm(200.0),
-            blue_color.clone(),
-        );
-
-        self.add_text_with_color(
-            &current_layer,
-            &font_bold,
-            &invoice.client.name,
-            12.0,
-            Mm(30.0),
-            Mm(195.0),
-            black_color.clone(),
-        );
+        // Add client details, simplified invoices may have none
+        if let Some(client) = &invoice.client {
+            self.add_text_with_color(
+                &current_layer,
+                &font_bold,
+                "BILL TO",
+                14.0,
+                Mm(30.0),
+                Mm(200.0),
+                blue_color.clone(),
+            );
 
-        self.add_text_with_color(
-            &current_layer,
-            &font_regular,
-            &format!("CIF/NIF: {}", invoice.client.cif),
-            10.0,
-            Mm(30.0),
-            Mm(190.0),
-            black_color.clone(),
-        );
+            self.add_text_with_color(
+                &current_layer,
+                &font_bold,
+                &client.name,
+                12.0,
+                Mm(30.0),
+                Mm(195.0),
+                black_color.clone(),
+            );
 
-        self.add_text_with_color(
-            &current_layer,
-            &font_regular,
-            &invoice.client.address,
-            10.0,
-            Mm(30.0),
-            Mm(185.0),
-            black_color.clone(),
-        );
+            self.add_text_with_color(
+                &current_layer,
+                &font_regular,
+                &format!("CIF/NIF: {}", client.cif),
+                10.0,
+                Mm(30.0),
+                Mm(190.0),
+                black_color.clone(),
+            );
 
-        if let Some(email) = &invoice.client.email {
             self.add_text_with_color(
                 &current_layer,
                 &font_regular,
-                &format!("Email: {}", email),
+                &client.address,
                 10.0,
                 Mm(30.0),
-                Mm(180.0),
+                Mm(185.0),
                 black_color.clone(),
             );
+
+            if let Some(email) = &client.email {
+                self.add_text_with_color(
+                    &current_layer,
+                    &font_regular,
+                    &format!("Email: {}", email),
+                    10.0,
+                    Mm(30.0),
+                    Mm(180.0),
+                    black_color.clone(),
+                );
+            }
         }
 
         // Add table header
@@ -374,6 +457,7 @@ impl PdfService {
         );
 
         y_position -= 4.0; // Reduced spacing
+        let summary_top = y_position;
 
         // Add summary
         self.add_text_with_color(
@@ -476,6 +560,28 @@ impl PdfService {
             blue_color.clone(),
         );
 
+        // SEPA transfer QR code next to the totals
+        if layout.payment_qr && self.currency == "EUR" {
+            if let Some(payload) = epc_payload(invoice) {
+                self.draw_qr_code(
+                    &current_layer,
+                    &payload,
+                    Mm(30.0),
+                    Mm(summary_top + 4.0),
+                    30.0,
+                )?;
+                self.add_text_with_color(
+                    &current_layer,
+                    &font_regular,
+                    "Scan to pay by bank transfer",
+                    8.0,
+                    Mm(30.0),
+                    Mm(summary_top - 30.0),
+                    gray_color.clone(),
+                );
+            }
+        }
+
         // Add footer
         let footer_y = 30.0;
         self.draw_line(
@@ -491,7 +597,7 @@ impl PdfService {
         self.add_text_with_color(
             &current_layer,
             &font_italic,
-            "Thank you for your business!",
+            layout.footer,
             10.0,
             Mm(105.0),
             Mm(footer_y),
@@ -499,7 +605,26 @@ impl PdfService {
         );
 
         // Save the PDF
-        let output_path = format!("{}/invoice_{}.pdf", self.output_dir, invoice.id);
+        let output_path = format!(
+            "{}/{}_{}.pdf",
+            self.output_dir,
+            layout.file_prefix,
+            file_stem(&invoice.id)
+        );
+
+        if self.factur_x && layout.e_invoice {
+            let pdf = doc
+                .save_to_bytes()
+                .map_err(|e| io::Error::other(format!("PDF generation error: {}", e)))?;
+            let title = format!("Invoice {}", invoice.id);
+            let properties = DocumentProperties {
+                title: &title,
+                author: &invoice.user.name,
+            };
+            let pdf = embed_factur_x(&pdf, &to_cii_xml(invoice), &properties)?;
+            std::fs::write(&output_path, pdf)?;
+            return Ok(output_path);
+        }
 
         // Create file and handle errors
         let file = File::create(&output_path)?;
@@ -512,6 +637,221 @@ impl PdfService {
         Ok(output_path)
     }
 
+    // Simplified invoices are printed as an 80 mm wide receipt with the prices IVA included
+    fn render_receipt(&self, invoice: &Invoice) -> io::Result<String> {
+        let left = 5.0;
+        let amounts = 55.0;
+        let height = 100.0
+            + 9.0 * invoice.items.len() as f32
+            + if invoice.client.is_some() { 10.0 } else { 0.0 }
+            + if invoice.irpf_amount != 0.0 { 5.0 } else { 0.0 };
+        let (doc, page1, layer1) = PdfDocument::new(
+            "Simplified invoice",
+            Mm(RECEIPT_WIDTH),
+            Mm(height),
+            "Layer 1",
+        );
+        let layer = doc.get_page(page1).get_layer(layer1);
+        let (font_regular, font_bold, font_italic) = self.load_fonts(&doc)?;
+        let black = printpdf::Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
+        let gray = printpdf::Color::Rgb(Rgb::new(0.5, 0.5, 0.5, None));
+
+        let mut y = height - 10.0;
+        let line = |font: &IndirectFontRef, text: &str, size: f32, x: f32, y: f32| {
+            self.add_text_with_color(&layer, font, text, size, Mm(x), Mm(y), black.clone());
+        };
+
+        // Issuer
+        line(&font_bold, &invoice.user.name, 10.0, left, y);
+        y -= 4.5;
+        line(
+            &font_regular,
+            &format!("NIF: {}", invoice.user.cif),
+            8.0,
+            left,
+            y,
+        );
+        y -= 4.0;
+        line(&font_regular, &invoice.user.address, 8.0, left, y);
+        y -= 4.0;
+        self.draw_line(
+            &layer,
+            Mm(left),
+            Mm(y),
+            Mm(RECEIPT_WIDTH - left),
+            Mm(y),
+            0.3,
+            gray.clone(),
+        );
+
+        y -= 6.0;
+        line(&font_bold, "SIMPLIFIED INVOICE", 11.0, left, y);
+        y -= 5.0;
+        line(&font_regular, &format!("No. {}", invoice.id), 8.0, left, y);
+        line(
+            &font_regular,
+            &format!("Date {}", invoice.date),
+            8.0,
+            45.0,
+            y,
+        );
+        if let Some(client) = &invoice.client {
+            y -= 5.0;
+            line(
+                &font_regular,
+                &format!("Client: {}", client.name),
+                8.0,
+                left,
+                y,
+            );
+            y -= 4.0;
+            line(&font_regular, &format!("NIF: {}", client.cif), 8.0, left, y);
+        }
+        y -= 3.0;
+        self.draw_line(
+            &layer,
+            Mm(left),
+            Mm(y),
+            Mm(RECEIPT_WIDTH - left),
+            Mm(y),
+            0.3,
+            gray.clone(),
+        );
+
+        // Items with the IVA included in their prices
+        let with_iva = 1.0 + invoice.rule.iva / 100.0;
+        for item in &invoice.items {
+            y -= 5.0;
+            let description: String = item.description.chars().take(42).collect();
+            line(&font_regular, &description, 8.0, left, y);
+            y -= 4.0;
+            line(
+                &font_regular,
+                &format!("  {} x {:.2} €", item.quantity, item.price * with_iva),
+                8.0,
+                left,
+                y,
+            );
+            line(
+                &font_regular,
+                &format!("{:.2} €", item.total() * with_iva),
+                8.0,
+                amounts,
+                y,
+            );
+        }
+        y -= 3.0;
+        self.draw_line(
+            &layer,
+            Mm(left),
+            Mm(y),
+            Mm(RECEIPT_WIDTH - left),
+            Mm(y),
+            0.3,
+            gray.clone(),
+        );
+
+        // Totals
+        y -= 6.0;
+        line(&font_bold, "TOTAL (IVA incluido)", 10.0, left, y);
+        line(
+            &font_bold,
+            &format!("{:.2} €", invoice.total),
+            10.0,
+            amounts,
+            y,
+        );
+        y -= 5.0;
+        line(&font_regular, "Base imponible", 8.0, left, y);
+        line(
+            &font_regular,
+            &format!("{:.2} €", invoice.subtotal),
+            8.0,
+            amounts,
+            y,
+        );
+        y -= 4.0;
+        line(
+            &font_regular,
+            &format!("IVA {}%", invoice.rule.iva),
+            8.0,
+            left,
+            y,
+        );
+        line(
+            &font_regular,
+            &format!("{:.2} €", invoice.iva_amount),
+            8.0,
+            amounts,
+            y,
+        );
+        if invoice.irpf_amount != 0.0 {
+            y -= 4.0;
+            line(
+                &font_regular,
+                &format!("IRPF {}%", invoice.rule.irpf),
+                8.0,
+                left,
+                y,
+            );
+            line(
+                &font_regular,
+                &format!("-{:.2} €", invoice.irpf_amount),
+                8.0,
+                amounts,
+                y,
+            );
+        }
+
+        y -= 8.0;
+        self.add_text_with_color(
+            &layer,
+            &font_italic,
+            "Thank you for your purchase!",
+            8.0,
+            Mm(left),
+            Mm(y),
+            gray,
+        );
+
+        let output_path = format!("{}/invoice_{}.pdf", self.output_dir, file_stem(&invoice.id));
+        let file = File::create(&output_path)?;
+        let mut writer = BufWriter::new(file);
+        doc.save(&mut writer)
+            .map_err(|e| io::Error::other(format!("PDF generation error: {}", e)))?;
+
+        Ok(output_path)
+    }
+
+    // Load regular, bold and italic fonts, embedding them when producing Factur-X
+    fn load_fonts(
+        &self,
+        doc: &PdfDocumentReference,
+    ) -> io::Result<(IndirectFontRef, IndirectFontRef, IndirectFontRef)> {
+        if self.factur_x {
+            let font_regular = doc
+                .add_external_font(EMBEDDED_FONT_REGULAR)
+                .map_err(|e| io::Error::other(format!("Failed to load regular font: {}", e)))?;
+            let font_bold = doc
+                .add_external_font(EMBEDDED_FONT_BOLD)
+                .map_err(|e| io::Error::other(format!("Failed to load bold font: {}", e)))?;
+            // No italic face is bundled, the footer uses the regular one
+            return Ok((font_regular.clone(), font_bold, font_regular));
+        }
+
+        let font_regular = doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|e| io::Error::other(format!("Failed to load regular font: {}", e)))?;
+        let font_bold = doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|e| io::Error::other(format!("Failed to load bold font: {}", e)))?;
+        let font_italic = doc
+            .add_builtin_font(BuiltinFont::HelveticaOblique)
+            .map_err(|e| io::Error::other(format!("Failed to load italic font: {}", e)))?;
+
+        Ok((font_regular, font_bold, font_italic))
+    }
+
     // Helper method to add text with a specific color
     #[allow(clippy::too_many_arguments)]
     fn add_text_with_color(
@@ -528,6 +868,43 @@ impl PdfService {
         layer.use_text(text, size, x, y, font);
     }
 
+    // Draw a QR code as filled squares, (x, top) being its upper left corner
+    fn draw_qr_code(
+        &self,
+        layer: &PdfLayerReference,
+        payload: &str,
+        x: Mm,
+        top: Mm,
+        size: f32,
+    ) -> io::Result<()> {
+        let (width, modules) = epc_qr_modules(payload)?;
+        let module = size / width as f32;
+
+        let rings = modules
+            .iter()
+            .enumerate()
+            .filter(|(_, dark)| **dark)
+            .map(|(i, _)| {
+                let left = x.0 + (i % width) as f32 * module;
+                let bottom = top.0 - ((i / width) + 1) as f32 * module;
+                vec![
+                    (Point::new(Mm(left), Mm(bottom)), false),
+                    (Point::new(Mm(left + module), Mm(bottom)), false),
+                    (Point::new(Mm(left + module), Mm(bottom + module)), false),
+                    (Point::new(Mm(left), Mm(bottom + module)), false),
+                ]
+            })
+            .collect();
+
+        layer.set_fill_color(printpdf::Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+        layer.add_polygon(Polygon {
+            rings,
+            mode: PolygonMode::Fill,
+            winding_order: WindingOrder::NonZero,
+        });
+        Ok(())
+    }
+
     // Helper method to draw a line
     #[allow(clippy::too_many_arguments)]
     fn draw_line(
@@ -0,0 +1,116 @@
+// Tax periods used by the AEAT self-assessment reports
+
+use chrono::{Datelike, NaiveDate};
+use std::fmt;
+use std::io;
+
+// A calendar quarter of a fiscal year
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quarter {
+    pub year: i32,
+    pub quarter: u32, // 1 to 4
+}
+
+impl Quarter {
+    pub fn new(year: i32, quarter: u32) -> io::Result<Self> {
+        if !(1..=4).contains(&quarter) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Quarter must be between 1 and 4, got {}", quarter),
+            ));
+        }
+        Ok(Quarter { year, quarter })
+    }
+
+    // The quarter a date falls in
+    pub fn of(date: NaiveDate) -> Self {
+        Quarter {
+            year: date.year(),
+            quarter: (date.month() - 1) / 3 + 1,
+        }
+    }
+
+    // The last closed quarter before a date, the one usually being filed
+    pub fn previous(date: NaiveDate) -> Self {
+        let current = Quarter::of(date);
+        if current.quarter == 1 {
+            Quarter {
+                year: current.year - 1,
+                quarter: 4,
+            }
+        } else {
+            Quarter {
+                year: current.year,
+                quarter: current.quarter - 1,
+            }
+        }
+    }
+
+    pub fn first_day(&self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year, (self.quarter - 1) * 3 + 1, 1).unwrap()
+    }
+
+    pub fn last_day(&self) -> NaiveDate {
+        let next = if self.quarter == 4 {
+            NaiveDate::from_ymd_opt(self.year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(self.year, self.quarter * 3 + 1, 1)
+        };
+        next.unwrap().pred_opt().unwrap()
+    }
+
+    // Whether a stored "YYYY-MM-DD" date falls in the quarter
+    pub fn contains(&self, date: &str) -> bool {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|d| d >= self.first_day() && d <= self.last_day())
+            .unwrap_or(false)
+    }
+
+    // Period code used by the AEAT forms ("1T" to "4T")
+    pub fn code(&self) -> String {
+        format!("{}T", self.quarter)
+    }
+}
+
+impl fmt::Display for Quarter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.year, self.code())
+    }
+}
+
+// An inclusive range of dates used to filter reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    pub fn year(year: i32) -> Self {
+        DateRange {
+            from: Quarter { year, quarter: 1 }.first_day(),
+            to: Quarter { year, quarter: 4 }.last_day(),
+        }
+    }
+
+    pub fn contains(&self, date: &str) -> bool {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|d| d >= self.from && d <= self.to)
+            .unwrap_or(false)
+    }
+}
+
+impl From<Quarter> for DateRange {
+    fn from(quarter: Quarter) -> Self {
+        DateRange {
+            from: quarter.first_day(),
+            to: quarter.last_day(),
+        }
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} to {}", self.from, self.to)
+    }
+}
@@ -0,0 +1,209 @@
+// Plain-text accounting exports: every invoice as a ledger-cli or beancount transaction
+// with its sales, IVA and IRPF postings, and the payment when it has been received.
+// The output only depends on the invoices, so exporting again gives the same file
+
+use crate::models::Invoice;
+use crate::services::einvoice::local_tax_id;
+use crate::services::libro_registro::number_key;
+use crate::services::money::{format_cents, to_cents};
+use crate::services::period::DateRange;
+use std::collections::BTreeMap;
+
+const RECEIVABLE_ACCOUNT: &str = "Assets:Receivable";
+const BANK_ACCOUNT: &str = "Assets:Bank";
+const WITHHOLDING_ACCOUNT: &str = "Assets:Taxes:IRPF";
+const SALES_ACCOUNT: &str = "Income:Sales";
+const IVA_ACCOUNT: &str = "Liabilities:Taxes:IVA";
+const CURRENCY: &str = "EUR";
+
+// Column where amounts end, so the postings line up
+const AMOUNT_COLUMN: usize = 60;
+
+struct Posting {
+    account: String,
+    amount: i64, // Cents
+}
+
+struct Transaction {
+    date: String,
+    payee: String,
+    narration: String,
+    invoice_id: String,
+    cif: String,
+    postings: Vec<Posting>,
+}
+
+// Issue transactions of the invoices in the range and the payments received for them,
+// in date order
+fn transactions(invoices: &[Invoice], range: DateRange) -> Vec<Transaction> {
+    let mut invoices: Vec<&Invoice> = invoices
+        .iter()
+        .filter(|invoice| range.contains(&invoice.date))
+        .collect();
+    invoices.sort_by_key(|invoice| (invoice.date.clone(), number_key(&invoice.id)));
+
+    let mut transactions = Vec::new();
+    let mut payments = Vec::new();
+    for invoice in invoices {
+        let cif = local_tax_id(invoice.client_cif());
+        // Simplified invoices without client data are kept in the parent account
+        let receivable = if cif.is_empty() {
+            RECEIVABLE_ACCOUNT.to_string()
+        } else {
+            format!("{}:{}", RECEIVABLE_ACCOUNT, account_name(&cif))
+        };
+        let base = to_cents(invoice.subtotal);
+        let iva = to_cents(invoice.iva_amount);
+        let irpf = to_cents(invoice.irpf_amount);
+
+        let mut postings = vec![Posting {
+            account: receivable.clone(),
+            amount: base + iva - irpf,
+        }];
+        if irpf != 0 {
+            postings.push(Posting {
+                account: WITHHOLDING_ACCOUNT.to_string(),
+                amount: irpf,
+            });
+        }
+        postings.push(Posting {
+            account: SALES_ACCOUNT.to_string(),
+            amount: -base,
+        });
+        if iva != 0 {
+            postings.push(Posting {
+                account: IVA_ACCOUNT.to_string(),
+                amount: -iva,
+            });
+        }
+        transactions.push(Transaction {
+            date: invoice.date.clone(),
+            payee: invoice.client_name().to_string(),
+            narration: format!("Invoice {}", invoice.id),
+            invoice_id: invoice.id.clone(),
+            cif: cif.clone(),
+            postings,
+        });
+
+        if let Some(paid_date) = &invoice.paid_date {
+            payments.push(Transaction {
+                date: paid_date.clone(),
+                payee: invoice.client_name().to_string(),
+                narration: format!("Payment of invoice {}", invoice.id),
+                invoice_id: invoice.id.clone(),
+                cif,
+                postings: vec![
+                    Posting {
+                        account: BANK_ACCOUNT.to_string(),
+                        amount: base + iva - irpf,
+                    },
+                    Posting {
+                        account: receivable,
+                        amount: -(base + iva - irpf),
+                    },
+                ],
+            });
+        }
+    }
+
+    // Stable sort, payments stay after the invoices of the same day
+    transactions.append(&mut payments);
+    transactions.sort_by(|a, b| a.date.cmp(&b.date));
+    transactions
+}
+
+// Account components may only have letters, digits and dashes and start with a capital
+fn account_name(text: &str) -> String {
+    let name: String = text
+        .to_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        name
+    } else {
+        format!("X{}", name)
+    }
+}
+
+fn posting_line(indent: &str, account: &str, amount: i64) -> String {
+    let amount = format_cents(amount);
+    let padding = AMOUNT_COLUMN
+        .saturating_sub(indent.len() + account.len() + amount.len())
+        .max(2);
+    format!(
+        "{}{}{}{} {}\n",
+        indent,
+        account,
+        " ".repeat(padding),
+        amount,
+        CURRENCY
+    )
+}
+
+// ledger-cli journal, with the invoice number as transaction code and the invoice
+// number and client tax ID as metadata tags
+pub fn to_ledger(invoices: &[Invoice], range: DateRange) -> String {
+    let mut output = String::new();
+    for transaction in transactions(invoices, range) {
+        output.push_str(&format!(
+            "{} * ({}) {}\n",
+            transaction.date,
+            transaction.invoice_id,
+            transaction.payee.replace(['\n', ';'], " ")
+        ));
+        output.push_str(&format!("    ; {}\n", transaction.narration));
+        output.push_str(&format!("    ; invoice: {}\n", transaction.invoice_id));
+        output.push_str(&format!("    ; cif: {}\n", transaction.cif));
+        for posting in &transaction.postings {
+            output.push_str(&posting_line("    ", &posting.account, posting.amount));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+// beancount file, accounts are opened on the date of their first posting
+pub fn to_beancount(invoices: &[Invoice], range: DateRange) -> String {
+    let transactions = transactions(invoices, range);
+
+    let mut opened: BTreeMap<&str, &str> = BTreeMap::new();
+    for transaction in &transactions {
+        for posting in &transaction.postings {
+            opened
+                .entry(posting.account.as_str())
+                .or_insert(transaction.date.as_str());
+        }
+    }
+
+    let mut output = format!("option \"operating_currency\" \"{}\"\n\n", CURRENCY);
+    for (account, date) in &opened {
+        output.push_str(&format!("{} open {} {}\n", date, account, CURRENCY));
+    }
+    output.push('\n');
+
+    for transaction in &transactions {
+        output.push_str(&format!(
+            "{} * \"{}\" \"{}\"\n",
+            transaction.date,
+            quote(&transaction.payee),
+            quote(&transaction.narration)
+        ));
+        output.push_str(&format!(
+            "  invoice: \"{}\"\n",
+            quote(&transaction.invoice_id)
+        ));
+        output.push_str(&format!("  cif: \"{}\"\n", quote(&transaction.cif)));
+        for posting in &transaction.postings {
+            output.push_str(&posting_line("  ", &posting.account, posting.amount));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn quote(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', " ")
+}
@@ -0,0 +1,166 @@
+// Quotes and proforma invoices: numbering, acceptance and conversion to invoices.
+// Each kind has its own yearly series (P2025-001, PF2025-001...) separate from invoices
+
+use crate::models::{Client, Invoice, Item, Quote, QuoteKind, QuoteStatus, Rule, User};
+use crate::repository::storage::Storage;
+use crate::services::invoice::{CreateInvoiceParams, InvoiceService};
+use crate::services::pdf::PdfService;
+use chrono::{Datelike, Local, NaiveDate};
+use std::io;
+
+// Days a quote is valid when no date is given
+const DEFAULT_VALIDITY_DAYS: u64 = 30;
+
+// Parameters for creating a quote or proforma
+pub struct CreateQuoteParams {
+    pub kind: QuoteKind,
+    pub date: Option<String>,
+    pub valid_until: Option<String>,
+    pub user: User,
+    pub client: Client,
+    pub rule: Rule,
+    pub items: Vec<Item>,
+}
+
+// Next number of a series, one more than the highest of the year
+pub fn next_quote_number(quotes: &[Quote], kind: QuoteKind, year: i32) -> String {
+    let series = format!("{}{}-", kind.prefix(), year);
+    let last = quotes
+        .iter()
+        .filter(|quote| quote.kind == kind)
+        .filter_map(|quote| quote.id.strip_prefix(&series)?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("{}{:03}", series, last + 1)
+}
+
+pub struct QuoteService {
+    storage: Storage,
+    pdf_service: PdfService,
+}
+
+impl QuoteService {
+    pub fn new(storage: Storage, pdf_service: PdfService) -> Self {
+        QuoteService {
+            storage,
+            pdf_service,
+        }
+    }
+
+    pub fn create_quote(&self, params: CreateQuoteParams) -> io::Result<Quote> {
+        if params.items.is_empty() {
+            return Err(invalid(format!(
+                "A {} needs at least one item",
+                params.kind.to_string().to_lowercase()
+            )));
+        }
+
+        let date = match params.date {
+            Some(date) => parse_date(&date)?,
+            None => Local::now().date_naive(),
+        };
+        let valid_until = match params.valid_until {
+            Some(valid_until) => parse_date(&valid_until)?,
+            None => date
+                .checked_add_days(chrono::Days::new(DEFAULT_VALIDITY_DAYS))
+                .unwrap_or(date),
+        };
+        if valid_until < date {
+            return Err(invalid(format!(
+                "Validity date {} is before the date {}",
+                valid_until, date
+            )));
+        }
+
+        let quotes = self.storage.list_quotes()?;
+        let quote = Quote::new(
+            next_quote_number(&quotes, params.kind, date.year()),
+            params.kind,
+            date.format("%Y-%m-%d").to_string(),
+            valid_until.format("%Y-%m-%d").to_string(),
+            params.user,
+            params.client,
+            params.rule,
+            params.items,
+        );
+        self.storage.save_quote(&quote)?;
+        Ok(quote)
+    }
+
+    // Quotes sorted by date and number, newest last
+    pub fn list_quotes(&self) -> io::Result<Vec<Quote>> {
+        let mut quotes = self.storage.list_quotes()?;
+        quotes.sort_by(|a, b| (&a.date, &a.id).cmp(&(&b.date, &b.id)));
+        Ok(quotes)
+    }
+
+    // Record the answer of the client, invoiced quotes can no longer change
+    pub fn set_status(&self, quote: &Quote, status: QuoteStatus) -> io::Result<Quote> {
+        if let Some(invoice_id) = &quote.invoice_id {
+            return Err(invalid(format!(
+                "{} {} has already been invoiced as #{}",
+                quote.kind, quote.id, invoice_id
+            )));
+        }
+
+        let mut quote = quote.clone();
+        quote.status = status;
+        self.storage.save_quote(&quote)?;
+        Ok(quote)
+    }
+
+    // Create the invoice of an accepted quote, both keep the number of the other. The
+    // invoice is issued with the current profile of the user
+    pub fn convert_to_invoice(
+        &self,
+        quote: &Quote,
+        invoice_service: &InvoiceService,
+        user: &User,
+        invoice_number: Option<String>,
+        date: Option<String>,
+    ) -> io::Result<Invoice> {
+        if quote.status != QuoteStatus::Accepted {
+            return Err(invalid(format!(
+                "Only accepted quotes can be invoiced, {} is {}",
+                quote.id,
+                quote.status.to_string().to_lowercase()
+            )));
+        }
+        if let Some(invoice_id) = &quote.invoice_id {
+            return Err(invalid(format!(
+                "{} {} has already been invoiced as #{}",
+                quote.kind, quote.id, invoice_id
+            )));
+        }
+
+        let invoice = invoice_service.create_invoice(CreateInvoiceParams {
+            invoice_number,
+            date,
+            due_date: None,
+            user: user.clone(),
+            client: quote.client.clone(),
+            rule: quote.rule.clone(),
+            items: quote.items.clone(),
+            recurring_id: None,
+            quote_id: Some(quote.id.clone()),
+        })?;
+
+        let mut quote = quote.clone();
+        quote.invoice_id = Some(invoice.id.clone());
+        self.storage.save_quote(&quote)?;
+        Ok(invoice)
+    }
+
+    pub fn generate_pdf(&self, quote: &Quote) -> io::Result<String> {
+        self.pdf_service.generate_quote_pdf(quote)
+    }
+}
+
+fn parse_date(date: &str) -> io::Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| invalid(format!("Invalid date '{}'", date)))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -0,0 +1,213 @@
+// Matching of bank statement credits with unpaid invoices, proposed to the user
+// before the invoices are marked as paid
+
+use crate::models::Invoice;
+use crate::repository::Storage;
+use crate::services::bank_statement::{parse_statement, BankTransaction};
+use crate::services::money::to_cents;
+use crate::services::sepa::sepa_text;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+// Smallest score proposed: the exact amount alone, or invoice number and client name
+const MIN_SCORE: u32 = 50;
+
+// Company forms left out when comparing client names
+const LEGAL_FORMS: &[&str] = &[
+    "SL", "SLU", "SLL", "SLP", "SA", "SAU", "SC", "CB", "SCOOP", "COOP", "GMBH", "LTD", "BV",
+    "SARL", "SAS", "SPA", "SRL",
+];
+
+// Words that announce an invoice number in a remittance text
+const NUMBER_PREFIXES: &[&str] = &["FACTURA", "FRA", "FACT", "FAC", "INVOICE", "INV", "NO", "N"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchReason {
+    Amount,
+    InvoiceNumber,
+    ClientName,
+}
+
+impl MatchReason {
+    fn score(&self) -> u32 {
+        match self {
+            MatchReason::Amount => 50,
+            MatchReason::InvoiceNumber => 40,
+            MatchReason::ClientName => 20,
+        }
+    }
+}
+
+impl fmt::Display for MatchReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            MatchReason::Amount => "same amount",
+            MatchReason::InvoiceNumber => "invoice number in the concept",
+            MatchReason::ClientName => "client name",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+// Credit that probably pays an invoice, waiting for the user to confirm it
+#[derive(Debug, Clone)]
+pub struct MatchProposal {
+    pub transaction: BankTransaction,
+    pub invoice: Invoice,
+    pub reasons: Vec<MatchReason>,
+}
+
+impl MatchProposal {
+    pub fn score(&self) -> u32 {
+        self.reasons.iter().map(MatchReason::score).sum()
+    }
+}
+
+// Best match of every credit, each credit and invoice used at most once. Ties go to
+// the oldest invoice, which is usually the one being paid
+pub fn propose_matches(
+    transactions: &[BankTransaction],
+    invoices: &[Invoice],
+) -> Vec<MatchProposal> {
+    let mut candidates: Vec<MatchProposal> = transactions
+        .iter()
+        .filter(|transaction| transaction.is_credit())
+        .flat_map(|transaction| {
+            invoices
+                .iter()
+                .filter(|invoice| {
+                    invoice.paid_date.is_none() && invoice.date <= transaction.booking_date
+                })
+                .map(|invoice| MatchProposal {
+                    transaction: transaction.clone(),
+                    invoice: invoice.clone(),
+                    reasons: match_reasons(transaction, invoice),
+                })
+        })
+        .filter(|proposal| proposal.score() >= MIN_SCORE)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.score()
+            .cmp(&a.score())
+            .then_with(|| a.invoice.date.cmp(&b.invoice.date))
+            .then_with(|| a.transaction.booking_date.cmp(&b.transaction.booking_date))
+    });
+
+    let mut used_transactions = HashSet::new();
+    let mut used_invoices = HashSet::new();
+    let mut proposals = Vec::new();
+    for proposal in candidates {
+        let transaction_id = proposal.transaction.id();
+        if used_transactions.contains(&transaction_id)
+            || used_invoices.contains(&proposal.invoice.id)
+        {
+            continue;
+        }
+        used_transactions.insert(transaction_id);
+        used_invoices.insert(proposal.invoice.id.clone());
+        proposals.push(proposal);
+    }
+    proposals.sort_by(|a, b| {
+        (&a.transaction.booking_date, &a.invoice.id)
+            .cmp(&(&b.transaction.booking_date, &b.invoice.id))
+    });
+    proposals
+}
+
+fn match_reasons(transaction: &BankTransaction, invoice: &Invoice) -> Vec<MatchReason> {
+    let text = words(&format!(
+        "{} {} {}",
+        transaction.counterparty.as_deref().unwrap_or_default(),
+        transaction.reference,
+        transaction.description
+    ));
+
+    let mut reasons = Vec::new();
+    if transaction.amount == to_cents(invoice.total) {
+        reasons.push(MatchReason::Amount);
+    }
+    if mentions_invoice_number(&text, &invoice.id) {
+        reasons.push(MatchReason::InvoiceNumber);
+    }
+    if invoice
+        .client
+        .as_ref()
+        .is_some_and(|client| mentions_name(&text, &client.name))
+    {
+        reasons.push(MatchReason::ClientName);
+    }
+    reasons
+}
+
+// Uppercase words without accents, split on anything but letters and digits
+fn words(text: &str) -> Vec<String> {
+    sepa_text(text, usize::MAX)
+        .to_uppercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// The number has to appear as whole words ("2025-10" is not found in "2025-100"),
+// and short numbers like "7" only count after "factura", "fra."...
+fn mentions_invoice_number(text: &[String], invoice_id: &str) -> bool {
+    let number = words(invoice_id);
+    if number.is_empty() || number.len() > text.len() {
+        return false;
+    }
+    let short = invoice_id.trim().len() < 4;
+
+    (0..=text.len() - number.len()).any(|start| {
+        text[start..start + number.len()] == number[..]
+            && (!short
+                || start
+                    .checked_sub(1)
+                    .is_some_and(|previous| NUMBER_PREFIXES.contains(&text[previous].as_str())))
+    })
+}
+
+// Every significant word of the client name appears in the text
+fn mentions_name(text: &[String], name: &str) -> bool {
+    let significant: Vec<String> = words(name)
+        .into_iter()
+        .filter(|word| word.len() > 2 && !LEGAL_FORMS.contains(&word.as_str()))
+        .collect();
+    !significant.is_empty() && significant.iter().all(|word| text.contains(word))
+}
+
+pub struct ReconciliationService {
+    storage: Storage,
+}
+
+impl ReconciliationService {
+    pub fn new(storage: Storage) -> Self {
+        ReconciliationService { storage }
+    }
+
+    pub fn read_statement(&self, path: &Path) -> io::Result<Vec<BankTransaction>> {
+        parse_statement(&std::fs::read(path)?)
+    }
+
+    // Proposals for the credits that have not been reconciled yet
+    pub fn propose_matches(
+        &self,
+        transactions: &[BankTransaction],
+    ) -> io::Result<Vec<MatchProposal>> {
+        let reconciled: HashSet<String> = self
+            .storage
+            .list_reconciliations()?
+            .into_iter()
+            .map(|reconciliation| reconciliation.transaction_id)
+            .collect();
+        let pending: Vec<BankTransaction> = transactions
+            .iter()
+            .filter(|transaction| !reconciled.contains(&transaction.id()))
+            .cloned()
+            .collect();
+
+        Ok(propose_matches(&pending, &self.storage.list_invoices()?))
+    }
+}
@@ -0,0 +1,184 @@
+// Recurring invoice templates and the generation of the invoices they have due.
+// Generated invoices keep the template id, so an occurrence already invoiced is
+// never created again however many times the generation runs
+
+use crate::models::{Client, Frequency, Invoice, Item, RecurringInvoice, Rule, User};
+use crate::repository::storage::Storage;
+use crate::services::invoice::{CreateInvoiceParams, InvoiceService};
+use chrono::{Datelike, Months, NaiveDate};
+use std::collections::HashSet;
+use std::io;
+use uuid::Uuid;
+
+// Parameters for creating a recurring invoice template
+pub struct CreateRecurringParams {
+    pub client: Client,
+    pub rule: Rule,
+    pub items: Vec<Item>,
+    pub frequency: Frequency,
+    pub day_of_month: u32,
+    pub start_date: String,
+    pub end_date: Option<String>,
+}
+
+// Invoice dates of a template from its start date up to `until`, both included
+pub fn occurrences(template: &RecurringInvoice, until: NaiveDate) -> Vec<NaiveDate> {
+    let Ok(start) = NaiveDate::parse_from_str(&template.start_date, "%Y-%m-%d") else {
+        return Vec::new();
+    };
+    let end = template
+        .end_date
+        .as_deref()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .map_or(until, |end| end.min(until));
+    let Some(first_month) = start.with_day(1) else {
+        return Vec::new();
+    };
+
+    let mut dates = Vec::new();
+    for period in 0.. {
+        let Some(month) =
+            first_month.checked_add_months(Months::new(period * template.frequency.months()))
+        else {
+            break;
+        };
+        let date = day_in_month(month, template.day_of_month);
+        if date > end {
+            break;
+        }
+        if date >= start {
+            dates.push(date);
+        }
+    }
+    dates
+}
+
+// The day of the month, or the last day when the month is shorter
+fn day_in_month(month: NaiveDate, day: u32) -> NaiveDate {
+    (1..=day.clamp(1, 31))
+        .rev()
+        .find_map(|day| month.with_day(day))
+        .unwrap_or(month)
+}
+
+pub struct RecurringService {
+    storage: Storage,
+}
+
+impl RecurringService {
+    pub fn new(storage: Storage) -> Self {
+        RecurringService { storage }
+    }
+
+    pub fn create_template(&self, params: CreateRecurringParams) -> io::Result<RecurringInvoice> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        if !(1..=31).contains(&params.day_of_month) {
+            return Err(invalid(format!(
+                "Day of month must be between 1 and 31, got {}",
+                params.day_of_month
+            )));
+        }
+        if params.items.is_empty() {
+            return Err(invalid(
+                "A recurring invoice needs at least one item".to_string(),
+            ));
+        }
+        let start = NaiveDate::parse_from_str(&params.start_date, "%Y-%m-%d")
+            .map_err(|_| invalid(format!("Invalid start date '{}'", params.start_date)))?;
+        if let Some(end_date) = &params.end_date {
+            let end = NaiveDate::parse_from_str(end_date, "%Y-%m-%d")
+                .map_err(|_| invalid(format!("Invalid end date '{}'", end_date)))?;
+            if end < start {
+                return Err(invalid(format!(
+                    "End date {} is before the start date {}",
+                    end_date, params.start_date
+                )));
+            }
+        }
+
+        let template = RecurringInvoice {
+            id: Uuid::new_v4().to_string(),
+            client: params.client,
+            rule: params.rule,
+            items: params.items,
+            frequency: params.frequency,
+            day_of_month: params.day_of_month,
+            start_date: params.start_date,
+            end_date: params.end_date,
+        };
+        self.storage.save_recurring_invoice(&template)?;
+        Ok(template)
+    }
+
+    // Templates sorted by client name
+    pub fn list_templates(&self) -> io::Result<Vec<RecurringInvoice>> {
+        let mut templates = self.storage.list_recurring_invoices()?;
+        templates.sort_by(|a, b| {
+            (&a.client.name, &a.start_date, &a.id).cmp(&(&b.client.name, &b.start_date, &b.id))
+        });
+        Ok(templates)
+    }
+
+    // Create every invoice due up to `today` that has not been generated yet, in date
+    // order. With `dry_run` nothing is stored and the invoices returned have no number
+    pub fn generate_due(
+        &self,
+        invoice_service: &InvoiceService,
+        user: &User,
+        today: NaiveDate,
+        dry_run: bool,
+    ) -> io::Result<Vec<Invoice>> {
+        let issued: HashSet<(String, String)> = self
+            .storage
+            .list_invoices()?
+            .into_iter()
+            .filter_map(|invoice| Some((invoice.recurring_id?, invoice.date)))
+            .collect();
+
+        let mut due: Vec<(NaiveDate, RecurringInvoice)> = self
+            .list_templates()?
+            .into_iter()
+            .flat_map(|template| {
+                occurrences(&template, today)
+                    .into_iter()
+                    .map(move |date| (date, template.clone()))
+            })
+            .filter(|(date, template)| {
+                !issued.contains(&(template.id.clone(), date.format("%Y-%m-%d").to_string()))
+            })
+            .collect();
+        due.sort_by_key(|(date, _)| *date);
+
+        let mut invoices = Vec::new();
+        for (date, template) in due {
+            let date = date.format("%Y-%m-%d").to_string();
+            if dry_run {
+                let mut invoice = Invoice::new(
+                    String::new(),
+                    date.clone(),
+                    date,
+                    user.clone(),
+                    template.client,
+                    template.rule,
+                    template.items,
+                );
+                invoice.recurring_id = Some(template.id);
+                invoices.push(invoice);
+            } else {
+                invoices.push(invoice_service.create_invoice(CreateInvoiceParams {
+                    invoice_number: None,
+                    date: Some(date),
+                    due_date: None,
+                    user: user.clone(),
+                    client: template.client,
+                    rule: template.rule,
+                    items: template.items,
+                    recurring_id: Some(template.id),
+                    quote_id: None,
+                })?);
+            }
+        }
+        Ok(invoices)
+    }
+}
@@ -0,0 +1,231 @@
+// Tax reports built from the stored invoices and exported for the AEAT
+
+use crate::models::User;
+use crate::repository::Storage;
+use crate::services::asset;
+use crate::services::libro_registro::{self, LedgerEntry};
+use crate::services::modelo130::Modelo130;
+use crate::services::modelo303::Modelo303;
+use crate::services::modelo347::Modelo347;
+use crate::services::modelo349::{intra_eu_operators, Modelo349, OperationKey};
+use crate::services::money::to_cents;
+use crate::services::period::{DateRange, Quarter};
+use crate::services::spreadsheet;
+use crate::services::stats::Stats;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+
+pub struct ReportService {
+    storage: Storage,
+    output_dir: String, // Directory where report files will be stored
+}
+
+impl ReportService {
+    pub fn new(storage: Storage, output_dir: String) -> io::Result<Self> {
+        std::fs::create_dir_all(&output_dir).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Failed to create report output directory '{}': {}",
+                    output_dir, e
+                ),
+            )
+        })?;
+        Ok(ReportService {
+            storage,
+            output_dir,
+        })
+    }
+
+    pub fn modelo303(&self, quarter: Quarter) -> io::Result<Modelo303> {
+        let invoices = self.storage.list_invoices()?;
+        let expenses = self.storage.list_expenses()?;
+        Ok(Modelo303::from_invoices(&invoices, &expenses, quarter))
+    }
+
+    // Write the JSON summary and the AEAT presentation file, returning their paths
+    pub fn export_modelo303(&self, report: &Modelo303, user: &User) -> io::Result<Vec<String>> {
+        let name = format!(
+            "modelo303_{}_{}",
+            report.quarter.year,
+            report.quarter.code()
+        );
+        let json = serde_json::to_string_pretty(&report.to_json())?;
+
+        Ok(vec![
+            self.write(&format!("{}.json", name), json.as_bytes())?,
+            self.write(&format!("{}.303", name), &report.to_aeat_file(user))?,
+        ])
+    }
+
+    // Deductible base of the expenses recorded in the quarter plus the depreciation
+    // of the fixed assets, in cents. Invoices of assets are depreciated, not expensed
+    pub fn deductible_expenses(&self, quarter: Quarter) -> io::Result<i64> {
+        self.deductible_expenses_in(quarter.into())
+    }
+
+    fn deductible_expenses_in(&self, range: DateRange) -> io::Result<i64> {
+        let assets = self.storage.list_assets()?;
+        let expenses: i64 = self
+            .storage
+            .list_expenses()?
+            .iter()
+            .filter(|e| range.contains(&e.date))
+            .filter(|e| !assets.iter().any(|a| a.expense_id.as_ref() == Some(&e.id)))
+            .map(|e| to_cents(e.deductible_base()))
+            .sum();
+        let depreciation: i64 = assets
+            .iter()
+            .map(|a| asset::depreciation_in(a, range))
+            .sum();
+
+        Ok(expenses + depreciation)
+    }
+
+    // Expenses are in cents, the previous year net income enables the art. 110.3.c reduction
+    pub fn modelo130(
+        &self,
+        quarter: Quarter,
+        expenses: i64,
+        previous_year_net: Option<i64>,
+    ) -> io::Result<Modelo130> {
+        let invoices = self.storage.list_invoices()?;
+        let filings = self.storage.list_modelo130_filings()?;
+        Ok(Modelo130::calculate(
+            &invoices,
+            quarter,
+            expenses,
+            &filings,
+            previous_year_net,
+        ))
+    }
+
+    // Write the JSON summary and the AEAT file, and remember the filing for later quarters
+    pub fn export_modelo130(&self, report: &Modelo130, user: &User) -> io::Result<Vec<String>> {
+        let name = format!(
+            "modelo130_{}_{}",
+            report.quarter.year,
+            report.quarter.code()
+        );
+        let json = serde_json::to_string_pretty(&report.to_json())?;
+
+        let paths = vec![
+            self.write(&format!("{}.json", name), json.as_bytes())?,
+            self.write(&format!("{}.130", name), &report.to_aeat_file(user))?,
+        ];
+        self.storage.save_modelo130_filing(&report.to_filing())?;
+
+        Ok(paths)
+    }
+
+    pub fn modelo347(&self, year: i32) -> io::Result<Modelo347> {
+        let invoices = self.storage.list_invoices()?;
+        Ok(Modelo347::from_invoices(&invoices, year))
+    }
+
+    pub fn export_modelo347(&self, report: &Modelo347, user: &User) -> io::Result<Vec<String>> {
+        let name = format!("modelo347_{}", report.year);
+        let json = serde_json::to_string_pretty(&report.to_json())?;
+
+        Ok(vec![
+            self.write(&format!("{}.json", name), json.as_bytes())?,
+            self.write(&format!("{}.347", name), &report.to_aeat_file(user))?,
+        ])
+    }
+
+    // EU operators invoiced in the quarter, as (VAT number, name)
+    pub fn modelo349_operators(&self, quarter: Quarter) -> io::Result<Vec<(String, String)>> {
+        let invoices = self.storage.list_invoices()?;
+        Ok(intra_eu_operators(&invoices, quarter))
+    }
+
+    pub fn modelo349(
+        &self,
+        quarter: Quarter,
+        keys: &BTreeMap<String, OperationKey>,
+    ) -> io::Result<Modelo349> {
+        let invoices = self.storage.list_invoices()?;
+        let filings = self.storage.list_modelo349_filings()?;
+        Ok(Modelo349::from_invoices(&invoices, quarter, keys, &filings))
+    }
+
+    // Write the JSON summary and the AEAT file, and store what has now been declared
+    pub fn export_modelo349(&self, report: &Modelo349, user: &User) -> io::Result<Vec<String>> {
+        let name = format!(
+            "modelo349_{}_{}",
+            report.quarter.year,
+            report.quarter.code()
+        );
+        let json = serde_json::to_string_pretty(&report.to_json())?;
+
+        let paths = vec![
+            self.write(&format!("{}.json", name), json.as_bytes())?,
+            self.write(&format!("{}.349", name), &report.to_aeat_file(user))?,
+        ];
+        self.storage.save_modelo349_filing(&report.to_filing())?;
+        for filing in &report.updated_filings {
+            self.storage.save_modelo349_filing(filing)?;
+        }
+
+        Ok(paths)
+    }
+
+    pub fn issued_invoices_ledger(&self, range: DateRange) -> io::Result<Vec<LedgerEntry>> {
+        let invoices = self.storage.list_invoices()?;
+        Ok(libro_registro::issued_invoices(&invoices, range))
+    }
+
+    // Write the ledger as CSV and XLSX, named after the period it covers
+    pub fn export_issued_invoices_ledger(
+        &self,
+        entries: &[LedgerEntry],
+        range: DateRange,
+    ) -> io::Result<Vec<String>> {
+        let name = format!(
+            "libro_facturas_expedidas_{}_{}",
+            range.from.format("%Y%m%d"),
+            range.to.format("%Y%m%d")
+        );
+
+        Ok(vec![
+            self.write(&format!("{}.csv", name), &libro_registro::to_csv(entries)?)?,
+            self.write(
+                &format!("{}.xlsx", name),
+                &libro_registro::to_xlsx(entries)?,
+            )?,
+        ])
+    }
+
+    // Write the Excel workbook of a year: invoices per quarter, taxes by rate and clients
+    pub fn export_xlsx(&self, year: i32) -> io::Result<String> {
+        let invoices = self.storage.list_invoices()?;
+        self.write(
+            &format!("invoices_{}.xlsx", year),
+            &spreadsheet::to_xlsx(&invoices, year)?,
+        )
+    }
+
+    // Income summary of a year compared with the previous one
+    pub fn stats(&self, year: i32) -> io::Result<Stats> {
+        let invoices = self.storage.list_invoices()?;
+        Ok(Stats::from_invoices(
+            &invoices,
+            year,
+            self.deductible_expenses_in(DateRange::year(year))?,
+            self.deductible_expenses_in(DateRange::year(year - 1))?,
+        ))
+    }
+
+    pub fn export_stats(&self, stats: &Stats) -> io::Result<String> {
+        let json = serde_json::to_string_pretty(&stats.to_json())?;
+        self.write(&format!("stats_{}.json", stats.year()), json.as_bytes())
+    }
+
+    fn write(&self, file_name: &str, contents: &[u8]) -> io::Result<String> {
+        let output_path = format!("{}/{}", self.output_dir, file_name);
+        let mut file = File::create(&output_path)?;
+        file.write_all(contents)?;
+        Ok(output_path)
+    }
+}
@@ -0,0 +1,374 @@
+// SEPA Direct Debit collections: the creditor identifier of the user and the
+// pain.008.001.02 batch that banks accept to collect invoices from clients' accounts
+
+use crate::models::{Invoice, SepaMandate, SepaScheme, User};
+use crate::repository::Storage;
+use crate::services::einvoice::local_tax_id;
+use crate::services::iban::{normalize_iban, validate_bic, validate_iban};
+use crate::services::money::{format_cents, to_cents};
+use crate::services::xml::XmlWriter;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{self, Write};
+
+pub const PAIN008_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.008.001.02";
+
+// Creditor business code, "000" unless the bank assigned another one
+const BUSINESS_CODE: &str = "000";
+
+// Unpaid invoice to collect with the mandate signed by its client
+#[derive(Debug, Clone)]
+pub struct DirectDebit {
+    pub invoice: Invoice,
+    pub mandate: SepaMandate,
+}
+
+// SEPA creditor identifier (EPC262-08): country, check digits over the national ID
+// and country with letters as numbers (A = 10 ... Z = 35), business code and NIF
+pub fn creditor_identifier(cif: &str) -> String {
+    let nif: String = local_tax_id(cif)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    let remainder = format!("{}ES00", nif).chars().fold(0, |remainder, c| {
+        let value = c.to_digit(36).unwrap_or(0);
+        let width = if value >= 10 { 100 } else { 10 };
+        (remainder * width + value) % 97
+    });
+    format!("ES{:02}{}{}", 98 - remainder, BUSINESS_CODE, nif)
+}
+
+// Sequence type of each debit: only the earliest invoice of a mandate that was never
+// collected goes out as FRST, its later invoices in the same batch are recurrent
+pub fn sequence_types(debits: &[DirectDebit]) -> Vec<&'static str> {
+    let mut order: Vec<usize> = (0..debits.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&debits[a].invoice, &debits[b].invoice);
+        (&a.date, &a.id).cmp(&(&b.date, &b.id))
+    });
+
+    let mut sequences = vec!["RCUR"; debits.len()];
+    let mut first_collections = HashSet::new();
+    for index in order {
+        let mandate = &debits[index].mandate;
+        if mandate.last_collection.is_none() && first_collections.insert(mandate.id.as_str()) {
+            sequences[index] = "FRST";
+        }
+    }
+    sequences
+}
+
+// Text restricted to the Latin character set every SEPA bank accepts
+pub fn sepa_text(text: &str, length: usize) -> String {
+    text.trim()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c,
+            '/' | '-' | '?' | ':' | '(' | ')' | '.' | ',' | '\'' | '+' | ' ' => c,
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'Á' | 'À' | 'Ä' | 'Â' => 'A',
+            'É' | 'È' | 'Ë' | 'Ê' => 'E',
+            'Í' | 'Ì' | 'Ï' | 'Î' => 'I',
+            'Ó' | 'Ò' | 'Ö' | 'Ô' => 'O',
+            'Ú' | 'Ù' | 'Ü' | 'Û' => 'U',
+            'ñ' => 'n',
+            'Ñ' => 'N',
+            'ç' => 'c',
+            'Ç' => 'C',
+            '&' => '+',
+            _ => ' ',
+        })
+        .take(length)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+// pain.008.001.02 message collecting the given invoices on the collection date,
+// grouped in one payment information block per scheme and sequence type
+pub fn to_pain008(
+    user: &User,
+    debits: &[DirectDebit],
+    collection_date: &str,
+    created: NaiveDateTime,
+) -> io::Result<String> {
+    let collection = parse_date(collection_date, "collection date")?;
+    let creditor_iban = normalize_iban(user.iban.as_deref().unwrap_or_default());
+    validate_iban(&creditor_iban).map_err(|e| {
+        invalid(format!(
+            "A valid IBAN is needed to receive direct debits: {}",
+            e
+        ))
+    })?;
+    if debits.is_empty() {
+        return Err(invalid("No invoices to collect".to_string()));
+    }
+    for debit in debits {
+        check_debit(debit, collection)?;
+    }
+
+    let mut blocks: BTreeMap<(SepaScheme, &str), Vec<&DirectDebit>> = BTreeMap::new();
+    for (debit, sequence) in debits.iter().zip(sequence_types(debits)) {
+        blocks
+            .entry((debit.mandate.scheme, sequence))
+            .or_default()
+            .push(debit);
+    }
+
+    let creditor_id = creditor_identifier(&user.cif);
+    let creditor_name = sepa_text(&user.name, 70);
+    let message_id = format!("DD-{}", created.format("%Y%m%d%H%M%S"));
+    let total: i64 = debits.iter().map(|d| to_cents(d.invoice.total)).sum();
+
+    let mut xml = XmlWriter::new();
+    xml.start("Document", &[("xmlns", PAIN008_NAMESPACE)]);
+    xml.start("CstmrDrctDbtInitn", &[]);
+
+    xml.start("GrpHdr", &[]);
+    xml.leaf("MsgId", &[], &message_id);
+    xml.leaf(
+        "CreDtTm",
+        &[],
+        &created.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    );
+    xml.leaf("NbOfTxs", &[], &debits.len().to_string());
+    xml.leaf("CtrlSum", &[], &format_cents(total));
+    xml.start("InitgPty", &[]);
+    xml.leaf("Nm", &[], &creditor_name);
+    xml.start("Id", &[]);
+    xml.start("OrgId", &[]);
+    xml.start("Othr", &[]);
+    xml.leaf("Id", &[], &creditor_id);
+    xml.end();
+    xml.end();
+    xml.end();
+    xml.end();
+    xml.end();
+
+    for ((scheme, sequence), block) in &blocks {
+        let block_total: i64 = block.iter().map(|d| to_cents(d.invoice.total)).sum();
+
+        xml.start("PmtInf", &[]);
+        xml.leaf(
+            "PmtInfId",
+            &[],
+            &format!("{}-{}-{}", message_id, scheme.code(), sequence),
+        );
+        xml.leaf("PmtMtd", &[], "DD");
+        xml.leaf("NbOfTxs", &[], &block.len().to_string());
+        xml.leaf("CtrlSum", &[], &format_cents(block_total));
+        xml.start("PmtTpInf", &[]);
+        xml.start("SvcLvl", &[]);
+        xml.leaf("Cd", &[], "SEPA");
+        xml.end();
+        xml.start("LclInstrm", &[]);
+        xml.leaf("Cd", &[], scheme.code());
+        xml.end();
+        xml.leaf("SeqTp", &[], sequence);
+        xml.end();
+        xml.leaf("ReqdColltnDt", &[], collection_date);
+        xml.start("Cdtr", &[]);
+        xml.leaf("Nm", &[], &creditor_name);
+        xml.end();
+        write_account(&mut xml, "CdtrAcct", &creditor_iban);
+        write_agent(&mut xml, "CdtrAgt", user.bic.as_deref());
+        xml.leaf("ChrgBr", &[], "SLEV");
+        xml.start("CdtrSchmeId", &[]);
+        xml.start("Id", &[]);
+        xml.start("PrvtId", &[]);
+        xml.start("Othr", &[]);
+        xml.leaf("Id", &[], &creditor_id);
+        xml.start("SchmeNm", &[]);
+        xml.leaf("Prtry", &[], "SEPA");
+        xml.end();
+        xml.end();
+        xml.end();
+        xml.end();
+        xml.end();
+
+        for debit in block {
+            write_transaction(&mut xml, debit);
+        }
+        xml.end();
+    }
+
+    Ok(xml.finish())
+}
+
+fn write_transaction(xml: &mut XmlWriter, debit: &DirectDebit) {
+    let invoice = &debit.invoice;
+    let mandate = &debit.mandate;
+
+    xml.start("DrctDbtTxInf", &[]);
+    xml.start("PmtId", &[]);
+    xml.leaf("EndToEndId", &[], &sepa_text(&invoice.id, 35));
+    xml.end();
+    xml.leaf(
+        "InstdAmt",
+        &[("Ccy", "EUR")],
+        &format_cents(to_cents(invoice.total)),
+    );
+    xml.start("DrctDbtTx", &[]);
+    xml.start("MndtRltdInf", &[]);
+    xml.leaf("MndtId", &[], &mandate.id);
+    xml.leaf("DtOfSgntr", &[], &mandate.signature_date);
+    xml.end();
+    xml.end();
+    write_agent(xml, "DbtrAgt", mandate.bic.as_deref());
+    xml.start("Dbtr", &[]);
+    xml.leaf("Nm", &[], &sepa_text(invoice.client_name(), 70));
+    xml.end();
+    write_account(xml, "DbtrAcct", &normalize_iban(&mandate.iban));
+    xml.start("RmtInf", &[]);
+    xml.leaf(
+        "Ustrd",
+        &[],
+        &sepa_text(&format!("Invoice {} of {}", invoice.id, invoice.date), 140),
+    );
+    xml.end();
+    xml.end();
+}
+
+fn write_account(xml: &mut XmlWriter, element: &str, iban: &str) {
+    xml.start(element, &[]);
+    xml.start("Id", &[]);
+    xml.leaf("IBAN", &[], iban);
+    xml.end();
+    xml.end();
+}
+
+// Banks inside the EEA are found from the IBAN when the BIC is not known
+fn write_agent(xml: &mut XmlWriter, element: &str, bic: Option<&str>) {
+    xml.start(element, &[]);
+    xml.start("FinInstnId", &[]);
+    match bic.filter(|bic| validate_bic(bic).is_ok()) {
+        Some(bic) => xml.leaf("BIC", &[], &bic.trim().to_uppercase()),
+        None => {
+            xml.start("Othr", &[]);
+            xml.leaf("Id", &[], "NOTPROVIDED");
+            xml.end();
+        }
+    }
+    xml.end();
+    xml.end();
+}
+
+fn check_debit(debit: &DirectDebit, collection: NaiveDate) -> io::Result<()> {
+    let invoice = &debit.invoice;
+    let mandate = &debit.mandate;
+
+    if invoice.paid_date.is_some() {
+        return Err(invalid(format!("Invoice {} is already paid", invoice.id)));
+    }
+    if to_cents(invoice.total) <= 0 {
+        return Err(invalid(format!(
+            "Invoice {} has nothing to collect",
+            invoice.id
+        )));
+    }
+    validate_iban(&mandate.iban).map_err(|e| {
+        invalid(format!(
+            "Mandate {} of {}: {}",
+            mandate.id,
+            invoice.client_name(),
+            e
+        ))
+    })?;
+    if parse_date(&mandate.signature_date, "mandate signature date")? > collection {
+        return Err(invalid(format!(
+            "Mandate {} of {} is signed after the collection date",
+            mandate.id,
+            invoice.client_name()
+        )));
+    }
+    Ok(())
+}
+
+fn parse_date(date: &str, name: &str) -> io::Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| invalid(format!("Invalid {} '{}'", name, date)))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub struct SepaService {
+    storage: Storage,
+    output_dir: String, // Directory where the batches for the bank are written
+}
+
+impl SepaService {
+    pub fn new(storage: Storage, output_dir: String) -> io::Result<Self> {
+        std::fs::create_dir_all(&output_dir)?;
+        Ok(SepaService {
+            storage,
+            output_dir,
+        })
+    }
+
+    // Unpaid invoices of clients that signed a mandate, oldest first
+    pub fn direct_debit_candidates(&self) -> io::Result<Vec<DirectDebit>> {
+        let mandates: BTreeMap<String, SepaMandate> = self
+            .storage
+            .list_clients()?
+            .into_iter()
+            .filter_map(|client| Some((local_tax_id(&client.cif), client.mandate?)))
+            .collect();
+
+        let mut debits: Vec<DirectDebit> = self
+            .storage
+            .list_invoices()?
+            .into_iter()
+            .filter(|invoice| invoice.paid_date.is_none() && to_cents(invoice.total) > 0)
+            .filter_map(|invoice| {
+                let mandate = mandates.get(&local_tax_id(invoice.client_cif()))?.clone();
+                Some(DirectDebit { invoice, mandate })
+            })
+            .collect();
+        debits.sort_by(|a, b| {
+            (&a.invoice.date, &a.invoice.id).cmp(&(&b.invoice.date, &b.invoice.id))
+        });
+        Ok(debits)
+    }
+
+    // Write the batch for the bank and remember the collection in the mandates,
+    // so the next batch sends them as recurrent
+    pub fn export_direct_debit(
+        &self,
+        user: &User,
+        debits: &[DirectDebit],
+        collection_date: &str,
+    ) -> io::Result<String> {
+        let created = Local::now().naive_local();
+        let xml = to_pain008(user, debits, collection_date, created)?;
+
+        let output_path = format!(
+            "{}/sepa_dd_{}.xml",
+            self.output_dir,
+            created.format("%Y%m%d%H%M%S")
+        );
+        let mut file = File::create(&output_path)?;
+        file.write_all(xml.as_bytes())?;
+
+        for mut client in self.storage.list_clients()? {
+            let cif = local_tax_id(&client.cif);
+            let Some(mandate) = client.mandate.as_mut() else {
+                continue;
+            };
+            if debits.iter().any(|debit| {
+                debit.mandate.id == mandate.id && local_tax_id(debit.invoice.client_cif()) == cif
+            }) {
+                mandate.last_collection = Some(collection_date.to_string());
+                self.storage.save_client(&client)?;
+            }
+        }
+
+        Ok(output_path)
+    }
+}
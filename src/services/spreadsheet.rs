@@ -0,0 +1,364 @@
+// Excel workbook of a year for the accountant: the invoices of each quarter on their
+// own sheet, the IVA and IRPF by rate and a summary per client. Dates are written as
+// date cells and amounts as numbers, so they can be filtered and summed in Excel
+
+use crate::models::Invoice;
+use crate::services::einvoice::local_tax_id;
+use crate::services::libro_registro::number_key;
+use crate::services::money::to_cents;
+use crate::services::period::Quarter;
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::utility::column_number_to_name;
+use rust_xlsxwriter::{
+    ExcelDateTime, Format, FormatBorder, Formula, Workbook, Worksheet, XlsxError,
+};
+use std::collections::BTreeMap;
+use std::io;
+
+pub const INVOICE_COLUMNS: &[&str] = &[
+    "Date", "Number", "Client", "NIF", "Base", "IVA %", "IVA", "IRPF %", "IRPF", "Total",
+    "Due date", "Paid",
+];
+
+pub const RATE_COLUMNS: &[&str] = &["Quarter", "Tax", "Rate %", "Invoices", "Base", "Tax amount"];
+
+pub const CLIENT_COLUMNS: &[&str] = &[
+    "Client",
+    "NIF",
+    "Invoices",
+    "Base",
+    "IVA",
+    "IRPF",
+    "Total",
+    "Paid",
+    "Outstanding",
+];
+
+// Cell formats shared by the sheets
+struct Formats {
+    header: Format,
+    date: Format,
+    amount: Format,
+    percent: Format,
+    total_label: Format,
+    total_amount: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        Formats {
+            header: Format::new()
+                .set_bold()
+                .set_border_bottom(FormatBorder::Thin),
+            date: Format::new().set_num_format("dd/mm/yyyy"),
+            amount: Format::new().set_num_format("#,##0.00 €"),
+            percent: Format::new().set_num_format("0.00"),
+            total_label: Format::new().set_bold(),
+            total_amount: Format::new().set_bold().set_num_format("#,##0.00 €"),
+        }
+    }
+}
+
+// Amounts of an invoice in cents
+struct Amounts {
+    base: i64,
+    iva: i64,
+    irpf: i64,
+    total: i64, // Base plus IVA minus the withholding, what the client pays
+}
+
+impl Amounts {
+    fn of(invoice: &Invoice) -> Self {
+        let base = to_cents(invoice.subtotal);
+        let iva = to_cents(invoice.iva_amount);
+        let irpf = to_cents(invoice.irpf_amount);
+        Amounts {
+            base,
+            iva,
+            irpf,
+            total: base + iva - irpf,
+        }
+    }
+}
+
+pub fn to_xlsx(invoices: &[Invoice], year: i32) -> io::Result<Vec<u8>> {
+    build_xlsx(invoices, year).map_err(|e| io::Error::other(e.to_string()))
+}
+
+fn build_xlsx(invoices: &[Invoice], year: i32) -> Result<Vec<u8>, XlsxError> {
+    let formats = Formats::new();
+    let mut workbook = Workbook::new();
+
+    // Invoices of the year with a valid date, by quarter and number
+    let mut by_quarter: BTreeMap<u32, Vec<(NaiveDate, &Invoice)>> = BTreeMap::new();
+    for invoice in invoices {
+        let Ok(date) = NaiveDate::parse_from_str(&invoice.date, "%Y-%m-%d") else {
+            continue;
+        };
+        if date.year() == year {
+            by_quarter
+                .entry(Quarter::of(date).quarter)
+                .or_default()
+                .push((date, invoice));
+        }
+    }
+    for invoices in by_quarter.values_mut() {
+        invoices.sort_by_key(|(date, invoice)| (*date, number_key(&invoice.id)));
+    }
+
+    for quarter in 1..=4 {
+        let quarter = Quarter { year, quarter };
+        let invoices = by_quarter
+            .get(&quarter.quarter)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(format!("{} {}", quarter.code(), year))?;
+        write_invoices(sheet, invoices, &formats)?;
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Tax by rate")?;
+    write_rates(sheet, &by_quarter, year, &formats)?;
+
+    let all: Vec<&Invoice> = by_quarter
+        .values()
+        .flatten()
+        .map(|(_, invoice)| *invoice)
+        .collect();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Clients")?;
+    write_clients(sheet, &all, &formats)?;
+
+    workbook.save_to_buffer()
+}
+
+fn write_header(
+    sheet: &mut Worksheet,
+    columns: &[&str],
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    for (col, title) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, &formats.header)?;
+        sheet.set_column_width(col as u16, 14)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+fn excel_date(date: NaiveDate) -> Result<ExcelDateTime, XlsxError> {
+    ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)
+}
+
+fn write_date(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    date: &str,
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        sheet.write_datetime_with_format(row, col, excel_date(date)?, &formats.date)?;
+    }
+    Ok(())
+}
+
+fn write_amount(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    cents: i64,
+    format: &Format,
+) -> Result<(), XlsxError> {
+    sheet.write_number_with_format(row, col, cents as f64 / 100.0, format)?;
+    Ok(())
+}
+
+// Totals row below the data: a SUM of each amount column, with its value cached for
+// viewers that do not calculate formulas
+fn write_totals(
+    sheet: &mut Worksheet,
+    rows: u32,
+    columns: &[(u16, i64)],
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    let total_row = rows + 1;
+    sheet.write_string_with_format(total_row, 0, "Total", &formats.total_label)?;
+    for (col, cents) in columns {
+        let letter = column_number_to_name(*col);
+        let formula = if rows == 0 {
+            Formula::new("=0")
+        } else {
+            Formula::new(format!("=SUM({}2:{}{})", letter, letter, rows + 1))
+        }
+        .set_result(format!("{}", *cents as f64 / 100.0));
+        sheet.write_formula_with_format(total_row, *col, formula, &formats.total_amount)?;
+    }
+    Ok(())
+}
+
+fn write_invoices(
+    sheet: &mut Worksheet,
+    invoices: &[(NaiveDate, &Invoice)],
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    write_header(sheet, INVOICE_COLUMNS, formats)?;
+    sheet.set_column_width(2, 32)?;
+
+    let mut totals = [0i64; 4];
+    for (i, (date, invoice)) in invoices.iter().enumerate() {
+        let r = i as u32 + 1;
+        let amounts = Amounts::of(invoice);
+        sheet.write_datetime_with_format(r, 0, excel_date(*date)?, &formats.date)?;
+        sheet.write_string(r, 1, &invoice.id)?;
+        sheet.write_string(r, 2, invoice.client_name())?;
+        sheet.write_string(r, 3, local_tax_id(invoice.client_cif()))?;
+        write_amount(sheet, r, 4, amounts.base, &formats.amount)?;
+        sheet.write_number_with_format(r, 5, invoice.rule.iva, &formats.percent)?;
+        write_amount(sheet, r, 6, amounts.iva, &formats.amount)?;
+        sheet.write_number_with_format(r, 7, invoice.rule.irpf, &formats.percent)?;
+        write_amount(sheet, r, 8, amounts.irpf, &formats.amount)?;
+        write_amount(sheet, r, 9, amounts.total, &formats.amount)?;
+        write_date(sheet, r, 10, &invoice.due_date, formats)?;
+        if let Some(paid_date) = &invoice.paid_date {
+            write_date(sheet, r, 11, paid_date, formats)?;
+        }
+
+        for (total, cents) in
+            totals
+                .iter_mut()
+                .zip([amounts.base, amounts.iva, amounts.irpf, amounts.total])
+        {
+            *total += cents;
+        }
+    }
+
+    let rows = invoices.len() as u32;
+    sheet.autofilter(0, 0, rows, INVOICE_COLUMNS.len() as u16 - 1)?;
+    write_totals(
+        sheet,
+        rows,
+        &[
+            (4, totals[0]),
+            (6, totals[1]),
+            (8, totals[2]),
+            (9, totals[3]),
+        ],
+        formats,
+    )
+}
+
+// Base and tax of every IVA and IRPF rate per quarter, the figures of Modelo 303 and 130
+fn write_rates(
+    sheet: &mut Worksheet,
+    by_quarter: &BTreeMap<u32, Vec<(NaiveDate, &Invoice)>>,
+    year: i32,
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    write_header(sheet, RATE_COLUMNS, formats)?;
+
+    // (quarter, tax, rate in hundredths) -> (invoices, base, tax)
+    let mut rates: BTreeMap<(u32, &str, i64), (u32, i64, i64)> = BTreeMap::new();
+    for (quarter, invoices) in by_quarter {
+        for (_, invoice) in invoices {
+            let amounts = Amounts::of(invoice);
+            let mut taxes = vec![("IVA", invoice.rule.iva, amounts.iva)];
+            if amounts.irpf != 0 {
+                taxes.push(("IRPF", invoice.rule.irpf, amounts.irpf));
+            }
+            for (tax, rate, cents) in taxes {
+                let entry = rates
+                    .entry((*quarter, tax, (rate * 100.0).round() as i64))
+                    .or_insert((0, 0, 0));
+                entry.0 += 1;
+                entry.1 += amounts.base;
+                entry.2 += cents;
+            }
+        }
+    }
+
+    let mut row = 0;
+    for ((quarter, tax, rate), (count, base, cents)) in &rates {
+        row += 1;
+        let quarter = Quarter {
+            year,
+            quarter: *quarter,
+        };
+        sheet.write_string(row, 0, quarter.to_string())?;
+        sheet.write_string(row, 1, *tax)?;
+        sheet.write_number_with_format(row, 2, *rate as f64 / 100.0, &formats.percent)?;
+        sheet.write_number(row, 3, *count)?;
+        write_amount(sheet, row, 4, *base, &formats.amount)?;
+        write_amount(sheet, row, 5, *cents, &formats.amount)?;
+    }
+    sheet.autofilter(0, 0, row, RATE_COLUMNS.len() as u16 - 1)?;
+    Ok(())
+}
+
+// Invoiced, paid and outstanding amounts of each client in the year
+fn write_clients(
+    sheet: &mut Worksheet,
+    invoices: &[&Invoice],
+    formats: &Formats,
+) -> Result<(), XlsxError> {
+    write_header(sheet, CLIENT_COLUMNS, formats)?;
+    sheet.set_column_width(0, 32)?;
+
+    // Tax ID, or name for clients without one -> (name, tax ID, invoices, amounts)
+    let mut clients: BTreeMap<String, (String, String, u32, [i64; 6])> = BTreeMap::new();
+    for invoice in invoices {
+        let tax_id = local_tax_id(invoice.client_cif());
+        let key = if tax_id.is_empty() {
+            invoice.client_name().to_string()
+        } else {
+            tax_id.clone()
+        };
+        let amounts = Amounts::of(invoice);
+        let paid = if invoice.paid_date.is_some() {
+            amounts.total
+        } else {
+            0
+        };
+
+        let client = clients
+            .entry(key)
+            .or_insert_with(|| (String::new(), tax_id, 0, [0; 6]));
+        // Invoices are sorted by date, the last one has the current name
+        client.0 = invoice.client_name().to_string();
+        client.2 += 1;
+        for (sum, cents) in client.3.iter_mut().zip([
+            amounts.base,
+            amounts.iva,
+            amounts.irpf,
+            amounts.total,
+            paid,
+            amounts.total - paid,
+        ]) {
+            *sum += cents;
+        }
+    }
+
+    let mut clients: Vec<_> = clients.into_values().collect();
+    clients.sort_by(|a, b| b.3[3].cmp(&a.3[3]).then_with(|| a.0.cmp(&b.0)));
+
+    let mut totals = [0i64; 6];
+    for (i, (name, tax_id, count, amounts)) in clients.iter().enumerate() {
+        let r = i as u32 + 1;
+        sheet.write_string(r, 0, name)?;
+        sheet.write_string(r, 1, tax_id)?;
+        sheet.write_number(r, 2, *count)?;
+        for (offset, cents) in amounts.iter().enumerate() {
+            write_amount(sheet, r, 3 + offset as u16, *cents, &formats.amount)?;
+            totals[offset] += cents;
+        }
+    }
+
+    let rows = clients.len() as u32;
+    sheet.autofilter(0, 0, rows, CLIENT_COLUMNS.len() as u16 - 1)?;
+    let columns: Vec<(u16, i64)> = totals
+        .iter()
+        .enumerate()
+        .map(|(offset, cents)| (3 + offset as u16, *cents))
+        .collect();
+    write_totals(sheet, rows, &columns, formats)
+}
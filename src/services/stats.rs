@@ -0,0 +1,244 @@
+// Income summary of a year: revenue per month, quarter and client, payment delays,
+// taxes and the comparison with the previous year. Amounts are kept in cents
+
+use crate::models::Invoice;
+use crate::services::einvoice::local_tax_id;
+use crate::services::money::{format_cents, to_cents};
+use crate::services::period::DateRange;
+use chrono::{Datelike, NaiveDate};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+pub const TOP_CLIENTS: usize = 5;
+
+pub const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// Block characters from one eighth to a full cell
+const BLOCKS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+// Totals of a single year
+#[derive(Debug, Clone, Default)]
+pub struct YearSummary {
+    pub year: i32,
+    pub months: [i64; 12], // Taxable base invoiced per month
+    pub revenue: i64,
+    pub iva: i64,
+    pub irpf: i64,
+    pub expenses: i64, // Deductible expenses and depreciation
+    pub invoice_count: usize,
+}
+
+impl YearSummary {
+    fn new(year: i32, expenses: i64) -> Self {
+        YearSummary {
+            year,
+            expenses,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, invoice: &Invoice, month: u32) {
+        let base = to_cents(invoice.subtotal);
+        self.months[month as usize - 1] += base;
+        self.revenue += base;
+        self.iva += to_cents(invoice.iva_amount);
+        self.irpf += to_cents(invoice.irpf_amount);
+        self.invoice_count += 1;
+    }
+
+    pub fn quarters(&self) -> [i64; 4] {
+        let mut quarters = [0; 4];
+        for (month, revenue) in self.months.iter().enumerate() {
+            quarters[month / 3] += revenue;
+        }
+        quarters
+    }
+
+    pub fn profit(&self) -> i64 {
+        self.revenue - self.expenses
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientRevenue {
+    pub cif: String,
+    pub name: String,
+    pub revenue: i64,
+    pub invoice_count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub current: YearSummary,
+    pub previous: YearSummary,
+    pub clients: Vec<ClientRevenue>, // Highest revenue first
+    pub paid_count: usize,
+    pub average_days_to_pay: Option<f64>,
+}
+
+impl Stats {
+    // Expenses of both years are computed by the caller, they depend on the assets register
+    pub fn from_invoices(
+        invoices: &[Invoice],
+        year: i32,
+        expenses: i64,
+        previous_expenses: i64,
+    ) -> Self {
+        let mut current = YearSummary::new(year, expenses);
+        let mut previous = YearSummary::new(year - 1, previous_expenses);
+        let mut clients: BTreeMap<String, ClientRevenue> = BTreeMap::new();
+        let mut days_to_pay = Vec::new();
+
+        let mut sorted: Vec<&Invoice> = invoices.iter().collect();
+        sorted.sort_by(|a, b| a.date.cmp(&b.date));
+
+        for invoice in sorted {
+            let Ok(date) = NaiveDate::parse_from_str(&invoice.date, "%Y-%m-%d") else {
+                continue;
+            };
+            if date.year() == year - 1 {
+                previous.add(invoice, date.month());
+                continue;
+            }
+            if date.year() != year {
+                continue;
+            }
+            current.add(invoice, date.month());
+
+            let cif = local_tax_id(invoice.client_cif());
+            let client = clients.entry(cif.clone()).or_insert_with(|| ClientRevenue {
+                cif,
+                name: String::new(),
+                revenue: 0,
+                invoice_count: 0,
+            });
+            // The most recent invoice has the current name
+            client.name = invoice.client_name().to_string();
+            client.revenue += to_cents(invoice.subtotal);
+            client.invoice_count += 1;
+
+            if let Some(paid) = invoice
+                .paid_date
+                .as_deref()
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            {
+                days_to_pay.push((paid - date).num_days());
+            }
+        }
+
+        let mut clients: Vec<ClientRevenue> = clients.into_values().collect();
+        clients.sort_by_key(|c| std::cmp::Reverse(c.revenue));
+
+        let average_days_to_pay = if days_to_pay.is_empty() {
+            None
+        } else {
+            Some(days_to_pay.iter().sum::<i64>() as f64 / days_to_pay.len() as f64)
+        };
+
+        Stats {
+            current,
+            previous,
+            clients,
+            paid_count: days_to_pay.len(),
+            average_days_to_pay,
+        }
+    }
+
+    pub fn year(&self) -> i32 {
+        self.current.year
+    }
+
+    pub fn top_clients(&self) -> &[ClientRevenue] {
+        &self.clients[..self.clients.len().min(TOP_CLIENTS)]
+    }
+
+    pub fn to_json(&self) -> Value {
+        let current_quarters = self.current.quarters();
+        let previous_quarters = self.previous.quarters();
+
+        let months: Vec<Value> = (0..12)
+            .map(|m| {
+                json!({
+                    "month": m + 1,
+                    "revenue": format_cents(self.current.months[m]),
+                    "previous_year": format_cents(self.previous.months[m]),
+                    "change_percent": change_percent(self.current.months[m], self.previous.months[m]),
+                })
+            })
+            .collect();
+        let quarters: Vec<Value> = (0..4)
+            .map(|q| {
+                json!({
+                    "quarter": format!("{}T", q + 1),
+                    "revenue": format_cents(current_quarters[q]),
+                    "previous_year": format_cents(previous_quarters[q]),
+                    "change_percent": change_percent(current_quarters[q], previous_quarters[q]),
+                })
+            })
+            .collect();
+        let clients: Vec<Value> = self
+            .clients
+            .iter()
+            .map(|client| {
+                json!({
+                    "cif": client.cif,
+                    "name": client.name,
+                    "revenue": format_cents(client.revenue),
+                    "invoices": client.invoice_count,
+                })
+            })
+            .collect();
+
+        json!({
+            "year": self.year(),
+            "period": DateRange::year(self.year()).to_string(),
+            "invoices": self.current.invoice_count,
+            "revenue": format_cents(self.current.revenue),
+            "expenses": format_cents(self.current.expenses),
+            "profit": format_cents(self.current.profit()),
+            "iva_collected": format_cents(self.current.iva),
+            "irpf_withheld": format_cents(self.current.irpf),
+            "paid_invoices": self.paid_count,
+            "average_days_to_pay": self.average_days_to_pay.map(|d| (d * 10.0).round() / 10.0),
+            "months": months,
+            "quarters": quarters,
+            "clients": clients,
+            "top_clients": clients.iter().take(TOP_CLIENTS).collect::<Vec<_>>(),
+            "previous_year": {
+                "year": self.previous.year,
+                "invoices": self.previous.invoice_count,
+                "revenue": format_cents(self.previous.revenue),
+                "expenses": format_cents(self.previous.expenses),
+                "profit": format_cents(self.previous.profit()),
+                "iva_collected": format_cents(self.previous.iva),
+                "irpf_withheld": format_cents(self.previous.irpf),
+                "revenue_change_percent": change_percent(self.current.revenue, self.previous.revenue),
+            },
+        })
+    }
+}
+
+// Year-over-year change, undefined when nothing was invoiced the previous year
+pub fn change_percent(current: i64, previous: i64) -> Option<f64> {
+    if previous == 0 {
+        return None;
+    }
+    let change = (current - previous) as f64 * 100.0 / previous.abs() as f64;
+    Some((change * 10.0).round() / 10.0)
+}
+
+// Horizontal bar of `width` cells scaled to `max`, with eighth-cell resolution
+pub fn bar(value: i64, max: i64, width: usize) -> String {
+    if value <= 0 || max <= 0 {
+        return String::new();
+    }
+    let eighths = (value.min(max) as f64 / max as f64 * (width * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    let remainder = eighths % 8;
+    if remainder > 0 {
+        bar.push(BLOCKS[remainder - 1]);
+    }
+    bar
+}
@@ -0,0 +1,113 @@
+// Spanish tax identification numbers: NIF of people (DNI), NIE of foreigners, CIF of
+// organisations and the special NIFs K, L and M, all 9 characters with a control character
+
+use crate::errors::ValidationError;
+use crate::services::einvoice::local_tax_id;
+
+// Control letter of a DNI or NIE number, indexed by the number modulo 23
+const NIF_LETTERS: &[u8; 23] = b"TRWAGMYFPDXBNJZSQVHLCKE";
+
+// Control letter of a CIF, indexed by its control digit
+const CIF_LETTERS: &[u8; 10] = b"JABCDEFGHI";
+
+// Organisation types: companies, cooperatives, public bodies...
+const ORGANISATION_LETTERS: &str = "ABCDEFGHJNPQRSUVW";
+
+// Check a NIF, NIE or CIF, with or without the "ES" VAT prefix
+pub fn validate_spanish_tax_id(tax_id: &str) -> Result<(), ValidationError> {
+    let id = local_tax_id(tax_id);
+    if id.is_empty() {
+        return Err(ValidationError::RequiredFieldEmpty {
+            field: "CIF/NIE".to_string(),
+        });
+    }
+
+    let chars: Vec<char> = id.chars().collect();
+    if chars.len() != 9 {
+        return Err(ValidationError::InvalidTaxIdLength {
+            length: chars.len(),
+            tax_id: id,
+        });
+    }
+
+    let digits = |from: usize, to: usize| chars[from..to].iter().all(char::is_ascii_digit);
+    let number = |from: usize, to: usize| {
+        chars[from..to]
+            .iter()
+            .filter_map(|c| c.to_digit(10))
+            .fold(0, |n, d| n * 10 + d)
+    };
+    let control = chars[8];
+
+    match chars[0] {
+        '0'..='9' if digits(0, 8) => {
+            let expected = nif_letter(number(0, 8));
+            if control != expected {
+                return Err(ValidationError::InvalidNifLetter { nif: id, expected });
+            }
+        }
+        'X' | 'Y' | 'Z' if digits(1, 8) => {
+            // The prefix stands for the leading digit 0, 1 or 2
+            let prefix = chars[0] as u32 - 'X' as u32;
+            let expected = nif_letter(prefix * 10_000_000 + number(1, 8));
+            if control != expected {
+                return Err(ValidationError::InvalidNieLetter { nie: id, expected });
+            }
+        }
+        'K' | 'L' | 'M' if digits(1, 8) => {
+            let expected = nif_letter(number(1, 8));
+            if control != expected {
+                return Err(ValidationError::InvalidNifLetter { nif: id, expected });
+            }
+        }
+        letter if letter.is_ascii_uppercase() && digits(1, 8) => {
+            if !ORGANISATION_LETTERS.contains(letter) {
+                return Err(ValidationError::InvalidCifOrganisation { cif: id, letter });
+            }
+            let digit = cif_control_digit(&chars[1..8]);
+            let as_digit = char::from_digit(digit, 10).unwrap();
+            let as_letter = CIF_LETTERS[digit as usize] as char;
+
+            // Public and non-profit bodies and non-residents use a letter, commercial
+            // companies a digit, the rest either of them
+            let expected: Vec<char> = match letter {
+                'P' | 'Q' | 'R' | 'S' | 'W' | 'N' => vec![as_letter],
+                'A' | 'B' | 'E' | 'H' => vec![as_digit],
+                _ => vec![as_digit, as_letter],
+            };
+            if !expected.contains(&control) {
+                let expected = expected
+                    .iter()
+                    .map(char::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+                return Err(ValidationError::InvalidCifControl { cif: id, expected });
+            }
+        }
+        _ => return Err(ValidationError::InvalidCif { cif: id }),
+    }
+
+    Ok(())
+}
+
+fn nif_letter(number: u32) -> char {
+    NIF_LETTERS[(number % 23) as usize] as char
+}
+
+// Digits in odd positions are doubled and their digits added, even ones are added as is
+fn cif_control_digit(digits: &[char]) -> u32 {
+    let sum: u32 = digits
+        .iter()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| {
+            if i % 2 == 0 {
+                let doubled = d * 2;
+                doubled / 10 + doubled % 10
+            } else {
+                d
+            }
+        })
+        .sum();
+    (10 - sum % 10) % 10
+}
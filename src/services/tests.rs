@@ -0,0 +1,3851 @@
+// Builders shared by the test modules below
+#[cfg(test)]
+mod fixtures {
+    use crate::models::{Client, Invoice, Item, Rule, User};
+    use crate::repository::Storage;
+    use crate::services::{InvoiceService, PdfService, UblService};
+
+    pub fn user() -> User {
+        User::new(
+            "John Doe".to_string(),
+            "Calle Mayor 1, Madrid".to_string(),
+            "12345678Z".to_string(),
+            None,
+            None,
+        )
+    }
+
+    pub fn client(name: &str, cif: &str) -> Client {
+        client_at(name, cif, "Madrid")
+    }
+
+    pub fn client_at(name: &str, cif: &str, address: &str) -> Client {
+        Client::new(name.to_string(), cif.to_string(), address.to_string(), None)
+    }
+
+    // One line invoice due on its issue date, to a client named after the tax ID
+    pub fn invoice(id: &str, date: &str, cif: &str, rule: Rule, price: f32) -> Invoice {
+        invoice_for(
+            id,
+            date,
+            client(&format!("Client {}", cif), cif),
+            rule,
+            price,
+        )
+    }
+
+    pub fn invoice_for(id: &str, date: &str, client: Client, rule: Rule, price: f32) -> Invoice {
+        Invoice::new(
+            id.to_string(),
+            date.to_string(),
+            date.to_string(),
+            user(),
+            client,
+            rule,
+            vec![Item::new("Work".to_string(), 1, price)],
+        )
+    }
+
+    // Invoice with everything the e-invoice formats carry: contact, IBAN, several lines
+    // and text that needs escaping
+    pub fn einvoice(iva: f32, irpf: f32) -> Invoice {
+        Invoice::new(
+            "INV-001".to_string(),
+            "2024-01-15".to_string(),
+            "2024-02-14".to_string(),
+            User::new(
+                "John Doe".to_string(),
+                "123 Main St, Madrid".to_string(),
+                "12345678Z".to_string(),
+                Some("john@example.com".to_string()),
+                Some("ES9121000418450200051332".to_string()),
+            ),
+            client_at("Acme Corp", "B12345674", "789 Business Blvd, Barcelona"),
+            Rule::new(iva, irpf),
+            vec![
+                Item::new("Web Development".to_string(), 3, 333.33),
+                Item::new("Hosting & <support>".to_string(), 1, 49.99),
+            ],
+        )
+    }
+
+    // Invoice service keeping PDFs and exports next to the data
+    pub fn invoice_service(path: &str) -> InvoiceService {
+        InvoiceService::new(
+            Storage::new(path).unwrap(),
+            PdfService::new(format!("{}/pdfs", path), false, "EUR".to_string()).unwrap(),
+            UblService::new(format!("{}/exports", path)).unwrap(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod ubl_tests {
+    use super::fixtures::einvoice;
+    use crate::services::einvoice::vat_identifier;
+    use crate::services::ubl::{to_ubl_xml, validate_peppol, UblService};
+
+    #[test]
+    fn test_ubl_export_passes_peppol_rules() {
+        let xml = to_ubl_xml(&einvoice(21.0, 15.0));
+        let violations = validate_peppol(&xml).unwrap();
+
+        assert!(violations.is_empty(), "{:?}", violations);
+        assert!(xml.contains("<cbc:CompanyID>ES12345678Z</cbc:CompanyID>"));
+        assert!(xml.contains("<cbc:ID>ES9121000418450200051332</cbc:ID>"));
+        assert!(xml.contains("<cbc:PaymentMeansCode>58</cbc:PaymentMeansCode>"));
+        assert!(xml.contains("Hosting &amp; &lt;support&gt;"));
+        // 999.99 + 49.99 = 1049.98, IVA 220.50, IRPF 157.50
+        assert!(xml.contains("<cbc:TaxAmount currencyID=\"EUR\">220.50</cbc:TaxAmount>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"EUR\">1112.98</cbc:PayableAmount>"));
+    }
+
+    #[test]
+    fn test_ubl_export_exempt_invoice() {
+        let mut invoice = einvoice(0.0, 0.0);
+        invoice.user.iban = None;
+        let xml = to_ubl_xml(&invoice);
+
+        assert!(validate_peppol(&xml).unwrap().is_empty());
+        assert!(xml.contains("<cbc:TaxExemptionReason>"));
+        assert!(!xml.contains("PaymentMeans"));
+        assert!(!xml.contains("PrepaidAmount"));
+    }
+
+    #[test]
+    fn test_peppol_rules_detect_broken_documents() {
+        let xml = to_ubl_xml(&einvoice(21.0, 15.0));
+
+        let wrong_total = xml.replace(">1112.98<", ">1000.00<");
+        let rules: Vec<&str> = validate_peppol(&wrong_total)
+            .unwrap()
+            .iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(rules, vec!["BR-CO-16"]);
+
+        let no_buyer_reference =
+            xml.replace("<cbc:BuyerReference>INV-001</cbc:BuyerReference>", "");
+        let rules: Vec<&str> = validate_peppol(&no_buyer_reference)
+            .unwrap()
+            .iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(rules, vec!["PEPPOL-EN16931-R003"]);
+
+        let wrong_tax = xml.replace(">220.50<", ">200.00<");
+        let rules: Vec<&str> = validate_peppol(&wrong_tax)
+            .unwrap()
+            .iter()
+            .map(|v| v.rule)
+            .collect();
+        assert!(rules.contains(&"BR-CO-17"));
+        assert!(rules.contains(&"BR-CO-15"));
+
+        let start = xml.find("<cac:TaxTotal>").unwrap();
+        let end = xml.find("</cac:TaxTotal>").unwrap() + "</cac:TaxTotal>".len();
+        let two_tax_totals = xml.replace(&xml[start..end], &xml[start..end].repeat(2));
+        let rules: Vec<&str> = validate_peppol(&two_tax_totals)
+            .unwrap()
+            .iter()
+            .map(|v| v.rule)
+            .collect();
+        assert!(rules.contains(&"PEPPOL-EN16931-R053"));
+
+        let zero_rated = xml.replace("<cbc:ID>S</cbc:ID>", "<cbc:ID>Z</cbc:ID>");
+        let rules: Vec<&str> = validate_peppol(&zero_rated)
+            .unwrap()
+            .iter()
+            .map(|v| v.rule)
+            .collect();
+        assert!(rules.contains(&"BR-Z-05"));
+        assert!(!rules.contains(&"BR-E-05"));
+
+        assert!(validate_peppol("<Invoice>").is_err());
+    }
+
+    #[test]
+    fn test_ubl_endpoint_scheme_follows_vat_country() {
+        let mut invoice = einvoice(21.0, 0.0);
+        invoice.client.as_mut().unwrap().cif = "DE123456789".to_string();
+        let xml = to_ubl_xml(&invoice);
+
+        assert!(xml.contains("<cbc:EndpointID schemeID=\"9920\">ES12345678Z</cbc:EndpointID>"));
+        assert!(xml.contains("<cbc:EndpointID schemeID=\"9930\">DE123456789</cbc:EndpointID>"));
+        assert!(validate_peppol(&xml).unwrap().is_empty());
+
+        // Sweden has no VAT scheme in Peppol, the export is refused instead of guessing
+        invoice.client.as_mut().unwrap().cif = "SE556677889901".to_string();
+        let rules: Vec<&str> = validate_peppol(&to_ubl_xml(&invoice))
+            .unwrap()
+            .iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(rules, vec!["PEPPOL-EN16931-R010"]);
+    }
+
+    #[test]
+    fn test_vat_identifier() {
+        assert_eq!(vat_identifier("B12345674"), "ESB12345674");
+        assert_eq!(vat_identifier("x1234567l"), "ESX1234567L");
+        assert_eq!(vat_identifier("FR12345678901"), "FR12345678901");
+    }
+
+    #[test]
+    fn test_ubl_service_writes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = UblService::new(dir.path().to_str().unwrap().to_string()).unwrap();
+        let path = service.export_invoice(&einvoice(21.0, 15.0)).unwrap();
+
+        assert!(path.ends_with("invoice_INV-001.ubl.xml"));
+        assert!(std::fs::read_to_string(path).unwrap().starts_with("<?xml"));
+    }
+}
+
+#[cfg(test)]
+mod facturx_tests {
+    use super::fixtures::invoice_for;
+    use crate::models::{Client, Invoice, Rule};
+    use crate::services::cii::to_cii_xml;
+    use crate::services::facturx::{embed_factur_x, DocumentProperties};
+    use crate::services::PdfService;
+    use printpdf::lopdf::{Dictionary, Document, Object};
+
+    // Invoice to a German business with an accented author for the XMP metadata
+    fn sample_invoice() -> Invoice {
+        let client = Client::new(
+            "Acme GmbH".to_string(),
+            "DE123456789".to_string(),
+            "Hauptstraße 5, Berlin".to_string(),
+            Some("billing@acme.de".to_string()),
+        );
+        let mut invoice = invoice_for("FX-7", "2024-03-01", client, Rule::new(21.0, 15.0), 500.0);
+        invoice.user.name = "José Pérez".to_string();
+        invoice.user.iban = Some("ES9121000418450200051332".to_string());
+        invoice
+    }
+
+    #[test]
+    fn test_cii_contains_en16931_data() {
+        let xml = to_cii_xml(&sample_invoice());
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        let text = |name: &str| {
+            doc.descendants()
+                .find(|n| n.tag_name().name() == name)
+                .and_then(|n| n.text())
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        assert_eq!(text("ID"), "urn:cen.eu:en16931:2017");
+        assert_eq!(text("DateTimeString"), "20240301");
+        assert_eq!(text("IBANID"), "ES9121000418450200051332");
+        assert_eq!(text("TaxBasisTotalAmount"), "500.00");
+        assert_eq!(text("TaxTotalAmount"), "105.00");
+        assert_eq!(text("GrandTotalAmount"), "605.00");
+        assert_eq!(text("TotalPrepaidAmount"), "75.00");
+        assert_eq!(text("DuePayableAmount"), "530.00");
+        assert!(xml.contains("<ram:ID schemeID=\"VA\">DE123456789</ram:ID>"));
+        assert!(xml.contains("<ram:CountryID>DE</ram:CountryID>"));
+    }
+
+    #[test]
+    fn test_invoice_numbers_with_slashes_get_a_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = PdfService::new(
+            dir.path().to_str().unwrap().to_string(),
+            false,
+            "EUR".to_string(),
+        )
+        .unwrap();
+        let mut invoice = sample_invoice();
+        invoice.id = "2024/001".to_string();
+
+        let path = service.generate_invoice_pdf(&invoice).unwrap();
+        assert!(path.ends_with("invoice_2024_001.pdf"));
+        assert!(std::path::Path::new(&path).is_file());
+    }
+
+    #[test]
+    fn test_factur_x_pdf_embeds_xml_as_associated_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = PdfService::new(
+            dir.path().to_str().unwrap().to_string(),
+            true,
+            "EUR".to_string(),
+        )
+        .unwrap();
+        let invoice = sample_invoice();
+        let path = service.generate_invoice_pdf(&invoice).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.starts_with(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n"));
+        assert_xref_offsets(&bytes);
+
+        let doc = Document::load_mem(&bytes).unwrap();
+        let catalog = doc.catalog().unwrap();
+
+        // Associated file pointing at the embedded CII
+        let af = catalog.get(b"AF").unwrap().as_array().unwrap();
+        let filespec = doc.get_dictionary(af[0].as_reference().unwrap()).unwrap();
+        assert_eq!(
+            filespec.get(b"UF").unwrap().as_str().unwrap(),
+            b"factur-x.xml"
+        );
+        assert_eq!(
+            filespec.get(b"AFRelationship").unwrap().as_name().unwrap(),
+            b"Alternative"
+        );
+        let file_ref = filespec
+            .get(b"EF")
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .get(b"F")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let Object::Stream(stream) = doc.get_object(file_ref).unwrap() else {
+            panic!("embedded file is not a stream");
+        };
+        let content = stream
+            .decompressed_content()
+            .unwrap_or(stream.content.clone());
+        assert_eq!(String::from_utf8(content).unwrap(), to_cii_xml(&invoice));
+
+        // PDF/A identification and Factur-X extension schema in the XMP
+        let metadata_ref = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let Object::Stream(metadata) = doc.get_object(metadata_ref).unwrap() else {
+            panic!("metadata is not a stream");
+        };
+        let xmp = String::from_utf8(metadata.content.clone()).unwrap();
+        assert!(xmp.contains("<pdfaid:part>3</pdfaid:part>"));
+        assert!(xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+        assert!(xmp.contains("<fx:ConformanceLevel>EN 16931</fx:ConformanceLevel>"));
+        assert!(xmp.contains("José Pérez"));
+
+        // sRGB output intent for PDF/A
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        let intent = intents[0].as_dict().unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(), b"GTS_PDFA1");
+
+        // Every font is embedded, no standard 14 fonts left
+        for object in doc.objects.values() {
+            if let Ok(dict) = object.as_dict() {
+                if dict.get(b"Type").and_then(|t| t.as_name()).ok() == Some(b"Font") {
+                    assert_eq!(dict.get(b"Subtype").unwrap().as_name().unwrap(), b"Type0");
+                }
+            }
+        }
+    }
+
+    // Every in-use entry of the cross-reference table points at its object
+    fn assert_xref_offsets(bytes: &[u8]) {
+        let startxref = bytes.windows(9).rposition(|w| w == b"startxref").unwrap();
+        let tail = std::str::from_utf8(&bytes[startxref + 9..]).unwrap();
+        let start: usize = tail.split_whitespace().next().unwrap().parse().unwrap();
+        assert!(bytes[start..].starts_with(b"xref"));
+
+        let table = std::str::from_utf8(&bytes[start..startxref]).unwrap();
+        let mut id = 0;
+        for line in table.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [first, _] => id = first.parse().unwrap(),
+                [offset, _, kind] => {
+                    if *kind == "n" {
+                        let offset: usize = offset.parse().unwrap();
+                        let object = format!("{} 0 obj", id);
+                        assert!(bytes[offset..].starts_with(object.as_bytes()), "{}", object);
+                    }
+                    id += 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    #[test]
+    fn test_factur_x_keeps_existing_name_trees() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = PdfService::new(
+            dir.path().to_str().unwrap().to_string(),
+            false,
+            "EUR".to_string(),
+        )
+        .unwrap();
+        let path = service.generate_invoice_pdf(&sample_invoice()).unwrap();
+
+        // A PDF that already has named destinations and an attachment
+        let mut doc = Document::load(&path).unwrap();
+        let note_id = doc.add_object(Dictionary::from_iter(vec![(
+            "Type",
+            Object::Name(b"Filespec".to_vec()),
+        )]));
+        let mut embedded = Dictionary::new();
+        embedded.set(
+            "Names",
+            vec![
+                Object::string_literal("notes.txt"),
+                Object::Reference(note_id),
+            ],
+        );
+        let mut names = Dictionary::new();
+        names.set("Dests", Dictionary::new());
+        names.set("EmbeddedFiles", embedded);
+        doc.catalog_mut().unwrap().set("Names", names);
+        let mut pdf = Vec::new();
+        doc.save_to(&mut pdf).unwrap();
+
+        let properties = DocumentProperties {
+            title: "Invoice",
+            author: "José Pérez",
+        };
+        let bytes = embed_factur_x(&pdf, &to_cii_xml(&sample_invoice()), &properties).unwrap();
+        assert_xref_offsets(&bytes);
+
+        let doc = Document::load_mem(&bytes).unwrap();
+        let names = doc
+            .catalog()
+            .unwrap()
+            .get(b"Names")
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        assert!(names.get(b"Dests").is_ok());
+        let files = names
+            .get(b"EmbeddedFiles")
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .get(b"Names")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        let keys: Vec<&[u8]> = files
+            .iter()
+            .step_by(2)
+            .map(|key| key.as_str().unwrap())
+            .collect();
+        assert_eq!(keys, vec![&b"factur-x.xml"[..], &b"notes.txt"[..]]);
+    }
+
+    #[test]
+    fn test_plain_pdf_has_no_attachment() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = PdfService::new(
+            dir.path().to_str().unwrap().to_string(),
+            false,
+            "EUR".to_string(),
+        )
+        .unwrap();
+        let path = service.generate_invoice_pdf(&sample_invoice()).unwrap();
+
+        let doc = Document::load(&path).unwrap();
+        assert!(doc.catalog().unwrap().get(b"AF").is_err());
+    }
+}
+
+#[cfg(test)]
+mod einvoice_import_tests {
+    use super::fixtures::einvoice;
+    use crate::models::ExpenseCategory;
+    use crate::repository::Storage;
+    use crate::services::cii::to_cii_xml;
+    use crate::services::einvoice_import::{
+        invoice_direction, parse_einvoice, EInvoiceFormat, InvoiceDirection,
+    };
+    use crate::services::ubl::to_ubl_xml;
+    use crate::services::ExpenseService;
+
+    fn assert_round_trip(xml: &str, format: EInvoiceFormat) {
+        let original = einvoice(21.0, 15.0);
+        let imported = parse_einvoice(xml).unwrap();
+
+        assert_eq!(imported.len(), 1);
+        let entry = &imported[0];
+        assert_eq!(entry.format, format);
+        assert!(entry.losses.is_empty(), "{:?}", entry.losses);
+        assert_eq!(entry.invoice.id, original.id);
+        assert_eq!(entry.invoice.date, original.date);
+        assert_eq!(entry.invoice.due_date, original.due_date);
+        assert_eq!(entry.invoice.user.cif, "12345678Z");
+        assert_eq!(entry.invoice.client_cif(), "B12345674");
+        assert_eq!(entry.invoice.user.iban, original.user.iban);
+        assert_eq!(entry.invoice.rule.iva, 21.0);
+        assert_eq!(entry.invoice.rule.irpf, 15.0);
+        assert_eq!(entry.invoice.items.len(), 2);
+        assert_eq!(entry.invoice.items[1].description, "Hosting & <support>");
+        assert!((entry.invoice.total - original.total).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_import_ubl_round_trip() {
+        assert_round_trip(&to_ubl_xml(&einvoice(21.0, 15.0)), EInvoiceFormat::Ubl);
+    }
+
+    #[test]
+    fn test_import_cii_round_trip() {
+        assert_round_trip(&to_cii_xml(&einvoice(21.0, 15.0)), EInvoiceFormat::Cii);
+    }
+
+    #[test]
+    fn test_import_facturae_batch() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<fe:Facturae xmlns:fe="http://www.facturae.gob.es/formato/Versiones/Facturaev3_2_2.xml">
+  <Parties>
+    <SellerParty>
+      <TaxIdentification><TaxIdentificationNumber>ES12345678Z</TaxIdentificationNumber></TaxIdentification>
+      <Individual>
+        <Name>John</Name><FirstSurname>Doe</FirstSurname>
+        <AddressInSpain><Address>123 Main St</Address><PostCode>28001</PostCode><Town>Madrid</Town><Province>Madrid</Province></AddressInSpain>
+      </Individual>
+    </SellerParty>
+    <BuyerParty>
+      <TaxIdentification><TaxIdentificationNumber>B12345674</TaxIdentificationNumber></TaxIdentification>
+      <LegalEntity><CorporateName>Acme Corp</CorporateName></LegalEntity>
+    </BuyerParty>
+  </Parties>
+  <Invoices>
+    <Invoice>
+      <InvoiceHeader><InvoiceNumber>7</InvoiceNumber><InvoiceSeriesCode>A</InvoiceSeriesCode><InvoiceClass>OO</InvoiceClass></InvoiceHeader>
+      <InvoiceIssueData><IssueDate>2024-03-01</IssueDate><InvoiceCurrencyCode>EUR</InvoiceCurrencyCode></InvoiceIssueData>
+      <TaxesOutputs><Tax><TaxTypeCode>01</TaxTypeCode><TaxRate>21.00</TaxRate><TaxableBase><TotalAmount>1000.00</TotalAmount></TaxableBase></Tax></TaxesOutputs>
+      <TaxesWithheld><Tax><TaxTypeCode>04</TaxTypeCode><TaxRate>15.00</TaxRate></Tax></TaxesWithheld>
+      <InvoiceTotals><InvoiceTotal>1060.00</InvoiceTotal></InvoiceTotals>
+      <Items>
+        <InvoiceLine><ItemDescription>Consulting</ItemDescription><Quantity>10</Quantity><UnitPriceWithoutTax>100.00</UnitPriceWithoutTax><GrossAmount>1000.00</GrossAmount></InvoiceLine>
+      </Items>
+      <PaymentDetails><Installment><InstallmentDueDate>2024-03-31</InstallmentDueDate></Installment></PaymentDetails>
+    </Invoice>
+    <Invoice>
+      <InvoiceHeader><InvoiceNumber>8</InvoiceNumber><InvoiceSeriesCode>A</InvoiceSeriesCode></InvoiceHeader>
+      <InvoiceIssueData><IssueDate>2024-03-02</IssueDate></InvoiceIssueData>
+      <TaxesOutputs><Tax><TaxTypeCode>01</TaxTypeCode><TaxRate>21.00</TaxRate><TaxableBase><TotalAmount>2.50</TotalAmount></TaxableBase></Tax></TaxesOutputs>
+      <Items>
+        <InvoiceLine><ItemDescription>Support hours</ItemDescription><Quantity>0.5</Quantity><UnitPriceWithoutTax>5.00</UnitPriceWithoutTax><GrossAmount>2.50</GrossAmount></InvoiceLine>
+      </Items>
+    </Invoice>
+  </Invoices>
+</fe:Facturae>"#;
+
+        let imported = parse_einvoice(xml).unwrap();
+        assert_eq!(imported.len(), 2);
+
+        let first = &imported[0];
+        assert_eq!(first.format, EInvoiceFormat::Facturae);
+        assert!(first.losses.is_empty(), "{:?}", first.losses);
+        assert_eq!(first.invoice.id, "A7");
+        assert_eq!(first.invoice.user.name, "John Doe");
+        assert_eq!(first.invoice.user.cif, "12345678Z");
+        assert_eq!(
+            first.invoice.user.address,
+            "123 Main St, 28001, Madrid, Madrid"
+        );
+        assert_eq!(first.invoice.client_name(), "Acme Corp");
+        assert_eq!(first.invoice.due_date, "2024-03-31");
+        assert_eq!(first.invoice.rule.irpf, 15.0);
+        assert!((first.invoice.total - 1060.0).abs() < 0.01);
+
+        // Fractional quantity and missing due date are reported, not dropped
+        let second = &imported[1];
+        assert_eq!(second.invoice.due_date, "2024-04-01");
+        assert_eq!(second.invoice.items[0].quantity, 1);
+        assert!((second.invoice.subtotal - 2.5).abs() < 0.01);
+        assert_eq!(second.losses.len(), 2, "{:?}", second.losses);
+    }
+
+    #[test]
+    fn test_import_reports_multiple_rates_and_rejects_unknown_documents() {
+        let xml = to_ubl_xml(&einvoice(21.0, 15.0)).replace(
+            "</cac:TaxTotal>",
+            "<cac:TaxSubtotal><cbc:TaxableAmount currencyID=\"EUR\">10.00</cbc:TaxableAmount>\
+             <cac:TaxCategory><cbc:Percent>10.00</cbc:Percent></cac:TaxCategory></cac:TaxSubtotal></cac:TaxTotal>",
+        );
+        let imported = parse_einvoice(&xml).unwrap();
+        assert_eq!(imported[0].invoice.rule.iva, 21.0);
+        assert!(imported[0]
+            .losses
+            .iter()
+            .any(|loss| loss.contains("Several IVA rates")));
+
+        assert!(parse_einvoice("<Order/>").is_err());
+        assert!(parse_einvoice("not xml").is_err());
+    }
+
+    #[test]
+    fn test_received_invoices_are_recorded_as_expenses() {
+        // A supplier invoice: the user of this install is the buyer
+        let own_tax_id = "B12345674";
+        let mut received = einvoice(21.0, 15.0);
+        received.rule.irpf = 0.0;
+        let xml = to_ubl_xml(&received);
+        let invoice = parse_einvoice(&xml).unwrap().remove(0).invoice;
+
+        assert_eq!(
+            invoice_direction(&invoice, own_tax_id).unwrap(),
+            InvoiceDirection::Received
+        );
+        assert_eq!(
+            invoice_direction(&invoice, "ES12345678Z").unwrap(),
+            InvoiceDirection::Issued
+        );
+        assert!(invoice_direction(&invoice, "A28015865").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().to_str().unwrap()).unwrap();
+        let service = ExpenseService::new(storage.clone());
+        assert!(service
+            .save_imported_expense(&invoice, ExpenseCategory::ProfessionalServices)
+            .unwrap());
+        assert!(!service
+            .save_imported_expense(&invoice, ExpenseCategory::ProfessionalServices)
+            .unwrap());
+
+        // Nothing lands on the issued side
+        assert!(storage.list_invoices().unwrap().is_empty());
+        let expenses = service.list_expenses().unwrap();
+        assert_eq!(expenses.len(), 1);
+        assert_eq!(expenses[0].number, "INV-001");
+        assert_eq!(expenses[0].supplier.cif, "12345678Z");
+        assert!((expenses[0].base - invoice.subtotal).abs() < 0.01);
+        assert_eq!(service.list_suppliers().unwrap().len(), 1);
+
+        // The expense model has no withholding, those are left to be entered by hand
+        let with_irpf = parse_einvoice(&to_ubl_xml(&einvoice(21.0, 15.0)))
+            .unwrap()
+            .remove(0)
+            .invoice;
+        assert!(service
+            .save_imported_expense(&with_irpf, ExpenseCategory::Other)
+            .is_err());
+    }
+
+    #[test]
+    fn test_imported_numbers_with_slashes_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().join("data").to_str().unwrap()).unwrap();
+
+        let mut invoice = einvoice(21.0, 15.0);
+        invoice.id = "2024/001".to_string();
+        let xml = to_ubl_xml(&invoice);
+        let imported = parse_einvoice(&xml).unwrap().remove(0).invoice;
+        storage.save_invoice(&imported).unwrap();
+
+        let stored = storage.get_invoice("2024/001").unwrap().unwrap();
+        assert_eq!(stored.id, "2024/001");
+        assert!(dir.path().join("data/invoices/2024_001.json").exists());
+
+        // Another number mapped to the same file is refused, not overwritten
+        invoice.id = "2024_001".to_string();
+        assert!(storage.get_invoice("2024_001").unwrap().is_none());
+        let error = storage.save_invoice(&invoice).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+
+        // Crafted numbers stay inside the data directory
+        invoice.id = "../../escaped".to_string();
+        storage.save_invoice(&invoice).unwrap();
+        assert!(dir.path().join("data/invoices/______escaped.json").exists());
+        assert!(!dir.path().join("escaped.json").exists());
+    }
+}
+
+#[cfg(test)]
+mod modelo303_tests {
+    use super::fixtures::{invoice, user};
+    use crate::models::Rule;
+    use crate::services::aeat::amount;
+    use crate::services::modelo303::Modelo303;
+    use crate::services::period::Quarter;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_quarter_boundaries() {
+        let q1 = Quarter::new(2024, 1).unwrap();
+        assert!(q1.contains("2024-01-01"));
+        assert!(q1.contains("2024-03-31"));
+        assert!(!q1.contains("2024-04-01"));
+        assert!(!q1.contains("2023-12-31"));
+        assert_eq!(
+            Quarter::new(2024, 4).unwrap().last_day().to_string(),
+            "2024-12-31"
+        );
+        assert!(Quarter::new(2024, 5).is_err());
+
+        let january = NaiveDate::from_ymd_opt(2025, 1, 20).unwrap();
+        assert_eq!(Quarter::previous(january), Quarter::new(2024, 4).unwrap());
+    }
+
+    #[test]
+    fn test_modelo303_aggregates_by_rate() {
+        let invoices = vec![
+            invoice(
+                "1",
+                "2024-04-02",
+                "B12345674",
+                Rule::new(21.0, 15.0),
+                1000.0,
+            ),
+            invoice("2", "2024-05-10", "B12345674", Rule::new(21.0, 15.0), 500.4),
+            invoice("3", "2024-06-30", "B12345674", Rule::new(10.0, 15.0), 200.0),
+            invoice(
+                "4",
+                "2024-06-01",
+                "DE123456789",
+                Rule::new(0.0, 15.0),
+                300.0,
+            ),
+            invoice("5", "2024-06-01", "US123456", Rule::new(0.0, 15.0), 50.0),
+            invoice("6", "2024-06-01", "B12345674", Rule::new(0.0, 15.0), 80.0),
+            invoice("7", "2024-07-01", "B12345674", Rule::new(21.0, 15.0), 999.0),
+        ];
+        let report = Modelo303::from_invoices(&invoices, &[], Quarter::new(2024, 2).unwrap());
+
+        assert_eq!(report.invoice_count, 6);
+        assert_eq!(report.value(7), 150040);
+        assert_eq!(report.value(8), 2100);
+        assert_eq!(report.value(9), 31508);
+        assert_eq!(report.value(4), 20000);
+        assert_eq!(report.value(6), 2000);
+        assert_eq!(report.value(59), 30000);
+        assert_eq!(report.value(120), 5000);
+        assert_eq!(report.value(27), 33508);
+        assert_eq!(report.value(71), 33508);
+        assert_eq!(report.declaration_type(), 'I');
+        assert_eq!(report.warnings.len(), 1);
+
+        let json = report.to_json();
+        assert_eq!(json["period"], "2T");
+        assert_eq!(json["boxes"]["09"], "315.08");
+    }
+
+    #[test]
+    fn test_modelo303_presentation_file() {
+        let invoices = vec![invoice(
+            "1",
+            "2024-01-15",
+            "B12345674",
+            Rule::new(21.0, 15.0),
+            1000.0,
+        )];
+        let report = Modelo303::from_invoices(&invoices, &[], Quarter::new(2024, 1).unwrap());
+        let file = String::from_utf8(report.to_aeat_file(&user())).unwrap();
+
+        assert!(file.starts_with("<T30320241T0000><AUX>"));
+        assert!(file.ends_with("</T30320241T0000>\r\n"));
+        assert!(file.contains("<T30301000> I12345678ZJOHN DOE"));
+        assert!(file.contains(&format!("{}{}", amount(100000, 17), "02100")));
+
+        let page1 = &file[file.find("<T30301000>").unwrap()..file.find("</T30301000>").unwrap()];
+        // Header fields plus 13 amounts of 17 and 4 percentages of 5
+        assert_eq!(
+            page1.len(),
+            11 + 1 + 1 + 9 + 80 + 15 + 4 + 2 + 4 + 8 + 5 + 13 * 17 + 4 * 5
+        );
+        assert_eq!(amount(-1234, 6), "N01234");
+    }
+}
+
+#[cfg(test)]
+mod modelo130_tests {
+    use super::fixtures::invoice;
+    use crate::models::{Modelo130Filing, Rule};
+    use crate::services::modelo130::Modelo130;
+    use crate::services::period::Quarter;
+
+    #[test]
+    fn test_modelo130_accumulates_year_to_date() {
+        let invoices = vec![
+            invoice("1", "2024-02-01", "B12345674", Rule::new(21.0, 7.0), 3000.0),
+            invoice("2", "2024-05-01", "B12345674", Rule::new(21.0, 7.0), 4000.0),
+            invoice("3", "2024-08-01", "B12345674", Rule::new(21.0, 7.0), 9000.0),
+            invoice("4", "2023-12-31", "B12345674", Rule::new(21.0, 7.0), 9000.0),
+        ];
+        let q1 = Modelo130::calculate(&invoices, Quarter::new(2024, 1).unwrap(), 50000, &[], None);
+        // (3000 - 500) * 20% - 210 withheld
+        assert_eq!(q1.value(1), 300000);
+        assert_eq!(q1.value(7), 29000);
+        assert_eq!(q1.value(19), 29000);
+        assert_eq!(q1.declaration_type(), 'I');
+
+        let previous = vec![q1.to_filing()];
+        let q2 = Modelo130::calculate(
+            &invoices,
+            Quarter::new(2024, 2).unwrap(),
+            100000,
+            &previous,
+            Some(850000),
+        );
+        // (7000 - 1500) * 20% = 1100, minus 290 paid and 490 withheld, minus 100 reduction
+        assert_eq!(q2.value(1), 700000);
+        assert_eq!(q2.value(2), 150000);
+        assert_eq!(q2.value(5), 29000);
+        assert_eq!(q2.value(6), 49000);
+        assert_eq!(q2.value(13), 10000);
+        assert_eq!(q2.value(19), 22000);
+        assert!(q2.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_modelo130_compensates_negative_results() {
+        let invoices = vec![invoice(
+            "1",
+            "2024-05-01",
+            "B12345674",
+            Rule::new(21.0, 7.0),
+            10000.0,
+        )];
+        let previous = vec![Modelo130Filing::new(2024, 1, 1000.0, -200.0, 0.0, -200.0)];
+        let report = Modelo130::calculate(
+            &invoices,
+            Quarter::new(2024, 2).unwrap(),
+            0,
+            &previous,
+            None,
+        );
+
+        // (10000 - 1000) * 20% - 700 withheld = 1100, minus the 200 negative result of 1T
+        assert_eq!(report.value(14), 110000);
+        assert_eq!(report.value(15), 20000);
+        assert_eq!(report.value(19), 90000);
+
+        let missing = Modelo130::calculate(
+            &invoices,
+            Quarter::new(2024, 3).unwrap(),
+            0,
+            &previous,
+            None,
+        );
+        assert_eq!(missing.warnings.len(), 1);
+
+        let file = String::from_utf8(report.to_aeat_file(&invoices[0].user)).unwrap();
+        assert!(file.starts_with("<T13020242T0000><AUX>"));
+        assert!(file.contains("<T13001000> I12345678ZJOHN DOE"));
+    }
+}
+
+#[cfg(test)]
+mod modelo347_tests {
+    use super::fixtures::{client_at, invoice_for, user};
+    use crate::models::Rule;
+    use crate::services::modelo347::Modelo347;
+
+    #[test]
+    fn test_modelo347_groups_clients_above_threshold() {
+        let invoices = vec![
+            invoice_for(
+                "1",
+                "2024-01-10",
+                client_at("Client", "B12345674", "Calle Mayor 1, 28013 Madrid"),
+                Rule::new(21.0, 15.0),
+                1000.0,
+            ),
+            invoice_for(
+                "2",
+                "2024-11-10",
+                client_at("Client", "ESB12345674", "Calle Mayor 1, 28013 Madrid"),
+                Rule::new(21.0, 15.0),
+                1500.0,
+            ),
+            // 2483.52 + 21% = 3005.06, not above the threshold
+            invoice_for(
+                "3",
+                "2024-03-01",
+                client_at("Client", "A58818501", "Barcelona"),
+                Rule::new(21.0, 15.0),
+                2483.52,
+            ),
+            invoice_for(
+                "4",
+                "2024-05-01",
+                client_at("Client", "", ""),
+                Rule::new(21.0, 15.0),
+                5000.0,
+            ),
+            invoice_for(
+                "5",
+                "2024-05-01",
+                client_at("Client", "DE123456789", "Berlin"),
+                Rule::new(21.0, 15.0),
+                9000.0,
+            ),
+            invoice_for(
+                "6",
+                "2023-12-31",
+                client_at("Client", "B12345674", "Madrid"),
+                Rule::new(21.0, 15.0),
+                9000.0,
+            ),
+        ];
+        let report = Modelo347::from_invoices(&invoices, 2024);
+
+        assert_eq!(report.clients.len(), 2);
+        assert_eq!(report.below_threshold, 1);
+
+        let acme = &report.clients[1];
+        assert_eq!(acme.cif, "B12345674");
+        assert_eq!(acme.quarters, [121000, 0, 0, 181500]);
+        assert_eq!(acme.total, 302500);
+        assert_eq!(acme.province.as_deref(), Some("28"));
+        assert!(acme.issue.is_none());
+
+        let missing = &report.clients[0];
+        assert_eq!(missing.issue.as_deref(), Some("Missing tax ID"));
+        assert_eq!(report.total(), 302500 + 605000);
+    }
+
+    #[test]
+    fn test_modelo347_presentation_file() {
+        let invoices = vec![invoice_for(
+            "7",
+            "2024-06-10",
+            client_at("Client", "B12345674", "28013 Madrid"),
+            Rule::new(21.0, 15.0),
+            5000.0,
+        )];
+        let report = Modelo347::from_invoices(&invoices, 2024);
+        let file = String::from_utf8(report.to_aeat_file(&user())).unwrap();
+        let records: Vec<&str> = file.split_terminator("\r\n").collect();
+
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.len() == 500));
+        assert!(records[0].starts_with("1347202412345678ZJOHN DOE"));
+        assert_eq!(&records[0][135..144], "000000001");
+        assert_eq!(&records[0][144..160], " 000000000605000");
+        assert_eq!(&records[1][17..26], "B12345674");
+        assert_eq!(&records[1][76..78], "28");
+        assert_eq!(&records[1][81..82], "B");
+        assert_eq!(&records[1][82..98], " 000000000605000");
+        // Second quarter amount
+        assert_eq!(&records[1][167..183], " 000000000605000");
+    }
+}
+
+#[cfg(test)]
+mod modelo349_tests {
+    use super::fixtures::{invoice, user};
+    use crate::models::Rule;
+    use crate::services::modelo349::{Modelo349, OperationKey};
+    use crate::services::period::Quarter;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_modelo349_groups_by_operator_and_key() {
+        let invoices = vec![
+            invoice(
+                "1",
+                "2024-04-10",
+                "DE123456789",
+                Rule::new(0.0, 0.0),
+                1000.0,
+            ),
+            invoice(
+                "2",
+                "2024-05-10",
+                "de 123456789",
+                Rule::new(0.0, 0.0),
+                500.0,
+            ),
+            invoice(
+                "3",
+                "2024-05-10",
+                "FR12345678901",
+                Rule::new(0.0, 0.0),
+                200.0,
+            ),
+            invoice("4", "2024-05-10", "B12345674", Rule::new(0.0, 0.0), 300.0),
+            invoice("5", "2024-05-10", "US123456", Rule::new(0.0, 0.0), 300.0),
+            invoice(
+                "6",
+                "2024-06-10",
+                "IT12345678901",
+                Rule::new(21.0, 0.0),
+                300.0,
+            ),
+        ];
+        let mut keys = BTreeMap::new();
+        keys.insert("FR12345678901".to_string(), OperationKey::Goods);
+        let report =
+            Modelo349::from_invoices(&invoices, Quarter::new(2024, 2).unwrap(), &keys, &[]);
+
+        assert_eq!(report.operations.len(), 2);
+        assert_eq!(report.operations[0].vat_id, "DE123456789");
+        assert_eq!(report.operations[0].key, OperationKey::Services);
+        assert_eq!(report.operations[0].amount, 150000);
+        assert_eq!(report.operations[1].key, OperationKey::Goods);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.rectifications.is_empty());
+
+        let file = String::from_utf8(report.to_aeat_file(&user())).unwrap();
+        let records: Vec<&str> = file.split_terminator("\r\n").collect();
+        assert_eq!(records.len(), 3);
+        assert!(records.iter().all(|r| r.len() == 500));
+        assert_eq!(&records[0][135..137], "2T");
+        assert_eq!(&records[0][137..146], "000000002");
+        assert_eq!(&records[1][75..92], "DE123456789      ");
+        assert_eq!(&records[1][132..146], "S0000000150000");
+    }
+
+    #[test]
+    fn test_modelo349_rectifies_previous_quarters() {
+        let mut invoices = vec![invoice(
+            "1",
+            "2024-02-10",
+            "DE123456789",
+            Rule::new(0.0, 0.0),
+            1000.0,
+        )];
+        let keys = BTreeMap::new();
+        let q1 = Modelo349::from_invoices(&invoices, Quarter::new(2024, 1).unwrap(), &keys, &[]);
+        let filings = vec![q1.to_filing()];
+
+        // A late invoice for the first quarter, stored after it was declared
+        invoices.push(invoice(
+            "2",
+            "2024-03-30",
+            "DE123456789",
+            Rule::new(0.0, 0.0),
+            250.0,
+        ));
+        invoices.push(invoice(
+            "3",
+            "2024-04-15",
+            "DE123456789",
+            Rule::new(0.0, 0.0),
+            400.0,
+        ));
+        let q2 =
+            Modelo349::from_invoices(&invoices, Quarter::new(2024, 2).unwrap(), &keys, &filings);
+
+        assert_eq!(q2.operations[0].amount, 40000);
+        assert_eq!(q2.rectifications.len(), 1);
+        let rectification = &q2.rectifications[0];
+        assert_eq!(rectification.quarter, Quarter::new(2024, 1).unwrap());
+        assert_eq!(rectification.declared, 100000);
+        assert_eq!(rectification.rectified, 125000);
+        assert_eq!(q2.updated_filings[0].operations[0].amount, 1250.0);
+
+        let file = String::from_utf8(q2.to_aeat_file(&user())).unwrap();
+        let records: Vec<&str> = file.split_terminator("\r\n").collect();
+        assert_eq!(
+            &records[2][132..178],
+            "S             20241T00000001250000000000100000"
+        );
+
+        // Once the rectified filing is stored there is nothing left to correct
+        let q2_again = Modelo349::from_invoices(
+            &invoices,
+            Quarter::new(2024, 2).unwrap(),
+            &keys,
+            &q2.updated_filings,
+        );
+        assert!(q2_again.rectifications.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod libro_registro_tests {
+    use super::fixtures::{client, invoice_for};
+    use crate::models::Rule;
+    use crate::services::libro_registro::{issued_invoices, to_csv, to_xlsx};
+    use crate::services::period::{DateRange, Quarter};
+
+    #[test]
+    fn test_ledger_filters_period_and_sorts_by_number() {
+        let invoices = vec![
+            invoice_for(
+                "A10",
+                "2024-02-01",
+                client("Acme; Corp", "ESB12345674"),
+                Rule::new(21.0, 15.0),
+                100.0,
+            ),
+            invoice_for(
+                "A2",
+                "2024-03-01",
+                client("Acme; Corp", "ESB12345674"),
+                Rule::new(21.0, 15.0),
+                200.0,
+            ),
+            invoice_for(
+                "A1",
+                "2024-04-01",
+                client("Acme; Corp", "ESB12345674"),
+                Rule::new(21.0, 15.0),
+                300.0,
+            ),
+            invoice_for(
+                "A9",
+                "2023-12-31",
+                client("Acme; Corp", "ESB12345674"),
+                Rule::new(21.0, 15.0),
+                400.0,
+            ),
+        ];
+        let entries = issued_invoices(&invoices, Quarter::new(2024, 1).unwrap().into());
+        let numbers: Vec<&str> = entries.iter().map(|e| e.number.as_str()).collect();
+        assert_eq!(numbers, ["A2", "A10"]);
+        assert_eq!(entries[0].nif, "B12345674");
+        assert_eq!(entries[0].total, 24200);
+
+        assert_eq!(issued_invoices(&invoices, DateRange::year(2024)).len(), 3);
+    }
+
+    #[test]
+    fn test_ledger_csv_and_xlsx() {
+        let entries = issued_invoices(
+            &[invoice_for(
+                "7",
+                "2024-02-01",
+                client("Acme; Corp", "ESB12345674"),
+                Rule::new(21.0, 15.0),
+                1000.0,
+            )],
+            DateRange::year(2024),
+        );
+        let csv = String::from_utf8(to_csv(&entries).unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert!(lines[0].starts_with("Ejercicio;Periodo;Fecha Expedición"));
+        assert_eq!(
+            lines[1],
+            "2024;1T;01/02/2024;;;7;B12345674;\"Acme; Corp\";1000,00;21,00;210,00;15,00;150,00;1210,00"
+        );
+
+        let xlsx = to_xlsx(&entries).unwrap();
+        assert!(xlsx.starts_with(b"PK"));
+    }
+}
+
+#[cfg(test)]
+mod expense_tests {
+    use super::fixtures::invoice;
+    use crate::models::{ExpenseCategory, Rule, Supplier};
+    use crate::repository::Storage;
+    use crate::services::expense::CreateExpenseParams;
+    use crate::services::modelo303::Modelo303;
+    use crate::services::period::Quarter;
+    use crate::services::ExpenseService;
+
+    fn params(number: &str, date: &str, base: f32, deductible: f32) -> CreateExpenseParams {
+        CreateExpenseParams {
+            number: number.to_string(),
+            date: date.to_string(),
+            supplier: Supplier::new(
+                "Telefónica".to_string(),
+                "A28015865".to_string(),
+                "Madrid".to_string(),
+                None,
+            ),
+            category: ExpenseCategory::Supplies,
+            base,
+            iva: 21.0,
+            deductible_percent: deductible,
+            attachment: None,
+        }
+    }
+
+    #[test]
+    fn test_expenses_are_stored_with_attachments() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().to_str().unwrap()).unwrap();
+        let service = ExpenseService::new(storage);
+
+        let pdf = dir.path().join("fibra.PDF");
+        std::fs::write(&pdf, b"%PDF-1.4").unwrap();
+        let mut with_pdf = params("F/2024/1", "2024-02-01", 50.0, 30.0);
+        with_pdf.attachment = Some(pdf);
+        let expense = service.create_expense(with_pdf).unwrap();
+
+        assert_eq!(expense.id, "A28015865_F_2024_1");
+        assert_eq!(
+            expense.attachment.as_deref(),
+            Some("A28015865_F_2024_1.pdf")
+        );
+        assert!(service.attachment_path(&expense).unwrap().is_file());
+        assert!((expense.deductible_base() - 15.0).abs() < 0.001);
+        assert!((expense.deductible_iva() - 3.15).abs() < 0.001);
+
+        // Same supplier invoice twice, and attachments that are not PDFs
+        assert!(service
+            .create_expense(params("F/2024/1", "2024-02-01", 50.0, 30.0))
+            .is_err());
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, b"notes").unwrap();
+        let mut with_text = params("F/2024/2", "2024-03-01", 50.0, 30.0);
+        with_text.attachment = Some(text);
+        assert!(service.create_expense(with_text).is_err());
+
+        service
+            .create_expense(params("F/2024/0", "2024-01-01", 80.0, 100.0))
+            .unwrap();
+        let numbers: Vec<String> = service
+            .list_expenses()
+            .unwrap()
+            .into_iter()
+            .map(|e| e.number)
+            .collect();
+        assert_eq!(numbers, ["F/2024/0", "F/2024/1"]);
+    }
+
+    #[test]
+    fn test_modelo303_deducts_expense_iva() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = ExpenseService::new(Storage::new(dir.path().to_str().unwrap()).unwrap());
+        service
+            .create_expense(params("1", "2024-01-10", 200.0, 50.0))
+            .unwrap();
+        service
+            .create_expense(params("2", "2024-04-10", 900.0, 100.0))
+            .unwrap();
+
+        let invoice = invoice(
+            "1",
+            "2024-01-15",
+            "B12345674",
+            Rule::new(21.0, 15.0),
+            1000.0,
+        );
+        let report = Modelo303::from_invoices(
+            &[invoice],
+            &service.list_expenses().unwrap(),
+            Quarter::new(2024, 1).unwrap(),
+        );
+
+        assert_eq!(report.value(28), 10000);
+        assert_eq!(report.value(29), 2100);
+        assert_eq!(report.value(45), 2100);
+        assert_eq!(report.value(71), 21000 - 2100);
+    }
+}
+
+#[cfg(test)]
+mod asset_tests {
+    use crate::models::{AssetCategory, ExpenseCategory, Supplier};
+    use crate::repository::Storage;
+    use crate::services::asset::{depreciation_in, schedule, CreateAssetParams};
+    use crate::services::expense::CreateExpenseParams;
+    use crate::services::period::Quarter;
+    use crate::services::{AssetService, ExpenseService, ReportService};
+
+    fn supplier() -> Supplier {
+        Supplier::new(
+            "PC Shop".to_string(),
+            "B12345674".to_string(),
+            String::new(),
+            None,
+        )
+    }
+
+    fn laptop(coefficient: f32) -> CreateAssetParams {
+        CreateAssetParams {
+            description: "Laptop".to_string(),
+            supplier: supplier(),
+            purchase_date: "2024-07-01".to_string(),
+            value: 1200.0,
+            category: AssetCategory::ComputerEquipment,
+            coefficient,
+            expense_id: None,
+        }
+    }
+
+    #[test]
+    fn test_depreciation_schedule() {
+        let dir = tempfile::tempdir().unwrap();
+        let service = AssetService::new(Storage::new(dir.path().to_str().unwrap()).unwrap());
+
+        // Computer equipment: at most 26% per year and 10 years
+        assert!(service.create_asset(laptop(30.0)).is_err());
+        assert!(service.create_asset(laptop(9.0)).is_err());
+        let asset = service.create_asset(laptop(26.0)).unwrap();
+
+        let years = schedule(&asset);
+        let amounts: Vec<(i32, i64)> = years.iter().map(|y| (y.year, y.amount)).collect();
+        // 184 of 366 days in 2024, then 312€ a year until 1200€ are reached
+        assert_eq!(
+            amounts,
+            [
+                (2024, 15685),
+                (2025, 31200),
+                (2026, 31200),
+                (2027, 31200),
+                (2028, 10715)
+            ]
+        );
+        assert_eq!(years.last().unwrap().remaining, 0);
+
+        assert_eq!(
+            depreciation_in(&asset, Quarter::new(2024, 2).unwrap().into()),
+            0
+        );
+        assert_eq!(
+            depreciation_in(&asset, Quarter::new(2024, 3).unwrap().into()),
+            7843
+        );
+        assert_eq!(
+            depreciation_in(&asset, Quarter::new(2025, 1).unwrap().into()),
+            7693
+        );
+        assert_eq!(service.list_assets().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_depreciation_replaces_the_asset_expense() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let storage = Storage::new(path).unwrap();
+        let expenses = ExpenseService::new(storage.clone());
+        let assets = AssetService::new(storage.clone());
+        let reports = ReportService::new(storage, format!("{}/exports", path)).unwrap();
+
+        let expense = expenses
+            .create_expense(CreateExpenseParams {
+                number: "1".to_string(),
+                date: "2025-01-01".to_string(),
+                supplier: supplier(),
+                category: ExpenseCategory::Equipment,
+                base: 1200.0,
+                iva: 21.0,
+                deductible_percent: 100.0,
+                attachment: None,
+            })
+            .unwrap();
+        let q1 = Quarter::new(2025, 1).unwrap();
+        assert_eq!(reports.deductible_expenses(q1).unwrap(), 120000);
+
+        let mut params = laptop(26.0);
+        params.purchase_date = "2025-01-01".to_string();
+        params.expense_id = Some(expense.id);
+        assets.create_asset(params).unwrap();
+        // 312€ a year, 90 of 365 days
+        assert_eq!(reports.deductible_expenses(q1).unwrap(), 7693);
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::fixtures::{invoice, invoice_service};
+    use crate::models::Rule;
+    use crate::repository::Storage;
+    use crate::services::stats::{bar, change_percent, Stats};
+    use crate::services::ReportService;
+
+    #[test]
+    fn test_stats_aggregate_year_and_compare_with_previous() {
+        let mut paid = invoice("3", "2025-02-10", "B12345674", Rule::new(21.0, 15.0), 500.0);
+        paid.paid_date = Some("2025-03-12".to_string());
+        let mut late = invoice(
+            "4",
+            "2025-11-01",
+            "ESB12345674",
+            Rule::new(21.0, 15.0),
+            1500.0,
+        );
+        late.paid_date = Some("2025-12-01".to_string());
+        let invoices = vec![
+            invoice("1", "2024-01-15", "B12345674", Rule::new(21.0, 15.0), 800.0),
+            invoice(
+                "2",
+                "2025-01-20",
+                "A28015865",
+                Rule::new(21.0, 15.0),
+                1000.0,
+            ),
+            paid,
+            late,
+            invoice(
+                "5",
+                "2026-01-01",
+                "A28015865",
+                Rule::new(21.0, 15.0),
+                9000.0,
+            ),
+        ];
+
+        let stats = Stats::from_invoices(&invoices, 2025, 50000, 0);
+        assert_eq!(stats.current.revenue, 300000);
+        assert_eq!(stats.current.months[0], 100000);
+        assert_eq!(stats.current.quarters(), [150000, 0, 0, 150000]);
+        assert_eq!(stats.current.iva, 63000);
+        assert_eq!(stats.current.irpf, 45000);
+        assert_eq!(stats.current.profit(), 250000);
+        assert_eq!(stats.previous.revenue, 80000);
+        assert_eq!(stats.previous.invoice_count, 1);
+
+        // Both spellings of the same tax ID are one client
+        assert_eq!(stats.clients.len(), 2);
+        assert_eq!(stats.clients[0].cif, "B12345674");
+        assert_eq!(stats.clients[0].revenue, 200000);
+        assert_eq!(stats.clients[0].invoice_count, 2);
+        assert_eq!(stats.paid_count, 2);
+        assert_eq!(stats.average_days_to_pay, Some(30.0));
+
+        assert_eq!(change_percent(300000, 80000), Some(275.0));
+        assert_eq!(change_percent(100, 0), None);
+
+        let json = stats.to_json();
+        assert_eq!(json["revenue"], "3000.00");
+        assert_eq!(json["quarters"][0]["change_percent"], 87.5);
+        assert_eq!(json["top_clients"][0]["name"], "Client ESB12345674");
+        assert_eq!(json["previous_year"]["revenue"], "800.00");
+    }
+
+    #[test]
+    fn test_bar_uses_eighth_blocks() {
+        assert_eq!(bar(100, 100, 4), "████");
+        assert_eq!(bar(50, 100, 4), "██");
+        assert_eq!(bar(1, 16, 2), "▏");
+        assert_eq!(bar(9, 16, 2), "█▏");
+        assert_eq!(bar(0, 100, 4), "");
+        assert_eq!(bar(200, 100, 4), "████");
+    }
+
+    #[test]
+    fn test_paid_invoices_feed_the_stats_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let storage = Storage::new(path).unwrap();
+        let invoices = invoice_service(path);
+        let reports = ReportService::new(storage.clone(), format!("{}/exports", path)).unwrap();
+
+        let stored = invoice("1", "2025-03-01", "B12345674", Rule::new(21.0, 15.0), 100.0);
+        assert!(invoices.save_imported_invoice(&stored).unwrap());
+        assert!(invoices.mark_paid(&stored, "2025-02-01").is_err());
+        assert!(invoices.mark_paid(&stored, "soon").is_err());
+        invoices.mark_paid(&stored, "2025-03-15").unwrap();
+        assert_eq!(
+            storage
+                .get_invoice("1")
+                .unwrap()
+                .unwrap()
+                .paid_date
+                .as_deref(),
+            Some("2025-03-15")
+        );
+
+        let stats = reports.stats(2025).unwrap();
+        assert_eq!(stats.average_days_to_pay, Some(14.0));
+        let exported = reports.export_stats(&stats).unwrap();
+        assert!(exported.ends_with("stats_2025.json"));
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(exported).unwrap()).unwrap();
+        assert_eq!(json["average_days_to_pay"], 14.0);
+    }
+}
+
+#[cfg(test)]
+mod vat_tests {
+    use crate::errors::ValidationError;
+    use crate::services::vat::validate_eu_vat;
+
+    #[test]
+    fn test_valid_eu_vat_numbers() {
+        for vat_id in [
+            "ATU13585627",
+            "BE0403019261",
+            "DE136695976",
+            "DK13585628",
+            "EL094259216",
+            "ESB12345674",
+            "FI20774740",
+            "FR83404833048",
+            "FRK7399859412",
+            "IE6388047V",
+            "IT00743110157",
+            "LU15027442",
+            "NL004495445B01",
+            "NL000099998B57",
+            "PL5260250274",
+            "PT501964843",
+            "SE556188840401",
+            "fr 83 404 833 048",
+        ] {
+            assert!(
+                validate_eu_vat(vat_id).is_ok(),
+                "{} should be valid",
+                vat_id
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_eu_vat_numbers() {
+        assert!(matches!(
+            validate_eu_vat("US123456789"),
+            Err(ValidationError::InvalidVatCountry { .. })
+        ));
+        assert!(matches!(
+            validate_eu_vat("DE12345678"),
+            Err(ValidationError::InvalidVatFormat { .. })
+        ));
+        assert!(matches!(
+            validate_eu_vat("NL004495445A01"),
+            Err(ValidationError::InvalidVatFormat { .. })
+        ));
+        for vat_id in [
+            "DE123456789",
+            "FR84404833048",
+            "IT00743110158",
+            "PT501964842",
+            "ATU13585628",
+            "BE0403019262",
+        ] {
+            assert!(
+                matches!(
+                    validate_eu_vat(vat_id),
+                    Err(ValidationError::InvalidVatChecksum { .. })
+                ),
+                "{} should fail the checksum",
+                vat_id
+            );
+        }
+        assert!(matches!(
+            validate_eu_vat("ESB12345670"),
+            Err(ValidationError::InvalidCifControl { .. })
+        ));
+    }
+}
+
+#[cfg(test)]
+mod vies_tests {
+    use crate::models::ViesCheck;
+    use crate::repository::Storage;
+    use crate::services::vies::{SoapViesClient, ViesClient};
+    use crate::services::ClientService;
+    use std::cell::Cell;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::rc::Rc;
+    use std::thread;
+
+    // Local stand-in for the VIES endpoint answering one request with a canned
+    // response, and handing back the request body it received
+    fn stand_in_server(status: &str, response: &str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/checkVatService", listener.local_addr().unwrap());
+        let status = status.to_string();
+        let response = response.to_string();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Type: text/xml; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                response.len(),
+                response
+            )
+            .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        (url, handle)
+    }
+
+    const VALID_RESPONSE: &str = r#"<env:Envelope xmlns:env="http://schemas.xmlsoap.org/soap/envelope/"><env:Header/><env:Body><ns2:checkVatResponse xmlns:ns2="urn:ec.europa.eu:taxud:vies:services:checkVat:types"><ns2:countryCode>FR</ns2:countryCode><ns2:vatNumber>83404833048</ns2:vatNumber><ns2:requestDate>2025-03-04+01:00</ns2:requestDate><ns2:valid>true</ns2:valid><ns2:name>SAS EXEMPLE</ns2:name><ns2:address>1 RUE DE LA PAIX
+75002 PARIS</ns2:address></ns2:checkVatResponse></env:Body></env:Envelope>"#;
+
+    #[test]
+    fn test_soap_client_against_stand_in_server() {
+        let (url, server) = stand_in_server("200 OK", VALID_RESPONSE);
+        let check = SoapViesClient::new(url)
+            .check_vat("FR", "83404833048")
+            .unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.contains("<urn:countryCode>FR</urn:countryCode>"));
+        assert!(request.contains("<urn:vatNumber>83404833048</urn:vatNumber>"));
+        assert!(check.valid);
+        assert_eq!(check.vat_id, "FR83404833048");
+        assert_eq!(check.name.as_deref(), Some("SAS EXEMPLE"));
+        assert_eq!(check.request_date, "2025-03-04+01:00");
+    }
+
+    #[test]
+    fn test_soap_client_reports_faults() {
+        let fault = r#"<env:Envelope xmlns:env="http://schemas.xmlsoap.org/soap/envelope/"><env:Body><env:Fault><faultcode>env:Server</faultcode><faultstring>MS_UNAVAILABLE</faultstring></env:Fault></env:Body></env:Envelope>"#;
+        let (url, server) = stand_in_server("500 Internal Server Error", fault);
+        let error = SoapViesClient::new(url)
+            .check_vat("DE", "136695976")
+            .unwrap_err();
+        server.join().unwrap();
+        assert!(error.to_string().contains("MS_UNAVAILABLE"));
+    }
+
+    // Answers without any network, counting how many times it is asked
+    struct FakeVies {
+        calls: Rc<Cell<usize>>,
+    }
+
+    impl ViesClient for FakeVies {
+        fn check_vat(&self, country: &str, number: &str) -> io::Result<ViesCheck> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(ViesCheck {
+                vat_id: number.to_string(),
+                valid: country == "DE",
+                name: None,
+                address: None,
+                request_date: "2025-03-04+01:00".to_string(),
+                checked_at: chrono::Local::now().to_rfc3339(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_vies_checks_are_cached_with_their_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().to_str().unwrap()).unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let service = ClientService::new(
+            storage.clone(),
+            Box::new(FakeVies {
+                calls: calls.clone(),
+            }),
+        );
+
+        assert!(service.check_vat("DE123456789").is_err());
+        assert_eq!(calls.get(), 0);
+
+        let check = service.check_vat("de 136695976").unwrap();
+        assert!(check.valid);
+        assert_eq!(check.vat_id, "DE136695976");
+        service.check_vat("DE136695976").unwrap();
+        assert_eq!(calls.get(), 1);
+
+        assert!(!service.check_vat("FR83404833048").unwrap().valid);
+        assert_eq!(calls.get(), 2);
+
+        let history = storage.list_vies_checks("DE136695976").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].request_date, "2025-03-04+01:00");
+        // Numbers given straight to the storage never reach a path unsanitized
+        let mut check = history[0].clone();
+        check.vat_id = "../DE/136695976".to_string();
+        storage.save_vies_check(&check).unwrap();
+        assert!(dir.path().join("vies/___DE_136695976.json").is_file());
+        assert_eq!(
+            storage.list_vies_checks("../DE/136695976").unwrap().len(),
+            1
+        );
+    }
+}
+
+#[cfg(test)]
+mod iban_tests {
+    use crate::errors::ValidationError;
+    use crate::models::User;
+    use crate::services::iban::{format_iban, spanish_bank_name, validate_iban};
+
+    #[test]
+    fn test_iban_check_digits_and_lengths() {
+        for iban in [
+            "ES9121000418450200051332",
+            "es91 2100 0418 4502 0005 1332",
+            "DE89370400440532013000",
+            "FR1420041010050500013M02606",
+            "NL91ABNA0417164300",
+            "BE68539007547034",
+            "NO9386011117947",
+        ] {
+            assert!(validate_iban(iban).is_ok(), "{} should be valid", iban);
+        }
+
+        assert!(matches!(
+            validate_iban("ES9121000418450200051333"),
+            Err(ValidationError::InvalidIbanChecksum { .. })
+        ));
+        assert!(matches!(
+            validate_iban("ES91210004184502000513"),
+            Err(ValidationError::InvalidIbanLength {
+                length: 22,
+                expected: 24,
+                ..
+            })
+        ));
+        assert!(matches!(
+            validate_iban("XX9121000418450200051332"),
+            Err(ValidationError::InvalidIbanCountry { .. })
+        ));
+        assert!(matches!(
+            validate_iban("ES91-2100-0418"),
+            Err(ValidationError::InvalidIban { .. })
+        ));
+    }
+
+    #[test]
+    fn test_iban_display_and_bank_lookup() {
+        assert_eq!(
+            format_iban("ES9121000418450200051332"),
+            "ES91 2100 0418 4502 0005 1332"
+        );
+        assert_eq!(format_iban("NO9386011117947"), "NO93 8601 1117 947");
+        assert_eq!(
+            spanish_bank_name("ES91 2100 0418 4502 0005 1332"),
+            Some("CaixaBank")
+        );
+        assert_eq!(
+            spanish_bank_name("ES7600491500051234567892"),
+            Some("Banco Santander")
+        );
+        assert_eq!(spanish_bank_name("DE89370400440532013000"), None);
+
+        // Codes of absorbed banks still show up on old account numbers
+        assert_eq!(
+            spanish_bank_name("ES0020380000000000000000"),
+            Some("Bankia (historical, now CaixaBank)")
+        );
+
+        let mut user = User::new(
+            "John Doe".to_string(),
+            "Madrid".to_string(),
+            "12345678Z".to_string(),
+            None,
+            Some("ES9121000418450200051332".to_string()),
+        );
+        user.bic = Some("CAIXESBBXXX".to_string());
+        let display = user.to_string();
+        assert!(display.contains("IBAN: ES91 2100 0418 4502 0005 1332 (CaixaBank)"));
+        assert!(display.contains("BIC: CAIXESBBXXX"));
+    }
+}
+
+#[cfg(test)]
+mod epc_qr_tests {
+    use super::fixtures;
+    use crate::models::{Invoice, Rule};
+    use crate::services::epc_qr::{epc_payload, epc_qr_modules};
+    use crate::services::PdfService;
+    use printpdf::lopdf::Document;
+
+    fn invoice(iban: Option<&str>) -> Invoice {
+        let mut invoice = fixtures::invoice(
+            "2024-15",
+            "2024-03-01",
+            "B12345674",
+            Rule::new(21.0, 15.0),
+            500.0,
+        );
+        // Accented names go into the payload as they are
+        invoice.user.name = "José Pérez".to_string();
+        invoice.user.iban = iban.map(str::to_string);
+        invoice.user.bic = Some("CAIXESBBXXX".to_string());
+        invoice
+    }
+
+    #[test]
+    fn test_epc_payload() {
+        let payload = epc_payload(&invoice(Some("ES91 2100 0418 4502 0005 1332"))).unwrap();
+        assert_eq!(
+            payload,
+            "BCD\n002\n1\nSCT\nCAIXESBBXXX\nJosé Pérez\nES9121000418450200051332\nEUR530.00\n\n\nInvoice 2024-15"
+        );
+        assert!(payload.len() <= 331);
+
+        let (width, modules) = epc_qr_modules(&payload).unwrap();
+        assert_eq!((width - 17) % 4, 0);
+        assert_eq!(modules.len(), width * width);
+
+        assert!(epc_payload(&invoice(None)).is_none());
+        assert!(epc_payload(&invoice(Some("ES9121000418450200051333"))).is_none());
+    }
+
+    fn page_text(currency: &str, iban: Option<&str>) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let service = PdfService::new(
+            dir.path().to_str().unwrap().to_string(),
+            false,
+            currency.to_string(),
+        )
+        .unwrap();
+        let path = service.generate_invoice_pdf(&invoice(iban)).unwrap();
+
+        let document = Document::load(path).unwrap();
+        let page = *document.get_pages().values().next().unwrap();
+        String::from_utf8_lossy(&document.get_page_content(page).unwrap()).to_string()
+    }
+
+    // Builtin font text is written as hex strings in the content stream
+    fn hex(text: &str) -> String {
+        text.bytes().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    #[test]
+    fn test_pdf_has_payment_qr_only_for_euro_accounts() {
+        let iban = Some("ES9121000418450200051332");
+        let with_qr = page_text("EUR", iban);
+        assert!(with_qr.contains(&hex("Scan to pay")));
+        assert!(with_qr.contains(&hex("ES91 2100 0418 4502 0005 1332   BIC: CAIXESBBXXX")));
+
+        assert!(!page_text("USD", iban).contains(&hex("Scan to pay")));
+        assert!(!page_text("EUR", None).contains(&hex("Scan to pay")));
+    }
+}
+
+#[cfg(test)]
+mod sepa_tests {
+    use super::fixtures::{client, invoice_for, user};
+    use crate::models::{Client, Invoice, Rule, SepaMandate, SepaScheme, User};
+    use crate::repository::Storage;
+    use crate::services::iban::validate_iban;
+    use crate::services::sepa::{
+        creditor_identifier, sepa_text, sequence_types, to_pain008, DirectDebit, SepaService,
+    };
+    use crate::services::xml::text;
+    use crate::services::ClientService;
+    use chrono::NaiveDate;
+    use roxmltree::Document;
+    use std::process::Command;
+
+    // Official ISO 20022 schema of the messages the batches are sent as
+    const PAIN008_XSD: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/pain.008.001.02.xsd"
+    );
+
+    // xmllint comes with libxml2-utils, without it the schema check is skipped
+    fn xmllint_installed() -> bool {
+        Command::new("xmllint").arg("--version").output().is_ok()
+    }
+
+    // Validate a message against the schema with xmllint
+    fn validate(xml: &str) -> Result<(), String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pain008.xml");
+        std::fs::write(&path, xml).unwrap();
+        let output = Command::new("xmllint")
+            .args(["--noout", "--schema", PAIN008_XSD])
+            .arg(&path)
+            .output()
+            .map_err(|e| format!("Cannot run xmllint: {}", e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).to_string())
+        }
+    }
+
+    // Company collecting into a Spanish account
+    fn creditor() -> User {
+        let mut user = user();
+        user.name = "Peña & Asociados".to_string();
+        user.cif = "B12345674".to_string();
+        user.iban = Some("ES91 2100 0418 4502 0005 1332".to_string());
+        user.bic = Some("CAIXESBBXXX".to_string());
+        user
+    }
+
+    fn mandate(id: &str, scheme: SepaScheme) -> SepaMandate {
+        SepaMandate::new(
+            id.to_string(),
+            "2025-01-10".to_string(),
+            "ES6000491500051234567892".to_string(),
+            None,
+            scheme,
+        )
+    }
+
+    // Invoice issued before any of the collection dates
+    fn invoice(id: &str, client: Client, price: f32) -> Invoice {
+        invoice_for(id, "2025-02-01", client, Rule::new(21.0, 0.0), price)
+    }
+
+    fn created() -> chrono::NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 3, 1)
+            .unwrap()
+            .and_hms_opt(10, 15, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_creditor_identifier() {
+        let id = creditor_identifier("B12345674");
+        assert_eq!(id, "ES11000B12345674");
+        assert_eq!(creditor_identifier("ES b12345674"), id);
+
+        // Same check digits as an IBAN once the business code is left out
+        let without_business_code = format!("{}{}", &id[..4], &id[7..]);
+        let remainder = without_business_code[4..]
+            .chars()
+            .chain(without_business_code[..4].chars())
+            .fold(0, |remainder, c| {
+                let value = c.to_digit(36).unwrap();
+                (remainder * if value >= 10 { 100 } else { 10 } + value) % 97
+            });
+        assert_eq!(remainder, 1);
+
+        assert_eq!(sepa_text("Peña & Cía, S.L.", 70), "Pena + Cia, S.L.");
+        assert_eq!(sepa_text("Müller_GmbH", 6), "Muller");
+    }
+
+    // A new and a recurrent CORE mandate and a new B2B one
+    fn batch() -> Vec<DirectDebit> {
+        let mut recurrent = mandate("MANDATE-2", SepaScheme::Core);
+        recurrent.last_collection = Some("2025-02-01".to_string());
+        recurrent.bic = Some("BSCHESMMXXX".to_string());
+        vec![
+            DirectDebit {
+                invoice: invoice("2025-10", client("Ñandú S.L.", "B12345674"), 100.0),
+                mandate: mandate("MANDATE-1", SepaScheme::Core),
+            },
+            DirectDebit {
+                invoice: invoice("2025-11", client("Acme S.A.", "A28015865"), 50.0),
+                mandate: recurrent,
+            },
+            DirectDebit {
+                invoice: invoice("2025-12", client("Beta S.L.", "B87654321"), 10.0),
+                mandate: mandate("MANDATE-3", SepaScheme::B2b),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_pain008_batch_structure() {
+        let xml = to_pain008(&creditor(), &batch(), "2025-03-05", created()).unwrap();
+        let document = Document::parse(&xml).unwrap();
+        // The schema only checks the shape of an IBAN, not its check digits
+        for iban in document.descendants().filter(|n| n.has_tag_name("IBAN")) {
+            validate_iban(iban.text().unwrap()).unwrap();
+        }
+
+        let header = document
+            .descendants()
+            .find(|n| n.has_tag_name("GrpHdr"))
+            .unwrap();
+        assert_eq!(text(header, &["NbOfTxs"]), Some("3"));
+        assert_eq!(text(header, &["CtrlSum"]), Some("193.60"));
+
+        // One payment block per scheme and sequence type
+        let blocks: Vec<(String, String)> = document
+            .descendants()
+            .filter(|n| n.has_tag_name("PmtTpInf"))
+            .map(|n| {
+                (
+                    text(n, &["LclInstrm", "Cd"]).unwrap().to_string(),
+                    text(n, &["SeqTp"]).unwrap().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                ("CORE".to_string(), "FRST".to_string()),
+                ("CORE".to_string(), "RCUR".to_string()),
+                ("B2B".to_string(), "FRST".to_string()),
+            ]
+        );
+        assert!(xml.contains("<Id>ES11000B12345674</Id>"));
+        assert!(xml.contains("<Nm>Pena + Asociados</Nm>"));
+        assert!(xml.contains("<Nm>Nandu S.L.</Nm>"));
+        assert!(xml.contains("<BIC>BSCHESMMXXX</BIC>"));
+        assert!(xml.contains("<Id>NOTPROVIDED</Id>"));
+        assert!(xml.contains("<InstdAmt Ccy=\"EUR\">121.00</InstdAmt>"));
+    }
+
+    #[test]
+    fn test_pain008_batch_is_valid_against_the_schema() {
+        if !xmllint_installed() {
+            println!("xmllint not found, install libxml2-utils to check the schema");
+            return;
+        }
+        let xml = to_pain008(&creditor(), &batch(), "2025-03-05", created()).unwrap();
+        validate(&xml).unwrap();
+        let acme = client("Acme S.A.", "A28015865");
+        let same_mandate: Vec<DirectDebit> = ["2025-10", "2025-11"]
+            .into_iter()
+            .map(|id| DirectDebit {
+                invoice: invoice(id, acme.clone(), 50.0),
+                mandate: mandate("MANDATE-1", SepaScheme::Core),
+            })
+            .collect();
+        validate(&to_pain008(&creditor(), &same_mandate, "2025-03-05", created()).unwrap())
+            .unwrap();
+
+        // The validator catches broken documents
+        assert!(validate(&xml.replacen("<PmtMtd>DD</PmtMtd>", "", 1)).is_err());
+        assert!(validate(&xml.replacen("<SeqTp>FRST</SeqTp>", "<SeqTp>FIRST</SeqTp>", 1)).is_err());
+    }
+
+    #[test]
+    fn test_only_the_first_debit_of_a_new_mandate_is_sent_as_frst() {
+        let acme = client("Acme S.A.", "A28015865");
+        let debits = vec![
+            DirectDebit {
+                invoice: invoice("2025-11", acme.clone(), 50.0),
+                mandate: mandate("MANDATE-1", SepaScheme::Core),
+            },
+            DirectDebit {
+                invoice: invoice("2025-10", acme, 100.0),
+                mandate: mandate("MANDATE-1", SepaScheme::Core),
+            },
+        ];
+        assert_eq!(sequence_types(&debits), ["RCUR", "FRST"]);
+
+        let xml = to_pain008(&creditor(), &debits, "2025-03-05", created()).unwrap();
+        let document = Document::parse(&xml).unwrap();
+        let blocks: Vec<(String, String)> = document
+            .descendants()
+            .filter(|n| n.has_tag_name("PmtInf"))
+            .map(|n| {
+                (
+                    text(n, &["PmtTpInf", "SeqTp"]).unwrap().to_string(),
+                    text(n, &["DrctDbtTxInf", "PmtId", "EndToEndId"])
+                        .unwrap()
+                        .to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                ("FRST".to_string(), "2025-10".to_string()),
+                ("RCUR".to_string(), "2025-11".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pain008_rejects_invalid_collections() {
+        let debit = DirectDebit {
+            invoice: invoice("1", client("Acme S.A.", "A28015865"), 100.0),
+            mandate: mandate("MANDATE-1", SepaScheme::Core),
+        };
+
+        let mut no_iban = creditor();
+        no_iban.iban = None;
+        let debits = std::slice::from_ref(&debit);
+        assert!(to_pain008(&no_iban, debits, "2025-03-05", created()).is_err());
+        assert!(to_pain008(&creditor(), &[], "2025-03-05", created()).is_err());
+        assert!(to_pain008(&creditor(), debits, "2025-01-05", created()).is_err());
+        assert!(to_pain008(&creditor(), debits, "tomorrow", created()).is_err());
+
+        let mut paid = debit;
+        paid.invoice.paid_date = Some("2025-02-10".to_string());
+        assert!(to_pain008(&creditor(), &[paid], "2025-03-05", created()).is_err());
+    }
+
+    #[test]
+    fn test_export_marks_mandates_as_recurrent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let storage = Storage::new(path).unwrap();
+        let clients = ClientService::new(
+            storage.clone(),
+            Box::new(crate::services::vies::SoapViesClient::new(String::new())),
+        );
+        let sepa = SepaService::new(storage.clone(), format!("{}/exports", path)).unwrap();
+
+        let acme = clients
+            .create_client(
+                "Acme S.A.".to_string(),
+                "A28015865".to_string(),
+                "Madrid".to_string(),
+                None,
+            )
+            .unwrap();
+        let other = client("Beta S.L.", "B87654321");
+        storage.save_client(&other).unwrap();
+
+        let mut bad = mandate("MANDATE-1", SepaScheme::Core);
+        bad.iban = "ES6000491500051234567893".to_string();
+        assert!(clients.set_mandate(&acme, bad).is_err());
+        let acme = clients
+            .set_mandate(&acme, mandate(" MANDATE-1 ", SepaScheme::Core))
+            .unwrap();
+        assert_eq!(acme.mandate.as_ref().unwrap().id, "MANDATE-1");
+
+        let mut paid = invoice("2", acme.clone(), 10.0);
+        paid.paid_date = Some("2025-02-10".to_string());
+        storage
+            .save_invoice(&invoice("1", acme.clone(), 100.0))
+            .unwrap();
+        storage.save_invoice(&paid).unwrap();
+        storage.save_invoice(&invoice("3", other, 100.0)).unwrap();
+
+        let candidates = sepa.direct_debit_candidates().unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].invoice.id, "1");
+
+        let exported = sepa
+            .export_direct_debit(&creditor(), &candidates, "2025-03-05")
+            .unwrap();
+        assert!(exported.contains("sepa_dd_"));
+        assert!(std::fs::read_to_string(exported)
+            .unwrap()
+            .contains("<SeqTp>FRST</SeqTp>"));
+
+        let candidates = sepa.direct_debit_candidates().unwrap();
+        assert_eq!(
+            candidates[0].mandate.last_collection.as_deref(),
+            Some("2025-03-05")
+        );
+        let xml = to_pain008(&creditor(), &candidates, "2025-04-05", created()).unwrap();
+        assert!(xml.contains("<SeqTp>RCUR</SeqTp>"));
+    }
+}
+
+#[cfg(test)]
+mod reconciliation_tests {
+    use super::fixtures::{client, invoice_for, invoice_service};
+    use crate::models::{Invoice, Rule};
+    use crate::repository::Storage;
+    use crate::services::bank_statement::{parse_camt053, parse_norma43, parse_statement};
+    use crate::services::reconciliation::{propose_matches, MatchReason};
+    use crate::services::ReconciliationService;
+
+    // Norma 43 account with a credit, a debit and a credit with two concept lines
+    fn norma43() -> String {
+        let records = [
+            format!(
+                "11{}{}{}{}{}{}{}{}{}{:<26}{:<3}",
+                "2100",
+                "0418",
+                "0200051332",
+                "250301",
+                "250331",
+                "2",
+                "00000000100000",
+                "978",
+                "3",
+                "JOHN DOE",
+                ""
+            ),
+            format!(
+                "22{:<4}{}{}{}{}{}{}{}{}{:<12}{:<16}",
+                "",
+                "0418",
+                "250305",
+                "250305",
+                "02",
+                "099",
+                "2",
+                "00000000121000",
+                "0000000000",
+                "",
+                ""
+            ),
+            format!(
+                "2301{:<38}{:<38}",
+                "TRANSF DE ACME SOLUCIONES S.L.", "FRA 2025-10"
+            ),
+            format!(
+                "22{:<4}{}{}{}{}{}{}{}{}{:<12}{:<16}",
+                "",
+                "0418",
+                "250306",
+                "250306",
+                "04",
+                "012",
+                "1",
+                "00000000005000",
+                "0000000000",
+                "",
+                ""
+            ),
+            format!("2301{:<38}{:<38}", "RECIBO LUZ", ""),
+            format!(
+                "22{:<4}{}{}{}{}{}{}{}{}{:<12}{:<16}",
+                "",
+                "0418",
+                "250310",
+                "250310",
+                "02",
+                "099",
+                "2",
+                "00000000050000",
+                "0000000000",
+                "REF123",
+                ""
+            ),
+            format!("2301{:<38}{:<38}", "TRANSFERENCIA DE BETA", "PAGO PROYECTO"),
+            format!("2302{:<38}{:<38}", "MARZO", ""),
+            format!(
+                "33{}{}{}{}{}{}{}{}{}{}{:<4}",
+                "2100",
+                "0418",
+                "0200051332",
+                "00001",
+                "00000000005000",
+                "00002",
+                "00000000171000",
+                "2",
+                "00000000266000",
+                "978",
+                ""
+            ),
+            format!("88{}{:06}{:<54}", "9".repeat(18), 9, ""),
+        ];
+        for record in &records {
+            assert_eq!(record.len(), 80, "{}", record);
+        }
+        records.join("\r\n")
+    }
+
+    const CAMT053: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <GrpHdr><MsgId>STMT-1</MsgId><CreDtTm>2025-03-31T20:00:00</CreDtTm></GrpHdr>
+    <Stmt>
+      <Id>1</Id>
+      <Acct><Id><IBAN>ES9121000418450200051332</IBAN></Id></Acct>
+      <Ntry>
+        <Amt Ccy="EUR">1210.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2025-03-05</Dt></BookgDt>
+        <ValDt><Dt>2025-03-05</Dt></ValDt>
+        <AcctSvcrRef>BANKREF1</AcctSvcrRef>
+        <NtryDtls><TxDtls>
+          <Refs><EndToEndId>NOTPROVIDED</EndToEndId></Refs>
+          <RltdPties><Dbtr><Nm>Acme Soluciones SL</Nm></Dbtr></RltdPties>
+          <RmtInf><Ustrd>Factura 2025-10</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">99.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>PDNG</Sts>
+        <BookgDt><Dt>2025-03-06</Dt></BookgDt>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">700.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><DtTm>2025-03-10T09:00:00</DtTm></BookgDt>
+        <NtryDtls>
+          <TxDtls>
+            <AmtDtls><TxAmt><Amt Ccy="EUR">500.00</Amt></TxAmt></AmtDtls>
+            <RltdPties><Dbtr><Nm>Beta</Nm></Dbtr></RltdPties>
+            <RmtInf><Ustrd>Pago proyecto</Ustrd></RmtInf>
+          </TxDtls>
+          <TxDtls>
+            <AmtDtls><TxAmt><Amt Ccy="EUR">200.00</Amt></TxAmt></AmtDtls>
+            <RltdPties><Dbtr><Nm>Gamma</Nm></Dbtr></RltdPties>
+            <RmtInf><Ustrd>Fra. 7</Ustrd></RmtInf>
+          </TxDtls>
+        </NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">50.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2025-03-06</Dt></BookgDt>
+        <AddtlNtryInf>RECIBO LUZ</AddtlNtryInf>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+    // Invoice without withholding, so the amount paid is the total
+    fn invoice(id: &str, date: &str, client_name: &str, price: f32) -> Invoice {
+        invoice_for(
+            id,
+            date,
+            client(client_name, "B12345674"),
+            Rule::new(21.0, 0.0),
+            price,
+        )
+    }
+
+    #[test]
+    fn test_parse_norma43() {
+        let transactions = parse_norma43(&norma43()).unwrap();
+        assert_eq!(transactions.len(), 3);
+
+        let first = &transactions[0];
+        assert_eq!(first.account, "210004180200051332");
+        assert_eq!(first.booking_date, "2025-03-05");
+        assert_eq!(first.amount, 121000);
+        assert_eq!(first.reference, "");
+        assert_eq!(
+            first.description,
+            "TRANSF DE ACME SOLUCIONES S.L. FRA 2025-10"
+        );
+        assert_eq!(transactions[1].amount, -5000);
+        assert_eq!(transactions[2].reference, "REF123");
+        assert_eq!(
+            transactions[2].description,
+            "TRANSFERENCIA DE BETA PAGO PROYECTO MARZO"
+        );
+
+        // Files in ISO-8859-1 and with wrong totals
+        let latin1: Vec<u8> = norma43()
+            .replace("RECIBO LUZ", "RECIBO LUÑ")
+            .chars()
+            .map(|c| c as u8)
+            .collect();
+        assert_eq!(
+            parse_statement(&latin1).unwrap()[1].description,
+            "RECIBO LUÑ"
+        );
+        let wrong_totals = norma43().replace("00002000000001710002", "00002000000001710012");
+        assert!(parse_norma43(&wrong_totals).is_err());
+        assert!(parse_statement(b"not a statement").is_err());
+    }
+
+    #[test]
+    fn test_parse_camt053() {
+        let transactions = parse_statement(CAMT053.as_bytes()).unwrap();
+        assert_eq!(transactions.len(), 4);
+
+        let first = &transactions[0];
+        assert_eq!(first.account, "ES9121000418450200051332");
+        assert_eq!(first.amount, 121000);
+        assert_eq!(first.reference, "BANKREF1");
+        assert_eq!(first.counterparty.as_deref(), Some("Acme Soluciones SL"));
+        assert_eq!(first.description, "Factura 2025-10");
+
+        // Batched entry split in its transactions, pending entry skipped
+        assert_eq!(transactions[1].amount, 50000);
+        assert_eq!(transactions[1].booking_date, "2025-03-10");
+        assert_eq!(transactions[2].amount, 20000);
+        assert_eq!(transactions[2].counterparty.as_deref(), Some("Gamma"));
+        assert_eq!(transactions[3].amount, -5000);
+        assert_eq!(transactions[3].description, "RECIBO LUZ");
+
+        assert!(parse_camt053("<Document><Other/></Document>").is_err());
+        assert_ne!(transactions[1].id(), transactions[2].id());
+        assert_eq!(
+            transactions[0].id(),
+            parse_camt053(CAMT053).unwrap()[0].id()
+        );
+    }
+
+    #[test]
+    fn test_identical_movements_have_their_own_id() {
+        // The same bill charged twice on the same day
+        let statement = norma43();
+        let lines: Vec<&str> = statement.split("\r\n").collect();
+        let mut repeated = lines[..5].to_vec();
+        repeated.extend_from_slice(&lines[3..]);
+        let repeated = repeated.join("\r\n").replace(
+            "020005133200001000000000050000",
+            "020005133200002000000000100000",
+        );
+        let transactions = parse_norma43(&repeated).unwrap();
+        assert_eq!(transactions.len(), 4);
+        assert_eq!(transactions[1].description, transactions[2].description);
+        assert_ne!(transactions[1].id(), transactions[2].id());
+        assert_eq!(
+            transactions[1].id(),
+            parse_norma43(&statement).unwrap()[1].id()
+        );
+        assert_eq!(
+            transactions[2].id(),
+            parse_norma43(&repeated).unwrap()[2].id()
+        );
+
+        let start = CAMT053.rfind("      <Ntry>").unwrap();
+        let end = CAMT053.find("    </Stmt>").unwrap();
+        let entry = &CAMT053[start..end];
+        let transactions = parse_camt053(&CAMT053.replacen(entry, &entry.repeat(2), 1)).unwrap();
+        assert_eq!(transactions.len(), 5);
+        assert_ne!(transactions[3].id(), transactions[4].id());
+    }
+
+    #[test]
+    fn test_matches_by_amount_number_and_name() {
+        let transactions = parse_camt053(CAMT053).unwrap();
+        let mut paid = invoice("2025-01", "2025-01-10", "Acme Soluciones S.L.", 1000.0);
+        paid.paid_date = Some("2025-02-01".to_string());
+        let invoices = vec![
+            paid,
+            invoice("2025-10", "2025-02-01", "Acme Soluciones S.L.", 1000.0),
+            invoice("2025-11", "2025-02-15", "Beta Consulting", 1000.0),
+            invoice("2025-12", "2025-02-20", "Zeta", 500.0 / 1.21),
+            invoice("7", "2025-02-25", "Gamma", 150.0),
+            invoice("2025-13", "2025-04-01", "Late", 500.0 / 1.21),
+        ];
+
+        let proposals = propose_matches(&transactions, &invoices);
+        let summary: Vec<(&str, &str, Vec<MatchReason>)> = proposals
+            .iter()
+            .map(|p| {
+                (
+                    p.transaction.booking_date.as_str(),
+                    p.invoice.id.as_str(),
+                    p.reasons.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "2025-03-05",
+                    "2025-10",
+                    vec![
+                        MatchReason::Amount,
+                        MatchReason::InvoiceNumber,
+                        MatchReason::ClientName
+                    ]
+                ),
+                ("2025-03-10", "2025-12", vec![MatchReason::Amount]),
+                (
+                    "2025-03-10",
+                    "7",
+                    vec![MatchReason::InvoiceNumber, MatchReason::ClientName]
+                ),
+            ]
+        );
+        assert_eq!(proposals[0].score(), 110);
+    }
+
+    #[test]
+    fn test_confirmed_matches_are_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let storage = Storage::new(path).unwrap();
+        let invoices = invoice_service(path);
+        let reconciliation = ReconciliationService::new(storage.clone());
+
+        let statement = dir.path().join("statement.n43");
+        std::fs::write(&statement, norma43()).unwrap();
+        storage
+            .save_invoice(&invoice(
+                "2025-10",
+                "2025-02-01",
+                "Acme Soluciones S.L.",
+                1000.0,
+            ))
+            .unwrap();
+
+        let transactions = reconciliation.read_statement(&statement).unwrap();
+        let proposals = reconciliation.propose_matches(&transactions).unwrap();
+        assert_eq!(proposals.len(), 1);
+        invoices
+            .record_bank_payment(&proposals[0].invoice, &proposals[0].transaction)
+            .unwrap();
+
+        let stored = storage.get_invoice("2025-10").unwrap().unwrap();
+        assert_eq!(stored.paid_date.as_deref(), Some("2025-03-05"));
+        let recorded = storage.list_reconciliations().unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].transaction_id, transactions[0].id());
+        assert_eq!(recorded[0].amount, 1210.0);
+
+        // The same movement is not proposed again, even for another invoice
+        storage
+            .save_invoice(&invoice(
+                "2025-14",
+                "2025-02-01",
+                "Acme Soluciones S.L.",
+                1000.0,
+            ))
+            .unwrap();
+        assert!(reconciliation
+            .propose_matches(&transactions)
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[cfg(test)]
+mod journal_tests {
+    use super::fixtures::invoice;
+    use crate::config::AccountMapping;
+    use crate::models::{Invoice, Rule};
+    use crate::repository::Storage;
+    use crate::services::journal::{
+        client_subaccounts, journal_entries, to_a3, to_contaplus, to_csv, JournalEntry,
+    };
+    use crate::services::period::{DateRange, Quarter};
+    use crate::services::AccountingService;
+
+    fn invoices() -> Vec<Invoice> {
+        vec![
+            invoice(
+                "2025-2",
+                "2025-02-01",
+                "B12345674",
+                Rule::new(21.0, 15.0),
+                1000.0,
+            ),
+            invoice(
+                "2025-10",
+                "2025-02-01",
+                "ESB12345674",
+                Rule::new(21.0, 0.0),
+                100.0,
+            ),
+            invoice(
+                "2025-1",
+                "2025-01-15",
+                "A28015865",
+                Rule::new(0.0, 0.0),
+                500.0,
+            ),
+            invoice(
+                "2025-11",
+                "2025-04-01",
+                "A28015865",
+                Rule::new(21.0, 15.0),
+                100.0,
+            ),
+            invoice(
+                "2025-3",
+                "2025-03-01",
+                "X1234567L",
+                Rule::new(21.0, 15.0),
+                100.0,
+            ),
+            invoice(
+                "2025-4",
+                "2025-03-02",
+                "Z2345674L",
+                Rule::new(21.0, 15.0),
+                100.0,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_journal_entries_follow_the_pgc() {
+        let mapping = AccountMapping::default();
+        let entries = journal_entries(
+            &invoices(),
+            Quarter::new(2025, 1).unwrap().into(),
+            &client_subaccounts(&invoices(), &[], &mapping),
+            &mapping,
+        );
+
+        let order: Vec<&str> = entries.iter().map(|e| e.invoice_id.as_str()).collect();
+        assert_eq!(order, ["2025-1", "2025-2", "2025-10", "2025-3", "2025-4"]);
+        assert_eq!(entries[0].number, 1);
+
+        // Client owes the total, the withholding is a credit against Hacienda
+        let lines: Vec<(&str, i64, i64)> = entries[1]
+            .lines
+            .iter()
+            .map(|line| (line.account.as_str(), line.debit, line.credit))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("43045674", 106000, 0),
+                ("47300000", 15000, 0),
+                ("70500000", 0, 100000),
+                ("47700000", 0, 21000),
+            ]
+        );
+        // Exempt invoices have no IVA line
+        assert_eq!(entries[0].lines.len(), 2);
+        for entry in &entries {
+            let debit: i64 = entry.lines.iter().map(|l| l.debit).sum();
+            let credit: i64 = entry.lines.iter().map(|l| l.credit).sum();
+            assert_eq!(debit, credit, "entry {} is not balanced", entry.number);
+        }
+
+        // Both spellings of a tax ID share a subaccount, equal endings get the next number
+        assert_eq!(entries[2].lines[0].account, "43045674");
+        assert_eq!(entries[3].lines[0].account, "43034567");
+        assert_eq!(entries[4].lines[0].account, "43045675");
+
+        let mapping = AccountMapping {
+            sales_account: "700".to_string(),
+            subaccount_digits: 10,
+            ..AccountMapping::default()
+        };
+        let accounts = client_subaccounts(&invoices(), &[], &mapping);
+        assert_eq!(accounts["B12345674"], "4302345674");
+        let entries = journal_entries(&invoices(), DateRange::year(2025), &accounts, &mapping);
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[1].lines[2].account, "7000000000");
+        assert_eq!(entries[1].lines[2].account_name, "Ventas de mercaderías");
+    }
+
+    #[test]
+    fn test_client_subaccounts_are_kept_between_exports() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let storage = Storage::new(path).unwrap();
+        let service = AccountingService::new(
+            storage.clone(),
+            format!("{}/exports", path),
+            AccountMapping::default(),
+        )
+        .unwrap();
+        let range = DateRange::year(2025);
+        let account = |entries: &[JournalEntry], id: &str| {
+            let entry = entries.iter().find(|e| e.invoice_id == id).unwrap();
+            entry.lines[0].account.clone()
+        };
+
+        let invoices = invoices();
+        storage.save_invoice(&invoices[5]).unwrap();
+        let first = service.journal(range).unwrap();
+        assert_eq!(account(&first, "2025-4"), "43045674");
+
+        // A client sorting first with the same tax ID ending gets the next number
+        for invoice in &invoices {
+            storage.save_invoice(invoice).unwrap();
+        }
+        let second = service.journal(range).unwrap();
+        assert_eq!(account(&second, "2025-4"), "43045674");
+        assert_eq!(account(&second, "2025-2"), "43045675");
+        assert_eq!(account(&second, "2025-10"), "43045675");
+
+        let stored = storage.list_clients().unwrap();
+        assert_eq!(stored.len(), 4);
+        assert!(stored.iter().all(|client| client.subaccount.is_some()));
+        let accounts = client_subaccounts(&invoices, &stored, &AccountMapping::default());
+        assert_eq!(accounts["Z2345674L"], "43045674");
+    }
+
+    #[test]
+    fn test_journal_export_formats() {
+        let mapping = AccountMapping::default();
+        let entries = journal_entries(
+            &invoices(),
+            Quarter::new(2025, 1).unwrap().into(),
+            &client_subaccounts(&invoices(), &[], &mapping),
+            &mapping,
+        );
+
+        let csv = String::from_utf8(to_csv(&entries).unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            rows[0],
+            "Asiento;Fecha;Cuenta;Nombre Cuenta;Concepto;Documento;Debe;Haber"
+        );
+        assert_eq!(
+            rows[3],
+            "2;01/02/2025;43045674;Client B12345674;Fra. 2025-2 Client B12345674;2025-2;1060,00;0,00"
+        );
+        assert_eq!(rows.len(), 1 + 2 + 4 + 3 + 4 + 4);
+
+        let contaplus = String::from_utf8(to_contaplus(&entries)).unwrap();
+        let records: Vec<&str> = contaplus.split("\r\n").filter(|l| !l.is_empty()).collect();
+        assert_eq!(records.len(), 17);
+        assert!(records.iter().all(|r| r.chars().count() == 297));
+        let vat = records[5];
+        assert_eq!(&vat[..6], "     2");
+        assert_eq!(&vat[6..14], "20250201");
+        assert_eq!(vat[14..26].trim(), "47700000");
+        assert_eq!(vat[26..38].trim(), "43045674");
+        assert_eq!(vat[95..103].trim(), "2");
+        assert_eq!(vat[103..119].trim(), "1000.00");
+        assert_eq!(vat[119..124].trim(), "21.00");
+        assert_eq!(vat[237..238].trim(), "2");
+        assert_eq!(vat[238..254].trim(), "0.00");
+        assert_eq!(vat[254..270].trim(), "210.00");
+
+        let a3 = String::from_utf8(to_a3(&entries, "12")).unwrap();
+        let records: Vec<&str> = a3.split("\r\n").filter(|l| !l.is_empty()).collect();
+        assert_eq!(records.len(), 17);
+        assert!(records.iter().all(|r| r.chars().count() == 254));
+        let first = records[2];
+        assert_eq!(&first[..15], "500012202502010");
+        assert_eq!(first[15..27].trim(), "43045674");
+        assert_eq!(&first[57..58], "D");
+        assert_eq!(first[58..68].trim(), "2025-2");
+        assert_eq!(&first[68..69], "I");
+        assert_eq!(&first[99..113], "+0000001060.00");
+        assert_eq!(&records[5][68..69], "U");
+        assert_eq!(&records[5][57..58], "H");
+        assert!(first.ends_with("N EN"));
+    }
+
+    #[test]
+    fn test_accounting_service_exports_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let storage = Storage::new(path).unwrap();
+        for invoice in invoices() {
+            storage.save_invoice(&invoice).unwrap();
+        }
+        let service = AccountingService::new(
+            storage,
+            format!("{}/exports", path),
+            AccountMapping::default(),
+        )
+        .unwrap();
+
+        let range = DateRange::year(2025);
+        let entries = service.journal(range).unwrap();
+        assert_eq!(entries.len(), 6);
+        let paths = service.export_journal(&entries, range).unwrap();
+        assert!(paths[0].ends_with("diario_20250101_20251231.csv"));
+        assert!(paths[1].ends_with("diario_20250101_20251231_contaplus.txt"));
+        assert!(paths[2].ends_with("diario_20250101_20251231_a3.txt"));
+        for path in paths {
+            assert!(std::fs::metadata(path).unwrap().len() > 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod plain_text_tests {
+    use super::fixtures::{client, invoice_for};
+    use crate::models::{Invoice, Rule};
+    use crate::services::period::{DateRange, Quarter};
+    use crate::services::plain_text::{to_beancount, to_ledger};
+
+    // Client name with quotes the exports have to escape
+    fn invoice(id: &str, date: &str, cif: &str, rule: Rule, price: f32) -> Invoice {
+        invoice_for(id, date, client("Acme \"Tools\" SL", cif), rule, price)
+    }
+
+    fn invoices() -> Vec<Invoice> {
+        let mut paid = invoice(
+            "2025-1",
+            "2025-01-15",
+            "A28015865",
+            Rule::new(21.0, 0.0),
+            500.0,
+        );
+        paid.paid_date = Some("2025-02-01".to_string());
+        vec![
+            invoice(
+                "2025-10",
+                "2025-02-01",
+                "ESB12345674",
+                Rule::new(21.0, 15.0),
+                1000.0,
+            ),
+            invoice(
+                "2025-2",
+                "2025-02-01",
+                "B12345674",
+                Rule::new(0.0, 0.0),
+                100.0,
+            ),
+            paid,
+            invoice(
+                "2025-11",
+                "2025-04-01",
+                "B12345674",
+                Rule::new(21.0, 15.0),
+                100.0,
+            ),
+        ]
+    }
+
+    fn quarter() -> DateRange {
+        Quarter::new(2025, 1).unwrap().into()
+    }
+
+    #[test]
+    fn test_ledger_transactions() {
+        let ledger = to_ledger(&invoices(), quarter());
+        let expected = "\
+2025-01-15 * (2025-1) Acme \"Tools\" SL
+    ; Invoice 2025-1
+    ; invoice: 2025-1
+    ; cif: A28015865
+    Assets:Receivable:A28015865                       605.00 EUR
+    Income:Sales                                     -500.00 EUR
+    Liabilities:Taxes:IVA                            -105.00 EUR
+
+2025-02-01 * (2025-2) Acme \"Tools\" SL
+    ; Invoice 2025-2
+    ; invoice: 2025-2
+    ; cif: B12345674
+    Assets:Receivable:B12345674                       100.00 EUR
+    Income:Sales                                     -100.00 EUR
+
+2025-02-01 * (2025-10) Acme \"Tools\" SL
+    ; Invoice 2025-10
+    ; invoice: 2025-10
+    ; cif: B12345674
+    Assets:Receivable:B12345674                      1060.00 EUR
+    Assets:Taxes:IRPF                                 150.00 EUR
+    Income:Sales                                    -1000.00 EUR
+    Liabilities:Taxes:IVA                            -210.00 EUR
+
+2025-02-01 * (2025-1) Acme \"Tools\" SL
+    ; Payment of invoice 2025-1
+    ; invoice: 2025-1
+    ; cif: A28015865
+    Assets:Bank                                       605.00 EUR
+    Assets:Receivable:A28015865                      -605.00 EUR
+
+";
+        assert_eq!(ledger, expected);
+    }
+
+    #[test]
+    fn test_beancount_opens_accounts_and_quotes_strings() {
+        let beancount = to_beancount(&invoices(), quarter());
+        let lines: Vec<&str> = beancount.lines().collect();
+        assert_eq!(lines[0], "option \"operating_currency\" \"EUR\"");
+        assert_eq!(
+            &lines[2..9],
+            [
+                "2025-02-01 open Assets:Bank EUR",
+                "2025-01-15 open Assets:Receivable:A28015865 EUR",
+                "2025-02-01 open Assets:Receivable:B12345674 EUR",
+                "2025-02-01 open Assets:Taxes:IRPF EUR",
+                "2025-01-15 open Income:Sales EUR",
+                "2025-01-15 open Liabilities:Taxes:IVA EUR",
+                "",
+            ]
+        );
+        assert_eq!(
+            &lines[9..15],
+            [
+                "2025-01-15 * \"Acme \\\"Tools\\\" SL\" \"Invoice 2025-1\"",
+                "  invoice: \"2025-1\"",
+                "  cif: \"A28015865\"",
+                "  Assets:Receivable:A28015865                         605.00 EUR",
+                "  Income:Sales                                       -500.00 EUR",
+                "  Liabilities:Taxes:IVA                              -105.00 EUR",
+            ]
+        );
+        assert!(!beancount.contains("2025-11"));
+    }
+
+    #[test]
+    fn test_exports_do_not_depend_on_the_invoice_order() {
+        let mut reversed = invoices();
+        reversed.reverse();
+        assert_eq!(
+            to_ledger(&invoices(), quarter()),
+            to_ledger(&reversed, quarter())
+        );
+        assert_eq!(
+            to_beancount(&invoices(), quarter()),
+            to_beancount(&reversed, quarter())
+        );
+    }
+}
+
+#[cfg(test)]
+mod recurring_tests {
+    use super::fixtures::{client, invoice_service, user};
+    use crate::models::{Frequency, Item, Rule};
+    use crate::repository::Storage;
+    use crate::services::recurring::{occurrences, CreateRecurringParams};
+    use crate::services::{InvoiceService, RecurringService};
+    use chrono::NaiveDate;
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn params(
+        frequency: Frequency,
+        day: u32,
+        start: &str,
+        end: Option<&str>,
+    ) -> CreateRecurringParams {
+        CreateRecurringParams {
+            client: client("Retainer SL", "B12345674"),
+            rule: Rule::new(21.0, 15.0),
+            items: vec![Item::new("Monthly support".to_string(), 1, 500.0)],
+            frequency,
+            day_of_month: day,
+            start_date: start.to_string(),
+            end_date: end.map(str::to_string),
+        }
+    }
+
+    fn services(path: &str) -> (Storage, RecurringService, InvoiceService) {
+        let storage = Storage::new(path).unwrap();
+        (
+            storage.clone(),
+            RecurringService::new(storage),
+            invoice_service(path),
+        )
+    }
+
+    #[test]
+    fn test_occurrences_use_the_last_day_of_short_months() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, service, _) = services(dir.path().to_str().unwrap());
+
+        let monthly = service
+            .create_template(params(
+                Frequency::Monthly,
+                31,
+                "2025-01-15",
+                Some("2025-04-30"),
+            ))
+            .unwrap();
+        let dates: Vec<NaiveDate> = occurrences(&monthly, date("2025-12-31"));
+        assert_eq!(
+            dates,
+            [
+                date("2025-01-31"),
+                date("2025-02-28"),
+                date("2025-03-31"),
+                date("2025-04-30")
+            ]
+        );
+
+        // The start month is skipped when its day has already passed
+        let quarterly = service
+            .create_template(params(Frequency::Quarterly, 1, "2025-01-15", None))
+            .unwrap();
+        assert_eq!(
+            occurrences(&quarterly, date("2025-10-01")),
+            [date("2025-04-01"), date("2025-07-01"), date("2025-10-01")]
+        );
+
+        let yearly = service
+            .create_template(params(Frequency::Yearly, 29, "2024-02-01", None))
+            .unwrap();
+        assert_eq!(
+            occurrences(&yearly, date("2026-03-01")),
+            [date("2024-02-29"), date("2025-02-28"), date("2026-02-28")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, service, _) = services(dir.path().to_str().unwrap());
+
+        assert!(service
+            .create_template(params(Frequency::Monthly, 0, "2025-01-01", None))
+            .is_err());
+        assert!(service
+            .create_template(params(Frequency::Monthly, 32, "2025-01-01", None))
+            .is_err());
+        assert!(service
+            .create_template(params(Frequency::Monthly, 1, "2025-13-01", None))
+            .is_err());
+        assert!(service
+            .create_template(params(
+                Frequency::Monthly,
+                1,
+                "2025-03-01",
+                Some("2025-02-01")
+            ))
+            .is_err());
+        let mut no_items = params(Frequency::Monthly, 1, "2025-01-01", None);
+        no_items.items.clear();
+        assert!(service.create_template(no_items).is_err());
+        assert!(service.list_templates().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_generate_due_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let (storage, service, invoices) = services(dir.path().to_str().unwrap());
+        let user = user();
+        service
+            .create_template(params(Frequency::Monthly, 5, "2025-01-01", None))
+            .unwrap();
+
+        // A dry run stores nothing
+        let preview = service
+            .generate_due(&invoices, &user, date("2025-03-10"), true)
+            .unwrap();
+        assert_eq!(preview.len(), 3);
+        assert!(storage.list_invoices().unwrap().is_empty());
+
+        let created = service
+            .generate_due(&invoices, &user, date("2025-03-10"), false)
+            .unwrap();
+        let dates: Vec<&str> = created.iter().map(|i| i.date.as_str()).collect();
+        assert_eq!(dates, ["2025-01-05", "2025-02-05", "2025-03-05"]);
+        assert_eq!(created[0].total, 530.0);
+        assert_eq!(created[0].due_date, "2025-02-04");
+        assert_eq!(storage.list_invoices().unwrap().len(), 3);
+
+        assert!(service
+            .generate_due(&invoices, &user, date("2025-03-10"), false)
+            .unwrap()
+            .is_empty());
+        let next = service
+            .generate_due(&invoices, &user, date("2025-04-05"), false)
+            .unwrap();
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].date, "2025-04-05");
+        assert_eq!(storage.list_invoices().unwrap().len(), 4);
+    }
+}
+
+#[cfg(test)]
+mod quote_tests {
+    use super::fixtures::{self, client, invoice_service};
+    use crate::models::{Item, QuoteKind, QuoteStatus, Rule, User};
+    use crate::repository::Storage;
+    use crate::services::quote::CreateQuoteParams;
+    use crate::services::{InvoiceService, PdfService, QuoteService};
+    use printpdf::lopdf::Document;
+
+    fn user() -> User {
+        let mut user = fixtures::user();
+        user.iban = Some("ES9121000418450200051332".to_string());
+        user
+    }
+
+    fn params(kind: QuoteKind, date: &str) -> CreateQuoteParams {
+        CreateQuoteParams {
+            kind,
+            date: Some(date.to_string()),
+            valid_until: None,
+            user: user(),
+            client: client("Project SL", "B12345674"),
+            rule: Rule::new(21.0, 15.0),
+            items: vec![Item::new("Website".to_string(), 2, 1000.0)],
+        }
+    }
+
+    fn services(path: &str) -> (Storage, QuoteService, InvoiceService) {
+        let storage = Storage::new(path).unwrap();
+        let pdf = PdfService::new(format!("{}/pdfs", path), false, "EUR".to_string()).unwrap();
+        (
+            storage.clone(),
+            QuoteService::new(storage, pdf),
+            invoice_service(path),
+        )
+    }
+
+    #[test]
+    fn test_quotes_have_their_own_series() {
+        let dir = tempfile::tempdir().unwrap();
+        let (storage, quotes, _) = services(dir.path().to_str().unwrap());
+
+        let first = quotes
+            .create_quote(params(QuoteKind::Quote, "2025-03-01"))
+            .unwrap();
+        assert_eq!(first.id, "P2025-001");
+        assert_eq!(first.valid_until, "2025-03-31");
+        assert_eq!(first.status, QuoteStatus::Pending);
+        assert_eq!(first.total, 2120.0);
+
+        let ids: Vec<String> = [
+            (QuoteKind::Quote, "2025-04-01"),
+            (QuoteKind::Proforma, "2025-04-02"),
+            (QuoteKind::Quote, "2026-01-10"),
+        ]
+        .into_iter()
+        .map(|(kind, date)| quotes.create_quote(params(kind, date)).unwrap().id)
+        .collect();
+        assert_eq!(ids, ["P2025-002", "PF2025-001", "P2026-001"]);
+        assert!(storage.list_invoices().unwrap().is_empty());
+
+        let mut expired = params(QuoteKind::Quote, "2025-03-01");
+        expired.valid_until = Some("2025-02-01".to_string());
+        assert!(quotes.create_quote(expired).is_err());
+        let mut empty = params(QuoteKind::Quote, "2025-03-01");
+        empty.items.clear();
+        assert!(quotes.create_quote(empty).is_err());
+    }
+
+    #[test]
+    fn test_accepted_quote_becomes_a_linked_invoice() {
+        let dir = tempfile::tempdir().unwrap();
+        let (storage, quotes, invoices) = services(dir.path().to_str().unwrap());
+        let quote = quotes
+            .create_quote(params(QuoteKind::Quote, "2025-03-01"))
+            .unwrap();
+
+        // Pending and rejected quotes are not invoiced
+        assert!(quotes
+            .convert_to_invoice(&quote, &invoices, &user(), None, None)
+            .is_err());
+        let rejected = quotes.set_status(&quote, QuoteStatus::Rejected).unwrap();
+        assert!(quotes
+            .convert_to_invoice(&rejected, &invoices, &user(), None, None)
+            .is_err());
+
+        let accepted = quotes.set_status(&rejected, QuoteStatus::Accepted).unwrap();
+        let invoice = quotes
+            .convert_to_invoice(
+                &accepted,
+                &invoices,
+                &user(),
+                Some("2025-7".to_string()),
+                Some("2025-03-20".to_string()),
+            )
+            .unwrap();
+        assert_eq!(invoice.id, "2025-7");
+        assert_eq!(invoice.quote_id.as_deref(), Some("P2025-001"));
+        assert_eq!(invoice.total, accepted.total);
+        assert_eq!(
+            storage.get_invoice("2025-7").unwrap().unwrap().quote_id,
+            invoice.quote_id
+        );
+
+        let stored = quotes.list_quotes().unwrap().remove(0);
+        assert_eq!(stored.invoice_id.as_deref(), Some("2025-7"));
+        assert!(quotes
+            .convert_to_invoice(&stored, &invoices, &user(), None, None)
+            .is_err());
+        assert!(quotes.set_status(&stored, QuoteStatus::Rejected).is_err());
+        assert_eq!(storage.list_invoices().unwrap().len(), 1);
+    }
+
+    // Builtin font text is written as hex strings in the content stream
+    fn page_text(path: &str) -> String {
+        let document = Document::load(path).unwrap();
+        let page = *document.get_pages().values().next().unwrap();
+        String::from_utf8_lossy(&document.get_page_content(page).unwrap()).to_string()
+    }
+
+    fn hex(text: &str) -> String {
+        text.bytes().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    #[test]
+    fn test_quote_numbers_with_slashes_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let (storage, quotes, _) = services(dir.path().to_str().unwrap());
+
+        let mut quote = quotes
+            .create_quote(params(QuoteKind::Quote, "2025-03-01"))
+            .unwrap();
+        quote.id = "2025/P-7".to_string();
+        storage.save_quote(&quote).unwrap();
+        assert!(dir.path().join("quotes/2025_P-7.json").is_file());
+        assert!(storage
+            .list_quotes()
+            .unwrap()
+            .iter()
+            .any(|stored| stored.id == "2025/P-7"));
+    }
+
+    #[test]
+    fn test_quote_pdf_titles() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, quotes, _) = services(dir.path().to_str().unwrap());
+
+        let quote = quotes
+            .create_quote(params(QuoteKind::Quote, "2025-03-01"))
+            .unwrap();
+        let path = quotes.generate_pdf(&quote).unwrap();
+        assert!(path.ends_with("quote_P2025-001.pdf"));
+        let text = page_text(&path);
+        assert!(text.contains(&hex("QUOTE #")));
+        assert!(text.contains(&hex("VALID UNTIL")));
+        assert!(!text.contains(&hex("DUE DATE")));
+        assert!(!text.contains(&hex("Scan to pay")));
+
+        let proforma = quotes
+            .create_quote(params(QuoteKind::Proforma, "2025-03-01"))
+            .unwrap();
+        let path = quotes.generate_pdf(&proforma).unwrap();
+        assert!(path.ends_with("proforma_PF2025-001.pdf"));
+        let text = page_text(&path);
+        assert!(text.contains(&hex("PROFORMA INVOICE")));
+        assert!(text.contains(&hex("This proforma is not a tax invoice")));
+        assert!(text.contains(&hex("Scan to pay")));
+    }
+}
+
+#[cfg(test)]
+mod simplified_invoice_tests {
+    use super::fixtures::{client_at, invoice_service, user};
+    use crate::models::{InvoiceKind, Item, Rule};
+    use crate::services::invoice::CreateSimplifiedInvoiceParams;
+    use crate::services::modelo347::Modelo347;
+    use printpdf::lopdf::Document;
+
+    fn params(date: &str, price: f32) -> CreateSimplifiedInvoiceParams {
+        CreateSimplifiedInvoiceParams {
+            invoice_number: None,
+            date: Some(date.to_string()),
+            user: user(),
+            client: None,
+            rule: Rule::new(21.0, 0.0),
+            items: vec![Item::new("Repair".to_string(), 1, price)],
+        }
+    }
+
+    #[test]
+    fn test_simplified_invoices_have_their_own_series() {
+        let dir = tempfile::tempdir().unwrap();
+        let invoices = invoice_service(dir.path().to_str().unwrap());
+
+        let first = invoices
+            .create_simplified_invoice(params("2025-03-01", 100.0))
+            .unwrap();
+        assert_eq!(first.id, "T2025-0001");
+        assert_eq!(first.kind, InvoiceKind::Simplified);
+        assert_eq!(first.due_date, "2025-03-01");
+        assert_eq!(first.client_name(), "Final consumer");
+        assert_eq!(first.total, 121.0);
+
+        let ids: Vec<String> = ["2025-03-02", "2026-01-05"]
+            .into_iter()
+            .map(|date| {
+                invoices
+                    .create_simplified_invoice(params(date, 10.0))
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        assert_eq!(ids, ["T2025-0002", "T2026-0001"]);
+    }
+
+    #[test]
+    fn test_simplified_invoice_limit_and_irpf() {
+        let dir = tempfile::tempdir().unwrap();
+        let invoices = invoice_service(dir.path().to_str().unwrap());
+
+        // 330.58 + 21% is 400.00 exactly, one cent more is over the limit
+        assert!(invoices
+            .create_simplified_invoice(params("2025-03-01", 330.58))
+            .is_ok());
+        assert!(invoices
+            .create_simplified_invoice(params("2025-03-01", 330.59))
+            .is_err());
+
+        let mut withheld = params("2025-03-01", 100.0);
+        withheld.rule = Rule::new(21.0, 15.0);
+        assert!(invoices.create_simplified_invoice(withheld).is_err());
+
+        // 340 + 71.40 IVA is over the limit, even if the total after IRPF is 360.40
+        let mut identified = params("2025-03-01", 340.0);
+        identified.client = Some(client_at(
+            "Project SL",
+            "B12345674",
+            "Calle Alcala 1, 28014 Madrid",
+        ));
+        identified.rule = Rule::new(21.0, 15.0);
+        let error = invoices.create_simplified_invoice(identified).unwrap_err();
+        assert!(error.to_string().contains("this one is 411.40€"));
+        let mut empty = params("2025-03-01", 100.0);
+        empty.items.clear();
+        assert!(invoices.create_simplified_invoice(empty).is_err());
+        assert_eq!(invoices.list_invoices().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_simplified_invoices_without_client_are_not_declared() {
+        let dir = tempfile::tempdir().unwrap();
+        let invoices = invoice_service(dir.path().to_str().unwrap());
+
+        let mut identified = params("2025-05-10", 300.0);
+        identified.client = Some(client_at(
+            "Project SL",
+            "B12345674",
+            "Calle Alcala 1, 28014 Madrid",
+        ));
+        let identified = invoices.create_simplified_invoice(identified).unwrap();
+        let anonymous = invoices
+            .create_simplified_invoice(params("2025-05-11", 300.0))
+            .unwrap();
+
+        let modelo = Modelo347::from_invoices(&[identified, anonymous.clone()], 2025);
+        assert!(modelo.clients.is_empty());
+        assert_eq!(modelo.below_threshold, 1);
+        assert!(invoices.export_ubl(&anonymous).is_err());
+    }
+
+    #[test]
+    fn test_simplified_invoice_receipt_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let invoices = invoice_service(dir.path().to_str().unwrap());
+
+        let invoice = invoices
+            .create_simplified_invoice(params("2025-03-01", 100.0))
+            .unwrap();
+        let path = invoices.generate_pdf(&invoice).unwrap();
+        assert!(path.ends_with("invoice_T2025-0001.pdf"));
+
+        let document = Document::load(&path).unwrap();
+        let page = *document.get_pages().values().next().unwrap();
+        let media_box = document
+            .get_object(page)
+            .and_then(|page| page.as_dict()?.get(b"MediaBox")?.as_array().cloned())
+            .unwrap();
+        // 80 mm in points
+        assert!((media_box[2].as_float().unwrap() - 226.77).abs() < 0.1);
+
+        let text = String::from_utf8_lossy(&document.get_page_content(page).unwrap()).to_string();
+        let hex = |text: &str| -> String { text.bytes().map(|b| format!("{:02X}", b)).collect() };
+        assert!(text.contains(&hex("SIMPLIFIED INVOICE")));
+        assert!(text.contains(&hex("TOTAL (IVA incluido)")));
+        assert!(!text.contains(&hex("BILL TO")));
+    }
+}
+
+#[cfg(test)]
+mod time_tracking_tests {
+    use super::fixtures::{client, client_at};
+    use crate::models::Client;
+    use crate::repository::Storage;
+    use crate::services::period::DateRange;
+    use crate::services::time_tracking::{
+        draft_invoices, match_client, parse_time_entries, AmbiguousDates, DateOrder, ItemGrouping,
+        ProjectKey, TimeTracker, TimeTrackingService,
+    };
+    use chrono::NaiveDate;
+    use std::collections::BTreeMap;
+
+    const TOGGL: &str = "\u{feff}User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags,Amount ()
+Jane,jane@example.com,Acme Corp,Website,,Home page,Yes,2025-03-03,09:00:00,2025-03-03,11:30:00,02:30:00,,
+Jane,jane@example.com,Acme Corp,Website,,Contact form,Yes,2025-03-04,09:00:00,2025-03-04,10:00:00,01:00:00,,
+Jane,jane@example.com,Acme Corp,Website,,Team meeting,No,2025-03-05,09:00:00,2025-03-05,10:00:00,01:00:00,,
+Jane,jane@example.com,,B87654321,,Audit,Yes,2025-03-10,09:00:00,2025-03-10,13:00:00,04:00:00,,
+Jane,jane@example.com,Acme Corp,Website,,Home page,Yes,2025-04-01,09:00:00,2025-04-01,10:00:00,01:00:00,,
+";
+
+    const CLOCKIFY: &str = "Project,Client,Description,Task,User,Group,Email,Tags,Billable,Start Date,Start Time,End Date,End Time,Duration (h),Duration (decimal),Billable Rate (EUR),Billable Amount (EUR)
+App,Globex,Login screen,Design,Jane,,jane@example.com,,Yes,03/12/2025,09:00:00,03/12/2025,10:45:00,01:45:00,1.75,0,0
+";
+
+    const HARVEST: &str = "Date,Client,Project,Project Code,Task,Notes,Hours,Hours Rounded,Billable?,Invoiced?,First Name,Last Name
+2025-03-20,Globex,App,,Development,API,\"2,5\",2.5,Yes,No,Jane,Doe
+";
+
+    fn clients() -> Vec<Client> {
+        vec![
+            client("ACME Corp.", "B12345674"),
+            client_at("Initech SL", "B87654321", "Valencia"),
+        ]
+    }
+
+    fn march() -> DateRange {
+        DateRange {
+            from: NaiveDate::from_ymd_opt(2025, 3, 1).unwrap(),
+            to: NaiveDate::from_ymd_opt(2025, 3, 31).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_tracker_exports() {
+        let toggl = parse_time_entries(TOGGL.as_bytes(), None).unwrap();
+        assert_eq!(toggl.tracker, TimeTracker::Toggl);
+        assert_eq!(toggl.entries.len(), 5);
+        assert_eq!(toggl.entries[0].seconds, 9000);
+        assert_eq!(toggl.entries[0].task, "Home page");
+        assert!(!toggl.entries[2].billable);
+
+        let clockify =
+            parse_time_entries(CLOCKIFY.as_bytes(), Some(DateOrder::MonthFirst)).unwrap();
+        assert_eq!(clockify.tracker, TimeTracker::Clockify);
+        assert_eq!(clockify.entries[0].date, "2025-03-12");
+        assert_eq!(clockify.entries[0].seconds, 6300);
+        assert_eq!(clockify.entries[0].task, "Design");
+
+        let harvest = parse_time_entries(HARVEST.as_bytes(), None).unwrap();
+        assert_eq!(harvest.tracker, TimeTracker::Harvest);
+        assert_eq!(harvest.entries[0].seconds, 9000);
+        assert_eq!(harvest.entries[0].client, "Globex");
+
+        assert!(parse_time_entries(b"Name,Amount\nA,1\n", None).is_err());
+        assert!(parse_time_entries(b"Date,Hours\n2025-03-01,many\n", None).is_err());
+    }
+
+    #[test]
+    fn test_date_order_is_found_from_the_whole_column() {
+        let export = |dates: &[&str]| {
+            let mut csv = "Date,Client,Project,Hours\n".to_string();
+            for date in dates {
+                csv.push_str(&format!("{},Globex,App,1\n", date));
+            }
+            csv
+        };
+        let dates = |csv: String, order| -> Vec<String> {
+            parse_time_entries(csv.as_bytes(), order)
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|entry| entry.date)
+                .collect()
+        };
+
+        // A day over 12 in any line gives the order of the others
+        assert_eq!(
+            dates(export(&["03/04/2025", "25/04/2025"]), None),
+            ["2025-04-03", "2025-04-25"]
+        );
+        assert_eq!(
+            dates(export(&["03/04/2025", "04/25/2025"]), None),
+            ["2025-03-04", "2025-04-25"]
+        );
+        assert_eq!(
+            dates(export(&["05/05/2025", "14.05.2025"]), None),
+            ["2025-05-05", "2025-05-14"]
+        );
+
+        // Without one the dates are not guessed, unless the order is given
+        let error =
+            parse_time_entries(export(&["03/04/2025", "04/04/2025"]).as_bytes(), None).unwrap_err();
+        assert!(error.get_ref().is_some_and(|e| e.is::<AmbiguousDates>()));
+        assert_eq!(
+            dates(export(&["03/04/2025"]), Some(DateOrder::DayFirst)),
+            ["2025-04-03"]
+        );
+        assert_eq!(
+            dates(export(&["03/04/2025"]), Some(DateOrder::MonthFirst)),
+            ["2025-03-04"]
+        );
+
+        let error =
+            parse_time_entries(export(&["25/04/2025", "04/25/2025"]).as_bytes(), None).unwrap_err();
+        assert!(error.to_string().contains("day first in some lines"));
+        let error =
+            parse_time_entries(export(&["25/04/2025", "31/31/2025"]).as_bytes(), None).unwrap_err();
+        assert_eq!(error.to_string(), "Line 3: invalid date");
+        assert!(parse_time_entries(
+            export(&["25/04/2025"]).as_bytes(),
+            Some(DateOrder::MonthFirst)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_projects_are_mapped_by_name_or_tax_id() {
+        let clients = clients();
+        let key = |client: &str, project: &str| ProjectKey {
+            client: client.to_string(),
+            project: project.to_string(),
+        };
+
+        let by_name = match_client(&key("Acme Corp", "Website"), &clients).unwrap();
+        assert_eq!(by_name.cif, "B12345674");
+        let by_project_tax_id = match_client(&key("", "ES-B87654321"), &clients).unwrap();
+        assert_eq!(by_project_tax_id.name, "Initech SL");
+        assert!(match_client(&key("Globex", "App"), &clients).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().to_str().unwrap()).unwrap();
+        for client in &clients {
+            storage.save_client(client).unwrap();
+        }
+        let entries = parse_time_entries(TOGGL.as_bytes(), None).unwrap().entries;
+        let projects = TimeTrackingService::new(storage)
+            .map_projects(&entries)
+            .unwrap();
+        assert_eq!(projects.len(), 2);
+        assert!(projects.iter().all(|(_, client)| client.is_some()));
+    }
+
+    #[test]
+    fn test_draft_invoices_per_project_and_task() {
+        let clients = clients();
+        let entries = parse_time_entries(TOGGL.as_bytes(), None).unwrap().entries;
+        let mut mapping = BTreeMap::new();
+        for entry in &entries {
+            if let Some(client) = match_client(&entry.project_key(), &clients) {
+                mapping.insert(entry.project_key(), client);
+            }
+        }
+
+        // Non billable and April hours are left out
+        let drafts = draft_invoices(&entries, march(), &mapping, ItemGrouping::Project, 5000);
+        assert_eq!(drafts.len(), 2);
+        assert_eq!(drafts[0].client.name, "ACME Corp.");
+        assert_eq!(drafts[0].seconds, 12600);
+        assert_eq!(drafts[0].entries, 2);
+        assert_eq!(drafts[0].items.len(), 1);
+        assert_eq!(
+            drafts[0].items[0].description,
+            "Website: 3.50 h at 50.00€/h"
+        );
+        assert_eq!(drafts[0].items[0].quantity, 1);
+        assert_eq!(drafts[0].items[0].price, 175.0);
+        assert_eq!(
+            drafts[1].items[0].description,
+            "B87654321: 4.00 h at 50.00€/h"
+        );
+
+        let drafts = draft_invoices(&entries, march(), &mapping, ItemGrouping::Task, 5000);
+        let descriptions: Vec<&str> = drafts[0]
+            .items
+            .iter()
+            .map(|item| item.description.as_str())
+            .collect();
+        assert_eq!(
+            descriptions,
+            [
+                "Website - Contact form: 1.00 h at 50.00€/h",
+                "Website - Home page: 2.50 h at 50.00€/h"
+            ]
+        );
+
+        mapping.clear();
+        assert!(
+            draft_invoices(&entries, march(), &mapping, ItemGrouping::Project, 5000).is_empty()
+        );
+    }
+}
+
+#[cfg(test)]
+mod bulk_import_tests {
+    use super::fixtures::{client, invoice_for, user};
+    use crate::config::{ClientColumns, ImportMapping, InvoiceColumns};
+    use crate::models::Rule;
+    use crate::repository::Storage;
+    use crate::services::bulk_import::{
+        check_clients, check_invoices, parse_csv, read_table, BulkImportService,
+    };
+    use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_clients_are_validated_and_deduplicated() {
+        let table = parse_csv(
+            "Nombre;NIF;Direccion;Correo
+Initech SL;a28015865;Valencia;info@initech.es
+Acme Corporation;ES-B12345674;Madrid;
+Initech Duplicate;A28015865;Valencia;
+Wrong Letter;12345678A;Sevilla;
+No Address;Q2826000H;;
+Bad Email;G12345674;Bilbao;nobody
+;;;
+Foreign GmbH;de 136695976;Berlin;
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        let columns = ClientColumns {
+            name: "Nombre".to_string(),
+            cif: "NIF".to_string(),
+            address: "Direccion".to_string(),
+            email: "Correo".to_string(),
+        };
+
+        let report = check_clients(&table, &columns, &[client("Acme Corp", "B12345674")]).unwrap();
+        let names: Vec<&str> = report.valid.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Initech SL", "Foreign GmbH"]);
+        assert_eq!(report.valid[0].cif, "A28015865");
+        assert_eq!(report.valid[0].email.as_deref(), Some("info@initech.es"));
+        assert_eq!(report.valid[1].cif, "DE136695976");
+
+        let duplicates: Vec<usize> = report.duplicates.iter().map(|d| d.row).collect();
+        assert_eq!(duplicates, [3, 4]);
+        assert!(report.duplicates[0].message.contains("Acme Corp"));
+        let errors: Vec<usize> = report.errors.iter().map(|e| e.row).collect();
+        assert_eq!(errors, [5, 6, 7]);
+        assert!(report.errors[0].message.contains("expected Z"));
+        assert!(report.errors[1].message.contains("address"));
+
+        assert!(check_clients(&table, &ClientColumns::default(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_invoice_rows_are_grouped_by_number() {
+        let table = parse_csv(
+            "number,date,client_cif,description,quantity,price,iva,irpf,paid_date
+2024-1,2024-01-10,B12345674,Design,2,\"1.000,50\",21,15,2024-02-01
+2024-1,2024-01-10,B12345674,Hosting,,100,21,15,2024-02-01
+2024-2,2024-02-10,B99999999,Design,1,100,21,,
+2024-3,2024-03-10,B12345674,Design,1,100,21,,
+2024-3,2024-03-11,B12345674,Extra,1,100,21,,
+2024-4,2024/04/10,B12345674,Design,1,100,21,,
+2024-5,2024-05-10,B12345674,Design,1,100,121,,
+2023-9,2023-12-10,B12345674,Old,1,100,21,,
+2024-6,2024-06-10,ESB12345674,Support,1,50,0,,
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        let existing: HashSet<String> = ["2023-9".to_string()].into();
+
+        let report = check_invoices(
+            &table,
+            &InvoiceColumns::default(),
+            &[client("Acme Corp", "B12345674")],
+            &existing,
+            &user(),
+        )
+        .unwrap();
+        let ids: Vec<&str> = report.valid.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["2024-1", "2024-6"]);
+        let first = &report.valid[0];
+        assert_eq!(first.items.len(), 2);
+        assert_eq!(first.items[0].price, 1000.5);
+        assert_eq!(first.items[1].quantity, 1);
+        assert_eq!(first.subtotal, 2101.0);
+        assert_eq!(first.due_date, "2024-02-09");
+        assert_eq!(first.paid_date.as_deref(), Some("2024-02-01"));
+        assert_eq!(first.rule.irpf, 15.0);
+        assert_eq!(report.valid[1].rule.irpf, 0.0);
+
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].row, 9);
+        let errors: Vec<usize> = report.errors.iter().map(|e| e.row).collect();
+        assert_eq!(errors, [4, 6, 7, 8]);
+        assert!(report.errors[0].message.contains("B99999999 not found"));
+        assert!(report.errors[1].message.contains("same as in row 5"));
+    }
+
+    #[test]
+    fn test_the_last_separator_of_an_amount_is_the_decimal_one() {
+        let table = parse_csv(
+            "number;date;client_cif;description;price;iva
+1;2024-01-10;B12345674;Design;1.234,50;21
+2;2024-01-10;B12345674;Design;1,234.50;21
+3;2024-01-10;B12345674;Design;1234,5;21
+4;2024-01-10;B12345674;Design;1.234.567;21
+5;2024-01-10;B12345674;Design;0,125;21
+6;2024-01-10;B12345674;Design;1.234;21
+7;2024-01-10;B12345674;Design;1,234;21
+8;2024-01-10;B12345674;Design;12.34,5;21
+9;2024-01-10;B12345674;Design;1,2,3;21
+"
+            .as_bytes(),
+        )
+        .unwrap();
+
+        let report = check_invoices(
+            &table,
+            &InvoiceColumns::default(),
+            &[client("Acme Corp", "B12345674")],
+            &HashSet::new(),
+            &user(),
+        )
+        .unwrap();
+        let prices: Vec<f32> = report.valid.iter().map(|i| i.items[0].price).collect();
+        assert_eq!(prices, [1234.5, 1234.5, 1234.5, 1234567.0, 0.125]);
+        let errors: Vec<usize> = report.errors.iter().map(|e| e.row).collect();
+        assert_eq!(errors, [7, 8, 9, 10]);
+        assert!(report.errors[0]
+            .message
+            .contains("Invalid price: 1.234 (thousands or decimals?)"));
+    }
+
+    #[test]
+    fn test_invoice_numbers_that_share_a_file_are_reported() {
+        let table = parse_csv(
+            "number,date,client_cif,description,price,iva
+2023_015,2023-05-10,B12345674,Design,100,21
+2024/1,2024-01-10,B12345674,Design,100,21
+2024/1,2024-01-10,B12345674,Hosting,50,21
+2024_1,2024-01-12,B12345674,Design,100,21
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        let existing: HashSet<String> = ["2023/015".to_string()].into();
+
+        let report = check_invoices(
+            &table,
+            &InvoiceColumns::default(),
+            &[client("Acme Corp", "B12345674")],
+            &existing,
+            &user(),
+        )
+        .unwrap();
+        let ids: Vec<&str> = report.valid.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["2024/1"]);
+        let errors: Vec<usize> = report.errors.iter().map(|e| e.row).collect();
+        assert_eq!(errors, [2, 5]);
+        assert!(report.errors[0]
+            .message
+            .contains("same file as invoice 2023/015"));
+        assert!(report.errors[1]
+            .message
+            .contains("same file as invoice 2024/1"));
+    }
+
+    #[test]
+    fn test_import_goes_on_after_a_record_cannot_be_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::new(dir.path().to_str().unwrap()).unwrap();
+        let acme = client("Acme Corp", "B12345674");
+        let invoice =
+            |id: &str| invoice_for(id, "2024-01-10", acme.clone(), Rule::new(21.0, 0.0), 100.0);
+        storage.save_invoice(&invoice("2023/015")).unwrap();
+        let service = BulkImportService::new(storage.clone(), ImportMapping::default());
+
+        let outcome =
+            service.import_invoices(&[invoice("2024-1"), invoice("2023_015"), invoice("2024-2")]);
+        assert_eq!(outcome.imported, 2);
+        assert_eq!(outcome.failed.len(), 1);
+        assert!(outcome.failed[0].starts_with("Invoice 2023_015:"));
+        assert_eq!(storage.list_invoices().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_spreadsheet_import_with_custom_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("facturas.xlsx");
+
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        let date_format = Format::new().set_num_format("dd/mm/yyyy");
+        for (column, header) in ["Factura", "Fecha", "NIF", "Concepto", "Importe", "IVA"]
+            .iter()
+            .enumerate()
+        {
+            sheet.write_string(0, column as u16, *header).unwrap();
+        }
+        sheet.write_string(1, 0, "F-1").unwrap();
+        sheet
+            .write_datetime_with_format(
+                1,
+                1,
+                ExcelDateTime::from_ymd(2024, 3, 5).unwrap(),
+                &date_format,
+            )
+            .unwrap();
+        sheet.write_string(1, 2, "B12345674").unwrap();
+        sheet.write_string(1, 3, "Consulting").unwrap();
+        sheet.write_number(1, 4, 250.0).unwrap();
+        sheet.write_number(1, 5, 21.0).unwrap();
+        workbook.save(&path).unwrap();
+
+        let table = read_table(&path).unwrap();
+        assert_eq!(table[1][1], "2024-03-05");
+        assert_eq!(table[1][4], "250");
+
+        let storage = Storage::new(dir.path().to_str().unwrap()).unwrap();
+        storage
+            .save_client(&client("Acme Corp", "B12345674"))
+            .unwrap();
+        let mapping = ImportMapping {
+            clients: ClientColumns::default(),
+            invoices: InvoiceColumns {
+                number: "Factura".to_string(),
+                date: "Fecha".to_string(),
+                client_cif: "NIF".to_string(),
+                description: "Concepto".to_string(),
+                price: "Importe".to_string(),
+                iva: "IVA".to_string(),
+                ..InvoiceColumns::default()
+            },
+        };
+        let service = BulkImportService::new(storage.clone(), mapping);
+
+        let report = service.check_invoices(&path, &user()).unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(report.valid[0].total, 302.5);
+        assert!(storage.list_invoices().unwrap().is_empty());
+
+        let outcome = service.import_invoices(&report.valid);
+        assert_eq!(outcome.imported, 1);
+        assert!(outcome.failed.is_empty());
+        let report = service.check_invoices(&path, &user()).unwrap();
+        assert!(report.valid.is_empty());
+        assert_eq!(report.duplicates.len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod spreadsheet_tests {
+    use super::fixtures::{client, invoice_for};
+    use crate::models::Rule;
+    use crate::services::spreadsheet::to_xlsx;
+    use calamine::{open_workbook_from_rs, Data, DataType, Reader, Xlsx};
+    use std::io::Cursor;
+
+    fn workbook() -> Xlsx<Cursor<Vec<u8>>> {
+        let mut paid = invoice_for(
+            "2025-2",
+            "2025-02-10",
+            client("Acme Corp", "B12345674"),
+            Rule::new(21.0, 15.0),
+            1000.0,
+        );
+        paid.paid_date = Some("2025-03-01".to_string());
+        let invoices = vec![
+            invoice_for(
+                "2025-10",
+                "2025-01-20",
+                client("Acme Corp", "ESB12345674"),
+                Rule::new(21.0, 0.0),
+                100.0,
+            ),
+            paid,
+            invoice_for(
+                "2025-11",
+                "2025-05-05",
+                client("Initech SL", "A28015865"),
+                Rule::new(10.0, 0.0),
+                200.0,
+            ),
+            invoice_for(
+                "2024-9",
+                "2024-12-30",
+                client("Acme Corp", "B12345674"),
+                Rule::new(21.0, 0.0),
+                500.0,
+            ),
+        ];
+        let bytes = to_xlsx(&invoices, 2025).unwrap();
+        open_workbook_from_rs(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_one_sheet_per_quarter_with_date_cells() {
+        let mut workbook = workbook();
+        assert_eq!(
+            workbook.sheet_names(),
+            [
+                "1T 2025",
+                "2T 2025",
+                "3T 2025",
+                "4T 2025",
+                "Tax by rate",
+                "Clients"
+            ]
+        );
+
+        let first = workbook.worksheet_range("1T 2025").unwrap();
+        assert_eq!(first.get((0, 0)), Some(&Data::String("Date".to_string())));
+        assert!(matches!(first.get((1, 0)), Some(Data::DateTime(_))));
+        assert_eq!(
+            first.get((1, 0)).unwrap().as_date(),
+            chrono::NaiveDate::from_ymd_opt(2025, 1, 20)
+        );
+        assert_eq!(
+            first.get((1, 1)),
+            Some(&Data::String("2025-10".to_string()))
+        );
+        assert_eq!(
+            first.get((1, 3)),
+            Some(&Data::String("B12345674".to_string()))
+        );
+        assert_eq!(first.get((2, 4)), Some(&Data::Float(1000.0)));
+        assert_eq!(first.get((2, 9)), Some(&Data::Float(1060.0)));
+        assert!(matches!(first.get((2, 11)), Some(Data::DateTime(_))));
+        // Totals row, with the cached result of the SUM
+        assert_eq!(first.get((3, 0)), Some(&Data::String("Total".to_string())));
+        assert_eq!(first.get((3, 9)), Some(&Data::Float(1181.0)));
+        let formulas = workbook.worksheet_formula("1T 2025").unwrap();
+        assert_eq!(formulas.get_value((3, 9)), Some(&"SUM(J2:J3)".to_string()));
+
+        let third = workbook.worksheet_range("3T 2025").unwrap();
+        assert_eq!(third.get((1, 0)), Some(&Data::String("Total".to_string())));
+    }
+
+    #[test]
+    fn test_tax_by_rate_and_client_summary() {
+        let mut workbook = workbook();
+
+        let rates = workbook.worksheet_range("Tax by rate").unwrap();
+        let rows: Vec<Vec<String>> = rates
+            .rows()
+            .skip(1)
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ["2025 1T", "IRPF", "15", "1", "1000", "150"],
+                ["2025 1T", "IVA", "21", "2", "1100", "231"],
+                ["2025 2T", "IVA", "10", "1", "200", "20"],
+            ]
+        );
+
+        let clients = workbook.worksheet_range("Clients").unwrap();
+        assert_eq!(
+            clients.get((1, 0)),
+            Some(&Data::String("Acme Corp".to_string()))
+        );
+        assert_eq!(clients.get((1, 2)), Some(&Data::Float(2.0)));
+        // Total, paid and outstanding
+        assert_eq!(clients.get((1, 6)), Some(&Data::Float(1181.0)));
+        assert_eq!(clients.get((1, 7)), Some(&Data::Float(1060.0)));
+        assert_eq!(clients.get((1, 8)), Some(&Data::Float(121.0)));
+        assert_eq!(
+            clients.get((2, 0)),
+            Some(&Data::String("Initech SL".to_string()))
+        );
+        assert_eq!(clients.get((3, 6)), Some(&Data::Float(1401.0)));
+    }
+}
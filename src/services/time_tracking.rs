@@ -0,0 +1,462 @@
+// Hours logged in Toggl Track, Clockify or Harvest, read from their detailed CSV
+// exports and turned into invoices at an hourly rate. Each tracker names its columns
+// differently, they are found by header so the column order does not matter
+
+use crate::models::{Client, Item};
+use crate::repository::Storage;
+use crate::services::einvoice::local_tax_id;
+use crate::services::money::format_cents;
+use crate::services::period::DateRange;
+use crate::services::sepa::sepa_text;
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+// Header names of each field, as exported by Toggl, Clockify and Harvest
+const DATE_COLUMNS: &[&str] = &["Start date", "Date"];
+const CLIENT_COLUMNS: &[&str] = &["Client"];
+const PROJECT_COLUMNS: &[&str] = &["Project"];
+const TASK_COLUMNS: &[&str] = &["Task"];
+const DESCRIPTION_COLUMNS: &[&str] = &["Description", "Notes"];
+const BILLABLE_COLUMNS: &[&str] = &["Billable", "Billable?"];
+const DECIMAL_HOURS_COLUMNS: &[&str] = &["Duration (decimal)", "Hours"];
+const CLOCK_DURATION_COLUMNS: &[&str] = &["Duration", "Duration (h)"];
+
+// Clockify writes dates as configured in the workspace, the others in ISO format. Dates
+// with dots always have the day first, the order of those with slashes is set by DateOrder
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%d.%m.%Y"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    DayFirst,   // 31/12/2025
+    MonthFirst, // 12/31/2025
+}
+
+impl DateOrder {
+    pub const ALL: [DateOrder; 2] = [DateOrder::DayFirst, DateOrder::MonthFirst];
+
+    fn format(self) -> &'static str {
+        match self {
+            DateOrder::DayFirst => "%d/%m/%Y",
+            DateOrder::MonthFirst => "%m/%d/%Y",
+        }
+    }
+}
+
+impl fmt::Display for DateOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let order = match self {
+            DateOrder::DayFirst => "Day first (31/12/2025)",
+            DateOrder::MonthFirst => "Month first (12/31/2025)",
+        };
+        write!(f, "{}", order)
+    }
+}
+
+// No date of the export tells whether it is written day or month first, e.g. only
+// 03/04/2025, so the order has to be given
+#[derive(Debug)]
+pub struct AmbiguousDates;
+
+impl fmt::Display for AmbiguousDates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The dates can be read both day first and month first, the date order is needed"
+        )
+    }
+}
+
+impl std::error::Error for AmbiguousDates {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeTracker {
+    Toggl,
+    Clockify,
+    Harvest,
+}
+
+impl fmt::Display for TimeTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TimeTracker::Toggl => "Toggl Track",
+            TimeTracker::Clockify => "Clockify",
+            TimeTracker::Harvest => "Harvest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemGrouping {
+    Project, // One item per project
+    Task,    // One item per task of each project
+}
+
+impl ItemGrouping {
+    pub const ALL: [ItemGrouping; 2] = [ItemGrouping::Project, ItemGrouping::Task];
+}
+
+impl fmt::Display for ItemGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let grouping = match self {
+            ItemGrouping::Project => "One item per project",
+            ItemGrouping::Task => "One item per task",
+        };
+        write!(f, "{}", grouping)
+    }
+}
+
+// A logged period of work
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeEntry {
+    pub date: String,    // YYYY-MM-DD
+    pub client: String,  // Client as named in the tracker, may be empty
+    pub project: String, // May be empty
+    pub task: String,    // Task, or the description when the entry has no task
+    pub seconds: i64,
+    pub billable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeSheet {
+    pub tracker: TimeTracker,
+    pub entries: Vec<TimeEntry>,
+}
+
+// Client and project of the tracker, what is mapped to a local client
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProjectKey {
+    pub client: String,
+    pub project: String,
+}
+
+impl fmt::Display for ProjectKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.client.is_empty(), self.project.is_empty()) {
+            (true, true) => write!(f, "(no client or project)"),
+            (true, _) => write!(f, "{}", self.project),
+            (false, true) => write!(f, "{}", self.client),
+            (false, false) => write!(f, "{} / {}", self.client, self.project),
+        }
+    }
+}
+
+impl TimeEntry {
+    pub fn project_key(&self) -> ProjectKey {
+        ProjectKey {
+            client: self.client.clone(),
+            project: self.project.clone(),
+        }
+    }
+}
+
+// Invoice waiting to be confirmed, with the hours of one client
+#[derive(Debug, Clone)]
+pub struct InvoiceDraft {
+    pub client: Client,
+    pub items: Vec<Item>,
+    pub seconds: i64,
+    pub entries: usize,
+}
+
+// Read a detailed time report exported as CSV by any of the supported trackers. Without a
+// date order, the order of the dates with slashes is found from the whole column and an
+// AmbiguousDates error is returned when it cannot be
+pub fn parse_time_entries(contents: &[u8], order: Option<DateOrder>) -> io::Result<TimeSheet> {
+    let text = String::from_utf8_lossy(contents);
+    let text = text.trim_start_matches('\u{feff}');
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| invalid(format!("Invalid CSV file: {}", e)))?
+        .iter()
+        .map(|header| header.trim().to_string())
+        .collect();
+
+    let find = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.iter().any(|name| header.eq_ignore_ascii_case(name)))
+    };
+    let date = find(DATE_COLUMNS);
+    let decimal_hours = find(DECIMAL_HOURS_COLUMNS);
+    let clock_duration = find(CLOCK_DURATION_COLUMNS);
+    let (Some(date), true) = (date, decimal_hours.is_some() || clock_duration.is_some()) else {
+        return Err(invalid(
+            "Unknown time report, expected a detailed CSV export of Toggl Track, Clockify or Harvest"
+                .to_string(),
+        ));
+    };
+    let tracker = if find(&["Hours"]).is_some() {
+        TimeTracker::Harvest
+    } else if find(&["Duration (decimal)"]).is_some() {
+        TimeTracker::Clockify
+    } else {
+        TimeTracker::Toggl
+    };
+    let client = find(CLIENT_COLUMNS);
+    let project = find(PROJECT_COLUMNS);
+    let task = find(TASK_COLUMNS);
+    let description = find(DESCRIPTION_COLUMNS);
+    let billable = find(BILLABLE_COLUMNS);
+
+    let mut entries = Vec::new();
+    let mut dates = Vec::new();
+    for (number, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| invalid(format!("Invalid CSV file: {}", e)))?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+        // Header is line 1
+        let line = number + 2;
+        if record.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+
+        let seconds = match (decimal_hours, clock_duration) {
+            (Some(column), _) => parse_decimal_hours(&field(Some(column))),
+            (None, column) => parse_clock_duration(&field(column)),
+        }
+        .ok_or_else(|| invalid(format!("Line {}: invalid duration", line)))?;
+        let task = Some(field(task))
+            .filter(|task| !task.is_empty())
+            .unwrap_or_else(|| field(description));
+
+        dates.push((line, field(Some(date))));
+        entries.push(TimeEntry {
+            date: String::new(),
+            client: field(client),
+            project: field(project),
+            task,
+            seconds,
+            billable: !matches!(
+                field(billable).to_lowercase().as_str(),
+                "no" | "false" | "0"
+            ),
+        });
+    }
+
+    let order = match order {
+        Some(order) => order,
+        None => date_order(&dates)?,
+    };
+    for (entry, (line, text)) in entries.iter_mut().zip(&dates) {
+        entry.date = parse_date(text, order)
+            .ok_or_else(|| invalid(format!("Line {}: invalid date", line)))?;
+    }
+
+    Ok(TimeSheet { tracker, entries })
+}
+
+// Order of the dates with slashes, the one that reads every date of the column. When both
+// do, the order only matters if some date has a different day and month
+fn date_order(dates: &[(usize, String)]) -> io::Result<DateOrder> {
+    let slashed: Vec<&(usize, String)> = dates
+        .iter()
+        .filter(|(_, text)| text.contains('/'))
+        .collect();
+    let read = |order: DateOrder| -> Option<Vec<NaiveDate>> {
+        slashed
+            .iter()
+            .map(|(_, text)| NaiveDate::parse_from_str(text, order.format()).ok())
+            .collect()
+    };
+    match (read(DateOrder::DayFirst), read(DateOrder::MonthFirst)) {
+        (Some(day_first), Some(month_first)) if day_first != month_first => {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, AmbiguousDates))
+        }
+        (Some(_), _) => Ok(DateOrder::DayFirst),
+        (None, Some(_)) => Ok(DateOrder::MonthFirst),
+        (None, None) => Err(invalid(
+            match slashed.iter().find(|(_, text)| {
+                DateOrder::ALL
+                    .iter()
+                    .all(|order| NaiveDate::parse_from_str(text, order.format()).is_err())
+            }) {
+                Some((line, _)) => format!("Line {}: invalid date", line),
+                None => "The dates are written day first in some lines and month first in others"
+                    .to_string(),
+            },
+        )),
+    }
+}
+
+fn parse_date(text: &str, order: DateOrder) -> Option<String> {
+    DATE_FORMATS
+        .iter()
+        .chain([&order.format()])
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+// "1.5" or "1,5" hours
+fn parse_decimal_hours(text: &str) -> Option<i64> {
+    let hours: f64 = text.replace(',', ".").parse().ok()?;
+    (hours >= 0.0).then(|| (hours * 3600.0).round() as i64)
+}
+
+// "01:30:00" or "1:30"
+fn parse_clock_duration(text: &str) -> Option<i64> {
+    let parts: Vec<i64> = text
+        .split(':')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match parts[..] {
+        [hours, minutes, seconds] if hours >= 0 && minutes < 60 && seconds < 60 => {
+            Some(hours * 3600 + minutes * 60 + seconds)
+        }
+        [hours, minutes] if hours >= 0 && minutes < 60 => Some(hours * 3600 + minutes * 60),
+        _ => None,
+    }
+}
+
+// Client of a project: the client of the tracker, or the project itself, named like a
+// local client or written as its tax ID
+pub fn match_client(key: &ProjectKey, clients: &[Client]) -> Option<Client> {
+    [&key.client, &key.project]
+        .into_iter()
+        .filter(|text| !text.is_empty())
+        .find_map(|text| {
+            let tax_id = local_tax_id(text);
+            clients
+                .iter()
+                .find(|client| {
+                    same_name(&client.name, text)
+                        || (!client.cif.trim().is_empty() && local_tax_id(&client.cif) == tax_id)
+                })
+                .cloned()
+        })
+}
+
+// Names are compared without case, accents or punctuation
+fn same_name(a: &str, b: &str) -> bool {
+    let normalize = |name: &str| -> Vec<String> {
+        sepa_text(name, usize::MAX)
+            .to_uppercase()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let a = normalize(a);
+    !a.is_empty() && a == normalize(b)
+}
+
+// One invoice per client with the billable hours logged in the range. Projects missing
+// from the mapping are left out
+pub fn draft_invoices(
+    entries: &[TimeEntry],
+    range: DateRange,
+    mapping: &BTreeMap<ProjectKey, Client>,
+    grouping: ItemGrouping,
+    hourly_rate: i64, // Cents
+) -> Vec<InvoiceDraft> {
+    // Client tax ID (or name) -> item description -> seconds
+    let mut grouped: BTreeMap<String, (Client, BTreeMap<String, i64>, usize)> = BTreeMap::new();
+    for entry in entries
+        .iter()
+        .filter(|entry| entry.billable && range.contains(&entry.date))
+    {
+        let Some(client) = mapping.get(&entry.project_key()) else {
+            continue;
+        };
+        let key = match local_tax_id(&client.cif) {
+            cif if cif.is_empty() => client.name.clone(),
+            cif => cif,
+        };
+        let project = if entry.project.is_empty() {
+            "(no project)"
+        } else {
+            entry.project.as_str()
+        };
+        let description = match grouping {
+            ItemGrouping::Project => project.to_string(),
+            ItemGrouping::Task if entry.task.is_empty() => format!("{} - (no task)", project),
+            ItemGrouping::Task => format!("{} - {}", project, entry.task),
+        };
+
+        let (_, items, count) = grouped
+            .entry(key)
+            .or_insert_with(|| (client.clone(), BTreeMap::new(), 0));
+        *items.entry(description).or_insert(0) += entry.seconds;
+        *count += 1;
+    }
+
+    grouped
+        .into_values()
+        .map(|(client, items, entries)| InvoiceDraft {
+            client,
+            seconds: items.values().sum(),
+            items: items
+                .into_iter()
+                .map(|(description, seconds)| hours_item(description, seconds, hourly_rate))
+                .collect(),
+            entries,
+        })
+        .collect()
+}
+
+// Items have whole quantities, the hours are billed as one unit priced at the rate
+fn hours_item(description: String, seconds: i64, hourly_rate: i64) -> Item {
+    let amount = (seconds as f64 * hourly_rate as f64 / 3600.0).round() as i64;
+    Item::new(
+        format!(
+            "{}: {} h at {}€/h",
+            description,
+            format_hours(seconds),
+            format_cents(hourly_rate)
+        ),
+        1,
+        amount as f32 / 100.0,
+    )
+}
+
+// Hours with two decimals, "1.50" for an hour and a half
+pub fn format_hours(seconds: i64) -> String {
+    format_cents((seconds as f64 / 36.0).round() as i64)
+}
+
+pub struct TimeTrackingService {
+    storage: Storage,
+}
+
+impl TimeTrackingService {
+    pub fn new(storage: Storage) -> Self {
+        TimeTrackingService { storage }
+    }
+
+    pub fn read_export(&self, path: &Path, order: Option<DateOrder>) -> io::Result<TimeSheet> {
+        parse_time_entries(&std::fs::read(path)?, order)
+    }
+
+    // Projects of the entries, with the local client each one belongs to when found
+    pub fn map_projects(
+        &self,
+        entries: &[TimeEntry],
+    ) -> io::Result<Vec<(ProjectKey, Option<Client>)>> {
+        let clients = self.storage.list_clients()?;
+        let mut keys: Vec<ProjectKey> = entries.iter().map(TimeEntry::project_key).collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let client = match_client(&key, &clients);
+                (key, client)
+            })
+            .collect())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
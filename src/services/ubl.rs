@@ -0,0 +1,751 @@
+// UBL 2.1 export following Peppol BIS Billing 3.0 (EN 16931)
+// Includes the business rules of the Peppol Schematron translated to Rust checks
+
+use crate::models::Invoice;
+use crate::repository::storage::file_stem;
+use crate::services::einvoice::{buyer, country_code, vat_identifier, InvoiceTotals, TaxCategory};
+use crate::services::money::{format_cents, parse_cents, percent_of, to_cents};
+use crate::services::xml::XmlWriter;
+use roxmltree::{Document, Node};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+
+const UBL_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2";
+const CAC_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+const CBC_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+
+pub const CUSTOMIZATION_ID: &str =
+    "urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0";
+pub const PROFILE_ID: &str = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0";
+
+// Peppol electronic address schemes (EAS) for VAT numbers, by VAT prefix
+const VAT_ENDPOINT_SCHEMES: &[(&str, &str)] = &[
+    ("AT", "9914"),
+    ("BE", "9925"),
+    ("BG", "9926"),
+    ("CY", "9928"),
+    ("CZ", "9929"),
+    ("DE", "9930"),
+    ("DK", "0198"),
+    ("EE", "9931"),
+    ("EL", "9933"),
+    ("ES", "9920"),
+    ("FI", "0213"),
+    ("FR", "9957"),
+    ("GB", "9932"),
+    ("HR", "9934"),
+    ("HU", "9910"),
+    ("IE", "9935"),
+    ("IT", "0211"),
+    ("LT", "9937"),
+    ("LU", "9938"),
+    ("LV", "9939"),
+    ("MT", "9943"),
+    ("NL", "9944"),
+    ("PL", "9945"),
+    ("PT", "9946"),
+    ("RO", "9947"),
+    ("SI", "9949"),
+    ("SK", "9950"),
+];
+
+// UN/ECE 4461 code for SEPA credit transfer
+const SEPA_CREDIT_TRANSFER: &str = "58";
+// UN/ECE Rec 20 code for "one" (unit)
+const UNIT_CODE: &str = "C62";
+pub const CURRENCY: &str = "EUR";
+
+// A broken business rule found in a UBL document
+#[derive(Debug, Clone)]
+pub struct RuleViolation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.rule, self.message)
+    }
+}
+
+// Service for exporting invoices as Peppol BIS Billing 3.0 UBL documents
+#[derive(Clone)]
+pub struct UblService {
+    output_dir: String, // Directory where generated XML files will be stored
+}
+
+impl UblService {
+    pub fn new(output_dir: String) -> io::Result<Self> {
+        std::fs::create_dir_all(&output_dir).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "Failed to create export output directory '{}': {}",
+                    output_dir, e
+                ),
+            )
+        })?;
+        Ok(UblService { output_dir })
+    }
+
+    // Export an invoice to a UBL file, refusing documents that break Peppol rules
+    pub fn export_invoice(&self, invoice: &Invoice) -> io::Result<String> {
+        if invoice.client.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A simplified invoice without client data cannot be sent as an e-invoice",
+            ));
+        }
+        let xml = to_ubl_xml(invoice);
+
+        let violations = validate_peppol(&xml)?;
+        if !violations.is_empty() {
+            let details: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Invoice does not conform to Peppol BIS 3.0:\n{}",
+                    details.join("\n")
+                ),
+            ));
+        }
+
+        let output_path = format!(
+            "{}/invoice_{}.ubl.xml",
+            self.output_dir,
+            file_stem(&invoice.id)
+        );
+        let mut file = File::create(&output_path)?;
+        file.write_all(xml.as_bytes())?;
+
+        Ok(output_path)
+    }
+}
+
+// Build the UBL 2.1 Invoice document for an invoice
+pub fn to_ubl_xml(invoice: &Invoice) -> String {
+    let totals = InvoiceTotals::from_invoice(invoice);
+    let currency = [("currencyID", CURRENCY)];
+    let mut xml = XmlWriter::new();
+
+    xml.start(
+        "Invoice",
+        &[
+            ("xmlns", UBL_NS),
+            ("xmlns:cac", CAC_NS),
+            ("xmlns:cbc", CBC_NS),
+        ],
+    );
+    xml.leaf("cbc:CustomizationID", &[], CUSTOMIZATION_ID);
+    xml.leaf("cbc:ProfileID", &[], PROFILE_ID);
+    xml.leaf("cbc:ID", &[], &invoice.id);
+    xml.leaf("cbc:IssueDate", &[], &invoice.date);
+    xml.leaf("cbc:DueDate", &[], &invoice.due_date);
+    xml.leaf("cbc:InvoiceTypeCode", &[], "380");
+    if totals.prepaid > 0 {
+        xml.leaf(
+            "cbc:Note",
+            &[],
+            &format!(
+                "IRPF withholding {}%: {} EUR",
+                invoice.rule.irpf,
+                format_cents(totals.prepaid)
+            ),
+        );
+    }
+    xml.leaf("cbc:DocumentCurrencyCode", &[], CURRENCY);
+    // We have no purchase order, the invoice number lets the buyer route it
+    xml.leaf("cbc:BuyerReference", &[], &invoice.id);
+
+    // Seller
+    let seller_vat = vat_identifier(&invoice.user.cif);
+    xml.start("cac:AccountingSupplierParty", &[]);
+    write_party(
+        &mut xml,
+        &seller_vat,
+        &invoice.user.name,
+        &invoice.user.address,
+        invoice.user.email.as_deref(),
+    );
+    xml.end();
+
+    // Buyer
+    let buyer = buyer(invoice);
+    let buyer_vat = vat_identifier(&buyer.cif);
+    xml.start("cac:AccountingCustomerParty", &[]);
+    write_party(
+        &mut xml,
+        &buyer_vat,
+        &buyer.name,
+        &buyer.address,
+        buyer.email.as_deref(),
+    );
+    xml.end();
+
+    // Payment by SEPA transfer to the issuer's account
+    if let Some(iban) = &invoice.user.iban {
+        xml.start("cac:PaymentMeans", &[]);
+        xml.leaf("cbc:PaymentMeansCode", &[], SEPA_CREDIT_TRANSFER);
+        xml.leaf("cbc:PaymentID", &[], &invoice.id);
+        xml.start("cac:PayeeFinancialAccount", &[]);
+        xml.leaf("cbc:ID", &[], &iban.replace(' ', ""));
+        xml.leaf("cbc:Name", &[], &invoice.user.name);
+        xml.end();
+        xml.end();
+    }
+
+    // Tax breakdown
+    xml.start("cac:TaxTotal", &[]);
+    xml.leaf("cbc:TaxAmount", &currency, &format_cents(totals.tax));
+    for (category, (rate, taxable, tax)) in &totals.breakdown {
+        xml.start("cac:TaxSubtotal", &[]);
+        xml.leaf("cbc:TaxableAmount", &currency, &format_cents(*taxable));
+        xml.leaf("cbc:TaxAmount", &currency, &format_cents(*tax));
+        xml.start("cac:TaxCategory", &[]);
+        write_tax_category(&mut xml, *category, *rate);
+        if let Some(reason) = category.exemption_reason() {
+            xml.leaf("cbc:TaxExemptionReason", &[], reason);
+        }
+        xml.start("cac:TaxScheme", &[]);
+        xml.leaf("cbc:ID", &[], "VAT");
+        xml.end();
+        xml.end();
+        xml.end();
+    }
+    xml.end();
+
+    // Document totals
+    xml.start("cac:LegalMonetaryTotal", &[]);
+    xml.leaf(
+        "cbc:LineExtensionAmount",
+        &currency,
+        &format_cents(totals.line_extension),
+    );
+    xml.leaf(
+        "cbc:TaxExclusiveAmount",
+        &currency,
+        &format_cents(totals.line_extension),
+    );
+    xml.leaf(
+        "cbc:TaxInclusiveAmount",
+        &currency,
+        &format_cents(totals.tax_inclusive),
+    );
+    if totals.prepaid > 0 {
+        xml.leaf(
+            "cbc:PrepaidAmount",
+            &currency,
+            &format_cents(totals.prepaid),
+        );
+    }
+    xml.leaf(
+        "cbc:PayableAmount",
+        &currency,
+        &format_cents(totals.payable),
+    );
+    xml.end();
+
+    // Invoice lines
+    let category = TaxCategory::for_rate(invoice.rule.iva);
+    for (i, (item, amount)) in invoice.items.iter().zip(&totals.lines).enumerate() {
+        xml.start("cac:InvoiceLine", &[]);
+        xml.leaf("cbc:ID", &[], &(i + 1).to_string());
+        xml.leaf(
+            "cbc:InvoicedQuantity",
+            &[("unitCode", UNIT_CODE)],
+            &item.quantity.to_string(),
+        );
+        xml.leaf("cbc:LineExtensionAmount", &currency, &format_cents(*amount));
+        xml.start("cac:Item", &[]);
+        xml.leaf("cbc:Name", &[], &item.description);
+        xml.start("cac:ClassifiedTaxCategory", &[]);
+        write_tax_category(&mut xml, category, invoice.rule.iva);
+        xml.start("cac:TaxScheme", &[]);
+        xml.leaf("cbc:ID", &[], "VAT");
+        xml.end();
+        xml.end();
+        xml.end();
+        xml.start("cac:Price", &[]);
+        xml.leaf(
+            "cbc:PriceAmount",
+            &currency,
+            &format_cents(to_cents(item.price)),
+        );
+        xml.end();
+        xml.end();
+    }
+
+    xml.finish()
+}
+
+// EAS code of the VAT number, None for countries without a VAT scheme in Peppol
+// (such as Sweden), whose parties need another electronic address
+fn endpoint_scheme(vat_id: &str) -> Option<&'static str> {
+    let country = vat_id.get(..2)?;
+    VAT_ENDPOINT_SCHEMES
+        .iter()
+        .find(|(prefix, _)| *prefix == country)
+        .map(|(_, scheme)| *scheme)
+}
+
+fn write_party(xml: &mut XmlWriter, vat_id: &str, name: &str, address: &str, email: Option<&str>) {
+    xml.start("cac:Party", &[]);
+    // Without a known scheme the endpoint is left out and PEPPOL-EN16931-R010/R020 fail
+    if let Some(scheme) = endpoint_scheme(vat_id) {
+        xml.leaf("cbc:EndpointID", &[("schemeID", scheme)], vat_id);
+    }
+    xml.start("cac:PartyName", &[]);
+    xml.leaf("cbc:Name", &[], name);
+    xml.end();
+    xml.start("cac:PostalAddress", &[]);
+    xml.leaf("cbc:StreetName", &[], address);
+    xml.start("cac:Country", &[]);
+    xml.leaf("cbc:IdentificationCode", &[], country_code(vat_id));
+    xml.end();
+    xml.end();
+    xml.start("cac:PartyTaxScheme", &[]);
+    xml.leaf("cbc:CompanyID", &[], vat_id);
+    xml.start("cac:TaxScheme", &[]);
+    xml.leaf("cbc:ID", &[], "VAT");
+    xml.end();
+    xml.end();
+    xml.start("cac:PartyLegalEntity", &[]);
+    xml.leaf("cbc:RegistrationName", &[], name);
+    xml.end();
+    if let Some(email) = email {
+        xml.start("cac:Contact", &[]);
+        xml.leaf("cbc:ElectronicMail", &[], email);
+        xml.end();
+    }
+    xml.end();
+}
+
+fn write_tax_category(xml: &mut XmlWriter, category: TaxCategory, rate: f32) {
+    xml.leaf("cbc:ID", &[], category.code());
+    xml.leaf("cbc:Percent", &[], &format!("{:.2}", rate));
+}
+
+// Check a UBL document against the Peppol BIS Billing 3.0 business rules
+pub fn validate_peppol(xml: &str) -> io::Result<Vec<RuleViolation>> {
+    let doc = Document::parse(xml)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid XML: {}", e)))?;
+    let root = doc.root_element();
+    let mut checks = RuleChecks::default();
+
+    if !root.has_tag_name((UBL_NS, "Invoice")) {
+        checks.fail("UBL-ROOT", "Root element must be a UBL 2.1 Invoice");
+        return Ok(checks.violations);
+    }
+
+    // Process and document level rules
+    checks.require(
+        cbc_text(root, "CustomizationID") == Some(CUSTOMIZATION_ID),
+        "PEPPOL-EN16931-R004",
+        "Specification identifier must be the Peppol BIS Billing 3.0 one",
+    );
+    checks.require(
+        cbc_text(root, "ProfileID").is_some(),
+        "PEPPOL-EN16931-R001",
+        "Business process (ProfileID) must be provided",
+    );
+    checks.require(
+        non_empty(cbc_text(root, "ID")),
+        "BR-02",
+        "An invoice shall have an invoice number",
+    );
+    for (field, rule) in [("IssueDate", "BR-03"), ("DueDate", "PEPPOL-EN16931-F001")] {
+        let value = cbc_text(root, field);
+        if field == "IssueDate" || value.is_some() {
+            checks.require(
+                value.is_some_and(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok()),
+                rule,
+                &format!("{} must be a valid YYYY-MM-DD date", field),
+            );
+        }
+    }
+    checks.require(
+        matches!(
+            cbc_text(root, "InvoiceTypeCode"),
+            Some("380" | "383" | "386" | "393")
+        ),
+        "BR-04",
+        "An invoice shall have a valid invoice type code",
+    );
+    let currency = cbc_text(root, "DocumentCurrencyCode");
+    checks.require(
+        non_empty(currency),
+        "BR-05",
+        "An invoice shall have a document currency code",
+    );
+    checks.require(
+        non_empty(cbc_text(root, "BuyerReference")) || cac(root, "OrderReference").is_some(),
+        "PEPPOL-EN16931-R003",
+        "A buyer reference or purchase order reference must be provided",
+    );
+
+    // Parties
+    let seller = cac(root, "AccountingSupplierParty").and_then(|p| cac(p, "Party"));
+    let buyer = cac(root, "AccountingCustomerParty").and_then(|p| cac(p, "Party"));
+    check_party(&mut checks, seller, PartyRules::SELLER);
+    check_party(&mut checks, buyer, PartyRules::BUYER);
+
+    // Payment instructions
+    for means in cac_all(root, "PaymentMeans") {
+        checks.require(
+            non_empty(cbc_text(means, "PaymentMeansCode")),
+            "BR-49",
+            "A payment instruction shall specify the payment means type code",
+        );
+        if matches!(cbc_text(means, "PaymentMeansCode"), Some("30" | "58")) {
+            checks.require(
+                cac(means, "PayeeFinancialAccount")
+                    .and_then(|a| cbc_text(a, "ID"))
+                    .is_some_and(|id| !id.is_empty()),
+                "BR-61",
+                "A payment account identifier is required for credit transfers",
+            );
+        }
+    }
+
+    // Lines
+    let lines = cac_all(root, "InvoiceLine");
+    checks.require(
+        !lines.is_empty(),
+        "BR-16",
+        "An invoice shall have at least one invoice line",
+    );
+    let mut line_sum = 0;
+    for line in &lines {
+        checks.require(
+            non_empty(cbc_text(*line, "ID")),
+            "BR-21",
+            "Each invoice line shall have an identifier",
+        );
+        checks.require(
+            cbc_text(*line, "InvoicedQuantity").is_some(),
+            "BR-22",
+            "Each invoice line shall have an invoiced quantity",
+        );
+        checks.require(
+            cbc(*line, "InvoicedQuantity").is_some_and(|q| q.attribute("unitCode").is_some()),
+            "BR-23",
+            "An invoiced quantity shall have a unit of measure code",
+        );
+        match amount(*line, "LineExtensionAmount") {
+            Some(value) => line_sum += value,
+            None => checks.fail("BR-24", "Each invoice line shall have a net amount"),
+        }
+        let item = cac(*line, "Item");
+        checks.require(
+            item.is_some_and(|i| non_empty(cbc_text(i, "Name"))),
+            "BR-25",
+            "Each invoice line shall contain the item name",
+        );
+        checks.require(
+            cac(*line, "Price")
+                .and_then(|p| amount(p, "PriceAmount"))
+                .is_some_and(|p| p >= 0),
+            "BR-26",
+            "Each invoice line shall contain a non negative item net price",
+        );
+        let category = item.and_then(|i| cac(i, "ClassifiedTaxCategory"));
+        checks.require(
+            category.is_some_and(|c| non_empty(cbc_text(c, "ID"))),
+            "BR-CO-04",
+            "Each invoice line shall be categorized with an invoiced item VAT category code",
+        );
+        if let Some(category) = category {
+            check_category_rate(&mut checks, category);
+        }
+    }
+
+    // Totals
+    let monetary = cac(root, "LegalMonetaryTotal");
+    let total = |name: &str| monetary.and_then(|m| amount(m, name));
+    let line_extension = total("LineExtensionAmount");
+    let tax_exclusive = total("TaxExclusiveAmount");
+    let tax_inclusive = total("TaxInclusiveAmount");
+    let payable = total("PayableAmount");
+    checks.require(
+        line_extension.is_some(),
+        "BR-12",
+        "Sum of line net amounts is required",
+    );
+    checks.require(
+        tax_exclusive.is_some(),
+        "BR-13",
+        "Total without VAT is required",
+    );
+    checks.require(
+        tax_inclusive.is_some(),
+        "BR-14",
+        "Total with VAT is required",
+    );
+    checks.require(
+        payable.is_some(),
+        "BR-15",
+        "Amount due for payment is required",
+    );
+    checks.require(
+        line_extension.is_none_or(|total| total == line_sum),
+        "BR-CO-10",
+        "Sum of invoice line net amounts must equal the sum of line net amounts",
+    );
+
+    let allowances: i64 = cac_all(root, "AllowanceCharge")
+        .iter()
+        .filter(|a| cbc_text(**a, "ChargeIndicator") == Some("false"))
+        .filter_map(|a| amount(*a, "Amount"))
+        .sum();
+    let charges: i64 = cac_all(root, "AllowanceCharge")
+        .iter()
+        .filter(|a| cbc_text(**a, "ChargeIndicator") == Some("true"))
+        .filter_map(|a| amount(*a, "Amount"))
+        .sum();
+    if let (Some(lines_total), Some(exclusive)) = (line_extension, tax_exclusive) {
+        checks.require(
+            exclusive == lines_total - allowances + charges,
+            "BR-CO-13",
+            "Total without VAT must equal line total minus allowances plus charges",
+        );
+    }
+
+    // Tax totals in document currency
+    let tax_totals: Vec<Node> = cac_all(root, "TaxTotal")
+        .into_iter()
+        .filter(|t| cbc(*t, "TaxAmount").and_then(|a| a.attribute("currencyID")) == currency)
+        .collect();
+    checks.require(
+        tax_totals.len() == 1,
+        "PEPPOL-EN16931-R053",
+        "Exactly one VAT total in the document currency is required",
+    );
+    let mut tax_total = 0;
+    if let Some(tax_node) = tax_totals.first() {
+        tax_total = amount(*tax_node, "TaxAmount").unwrap_or(0);
+        let subtotals = cac_all(*tax_node, "TaxSubtotal");
+        checks.require(
+            !subtotals.is_empty(),
+            "BR-CO-18",
+            "An invoice shall have at least one VAT breakdown",
+        );
+        let mut subtotal_sum = 0;
+        for subtotal in subtotals {
+            let taxable = amount(subtotal, "TaxableAmount");
+            let tax = amount(subtotal, "TaxAmount");
+            checks.require(
+                taxable.is_some(),
+                "BR-45",
+                "Each VAT breakdown needs a taxable amount",
+            );
+            checks.require(
+                tax.is_some(),
+                "BR-46",
+                "Each VAT breakdown needs a tax amount",
+            );
+            subtotal_sum += tax.unwrap_or(0);
+
+            let Some(category) = cac(subtotal, "TaxCategory") else {
+                checks.fail(
+                    "BR-47",
+                    "Each VAT breakdown shall be defined through a category",
+                );
+                continue;
+            };
+            check_category_rate(&mut checks, category);
+            let rate = cbc_text(category, "Percent").and_then(|p| p.parse::<f32>().ok());
+            if let (Some(taxable), Some(tax), Some(rate)) = (taxable, tax, rate) {
+                checks.require(
+                    (tax - percent_of(taxable, rate)).abs() <= 1,
+                    "BR-CO-17",
+                    "VAT category tax amount must equal taxable amount times the rate",
+                );
+            }
+            if cbc_text(category, "ID") == Some("E") {
+                checks.require(
+                    non_empty(cbc_text(category, "TaxExemptionReason"))
+                        || non_empty(cbc_text(category, "TaxExemptionReasonCode")),
+                    "BR-E-10",
+                    "An exempt VAT breakdown shall have an exemption reason",
+                );
+            }
+        }
+        checks.require(
+            subtotal_sum == tax_total,
+            "BR-CO-14",
+            "Invoice total VAT must equal the sum of VAT category tax amounts",
+        );
+    }
+
+    if let (Some(exclusive), Some(inclusive)) = (tax_exclusive, tax_inclusive) {
+        checks.require(
+            inclusive == exclusive + tax_total,
+            "BR-CO-15",
+            "Total with VAT must equal total without VAT plus the VAT total",
+        );
+    }
+    if let (Some(inclusive), Some(payable)) = (tax_inclusive, payable) {
+        let prepaid = total("PrepaidAmount").unwrap_or(0);
+        let rounding = total("PayableRoundingAmount").unwrap_or(0);
+        checks.require(
+            payable == inclusive - prepaid + rounding,
+            "BR-CO-16",
+            "Amount due must equal total with VAT minus paid amounts plus rounding",
+        );
+    }
+
+    Ok(checks.violations)
+}
+
+#[derive(Default)]
+struct RuleChecks {
+    violations: Vec<RuleViolation>,
+}
+
+impl RuleChecks {
+    fn require(&mut self, condition: bool, rule: &'static str, message: &str) {
+        if !condition {
+            self.fail(rule, message);
+        }
+    }
+
+    fn fail(&mut self, rule: &'static str, message: &str) {
+        self.violations.push(RuleViolation {
+            rule,
+            message: message.to_string(),
+        });
+    }
+}
+
+// Rule identifiers for the seller and buyer versions of each party check
+struct PartyRules {
+    role: &'static str,
+    name: &'static str,
+    address: &'static str,
+    country: &'static str,
+    endpoint: &'static str,
+}
+
+impl PartyRules {
+    const SELLER: PartyRules = PartyRules {
+        role: "Seller",
+        name: "BR-06",
+        address: "BR-08",
+        country: "BR-09",
+        endpoint: "PEPPOL-EN16931-R020",
+    };
+    const BUYER: PartyRules = PartyRules {
+        role: "Buyer",
+        name: "BR-07",
+        address: "BR-10",
+        country: "BR-11",
+        endpoint: "PEPPOL-EN16931-R010",
+    };
+}
+
+fn check_party(checks: &mut RuleChecks, party: Option<Node>, rules: PartyRules) {
+    let Some(party) = party else {
+        checks.fail(rules.name, &format!("{} party is missing", rules.role));
+        return;
+    };
+
+    checks.require(
+        cbc(party, "EndpointID")
+            .is_some_and(|e| e.attribute("schemeID").is_some() && non_empty(e.text())),
+        rules.endpoint,
+        &format!(
+            "{} electronic address with scheme must be provided",
+            rules.role
+        ),
+    );
+    checks.require(
+        cac(party, "PartyLegalEntity").is_some_and(|e| non_empty(cbc_text(e, "RegistrationName"))),
+        rules.name,
+        &format!("{} name is required", rules.role),
+    );
+    let address = cac(party, "PostalAddress");
+    checks.require(
+        address.is_some(),
+        rules.address,
+        &format!("{} postal address is required", rules.role),
+    );
+    let country = address
+        .and_then(|a| cac(a, "Country"))
+        .and_then(|c| cbc_text(c, "IdentificationCode"));
+    checks.require(
+        country.is_some_and(|c| c.len() == 2 && c.chars().all(|ch| ch.is_ascii_uppercase())),
+        rules.country,
+        &format!("{} postal address must have a country code", rules.role),
+    );
+
+    // VAT identifiers must carry an ISO 3166 country prefix (Greece uses EL)
+    for scheme in cac_all(party, "PartyTaxScheme") {
+        let is_vat = cac(scheme, "TaxScheme").and_then(|t| cbc_text(t, "ID")) == Some("VAT");
+        if is_vat {
+            checks.require(
+                cbc_text(scheme, "CompanyID").is_some_and(|id| {
+                    id.len() > 2 && id.chars().take(2).all(|c| c.is_ascii_uppercase())
+                }),
+                "BR-CO-09",
+                &format!(
+                    "{} VAT identifier must be prefixed with a country code",
+                    rules.role
+                ),
+            );
+        }
+    }
+}
+
+fn check_category_rate(checks: &mut RuleChecks, category: Node) {
+    let rate = cbc_text(category, "Percent").and_then(|p| p.parse::<f32>().ok());
+    match cbc_text(category, "ID") {
+        Some("S") => checks.require(
+            rate.is_some_and(|r| r > 0.0),
+            "BR-S-05",
+            "Standard rated VAT category must have a rate greater than zero",
+        ),
+        Some(code @ ("E" | "Z" | "K" | "G" | "AE")) => {
+            let (rule, name) = match code {
+                "E" => ("BR-E-05", "Exempt"),
+                "Z" => ("BR-Z-05", "Zero rated"),
+                "K" => ("BR-IC-05", "Intra-community supply"),
+                "G" => ("BR-G-05", "Export outside the EU"),
+                _ => ("BR-AE-05", "Reverse charge"),
+            };
+            checks.require(
+                rate.is_some_and(|r| r == 0.0),
+                rule,
+                &format!("{} VAT category must have a 0% rate", name),
+            );
+        }
+        Some(_) => {}
+        None => checks.fail("BR-CO-04", "VAT category code is missing"),
+    }
+}
+
+fn non_empty(text: Option<&str>) -> bool {
+    text.is_some_and(|t| !t.trim().is_empty())
+}
+
+fn cbc<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name((CBC_NS, name)))
+}
+
+fn cbc_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    cbc(node, name).and_then(|n| n.text()).map(str::trim)
+}
+
+fn cac<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name((CAC_NS, name)))
+}
+
+fn cac_all<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Vec<Node<'a, 'input>> {
+    node.children()
+        .filter(|n| n.has_tag_name((CAC_NS, name)))
+        .collect()
+}
+
+fn amount(node: Node, name: &str) -> Option<i64> {
+    cbc_text(node, name).and_then(parse_cents)
+}
@@ -0,0 +1,68 @@
+// Rules shared by the interactive forms and the importers, independent of the CLI
+
+use crate::errors::ValidationError;
+use crate::services::einvoice::{country_code, is_eu_country, vat_identifier};
+use crate::services::tax_id::validate_spanish_tax_id;
+use crate::services::vat::validate_eu_vat;
+
+// An empty email is allowed, it is optional everywhere
+pub fn validate_email(email: &str) -> Result<(), ValidationError> {
+    if email.is_empty() {
+        return Ok(());
+    }
+
+    // Something before and after the '@', and a dot somewhere
+    if !email.contains('@')
+        || !email.contains('.')
+        || email.starts_with('@')
+        || email.ends_with('@')
+    {
+        return Err(ValidationError::InvalidEmail {
+            email: email.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+// Clients and suppliers may also be identified by a VAT number of another country
+pub fn validate_client_tax_id(cif: &str) -> Result<(), ValidationError> {
+    let vat_id = vat_identifier(cif).replace([' ', '-'], "");
+    if country_code(&vat_id) == "ES" {
+        return validate_spanish_tax_id(cif);
+    }
+    if is_eu_country(country_code(&vat_id)) {
+        return validate_eu_vat(&vat_id);
+    }
+
+    if vat_id.len() < 4 || vat_id.len() > 14 || !vat_id.chars().all(|c| c.is_alphanumeric()) {
+        return Err(ValidationError::InvalidCif {
+            cif: cif.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+// YYYY-MM-DD, or empty for optional dates
+pub fn validate_date(date_str: &str) -> Result<(), ValidationError> {
+    if date_str.is_empty() {
+        return Ok(());
+    }
+
+    if chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d").is_err() {
+        return Err(ValidationError::InvalidDate {
+            date: date_str.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+pub fn validate_percentage(value: f32) -> Result<(), ValidationError> {
+    if !(0.0..=100.0).contains(&value) {
+        return Err(ValidationError::InvalidPercentage { value });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+// Format and check digit validation of EU VAT numbers, before asking VIES about them.
+// Formats use '#' for a digit, '@' for a letter and '?' for either, anything else is literal
+
+use crate::errors::ValidationError;
+use crate::services::einvoice::{country_code, is_eu_country};
+use crate::services::tax_id::validate_spanish_tax_id;
+
+const FORMATS: &[(&str, &[&str])] = &[
+    ("AT", &["U########"]),
+    ("BE", &["0#########", "1#########"]),
+    ("BG", &["#########", "##########"]),
+    ("CY", &["########@"]),
+    ("CZ", &["########", "#########", "##########"]),
+    ("DE", &["#########"]),
+    ("DK", &["########"]),
+    ("EE", &["#########"]),
+    ("EL", &["#########"]),
+    ("FI", &["########"]),
+    ("FR", &["??#########"]),
+    ("HR", &["###########"]),
+    ("HU", &["########"]),
+    ("IE", &["#######@", "#######@@", "#?#####@"]),
+    ("IT", &["###########"]),
+    ("LT", &["#########", "############"]),
+    ("LU", &["########"]),
+    ("LV", &["###########"]),
+    ("MT", &["########"]),
+    ("NL", &["#########B##"]),
+    ("PL", &["##########"]),
+    ("PT", &["#########"]),
+    (
+        "RO",
+        &[
+            "##",
+            "###",
+            "####",
+            "#####",
+            "######",
+            "#######",
+            "########",
+            "#########",
+            "##########",
+        ],
+    ),
+    ("SE", &["##########01"]),
+    ("SI", &["########"]),
+    ("SK", &["##########"]),
+];
+
+// Check an EU VAT number with its country prefix ("FR12345678901")
+pub fn validate_eu_vat(vat_id: &str) -> Result<(), ValidationError> {
+    let vat_id = vat_id.trim().to_uppercase().replace([' ', '-', '.'], "");
+    if vat_id.len() < 3 || !is_eu_country(country_code(&vat_id)) {
+        return Err(ValidationError::InvalidVatCountry { vat_id });
+    }
+
+    let country = country_code(&vat_id).to_string();
+    let number = &vat_id[2..];
+    if country == "ES" {
+        return validate_spanish_tax_id(number);
+    }
+
+    let formats = FORMATS
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|(_, formats)| *formats)
+        .unwrap_or_default();
+    if !formats.iter().any(|format| matches_format(number, format)) {
+        return Err(ValidationError::InvalidVatFormat {
+            format: formats
+                .iter()
+                .map(|f| format!("{}{}", country, f))
+                .collect::<Vec<_>>()
+                .join(" or "),
+            vat_id,
+        });
+    }
+
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let valid = match country.as_str() {
+        "AT" => austria(&digits),
+        "BE" => belgium(&digits),
+        "DE" => germany(&digits),
+        "DK" => weighted(&digits, &[2, 7, 6, 5, 4, 3, 2, 1]).is_multiple_of(11),
+        "EL" => greece(&digits),
+        "FI" => finland(&digits),
+        "FR" => france(number),
+        "IT" => luhn(&digits),
+        "LU" => digits_value(&digits[..6]) % 89 == digits_value(&digits[6..]),
+        "NL" => netherlands(&vat_id, &digits),
+        "PL" => weighted(&digits, &[6, 5, 7, 2, 3, 4, 5, 6, 7]) % 11 == digits[9],
+        "PT" => portugal(&digits),
+        "SE" => luhn(&digits[..10]),
+        // The remaining countries are checked for their format only
+        _ => true,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::InvalidVatChecksum { vat_id })
+    }
+}
+
+fn matches_format(number: &str, format: &str) -> bool {
+    number.len() == format.len()
+        && number.chars().zip(format.chars()).all(|(c, f)| match f {
+            '#' => c.is_ascii_digit(),
+            '@' => c.is_ascii_uppercase(),
+            '?' => c.is_ascii_alphanumeric(),
+            literal => c == literal,
+        })
+}
+
+fn weighted(digits: &[u32], weights: &[u32]) -> u32 {
+    digits.iter().zip(weights).map(|(d, w)| d * w).sum()
+}
+
+fn digits_value(digits: &[u32]) -> u64 {
+    digits.iter().fold(0, |n, d| n * 10 + *d as u64)
+}
+
+// Sum of the digits of a doubled digit
+fn doubled(digit: u32) -> u32 {
+    let doubled = digit * 2;
+    doubled / 10 + doubled % 10
+}
+
+// Luhn checksum over the whole number, the last digit being the check digit
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 1 { doubled(*d) } else { *d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn austria(digits: &[u32]) -> bool {
+    let sum: u32 = digits[..7]
+        .iter()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 1 { doubled(*d) } else { *d })
+        .sum();
+    (10 - (sum + 4) % 10) % 10 == digits[7]
+}
+
+fn belgium(digits: &[u32]) -> bool {
+    97 - digits_value(&digits[..8]) % 97 == digits_value(&digits[8..])
+}
+
+// ISO 7064 MOD 11,10
+fn germany(digits: &[u32]) -> bool {
+    let mut product = 10;
+    for digit in &digits[..8] {
+        let mut sum = (digit + product) % 10;
+        if sum == 0 {
+            sum = 10;
+        }
+        product = (2 * sum) % 11;
+    }
+    (11 - product) % 10 == digits[8]
+}
+
+fn greece(digits: &[u32]) -> bool {
+    let sum: u32 = digits[..8]
+        .iter()
+        .enumerate()
+        .map(|(i, d)| d << (8 - i))
+        .sum();
+    sum % 11 % 10 == digits[8]
+}
+
+fn finland(digits: &[u32]) -> bool {
+    match weighted(&digits[..7], &[7, 9, 10, 5, 8, 4, 2]) % 11 {
+        0 => digits[7] == 0,
+        1 => false,
+        remainder => 11 - remainder == digits[7],
+    }
+}
+
+// The numeric key is derived from the SIREN, keys with letters are not checkable
+fn france(number: &str) -> bool {
+    let (key, siren) = number.split_at(2);
+    match (key.parse::<u64>(), siren.parse::<u64>()) {
+        (Ok(key), Ok(siren)) => key == (12 + 3 * (siren % 97)) % 97,
+        _ => true,
+    }
+}
+
+// Companies use a mod 11 check digit, sole proprietors since 2020 the mod 97 of the
+// whole identifier with letters as numbers (A = 10 ... Z = 35)
+fn netherlands(vat_id: &str, digits: &[u32]) -> bool {
+    let mod11 = weighted(&digits[..8], &[9, 8, 7, 6, 5, 4, 3, 2]) % 11;
+    if mod11 != 10 && mod11 == digits[8] {
+        return true;
+    }
+
+    let remainder = vat_id.chars().fold(0u64, |remainder, c| {
+        let value = c.to_digit(36).unwrap_or(0) as u64;
+        let width = if value >= 10 { 100 } else { 10 };
+        (remainder * width + value) % 97
+    });
+    remainder == 1
+}
+
+fn portugal(digits: &[u32]) -> bool {
+    let check = 11 - weighted(&digits[..8], &[9, 8, 7, 6, 5, 4, 3, 2]) % 11;
+    (if check > 9 { 0 } else { check }) == digits[8]
+}
@@ -0,0 +1,105 @@
+// VIES, the VAT Information Exchange System of the European Commission, confirms
+// whether a VAT number is registered for intra-community operations
+
+use crate::models::ViesCheck;
+use crate::services::xml::{self, XmlWriter};
+use chrono::Local;
+use std::io;
+use std::time::Duration;
+
+pub const VIES_ENDPOINT: &str =
+    "https://ec.europa.eu/taxation_customs/vies/services/checkVatService";
+
+const SOAP_ENVELOPE: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+const VIES_TYPES: &str = "urn:ec.europa.eu:taxud:vies:services:checkVat:types";
+
+// Source of VIES answers, so the service can be replaced in tests or offline
+pub trait ViesClient {
+    // `country` is the VAT prefix ("FR") and `number` the rest of the VAT number
+    fn check_vat(&self, country: &str, number: &str) -> io::Result<ViesCheck>;
+}
+
+// Client of the public checkVat SOAP service
+pub struct SoapViesClient {
+    endpoint: String,
+    timeout: Duration,
+}
+
+impl SoapViesClient {
+    pub fn new(endpoint: String) -> Self {
+        SoapViesClient {
+            endpoint,
+            timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+impl ViesClient for SoapViesClient {
+    fn check_vat(&self, country: &str, number: &str) -> io::Result<ViesCheck> {
+        let response = ureq::post(&self.endpoint)
+            .timeout(self.timeout)
+            .set("Content-Type", "text/xml; charset=utf-8")
+            .set("SOAPAction", "")
+            .send_string(&check_vat_request(country, number));
+
+        let body = match response {
+            Ok(response) => response.into_string()?,
+            // SOAP faults such as MS_UNAVAILABLE come with an HTTP 500 status
+            Err(ureq::Error::Status(_, response)) => response.into_string()?,
+            Err(e) => return Err(io::Error::other(format!("VIES is not reachable: {}", e))),
+        };
+
+        parse_check_vat_response(&body)
+    }
+}
+
+pub fn check_vat_request(country: &str, number: &str) -> String {
+    let mut writer = XmlWriter::new();
+    writer.start(
+        "soapenv:Envelope",
+        &[("xmlns:soapenv", SOAP_ENVELOPE), ("xmlns:urn", VIES_TYPES)],
+    );
+    writer.start("soapenv:Body", &[]);
+    writer.start("urn:checkVat", &[]);
+    writer.leaf("urn:countryCode", &[], country);
+    writer.leaf("urn:vatNumber", &[], number);
+    writer.finish()
+}
+
+pub fn parse_check_vat_response(body: &str) -> io::Result<ViesCheck> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let document = roxmltree::Document::parse(body)
+        .map_err(|e| invalid(format!("Invalid VIES response: {}", e)))?;
+    let soap_body = xml::child(document.root_element(), "Body")
+        .ok_or_else(|| invalid("VIES response without a SOAP body".to_string()))?;
+
+    if let Some(fault) = xml::child(soap_body, "Fault") {
+        let reason = xml::text(fault, &["faultstring"]).unwrap_or("unknown error");
+        return Err(io::Error::other(format!(
+            "VIES rejected the request: {}",
+            reason
+        )));
+    }
+
+    let response = xml::child(soap_body, "checkVatResponse")
+        .ok_or_else(|| invalid("VIES response without a checkVatResponse".to_string()))?;
+    let field = |name: &str| xml::text(response, &[name]).map(str::to_string);
+
+    // Member states that do not share the registered data answer "---"
+    let shared = |value: Option<String>| value.filter(|v| v != "---");
+
+    Ok(ViesCheck {
+        vat_id: format!(
+            "{}{}",
+            field("countryCode").unwrap_or_default(),
+            field("vatNumber").unwrap_or_default()
+        ),
+        valid: field("valid").as_deref() == Some("true"),
+        name: shared(field("name")),
+        address: shared(field("address")),
+        request_date: field("requestDate")
+            .ok_or_else(|| invalid("VIES response without a request date".to_string()))?,
+        checked_at: Local::now().to_rfc3339(),
+    })
+}
@@ -0,0 +1,134 @@
+// Minimal XML writer shared by the e-invoice exporters
+
+// Escape the characters that are not allowed in XML text and attributes
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Writer that builds an indented XML document element by element
+pub struct XmlWriter {
+    buffer: String,
+    open_elements: Vec<String>,
+}
+
+impl XmlWriter {
+    pub fn new() -> Self {
+        XmlWriter {
+            buffer: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            open_elements: Vec::new(),
+        }
+    }
+
+    // Open an element that will contain child elements
+    pub fn start(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.indent();
+        self.buffer.push('<');
+        self.buffer.push_str(name);
+        self.write_attributes(attributes);
+        self.buffer.push_str(">\n");
+        self.open_elements.push(name.to_string());
+    }
+
+    // Close the most recently opened element
+    pub fn end(&mut self) {
+        if let Some(name) = self.open_elements.pop() {
+            self.indent();
+            self.buffer.push_str("</");
+            self.buffer.push_str(&name);
+            self.buffer.push_str(">\n");
+        }
+    }
+
+    // Write an element with text content and no children
+    pub fn leaf(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) {
+        self.indent();
+        self.buffer.push('<');
+        self.buffer.push_str(name);
+        self.write_attributes(attributes);
+        self.buffer.push('>');
+        self.buffer.push_str(&escape(text));
+        self.buffer.push_str("</");
+        self.buffer.push_str(name);
+        self.buffer.push_str(">\n");
+    }
+
+    // Close any element left open and return the document
+    pub fn finish(mut self) -> String {
+        while !self.open_elements.is_empty() {
+            self.end();
+        }
+        self.buffer
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.open_elements.len() {
+            self.buffer.push_str("  ");
+        }
+    }
+
+    fn write_attributes(&mut self, attributes: &[(&str, &str)]) {
+        for (key, value) in attributes {
+            self.buffer.push(' ');
+            self.buffer.push_str(key);
+            self.buffer.push_str("=\"");
+            self.buffer.push_str(&escape(value));
+            self.buffer.push('"');
+        }
+    }
+}
+
+impl Default for XmlWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Helpers to read documents parsed with roxmltree, matching elements by local
+// name so the same code works whatever prefixes or namespace versions are used
+
+// First child element with the given local name
+pub fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children()
+        .find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+// All child elements with the given local name
+pub fn children<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Vec<roxmltree::Node<'a, 'input>> {
+    node.children()
+        .filter(|n| n.is_element() && n.tag_name().name() == name)
+        .collect()
+}
+
+// Element reached by following a path of child local names
+pub fn find<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    path: &[&str],
+) -> Option<roxmltree::Node<'a, 'input>> {
+    path.iter()
+        .try_fold(node, |current, name| child(current, name))
+}
+
+// Trimmed, non empty text of the element at the given path
+pub fn text<'a>(node: roxmltree::Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    find(node, path)
+        .and_then(|n| n.text())
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}